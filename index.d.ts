@@ -13,13 +13,812 @@ export const enum ProtocolType {
   Surveyor0 = 6,
   Push0 = 7,
   Pull0 = 8,
-  Bus0 = 9
+  Bus0 = 9,
+  Respondent0 = 10
+}
+export const enum NngErrorCode {
+  Interrupted = 0,
+  OutOfMemory = 1,
+  InvalidInput = 2,
+  Busy = 3,
+  TimedOut = 4,
+  ConnectionRefused = 5,
+  Closed = 6,
+  TryAgain = 7,
+  NotSupported = 8,
+  AddressInUse = 9,
+  IncorrectState = 10,
+  EntryNotFound = 11,
+  Protocol = 12,
+  DestUnreachable = 13,
+  AddressInvalid = 14,
+  PermissionDenied = 15,
+  MessageTooLarge = 16,
+  ConnectionAborted = 17,
+  ConnectionReset = 18,
+  Canceled = 19,
+  OutOfFiles = 20,
+  OutOfSpace = 21,
+  ResourceExists = 22,
+  ReadOnly = 23,
+  WriteOnly = 24,
+  Crypto = 25,
+  PeerAuth = 26,
+  NoArgument = 27,
+  Ambiguous = 28,
+  BadType = 29,
+  ConnectionShutdown = 30,
+  Internal = 31,
+  SystemError = 32,
+  TransportError = 33,
+  Unknown = 34
+}
+export const enum AckMode {
+  AtMostOnce = 0,
+  AtLeastOnce = 1
+}
+export interface AckOptions {
+  mode: AckMode
+  timeoutMs?: number
+  maxRedeliveries?: number
+}
+export interface AdaptiveTimeoutOptions {
+  percentile?: number
+  factor?: number
+  minMs?: number
+  maxMs?: number
+  window?: number
+  minSamples?: number
+}
+export const enum CallbackErrorPolicy {
+  Throw = 0,
+  Log = 1,
+  Emit = 2,
+  Stop = 3
+}
+export interface CapturedMessage {
+  direction: string
+  data: Buffer
+  header?: Buffer
+  pipe?: number
+  at: number
+}
+export interface DeadLetter {
+  message: Buffer
+  reason: string
+  error?: string
+}
+export interface ReconnectBackoff {
+  minMs?: number
+  maxMs?: number
+}
+export interface EndpointOptions {
+  recvMaxSize?: number
+  tcpNoDelay?: boolean
+  tcpKeepAlive?: boolean
+  tlsCertKeyFile?: string
+  tlsCaFile?: string
+  tlsAuthMode?: TlsAuthMode
+  tlsServerName?: string
+  reconnectMinMs?: number
+  reconnectMaxMs?: number
+}
+export interface SocketStats {
+  outboxDepth: number
+  sendQueueDepth?: number
+  recvQueueDepth?: number
+  sendBuffer: number
+  recvBuffer: number
+  labels: Record<string, string>
+  endpoints: Array<EndpointStats>
+}
+export interface ListenerInfo {
+  id: number
+  url: string
+}
+export interface RequestOutcome {
+  ok: boolean
+  data?: Buffer
+  reason?: 'timeout' | 'closed' | 'error'
+  message?: string
+  nngCode?: NngErrorCode
+}
+export interface MemoryUsage {
+  queuedBytes: number
+  inFlightBytes: number
+  retainedBytes: number
+  totalBytes: number
+  limitBytes?: number
+}
+export interface MessagePart {
+  kind: string
+  data?: Buffer
+  size?: number
+}
+export interface PipeInfo {
+  pipeId: number
+  transport: string
+  url?: string
+  remoteAddress?: string
+  localAddress?: string
+  dialerId?: number
+  listenerId?: number
+  tlsVerified?: boolean
+  peerPid?: number
+  peerUid?: number
+  peerGid?: number
+}
+export interface EndpointStats {
+  kind: string
+  id: number
+  url?: string
+  connected: number
+  connects: number
+  disconnects: number
+  reconnects: number
+  lastConnectedAt?: number
+  lastDisconnectedAt?: number
+  uptimeMs: number
+}
+export const enum PipeEventType {
+  AddPre = 0,
+  AddPost = 1,
+  RemovePost = 2
+}
+export interface PipeEventInfo {
+  event: PipeEventType
+  pipe: PipeInfo
+}
+export interface ReplayOptions {
+  speed?: number
+  direction?: string
+}
+export interface Message {
+  header: Buffer
+  body: Buffer
+  pipeId?: number
+}
+export interface OutgoingMessage {
+  header?: Buffer | Uint8Array | string | ArrayBuffer
+  body: Buffer | Uint8Array | string | ArrayBuffer
+}
+export interface SocketCapabilities {
+  canSend: boolean
+  canRecv: boolean
+  raw: boolean
+  alternating: boolean
+}
+export interface SocketEvent {
+  name: string
+  pipeId?: number
+  message?: string
+  value?: number
+  code?: string
+  labels?: Record<string, string>
+}
+export interface FaultOptions {
+  dropRate?: number
+  duplicateRate?: number
+  reorderRate?: number
+  reorderDelayMs?: number
+  latencyMs?: number
+  jitterMs?: number
+  direction?: string
+  seed?: number
+}
+export interface MalformedFrames {
+  truncated: number
+  missingDelimiter: number
+  invalidUtf8: number
+  unknownKind: number
+  unknownFlags: number
+}
+export interface ThreadPinning {
+  cpu?: number
+  niceness?: number
+}
+export interface DisconnectCoalescing {
+  windowMs?: number
+  pauseSenders?: boolean
+}
+export interface OptionEntry {
+  name: string
+  value: number | boolean | string
+  defaultValue?: number | boolean | string
+}
+export interface EndpointOptionDump {
+  kind: string
+  id: number
+  url: string
+  started: boolean
+  options: Array<OptionEntry>
+}
+export interface OptionDump {
+  socket: Array<OptionEntry>
+  endpoints: Array<EndpointOptionDump>
+  labels: Record<string, string>
+}
+export interface RequeueOptions {
+  maxAttempts?: number
+}
+export const enum SlowConsumerPolicy {
+  Skip = 0,
+  Disconnect = 1,
+  SocketBuffer = 2
+}
+export interface SlowConsumerOptions {
+  policy: SlowConsumerPolicy
+  graceMs?: number
+  bufferLimit?: number
+  checkIntervalMs?: number
+}
+export const enum TlsAuthMode {
+  None = 0,
+  Optional = 1,
+  Required = 2
+}
+export interface TlsOptions {
+  certKeyFile: string
+  caFile?: string
+  authMode?: TlsAuthMode
+  watchIntervalMs?: number
+}
+export function partitionFor(topic: string, partitions: number): number
+export interface TopicMessage {
+  topic: string
+  data: Buffer
+  replayed: boolean
+  snapshot: boolean
+}
+export interface TelemetrySampling {
+  everyNth?: number
+  probability?: number
+  maxPerSecond?: number
+}
+export interface TopicTraffic {
+  topic: string
+  messages: number
+  bytes: number
+}
+export interface SubscriptionStats {
+  topic: string
+  received: number
+  dropped: number
+  lag: number
+  labels: Record<string, string>
+}
+export interface PauseState {
+  paused: boolean
+  buffered: number
+  overflowed: number
+}
+export interface HandshakeOptions {
+  version: number
+  minVersion?: number
+  metadata?: Record<string, string>
+}
+export interface PeerInfo {
+  peerId: number
+  version: number
+  metadata: Record<string, string>
+}
+export interface RpcChunk {
+  value?: Buffer
+  done: boolean
+}
+export const enum CloseMode {
+  Reject = 0,
+  Wait = 1,
+  Detach = 2
+}
+export interface CloseOptions {
+  mode?: CloseMode
+  waitMs?: number
+}
+export interface CallOptions {
+  timeoutMs?: number
+}
+export interface TopicReply {
+  responder: string
+  data?: Buffer
+  error?: string
+}
+export interface TopicCallOptions {
+  timeoutMs?: number
+  expected?: number
+}
+export function requestId(message: Buffer): number | null
+export function setRequestId(message: Buffer, requestId: number): Buffer
+export function backtrace(message: Buffer): Buffer
+export function stripBacktrace(message: Buffer): Buffer
+export const enum Transport {
+  Tcp = 0,
+  Ipc = 1,
+  Inproc = 2,
+  Ws = 3,
+  TlsTcp = 4,
+  Wss = 5,
+  Zt = 6
+}
+export function buildUrl(transport: Transport, host?: string | undefined | null, port?: number | undefined | null, path?: string | undefined | null): string
+export function parseTransport(url: string): Transport
+export function inprocUrl(name?: string | undefined | null): string
+export function namespacedUrl(namespace: string, url: string): string
+export function startEchoServer(protocol: ProtocolType, url: string): EchoServer
+export const enum BridgeProtocol {
+  PushPull = 0,
+  PubSub = 1,
+  ReqRep = 2,
+  Pair = 3,
+  Bus = 4
+}
+export interface BridgeOptions {
+  from: string
+  to: string
+  protocolPair: BridgeProtocol
+  dialFrom?: boolean
+  listenTo?: boolean
+  bufferSize?: number
+}
+export interface BridgeStats {
+  forwarded: number
+  returned: number
+  bytes: number
+  blocked: number
+  dropped: number
+}
+export function bridge(options: BridgeOptions): Bridge
+export interface ConformanceCheck {
+  protocol: string
+  passed: boolean
+  detail?: string
+}
+export function runConformance(transport?: Transport | undefined | null): Promise<Array<ConformanceCheck>>
+export interface GroupStats {
+  sockets: number
+  open: number
+  outboxDepth: number
+  sendQueueDepth: number
+  recvQueueDepth: number
+  members: Array<SocketStats>
+}
+export interface JobQueueOptions {
+  ackTimeoutMs?: number
+  maxAttempts?: number
+  starvationMs?: number
+  retryDelayMs?: number
+  maxRetryDelayMs?: number
+  dedupTtlMs?: number
+  dedupPath?: string
+}
+export interface EnqueueOptions {
+  priority?: number
+  delayMs?: number
+  jobId?: string
+}
+export interface Job {
+  id: number
+  data: Buffer
+  attempts: number
+}
+export interface JobQueueStats {
+  outstanding: number
+  waiting: number
+  delayed: number
+  completed: number
+  retried: number
+  deadLettered: number
+  deduplicated: number
+  inFlight: number
+  processed: number
+}
+export interface EventLogOptions {
+  sync?: boolean
+}
+export interface LogRecord {
+  offset: number
+  topic: string
+  data: Buffer
+  timestamp: number
+}
+export interface ConsumerGroupOptions {
+  bufferSize?: number
+}
+export interface ConsumerGroupStats {
+  members: number
+  received: number
+  forwarded: number
+}
+export interface FanOutOptions {
+  protocol?: ProtocolType
+  dial?: boolean
+  firstMatch?: boolean
+}
+export interface DownstreamOptions {
+  url: string
+  protocol: ProtocolType
+  listen?: boolean
+  queueSize?: number
+  sendTimeoutMs?: number
+  rules?: Array<RoutingRule>
+}
+export interface DownstreamStats {
+  name: string
+  sent: number
+  dropped: number
+  failed: number
+  queued: number
+}
+export interface FanOutStats {
+  received: number
+  unrouted: number
+  downstreams: Array<DownstreamStats>
+}
+export interface FanInOptions {
+  queueSize?: number
+}
+export interface UpstreamOptions {
+  url: string
+  protocol?: ProtocolType
+  dial?: boolean
+}
+export interface SourcedMessage {
+  source: string
+  sequence: number
+  data: Buffer
+  receivedAt: number
+}
+export interface UpstreamStats {
+  name: string
+  received: number
+}
+export interface FanInStats {
+  delivered: number
+  queued: number
+  upstreams: Array<UpstreamStats>
+}
+export interface RoutingRule {
+  prefix?: Buffer
+  topic?: string
+  minSize?: number
+  maxSize?: number
+}
+export interface SignalOptions {
+  drainMs?: number
+}
+export function installSignalHandlers(signals: Array<string>, options?: SignalOptions | undefined | null): void
+export interface WatchdogOptions {
+  stallMs?: number
+}
+export class Envelope {
+  get header(): Buffer
+  get body(): Buffer
+  get redelivered(): number
+  get attempts(): number
+  ack(): boolean
+  nack(requeue?: boolean | undefined | null): boolean
 }
 export class SocketWrapper {
   constructor()
-  connect(protocol: ProtocolType, url: string, recvTimeout: number, sendTimeout: number): boolean
-  send(message: Buffer): Buffer
-  recv(callback: (err: Error | null, arg: Buffer) => any): void
+  connect(protocol: ProtocolType, url: string, recvTimeout: number, sendTimeout: number, raw?: boolean | undefined | null, reconnect?: ReconnectBackoff | undefined | null): boolean
+  open(protocol: ProtocolType, raw?: boolean | undefined | null): void
+  setReconnectBackoff(backoff: ReconnectBackoff): void
+  setTimeouts(recvTimeout: number, sendTimeout: number): void
+  setAdaptiveTimeout(options?: AdaptiveTimeoutOptions | undefined | null): void
+  adaptiveTimeout(): number | null
+  subscribe(prefix: Buffer | Uint8Array | string | ArrayBuffer): void
+  subscribeMany(prefixes: Array<Buffer | Uint8Array | string | ArrayBuffer>): void
+  unsubscribe(prefix: Buffer | Uint8Array | string | ArrayBuffer): void
+  setNanomsgOption(name: string, value: number | boolean | string | Buffer): void
+  getNanomsgOption(name: string): number | string
+  dumpOptions(): OptionDump
+  dial(url: string, nonblocking?: boolean | undefined | null): void
+  listen(url: string): number
+  closeListener(id: number): boolean
+  listeners(): Array<ListenerInfo>
+  createListener(url: string, options?: EndpointOptions | undefined | null): number
+  startListener(id: number): void
+  createDialer(url: string, options?: EndpointOptions | undefined | null): number
+  startDialer(id: number, nonblocking?: boolean | undefined | null): void
+  send(message: Buffer | Uint8Array | string | ArrayBuffer): Promise<Buffer>
+  tryRequest(message: Buffer | Uint8Array | string | ArrayBuffer): Promise<RequestOutcome>
+  survey(message: Buffer | Uint8Array | string | ArrayBuffer, deadlineMs: number, callback?: ((err: Error | null, reply: Buffer) => any) | undefined | null): Promise<Buffer[]>
+  openContext(): Context
+  sendAsync(message: Buffer | Uint8Array | string | ArrayBuffer): Promise<void>
+  sendMessage(message: Message): Promise<void>
+  sendMsg(message: OutgoingMessage): Promise<void>
+  trySend(message: Buffer | Uint8Array | string | ArrayBuffer): boolean
+  post(message: Buffer | Uint8Array | string | ArrayBuffer): void
+  postDropped(): number
+  sendReliable(message: Buffer | Uint8Array | string | ArrayBuffer): Promise<void>
+  sendAfter(message: Buffer | Uint8Array | string | ArrayBuffer, delayMs: number): Promise<void>
+  drain(timeoutMs?: number | undefined | null): Promise<void>
+  setDeadLetter(sink: ((err: Error | null, letter: DeadLetter) => any) | SocketWrapper | undefined | null): void
+  setDisconnectedQueue(limit?: number | undefined | null): void
+  setDisconnectCoalescing(options?: DisconnectCoalescing | undefined | null): void
+  ping(timeoutMs: number): Promise<number>
+  warmUp(timeoutMs?: number | undefined | null): Promise<void>
+  recv(callback: (err: Error | null, arg: Buffer) => any, signal?: AbortSignal | undefined | null): Promise<void>
+  recvTransferable(callback: (err: Error | null, arg: ArrayBuffer) => any, signal?: AbortSignal | undefined | null): Promise<void>
+  recvChunked(callback: (err: Error | null, part: MessagePart) => any, thresholdBytes: number, chunkBytes?: number | undefined | null, signal?: AbortSignal | undefined | null): Promise<void>
+  recvBorrowed(callback: (err: Error | null, buffer: Buffer, length: number) => any, initialBytes?: number | undefined | null, signal?: AbortSignal | undefined | null): Promise<void>
+  recvInto(buffer: Buffer): Promise<number>
+  recvOnce(timeoutMs?: number | undefined | null): Promise<Buffer>
+  recvMessages(callback: (err: Error | null, arg: Message) => any, signal?: AbortSignal | undefined | null): Promise<void>
+  recvEnvelopes(callback: (err: Error | null, envelope: Envelope) => any, signal?: AbortSignal | undefined | null): Promise<void>
+  setRecvWatchdog(options?: WatchdogOptions | undefined | null): void
+  unref(): void
+  ref(): void
+  hasRef(): boolean
   close(): void
+  onEvent(callback: (err: Error | null, arg: SocketEvent) => any): void
+  pipe(pipeId: number): PipeInfo | null
+  onPipeAdded(callback: (err: Error | null, arg: PipeInfo) => any): void
+  onPipeRemoved(callback: (err: Error | null, arg: PipeInfo) => any): void
+  onPipeEvent(callback: (err: Error | null, arg: PipeEventInfo) => any): void
+  setLabels(labels: Record<string, string>): void
+  setNamespace(namespace?: string | undefined | null): void
+  namespace(): string | null
+  labels(): Record<string, string>
+  stats(): SocketStats
+  pauseRecv(): void
+  resumeRecv(): void
+  memoryUsage(): MemoryUsage
+  setMemoryLimit(limitBytes?: number | undefined | null): void
+  setDeliveryBudget(messages?: number | undefined | null): void
+  setCapture(limit?: number | undefined | null): void
+  setCrashDump(dir?: string | undefined | null): void
+  capture(): Array<CapturedMessage>
+  setFaults(options?: FaultOptions | undefined | null): void
+  setReceiveThread(options?: ThreadPinning | undefined | null): void
+  setAcknowledgements(options?: AckOptions | undefined | null): void
+  setRequeue(target: SocketWrapper | undefined | null, options?: RequeueOptions | undefined | null): void
+  setCallbackErrorPolicy(policy: CallbackErrorPolicy): void
+  record(path: string): void
+  stopRecording(): void
+  replay(path: string, options?: ReplayOptions | undefined | null): Promise<number>
+  capabilities(): SocketCapabilities | null
   isConnect(): boolean
 }
+export class Context {
+  get id(): number
+  send(message: Buffer | Uint8Array | string | ArrayBuffer): Promise<void>
+  recv(timeoutMs?: number | undefined | null): Promise<Buffer>
+  request(message: Buffer | Uint8Array | string | ArrayBuffer, timeoutMs?: number | undefined | null): Promise<Buffer>
+  close(): void
+}
+export class StickyRouter {
+  constructor(protocol: ProtocolType)
+  listen(url: string): void
+  addWorker(url: string): number
+  removeWorker(id: number): boolean
+  workerCount(): number
+  send(key: string, message: Buffer | Uint8Array | string | ArrayBuffer): number
+  close(): void
+}
+export class PartitionedPublisher {
+  constructor(urls: Array<string>)
+  partitions(): number
+  publish(topic: string, message: Buffer | Uint8Array | string | ArrayBuffer): number
+  topicStats(): Array<TopicTraffic>
+  setTopicStatsLimit(limit: number): void
+  setTopicStatsSampling(sampling?: TelemetrySampling | undefined | null): void
+  close(): void
+}
+export class PartitionedSubscriber {
+  constructor(urls: Array<string>)
+  subscribe(topic: string): number
+  topicStats(): Array<TopicTraffic>
+  setTopicStatsLimit(limit: number): void
+  setTopicStatsSampling(sampling?: TelemetrySampling | undefined | null): void
+  malformedFrames(): MalformedFrames
+  recv(callback: (err: Error | null, arg: TopicMessage) => any): void
+  close(): void
+}
+export class Publisher {
+  constructor()
+  listen(url: string): void
+  listenTls(url: string, options: TlsOptions): void
+  reloadTls(options?: TlsOptions | undefined | null): void
+  dial(url: string): void
+  setRetain(count: number): void
+  clearRetained(topic?: string | undefined | null): void
+  publish(topic: string, message: Buffer | Uint8Array | string | ArrayBuffer): void
+  publishInterval(topic: string, producer: (() => Buffer | Uint8Array | string | ArrayBuffer | undefined | null) | Buffer | Uint8Array | string | ArrayBuffer, intervalMs: number): number
+  clearPublishInterval(id: number): boolean
+  setSnapshotProvider(topic: string, provider: (() => Buffer | Uint8Array | string | ArrayBuffer | undefined | null) | null): void
+  pause(limit?: number | undefined | null): void
+  resume(): number
+  pauseState(): PauseState
+  topicStats(): Array<TopicTraffic>
+  setTopicStatsLimit(limit: number): void
+  setTopicStatsSampling(sampling?: TelemetrySampling | undefined | null): void
+  memoryUsage(): MemoryUsage
+  setMemoryLimit(limitBytes?: number | undefined | null): void
+  onEvent(callback: (err: Error | null, arg: SocketEvent) => any): void
+  setLabels(labels: Record<string, string>): void
+  labels(): Record<string, string>
+  /** Only subscribers on tcp, ipc or tls are watched: the inproc transport does not count sent messages per pipe, so inproc subscribers never trigger a policy. */
+  setSlowConsumerPolicy(options: SlowConsumerOptions): void
+  close(): void
+}
+export class Subscriber {
+  constructor()
+  connect(url: string): void
+  subscribe(topic: string): void
+  unsubscribe(topic: string): void
+  setLabels(labels: Record<string, string>): void
+  labels(): Record<string, string>
+  onEvent(callback: (err: Error | null, arg: SocketEvent) => any): void
+  stats(): Array<SubscriptionStats>
+  malformedFrames(): MalformedFrames
+  topicStats(): Array<TopicTraffic>
+  setTopicStatsLimit(limit: number): void
+  setTopicStatsSampling(sampling?: TelemetrySampling | undefined | null): void
+  recv(callback: (err: Error | null, arg: TopicMessage) => any): void
+  close(): void
+}
+export class RpcCall {
+  get method(): string
+  get data(): Buffer
+  get peerId(): number
+  get topic(): string | null
+  write(chunk: Buffer | Uint8Array | string | ArrayBuffer): void
+  end(chunk?: Buffer | Uint8Array | string | ArrayBuffer | undefined | null): void
+  fail(message: string): void
+}
+export class AuthRequest {
+  get peerId(): number
+  get token(): string
+  accept(): void
+  reject(reason?: string | undefined | null): void
+}
+export class RpcServer {
+  constructor()
+  setHandshake(options: HandshakeOptions): void
+  listen(url: string): void
+  listenTls(url: string, options: TlsOptions): void
+  reloadTls(options?: TlsOptions | undefined | null): void
+  handle(method: string, callback: (err: Error | null, arg: RpcCall) => any): void
+  setAuthVerifier(callback: (err: Error | null, arg: AuthRequest) => any, timeoutMs?: number | undefined | null): void
+  setSharedSecret(secret: Buffer, timeoutMs?: number | undefined | null): void
+  onEvent(callback: (err: Error | null, arg: SocketEvent) => any): void
+  setLabels(labels: Record<string, string>): void
+  labels(): Record<string, string>
+  peers(): Array<number>
+  peerInfo(peerId: number): PeerInfo | null
+  malformedFrames(): MalformedFrames
+  call(peerId: number, method: string, data: Buffer | Uint8Array | string | ArrayBuffer, options?: CallOptions | undefined | null): Promise<Buffer>
+  stream(peerId: number, method: string, data: Buffer | Uint8Array | string | ArrayBuffer, options?: CallOptions | undefined | null): RpcStream & AsyncIterable<Buffer>
+  notify(method: string, data: Buffer | Uint8Array | string | ArrayBuffer, peerId?: number | undefined | null): void
+  close(options?: CloseOptions | undefined | null): void
+}
+export class RpcStream {
+  next(): Promise<RpcChunk>
+}
+export class RpcClient {
+  constructor()
+  setHandshake(options: HandshakeOptions): void
+  setAuthToken(token: string): void
+  setSharedSecret(secret: Buffer, timeoutMs?: number | undefined | null): void
+  connect(url: string): void
+  handle(method: string, callback: (err: Error | null, arg: RpcCall) => any): void
+  onEvent(callback: (err: Error | null, arg: SocketEvent) => any): void
+  setLabels(labels: Record<string, string>): void
+  labels(): Record<string, string>
+  serverInfo(): PeerInfo | null
+  malformedFrames(): MalformedFrames
+  call(method: string, data: Buffer | Uint8Array | string | ArrayBuffer, options?: CallOptions | undefined | null): Promise<Buffer>
+  stream(method: string, data: Buffer | Uint8Array | string | ArrayBuffer, options?: CallOptions | undefined | null): RpcStream & AsyncIterable<Buffer>
+  notify(method: string, data: Buffer | Uint8Array | string | ArrayBuffer): void
+  close(options?: CloseOptions | undefined | null): void
+}
+export class TopicRpcServer {
+  constructor(name: string)
+  connect(url: string): void
+  handle(topic: string, method: string, callback: (err: Error | null, arg: RpcCall) => any): void
+  onEvent(callback: (err: Error | null, arg: SocketEvent) => any): void
+  malformedFrames(): MalformedFrames
+  close(): void
+}
+export class TopicRpcClient {
+  constructor()
+  listen(url: string, replyUrl: string): void
+  call(topic: string, method: string, data: Buffer | Uint8Array | string | ArrayBuffer, options?: TopicCallOptions | undefined | null): Promise<Array<TopicReply>>
+  notify(topic: string, method: string, data: Buffer | Uint8Array | string | ArrayBuffer): void
+  onEvent(callback: (err: Error | null, arg: SocketEvent) => any): void
+  malformedFrames(): MalformedFrames
+  close(options?: CloseOptions | undefined | null): void
+}
+export class EchoServer {
+  received(): number
+  close(): void
+}
+export class SocketGroup {
+  constructor()
+  add(socket: SocketWrapper): boolean
+  remove(socket: SocketWrapper): boolean
+  size(): number
+  close(): void
+  pauseRecv(): void
+  resumeRecv(): void
+  drain(timeoutMs?: number | undefined | null): Promise<void>
+  stats(): GroupStats
+}
+export class JobQueue {
+  constructor(options?: JobQueueOptions | undefined | null)
+  listen(jobsUrl: string, acksUrl: string): void
+  connect(jobsUrl: string, acksUrl: string): void
+  enqueue(job: Buffer | Uint8Array | string | ArrayBuffer, options?: EnqueueOptions | undefined | null): Promise<void>
+  schedule(name: string, expression: string, job: Buffer | Uint8Array | string | ArrayBuffer, options?: EnqueueOptions | undefined | null): void
+  unschedule(name: string): boolean
+  schedules(): Array<string>
+  process(handler: (err: Error | null, job: Job) => any, concurrency?: number | undefined | null): void
+  setDeadLetter(callback?: ((err: Error | null, letter: DeadLetter) => any) | undefined | null): void
+  onEvent(callback: (err: Error | null, arg: SocketEvent) => any): void
+  stats(): JobQueueStats
+  close(): void
+}
+export class ConsumerGroup {
+  constructor(groupUrl: string, options?: ConsumerGroupOptions | undefined | null)
+  connect(url: string): void
+  subscribe(topic: string): void
+  unsubscribe(topic: string): void
+  stats(): ConsumerGroupStats
+  malformedFrames(): MalformedFrames
+  onEvent(callback: (err: Error | null, arg: SocketEvent) => any): void
+  close(): void
+}
+export class GroupConsumer {
+  constructor()
+  connect(groupUrl: string): void
+  recv(callback: (err: Error | null, arg: TopicMessage) => any): void
+  received(): number
+  malformedFrames(): MalformedFrames
+  onEvent(callback: (err: Error | null, arg: SocketEvent) => any): void
+  close(): void
+}
+export class EventLog {
+  constructor(path: string, options?: EventLogOptions | undefined | null)
+  listen(url: string): void
+  listenCatchUp(url: string): void
+  publish(topic: string, message: Buffer | Uint8Array | string | ArrayBuffer): number
+  read(fromOffset: number, limit?: number | undefined | null): Array<LogRecord>
+  nextOffset(): number
+  follow(fromOffset: number, callback: (err: Error | null, record: LogRecord) => any): number
+  unfollow(id: number): boolean
+  onEvent(callback: (err: Error | null, arg: SocketEvent) => any): void
+  close(): void
+}
+export class LogSubscriber {
+  constructor()
+  connect(liveUrl: string, catchUpUrl: string): void
+  subscribe(topic: string): void
+  unsubscribe(topic: string): boolean
+  recv(fromOffset: number, callback: (err: Error | null, record: LogRecord) => any): void
+  offset(): number
+  onEvent(callback: (err: Error | null, arg: SocketEvent) => any): void
+  close(): void
+}
+export class Bridge {
+  stats(): BridgeStats
+  onEvent(callback: (err: Error | null, arg: SocketEvent) => any): void
+  close(): void
+}
+export class FanOut {
+  constructor(url: string, options?: FanOutOptions | undefined | null)
+  addDownstream(name: string, options: DownstreamOptions): void
+  removeDownstream(name: string): boolean
+  stats(): FanOutStats
+  onEvent(callback: (err: Error | null, arg: SocketEvent) => any): void
+  close(): void
+}
+export class FanIn {
+  constructor(options?: FanInOptions | undefined | null)
+  addUpstream(name: string, options: UpstreamOptions): void
+  removeUpstream(name: string): boolean
+  recv(callback: (err: Error | null, message: SourcedMessage) => any): void
+  stats(): FanInStats
+  onEvent(callback: (err: Error | null, arg: SocketEvent) => any): void
+  close(): void
+}
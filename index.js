@@ -281,7 +281,53 @@ if (!nativeBinding) {
   throw new Error(`Failed to load native binding`)
 }
 
-const { SocketWrapper, ProtocolType } = nativeBinding
+const { AckMode, Envelope, CallbackErrorPolicy, SocketWrapper, Context, ProtocolType, NngErrorCode, StickyRouter, partitionFor, PartitionedPublisher, PartitionedSubscriber, PipeEventType, Publisher, Subscriber, SlowConsumerPolicy, TlsAuthMode, RpcCall, AuthRequest, RpcServer, RpcStream, RpcClient, CloseMode, TopicRpcServer, TopicRpcClient, requestId, setRequestId, backtrace, stripBacktrace, Transport, buildUrl, parseTransport, inprocUrl, namespacedUrl, EchoServer, startEchoServer, BridgeProtocol, Bridge, bridge, runConformance, SocketGroup, JobQueue, ConsumerGroup, GroupConsumer, EventLog, LogSubscriber, FanOut, FanIn, installSignalHandlers } = nativeBinding
 
+module.exports.AckMode = AckMode
+module.exports.Envelope = Envelope
+module.exports.CallbackErrorPolicy = CallbackErrorPolicy
 module.exports.SocketWrapper = SocketWrapper
+module.exports.Context = Context
 module.exports.ProtocolType = ProtocolType
+module.exports.NngErrorCode = NngErrorCode
+module.exports.StickyRouter = StickyRouter
+module.exports.partitionFor = partitionFor
+module.exports.PartitionedPublisher = PartitionedPublisher
+module.exports.PartitionedSubscriber = PartitionedSubscriber
+module.exports.PipeEventType = PipeEventType
+module.exports.Publisher = Publisher
+module.exports.Subscriber = Subscriber
+module.exports.SlowConsumerPolicy = SlowConsumerPolicy
+module.exports.TlsAuthMode = TlsAuthMode
+module.exports.RpcCall = RpcCall
+module.exports.AuthRequest = AuthRequest
+module.exports.RpcServer = RpcServer
+module.exports.RpcStream = RpcStream
+module.exports.RpcClient = RpcClient
+module.exports.CloseMode = CloseMode
+module.exports.TopicRpcServer = TopicRpcServer
+module.exports.TopicRpcClient = TopicRpcClient
+module.exports.requestId = requestId
+module.exports.setRequestId = setRequestId
+module.exports.backtrace = backtrace
+module.exports.stripBacktrace = stripBacktrace
+module.exports.Transport = Transport
+module.exports.buildUrl = buildUrl
+module.exports.parseTransport = parseTransport
+module.exports.inprocUrl = inprocUrl
+module.exports.namespacedUrl = namespacedUrl
+module.exports.EchoServer = EchoServer
+module.exports.startEchoServer = startEchoServer
+module.exports.BridgeProtocol = BridgeProtocol
+module.exports.Bridge = Bridge
+module.exports.bridge = bridge
+module.exports.runConformance = runConformance
+module.exports.SocketGroup = SocketGroup
+module.exports.JobQueue = JobQueue
+module.exports.ConsumerGroup = ConsumerGroup
+module.exports.GroupConsumer = GroupConsumer
+module.exports.EventLog = EventLog
+module.exports.LogSubscriber = LogSubscriber
+module.exports.FanOut = FanOut
+module.exports.FanIn = FanIn
+module.exports.installSignalHandlers = installSignalHandlers
@@ -0,0 +1,43 @@
+use napi::bindgen_prelude::{External, FromNapiValue, ToNapiValue};
+use napi::{CallContext, Env, JsFunction, JsObject, JsUndefined, JsUnknown, NapiRaw, NapiValue, Result};
+use napi_derive::js_function;
+
+type AbortCallback = Box<dyn Fn() + Send>;
+
+// 在 AbortSignal 触发时调用 callback；signal 已经 abort 时立即调用
+//
+// napi4 下没有原生闭包，这里把回调放进 External，再用 bind 绑到一个固定的 JS 函数上。
+pub fn on_abort<F>(env: &Env, signal: &JsObject, callback: F) -> Result<()>
+where
+    F: Fn() + Send + 'static,
+{
+    if signal.get_named_property::<napi::JsBoolean>("aborted")?.get_value()? {
+        callback();
+        return Ok(());
+    }
+    let external: AbortCallback = Box::new(callback);
+    let external = unsafe {
+        let raw = External::to_napi_value(env.raw(), External::new(external))?;
+        JsUnknown::from_raw_unchecked(env.raw(), raw)
+    };
+    let trigger = env.create_function("onAbort", trigger)?.coerce_to_object()?;
+    let bind: JsFunction = trigger.get_named_property("bind")?;
+    let listener = bind.call(Some(&trigger), &[env.get_null()?.into_unknown(), external])?;
+
+    let mut options = env.create_object()?;
+    options.set_named_property("once", env.get_boolean(true)?)?;
+    let add_listener: JsFunction = signal.get_named_property("addEventListener")?;
+    add_listener.call(
+        Some(signal),
+        &[env.create_string("abort")?.into_unknown(), listener, options.into_unknown()],
+    )?;
+    Ok(())
+}
+
+#[js_function(1)]
+fn trigger(ctx: CallContext) -> Result<JsUndefined> {
+    let external = ctx.get::<JsUnknown>(0)?;
+    let callback = unsafe { External::<AbortCallback>::from_napi_value(ctx.env.raw(), external.raw())? };
+    callback();
+    ctx.env.get_undefined()
+}
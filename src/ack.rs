@@ -0,0 +1,314 @@
+use napi::bindgen_prelude::Buffer;
+use napi::Result;
+use napi_derive::napi;
+use nng::Message;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::dead_letter::DeadLetters;
+use crate::events::EventEmitter;
+use crate::interval::Interval;
+use crate::memory::{Charge, MemoryAccount, Pool};
+use crate::requeue::{self, Requeue};
+
+const DEFAULT_TIMEOUT_MS: u32 = 30_000;
+// 检查 ack 超时的周期，timeoutMs 更短时按 timeoutMs
+const SWEEP_PERIOD: Duration = Duration::from_millis(100);
+
+#[napi]
+pub enum AckMode {
+    AtMostOnce,  // 默认，交付即算完成，ack/nack 不起作用
+    AtLeastOnce, // 交付后等 ack，超时或 nack 时重新投递
+}
+
+#[napi(object)]
+pub struct AckOptions {
+    pub mode: AckMode,
+    pub timeout_ms: Option<u32>,       // 多久没有 ack 就重新投递，默认 30000
+    pub max_redeliveries: Option<u32>, // 重新投递超过这么多次还没 ack 时交给死信出口，默认不限
+}
+
+type Sink = Arc<dyn Fn(Envelope) + Send + Sync>;
+
+// 交付了、还没 ack 的消息，复制一份留着重新投递
+struct Pending {
+    message: Message,
+    redelivered: u32,
+    attempts: u32, // 重新排队带来的失败次数
+    deadline: Instant,
+    _charge: Charge,
+}
+
+#[derive(Default)]
+struct Ledger {
+    timeout: Option<Duration>, // None 表示 AtMostOnce
+    max_redeliveries: Option<u32>,
+    next_id: u64,
+    pending: HashMap<u64, Pending>,
+    sink: Option<(u64, Sink)>, // 当前的 recvEnvelopes 循环和它的序号
+    next_sink: u64,
+    sweeper: Option<Interval>,
+}
+
+// 消费端的确认：recvEnvelopes 交付的每条消息在 AtLeastOnce 下要 ack，
+// 超时或 nack(true) 的重新交给接收回调，像一个轻量的队列客户端。
+// 只在本进程内重新投递，进程退出时还没 ack 的消息随之丢失；设置了 setRequeue 时改为推给重试 socket
+#[derive(Clone)]
+pub struct Acks {
+    ledger: Arc<Mutex<Ledger>>,
+    events: EventEmitter,
+    memory: MemoryAccount,
+    dead_letters: DeadLetters,
+    requeue: Requeue,
+}
+
+impl Acks {
+    pub fn new(events: EventEmitter, memory: MemoryAccount, dead_letters: DeadLetters, requeue: Requeue) -> Self {
+        Acks { ledger: Arc::default(), events, memory, dead_letters, requeue }
+    }
+
+    // None 恢复 AtMostOnce；切回 AtMostOnce 时还没 ack 的消息算作已完成
+    pub fn configure(&self, options: Option<AckOptions>) -> Result<()> {
+        let mut ledger = self.ledger.lock().unwrap();
+        let Some(AckOptions { mode: AckMode::AtLeastOnce, timeout_ms, max_redeliveries }) = options else {
+            ledger.timeout = None;
+            ledger.max_redeliveries = None;
+            ledger.pending.clear();
+            ledger.sweeper = None;
+            return Ok(());
+        };
+        let timeout_ms = timeout_ms.unwrap_or(DEFAULT_TIMEOUT_MS);
+        if timeout_ms == 0 {
+            return Err(napi::Error::new(napi::Status::InvalidArg, "timeoutMs must be greater than 0".to_string()));
+        }
+        let timeout = Duration::from_millis(timeout_ms as u64);
+        ledger.timeout = Some(timeout);
+        ledger.max_redeliveries = max_redeliveries;
+        // 定时器只持有弱引用，ledger 和它的定时器不互相引用
+        let weak = Arc::downgrade(&self.ledger);
+        let (events, memory, dead_letters, requeue) =
+            (self.events.clone(), self.memory.clone(), self.dead_letters.clone(), self.requeue.clone());
+        ledger.sweeper = Some(Interval::start(self.events.clone(), SWEEP_PERIOD.min(timeout), move || {
+            if let Some(ledger) = weak.upgrade() {
+                let (events, memory, dead_letters, requeue) = (events.clone(), memory.clone(), dead_letters.clone(), requeue.clone());
+                Acks { ledger, events, memory, dead_letters, requeue }.sweep();
+            }
+        }));
+        Ok(())
+    }
+
+    // socket 关闭时还没 ack 的消息丢弃，设置保留
+    pub fn clear(&self) {
+        self.ledger.lock().unwrap().pending.clear();
+    }
+
+    // 接收循环开始交付；返回的 Attachment 随循环释放时停止向它重新投递
+    pub fn attach<F>(&self, sink: F) -> Attachment
+    where
+        F: Fn(Envelope) + Send + Sync + 'static,
+    {
+        let mut ledger = self.ledger.lock().unwrap();
+        ledger.next_sink += 1;
+        let id = ledger.next_sink;
+        ledger.sink = Some((id, Arc::new(sink)));
+        Attachment { acks: self.clone(), id }
+    }
+
+    // 登记（AtLeastOnce 下）并交给当前的接收循环；没有接收循环时留到下一个循环启动后按超时重新投递
+    fn dispatch(&self, message: &Message, redelivered: u32, attempts: u32) {
+        let mut ledger = self.ledger.lock().unwrap();
+        let mut envelope = Envelope::new(message, redelivered, attempts, self.clone());
+        if let Some(timeout) = ledger.timeout {
+            ledger.next_id += 1;
+            envelope.id = ledger.next_id;
+            let charge = self.memory.charge(Pool::Retained, envelope.size());
+            let deadline = Instant::now() + timeout;
+            let pending = Pending { message: message.clone(), redelivered, attempts, deadline, _charge: charge };
+            ledger.pending.insert(envelope.id, pending);
+        }
+        let sink = ledger.sink.as_ref().map(|(_, sink)| sink.clone());
+        drop(ledger);
+        if let Some(sink) = sink {
+            sink(envelope);
+        }
+    }
+
+    fn redeliver(&self, pending: Pending) {
+        let redelivered = pending.redelivered + 1;
+        let limit = self.ledger.lock().unwrap().max_redeliveries;
+        if limit.is_some_and(|limit| redelivered > limit) {
+            let error = format!("Not acknowledged after {} redeliveries", pending.redelivered);
+            self.dead_letters.deliver(&self.events, pending.message, "redeliveryLimit", Some(error));
+            return;
+        }
+        self.dispatch(&pending.message, redelivered, pending.attempts);
+    }
+
+    // 处理失败：设置了 setRequeue 时带着次数推给重试 socket，否则 AtLeastOnce 下在本地重新投递
+    fn fail(&self, pending: Option<Pending>, body: &[u8], attempts: u32) -> bool {
+        if self.requeue.forward(&self.events, &self.dead_letters, body, attempts) {
+            return true;
+        }
+        match pending {
+            Some(pending) => {
+                self.redeliver(pending);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn settle(&self, id: u64) -> Option<Pending> {
+        self.ledger.lock().unwrap().pending.remove(&id)
+    }
+
+    // 超时没有 ack 的重新投递；没有接收循环时先留着
+    fn sweep(&self) {
+        let now = Instant::now();
+        let expired: Vec<Pending> = {
+            let mut ledger = self.ledger.lock().unwrap();
+            if ledger.sink.is_none() {
+                return;
+            }
+            let ids: Vec<u64> = ledger.pending.iter().filter(|(_, pending)| pending.deadline <= now).map(|(id, _)| *id).collect();
+            ids.iter().filter_map(|id| ledger.pending.remove(id)).collect()
+        };
+        for pending in expired {
+            self.redeliver(pending);
+        }
+    }
+}
+
+// recvEnvelopes 的接收循环持有，循环退出释放回调时一起释放
+pub struct Attachment {
+    acks: Acks,
+    id: u64,
+}
+
+impl Attachment {
+    // 拆掉重新排队的帧头，次数记在 Envelope 上
+    pub fn deliver(&self, message: &Message) {
+        let (attempts, body) = requeue::strip(message.as_slice());
+        if body.len() == message.len() {
+            return self.acks.dispatch(message, 0, 0);
+        }
+        let mut stripped = Message::from(body);
+        stripped.as_mut_header().push_back(message.as_header().as_slice());
+        self.acks.dispatch(&stripped, 0, attempts);
+    }
+}
+
+impl Drop for Attachment {
+    fn drop(&mut self) {
+        let mut ledger = self.acks.ledger.lock().unwrap();
+        if ledger.sink.as_ref().is_some_and(|(id, _)| *id == self.id) {
+            ledger.sink = None;
+        }
+    }
+}
+
+// recvEnvelopes 交付的一条消息；非 raw socket 的消息头总是空的
+#[napi]
+pub struct Envelope {
+    id: u64, // 0 表示 AtMostOnce 下交付的，不跟踪
+    header: Vec<u8>,
+    body: Arc<[u8]>,
+    redelivered: u32,
+    attempts: u32,
+    acks: Acks,
+    settled: Arc<AtomicBool>, // ack/nack 过，或者回调失败后已经处理过
+}
+
+impl Envelope {
+    fn new(message: &Message, redelivered: u32, attempts: u32, acks: Acks) -> Self {
+        Envelope {
+            id: 0,
+            header: message.as_header().as_slice().to_vec(),
+            body: message.as_slice().into(),
+            redelivered,
+            attempts,
+            acks,
+            settled: Arc::default(),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.header.len() + self.body.len()
+    }
+
+    // 回调抛出异常或返回的 Promise reject 时（CallbackErrorPolicy 不是 Throw）按 nack() 处理，
+    // 回调里已经 ack/nack 过的不再处理
+    pub fn on_failure(&self) -> impl FnOnce() + Send + 'static {
+        let (acks, id, settled) = (self.acks.clone(), self.id, self.settled.clone());
+        let (body, attempts) = (self.body.clone(), self.attempts);
+        move || {
+            if let Some(pending) = claim(&acks, id, &settled) {
+                acks.fail(pending, &body, attempts);
+            }
+        }
+    }
+}
+
+// 结算一次交付：已经结算过（包括超时被重新投递）时为 None，否则为登记的消息（AtMostOnce 下为 None）
+fn claim(acks: &Acks, id: u64, settled: &AtomicBool) -> Option<Option<Pending>> {
+    if settled.swap(true, Ordering::SeqCst) {
+        return None;
+    }
+    if id == 0 {
+        return Some(None);
+    }
+    acks.settle(id).map(Some)
+}
+
+#[napi]
+impl Envelope {
+    #[napi(getter)]
+    pub fn header(&self) -> Buffer {
+        self.header.clone().into()
+    }
+
+    #[napi(getter)]
+    pub fn body(&self) -> Buffer {
+        self.body.to_vec().into()
+    }
+
+    // 这是第几次重新投递，首次交付为 0
+    #[napi(getter)]
+    pub fn redelivered(&self) -> u32 {
+        self.redelivered
+    }
+
+    // 之前被重新排队（setRequeue）过几次，跨 worker 累计
+    #[napi(getter)]
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    // 确认处理完成，不再重新投递。返回 false 表示这次交付已经结算过：
+    // AtMostOnce 下交付的、已经 ack/nack 过的，或者已经超时被重新投递的
+    #[napi]
+    pub fn ack(&self) -> bool {
+        matches!(claim(&self.acks, self.id, &self.settled), Some(Some(_)))
+    }
+
+    // 处理失败：requeue 为 true（默认）时设置了 setRequeue 就推给重试 socket，否则 AtLeastOnce 下立即重新投递；
+    // requeue 为 false 时交给死信出口（reason 为 nacked）。返回 false 表示已经结算过，或者没有可做的
+    #[napi]
+    pub fn nack(&self, requeue: Option<bool>) -> bool {
+        let Some(pending) = claim(&self.acks, self.id, &self.settled) else {
+            return false;
+        };
+        if requeue.unwrap_or(true) {
+            return self.acks.fail(pending, &self.body, self.attempts);
+        }
+        match pending {
+            Some(pending) => {
+                self.acks.dead_letters.deliver(&self.acks.events, pending.message, "nacked", None);
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use napi::Result;
+use napi_derive::napi;
+use nng::{Aio, AioResult, Context, Message, Socket};
+use std::collections::VecDeque;
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const DEFAULT_PERCENTILE: f64 = 99.0;
+const DEFAULT_FACTOR: f64 = 2.0;
+const DEFAULT_MIN_MS: u32 = 100;
+const DEFAULT_MAX_MS: u32 = 30_000;
+const DEFAULT_WINDOW: u32 = 200;
+const DEFAULT_MIN_SAMPLES: u32 = 20;
+
+// 请求超时 = 最近 window 次往返时间的 percentile 分位数 × factor，限制在 [minMs, maxMs]。
+// 样本不足 minSamples 时沿用 setTimeouts 设置的接收超时
+#[napi(object)]
+pub struct AdaptiveTimeoutOptions {
+    pub percentile: Option<f64>, // 0 到 100，默认 99
+    pub factor: Option<f64>,     // 默认 2
+    pub min_ms: Option<u32>,     // 默认 100
+    pub max_ms: Option<u32>,     // 默认 30000
+    pub window: Option<u32>,     // 参与计算的最近样本数，默认 200
+    pub min_samples: Option<u32>, // 默认 20
+}
+
+struct Settings {
+    percentile: f64,
+    factor: f64,
+    min: Duration,
+    max: Duration,
+    window: usize,
+    min_samples: usize,
+}
+
+#[derive(Clone)]
+pub struct AdaptiveTimeout {
+    settings: Arc<Settings>,
+    samples: Arc<Mutex<VecDeque<Duration>>>,
+}
+
+fn invalid(reason: String) -> napi::Error {
+    napi::Error::new(napi::Status::InvalidArg, reason)
+}
+
+impl AdaptiveTimeout {
+    pub fn new(options: AdaptiveTimeoutOptions) -> Result<Self> {
+        let percentile = options.percentile.unwrap_or(DEFAULT_PERCENTILE);
+        if !(percentile > 0.0 && percentile <= 100.0) {
+            return Err(invalid(format!("percentile must be in (0, 100], got {}", percentile)));
+        }
+        let factor = options.factor.unwrap_or(DEFAULT_FACTOR);
+        if !(factor >= 1.0 && factor.is_finite()) {
+            return Err(invalid(format!("factor must be at least 1, got {}", factor)));
+        }
+        let min_ms = options.min_ms.unwrap_or(DEFAULT_MIN_MS);
+        let max_ms = options.max_ms.unwrap_or(DEFAULT_MAX_MS);
+        if min_ms > max_ms {
+            return Err(invalid(format!("minMs ({}) is greater than maxMs ({})", min_ms, max_ms)));
+        }
+        let window = options.window.unwrap_or(DEFAULT_WINDOW).max(1) as usize;
+        let min_samples = (options.min_samples.unwrap_or(DEFAULT_MIN_SAMPLES) as usize).clamp(1, window);
+        Ok(AdaptiveTimeout {
+            settings: Arc::new(Settings {
+                percentile,
+                factor,
+                min: Duration::from_millis(min_ms as u64),
+                max: Duration::from_millis(max_ms as u64),
+                window,
+                min_samples,
+            }),
+            samples: Arc::default(),
+        })
+    }
+
+    pub fn record(&self, rtt: Duration) {
+        let mut samples = self.samples.lock().unwrap();
+        if samples.len() == self.settings.window {
+            samples.pop_front();
+        }
+        samples.push_back(rtt);
+    }
+
+    // 样本不足时返回 None
+    pub fn current(&self) -> Option<Duration> {
+        let mut samples: Vec<Duration> = self.samples.lock().unwrap().iter().copied().collect();
+        let settings = &self.settings;
+        if samples.len() < settings.min_samples {
+            return None;
+        }
+        samples.sort();
+        let rank = ((settings.percentile / 100.0) * samples.len() as f64).ceil() as usize;
+        let quantile = samples[rank.clamp(1, samples.len()) - 1];
+        Some(quantile.mul_f64(settings.factor).clamp(settings.min, settings.max))
+    }
+
+    // 在单独的 context 里发请求、等回复，超时只作用于这一个请求。
+    // 超时的请求按超时时长计入样本：延迟整体变高时超时才会跟着放宽，而不是一直超时
+    pub fn request<F: FnOnce()>(
+        &self,
+        socket: &Socket,
+        message: Message,
+        fallback: Option<Duration>,
+        sent: F,
+    ) -> std::result::Result<Message, nng::Error> {
+        let timeout = self.current().or(fallback);
+        let started = Instant::now();
+        let result = round_trip(socket, message, timeout, sent);
+        match &result {
+            Ok(_) => self.record(started.elapsed()),
+            Err(nng::Error::TimedOut) => self.record(started.elapsed()),
+            Err(_) => {}
+        }
+        result
+    }
+}
+
+fn round_trip<F: FnOnce()>(
+    socket: &Socket,
+    message: Message,
+    timeout: Option<Duration>,
+    sent: F,
+) -> std::result::Result<Message, nng::Error> {
+    let context = Context::new(socket)?;
+    let (results, received) = mpsc::channel();
+    // 和 recvOnce 一样，回调拿到的 aio 句柄送回来在这个线程释放
+    let aio = Aio::new(move |aio, result| {
+        let _ = results.send((aio, result));
+    })?;
+    aio.set_timeout(timeout)?;
+    context.send(&aio, message).map_err(|(_, err)| err)?;
+    match received.recv() {
+        Ok((_, AioResult::Send(Ok(())))) => sent(),
+        Ok((_, AioResult::Send(Err((_, err))))) => return Err(err),
+        _ => return Err(nng::Error::Closed),
+    }
+    context.recv(&aio)?;
+    match received.recv() {
+        Ok((_, AioResult::Recv(result))) => result,
+        _ => Err(nng::Error::Closed),
+    }
+}
@@ -0,0 +1,79 @@
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+use nng::Message;
+
+// REQ/REP 的回溯头由若干个 32 位大端整数组成，最后一个是请求 ID，最高位为 1；
+// 前面的是沿途经过的 pipe ID（raw REP 收到的第一个就是来源 pipe）。
+//
+// raw 模式的 SocketWrapper 收发的 Buffer 是“回溯头 + 正文”，下面的函数用来读写它。
+const REQUEST_ID_FLAG: u32 = 0x8000_0000;
+
+// 回溯头的长度，没有以请求 ID 结尾时返回 None
+fn backtrace_len(data: &[u8]) -> Option<usize> {
+    let mut offset = 0;
+    while offset + 4 <= data.len() {
+        let word = u32::from_be_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]]);
+        offset += 4;
+        if word & REQUEST_ID_FLAG != 0 {
+            return Some(offset);
+        }
+    }
+    None
+}
+
+// 请求 ID（不含最高位），没有回溯头时返回 null
+#[napi]
+pub fn request_id(message: Buffer) -> Option<u32> {
+    let len = backtrace_len(&message)?;
+    let id = u32::from_be_bytes([message[len - 4], message[len - 3], message[len - 2], message[len - 1]]);
+    Some(id & !REQUEST_ID_FLAG)
+}
+
+// 替换请求 ID；没有回溯头时在前面加上一个，用于 raw REQ 发出的请求
+#[napi]
+pub fn set_request_id(message: Buffer, request_id: u32) -> Result<Buffer> {
+    if request_id & REQUEST_ID_FLAG != 0 {
+        return Err(napi::Error::new(napi::Status::InvalidArg, "requestId must be less than 2^31".to_string()));
+    }
+    let (head, body) = match backtrace_len(&message) {
+        Some(len) => (&message[..len - 4], &message[len..]),
+        None => (&message[..0], &message[..]),
+    };
+    let mut data = Vec::with_capacity(head.len() + 4 + body.len());
+    data.extend_from_slice(head);
+    data.extend_from_slice(&(request_id | REQUEST_ID_FLAG).to_be_bytes());
+    data.extend_from_slice(body);
+    Ok(data.into())
+}
+
+// 整个回溯头，broker 用它把回复拼回去交给 raw REP
+#[napi]
+pub fn backtrace(message: Buffer) -> Buffer {
+    let len = backtrace_len(&message).unwrap_or(0);
+    message[..len].to_vec().into()
+}
+
+// 去掉回溯头后的正文
+#[napi]
+pub fn strip_backtrace(message: Buffer) -> Buffer {
+    let len = backtrace_len(&message).unwrap_or(0);
+    message[len..].to_vec().into()
+}
+
+// raw socket 收到的消息：把 nng 放在消息头里的回溯放回正文前面
+pub fn encode(message: &Message) -> Vec<u8> {
+    let mut data = Vec::with_capacity(message.as_header().len() + message.len());
+    data.extend_from_slice(message.as_header().as_slice());
+    data.extend_from_slice(message.as_slice());
+    data
+}
+
+// raw socket 要发出的消息：拆出回溯头放进 nng 的消息头
+pub fn decode(data: &[u8]) -> Result<Message> {
+    let len = backtrace_len(data).ok_or_else(|| {
+        napi::Error::new(napi::Status::InvalidArg, "Raw message has no request id backtrace".to_string())
+    })?;
+    let mut message = Message::from(&data[len..]);
+    message.as_mut_header().push_back(&data[..len]);
+    Ok(message)
+}
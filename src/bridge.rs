@@ -0,0 +1,208 @@
+use napi::bindgen_prelude::*;
+use napi::threadsafe_function::ThreadsafeFunction;
+use napi_derive::napi;
+use nng::options::{Options, RecvBufferSize, SendBufferSize};
+use nng::{Error as NngError, Protocol, RawSocket, Socket};
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::Arc;
+
+use crate::events::{EventEmitter, SocketEvent};
+use crate::guard;
+
+// 两头的缓冲区默认能放下的消息数，nng 最多 8192
+const DEFAULT_BUFFER_SIZE: u32 = 1024;
+
+fn failed(reason: String) -> napi::Error {
+    napi::Error::new(napi::Status::GenericFailure, reason)
+}
+
+// 桥接的协议，前一半在 from 一侧，后一半在 to 一侧
+#[napi]
+pub enum BridgeProtocol {
+    PushPull, // from 上的 Pull 收，to 上的 Push 发
+    PubSub,   // from 上的 Sub 收全部主题，to 上的 Pub 发
+    ReqRep,   // from 上的 Rep 收请求，to 上的 Req 转给服务端，应答原路返回
+    Pair,     // 两头都是 Pair1
+    Bus,
+}
+
+impl BridgeProtocol {
+    // (from 一侧, to 一侧, 是否双向转发)
+    fn sockets(&self) -> (Protocol, Protocol, bool) {
+        match self {
+            BridgeProtocol::PushPull => (Protocol::Pull0, Protocol::Push0, false),
+            BridgeProtocol::PubSub => (Protocol::Sub0, Protocol::Pub0, false),
+            BridgeProtocol::ReqRep => (Protocol::Rep0, Protocol::Req0, true),
+            BridgeProtocol::Pair => (Protocol::Pair1, Protocol::Pair1, true),
+            BridgeProtocol::Bus => (Protocol::Bus0, Protocol::Bus0, true),
+        }
+    }
+}
+
+#[napi(object)]
+pub struct BridgeOptions {
+    pub from: String,
+    pub to: String,
+    pub protocol_pair: BridgeProtocol,
+    pub dial_from: Option<bool>,  // 默认在 from 上监听，等本地的生产者连上来
+    pub listen_to: Option<bool>,  // 默认拨号到 to，远端晚于桥启动时自动重连
+    pub buffer_size: Option<u32>, // 两头的收发缓冲区（条数），默认 1024
+}
+
+#[napi(object)]
+pub struct BridgeStats {
+    pub forwarded: i64, // from 到 to
+    pub returned: i64,  // to 到 from，只有双向的协议有
+    pub bytes: i64,     // 两个方向转发的正文字节数
+    pub blocked: i64,   // 目的端发送缓冲区满、转发停下来等待的次数
+    pub dropped: i64,   // 发送出错丢掉的
+}
+
+#[derive(Default)]
+struct Counters {
+    forwarded: AtomicI64,
+    returned: AtomicI64,
+    bytes: AtomicI64,
+    blocked: AtomicI64,
+    dropped: AtomicI64,
+}
+
+// 在两个地址之间原样转发消息，收发都在原生线程里，不经过 JS 线程。
+// 两头都是 raw socket，Req/Rep 的回溯头跟着消息走，应答能找回发请求的那一端。
+// 目的端的缓冲区满时转发停下来等待、不再从来源端读，来源端的缓冲区满了以后
+// Push、Req、Pair、Bus 的发送方随之被阻塞；Pub/Sub 按 pub/sub 的规则在发布端丢弃
+#[napi]
+pub struct Bridge {
+    sockets: Option<(Socket, Socket)>,
+    counters: Arc<Counters>,
+    events: EventEmitter,
+    is_closing: Arc<AtomicBool>,
+}
+
+#[napi]
+impl Bridge {
+    #[napi]
+    pub fn stats(&self) -> BridgeStats {
+        BridgeStats {
+            forwarded: self.counters.forwarded.load(Ordering::SeqCst),
+            returned: self.counters.returned.load(Ordering::SeqCst),
+            bytes: self.counters.bytes.load(Ordering::SeqCst),
+            blocked: self.counters.blocked.load(Ordering::SeqCst),
+            dropped: self.counters.dropped.load(Ordering::SeqCst),
+        }
+    }
+
+    // recvFailed、sendFailed
+    #[napi]
+    pub fn on_event(&self, callback: ThreadsafeFunction<SocketEvent>) {
+        self.events.set(callback);
+    }
+
+    // 缓冲区里还没转发的消息随之丢弃
+    #[napi]
+    pub fn close(&mut self) {
+        self.is_closing.store(true, Ordering::SeqCst);
+        if let Some((from, to)) = self.sockets.take() {
+            from.close();
+            to.close();
+        }
+        self.events.clear();
+    }
+}
+
+#[napi]
+pub fn bridge(options: BridgeOptions) -> Result<Bridge> {
+    let (from_protocol, to_protocol, both_ways) = options.protocol_pair.sockets();
+    let buffer_size = options.buffer_size.unwrap_or(DEFAULT_BUFFER_SIZE) as i32;
+    let from = RawSocket::new(from_protocol).map_err(|err| failed(format!("Socket creation failed: {:?}", err)))?.socket;
+    let to = match RawSocket::new(to_protocol) {
+        Ok(raw) => raw.socket,
+        Err(err) => {
+            from.close();
+            return Err(failed(format!("Socket creation failed: {:?}", err)));
+        }
+    };
+    let opened = [&from, &to]
+        .into_iter()
+        .try_for_each(|socket| {
+            socket.set_opt::<RecvBufferSize>(buffer_size).and_then(|_| socket.set_opt::<SendBufferSize>(buffer_size))
+        })
+        .map_err(|err| napi::Error::new(napi::Status::InvalidArg, format!("Invalid bufferSize: {:?}", err)))
+        .and_then(|_| attach(&from, &options.from, options.dial_from.unwrap_or(false)))
+        .and_then(|_| attach(&to, &options.to, !options.listen_to.unwrap_or(false)));
+    if let Err(err) = opened {
+        from.close();
+        to.close();
+        return Err(err);
+    }
+
+    let bridge = Bridge {
+        sockets: Some((from.clone(), to.clone())),
+        counters: Arc::default(),
+        events: EventEmitter::default(),
+        is_closing: Arc::new(AtomicBool::new(false)),
+    };
+    let relay = Relay {
+        counters: bridge.counters.clone(),
+        events: bridge.events.clone(),
+        is_closing: bridge.is_closing.clone(),
+    };
+    if both_ways {
+        let back = Relay { counters: relay.counters.clone(), events: relay.events.clone(), is_closing: relay.is_closing.clone() };
+        let (from, to) = (from.clone(), to.clone());
+        guard::spawn(bridge.events.clone(), "Bridge return", move || back.run(&to, &from, &back.counters.returned));
+    }
+    guard::spawn(bridge.events.clone(), "Bridge forward", move || relay.run(&from, &to, &relay.counters.forwarded));
+    Ok(bridge)
+}
+
+fn attach(socket: &Socket, url: &str, dial: bool) -> Result<()> {
+    if dial {
+        socket.dial_async(url).map_err(|err| failed(format!("Connection failed: {:?}", err)))
+    } else {
+        socket.listen(url).map_err(|err| failed(format!("Listen failed: {:?}", err)))
+    }
+}
+
+// 一个方向的转发
+struct Relay {
+    counters: Arc<Counters>,
+    events: EventEmitter,
+    is_closing: Arc<AtomicBool>,
+}
+
+impl Relay {
+    fn run(&self, source: &Socket, target: &Socket, relayed: &AtomicI64) {
+        loop {
+            let message = match source.recv() {
+                Ok(message) => message,
+                Err(NngError::Closed) => return,
+                Err(_) if self.is_closing.load(Ordering::SeqCst) => return,
+                Err(err) => {
+                    self.events.warn("recvFailed", format!("Bridge receive failed: {:?}", err));
+                    continue;
+                }
+            };
+            let len = message.len() as i64;
+            // 先试一次不阻塞的发送，满了再计一次等待
+            let result = match target.try_send(message) {
+                Err((message, NngError::TryAgain)) => {
+                    self.counters.blocked.fetch_add(1, Ordering::SeqCst);
+                    target.send(message)
+                }
+                result => result,
+            };
+            match result {
+                Ok(()) => {
+                    relayed.fetch_add(1, Ordering::SeqCst);
+                    self.counters.bytes.fetch_add(len, Ordering::SeqCst);
+                }
+                Err((_, NngError::Closed)) => return,
+                Err((_, err)) => {
+                    self.counters.dropped.fetch_add(1, Ordering::SeqCst);
+                    self.events.warn("sendFailed", format!("Bridge send failed: {:?}", err));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use napi::bindgen_prelude::{External, FromNapiValue, ToNapiValue};
+use napi::threadsafe_function::{ThreadSafeCallContext, ThreadsafeFunction};
+use napi::{sys, CallContext, Env, JsFunction, JsUndefined, JsUnknown, NapiRaw, NapiValue, Result};
+use napi_derive::{js_function, napi};
+use std::ptr;
+use std::sync::{Arc, Mutex};
+
+use crate::events::{EventEmitter, SocketEvent};
+
+// 接收回调抛出异常时怎么处理；对之后启动的 recv 系列循环生效
+#[napi]
+pub enum CallbackErrorPolicy {
+    Throw, // 默认，和以前一样成为未捕获异常，原生侧不知情
+    Log,   // 记成 code 为 callbackThrew 的 warning 事件，继续接收
+    Emit,  // 发 callbackError 事件，继续接收
+    Stop,  // 发 callbackError 事件后停止接收循环，recv 返回的 Promise 随之 resolve
+}
+
+// 持有 JS 函数的强引用，随线程安全函数的回调一起在 JS 线程上释放
+pub struct FunctionRef {
+    env: sys::napi_env,
+    reference: sys::napi_ref,
+}
+
+// 只在 JS 线程上使用和释放
+unsafe impl Send for FunctionRef {}
+
+impl Drop for FunctionRef {
+    fn drop(&mut self) {
+        unsafe { sys::napi_delete_reference(self.env, self.reference) };
+    }
+}
+
+impl FunctionRef {
+    pub fn retain(env: &Env, function: JsFunction) -> Result<Self> {
+        let mut reference = ptr::null_mut();
+        let status = unsafe { sys::napi_create_reference(env.raw(), function.raw(), 1, &mut reference) };
+        if status != sys::Status::napi_ok {
+            return Err(napi::Error::new(napi::Status::GenericFailure, "Failed to retain callback".to_string()));
+        }
+        Ok(FunctionRef { env: env.raw(), reference })
+    }
+
+    // 函数抛出的异常从 JS 引擎里取出来放进 Err，不让它变成未捕获异常
+    pub fn call(&self, env: &Env, args: &[JsUnknown]) -> Result<JsUnknown> {
+        let mut value = ptr::null_mut();
+        let status = unsafe { sys::napi_get_reference_value(env.raw(), self.reference, &mut value) };
+        if status != sys::Status::napi_ok {
+            return Err(napi::Error::new(napi::Status::GenericFailure, "Callback was released".to_string()));
+        }
+        let function = unsafe { JsFunction::from_raw_unchecked(env.raw(), value) };
+        function.call(None, args).map_err(|err| {
+            let mut pending = false;
+            unsafe { sys::napi_is_exception_pending(env.raw(), &mut pending) };
+            if !pending {
+                return err;
+            }
+            let mut exception = ptr::null_mut();
+            unsafe { sys::napi_get_and_clear_last_exception(env.raw(), &mut exception) };
+            let exception = unsafe { JsUnknown::from_raw_unchecked(env.raw(), exception) };
+            let text = exception.coerce_to_string().and_then(|text| text.into_utf8()).and_then(|text| text.into_owned());
+            napi::Error::new(napi::Status::GenericFailure, text.unwrap_or(err.reason))
+        })
+    }
+}
+
+type Stop = Box<dyn Fn() + Send>;
+type Failed = Box<dyn FnOnce() + Send>;
+type Settled = Mutex<Option<Box<dyn FnOnce(std::result::Result<(), String>) + Send>>>;
+
+// 接收循环启动后才知道怎么停下它，Stop 策略先拿着这个空位；
+// failed 是回调处理某条消息失败时对这条消息的补救（如 recvEnvelopes 把它重新排队），交付前设置
+#[derive(Clone, Default)]
+pub struct RecvHooks {
+    stop: Arc<Mutex<Option<Stop>>>,
+    failed: Arc<Mutex<Option<Failed>>>,
+}
+
+impl RecvHooks {
+    pub fn arm_stop<F>(&self, stop: F)
+    where
+        F: Fn() + Send + 'static,
+    {
+        *self.stop.lock().unwrap() = Some(Box::new(stop));
+    }
+
+    // 只在 JS 线程上、交付这条消息之前调用
+    pub fn set_failed<F>(&self, failed: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        *self.failed.lock().unwrap() = Some(Box::new(failed));
+    }
+
+    fn stop(&self) {
+        if let Some(stop) = self.stop.lock().unwrap().as_ref() {
+            stop();
+        }
+    }
+
+    fn take_failed(&self) -> Option<Failed> {
+        self.failed.lock().unwrap().take()
+    }
+}
+
+// 把一个值转成只有它一个参数的参数表
+pub fn single<T: ToNapiValue>(env: &Env, value: T) -> Result<Vec<JsUnknown>> {
+    let value = unsafe { T::to_napi_value(env.raw(), value)? };
+    Ok(vec![unsafe { JsUnknown::from_raw_unchecked(env.raw(), value) }])
+}
+
+// 把接收回调包装成线程安全函数，args 在 JS 线程上生成回调的参数（不含开头的 err）。
+// Throw 直接让 napi 调用回调；其余策略由这里自己调用，同步抛出的异常和返回的 Promise reject 都按策略处理
+pub fn recv_callback<T, F>(
+    env: &Env,
+    callback: JsFunction,
+    max_queue_size: usize,
+    policy: CallbackErrorPolicy,
+    events: EventEmitter,
+    hooks: RecvHooks,
+    mut args: F,
+) -> Result<ThreadsafeFunction<T>>
+where
+    T: Send + 'static,
+    F: FnMut(&Env, T) -> Result<Vec<JsUnknown>> + Send + 'static,
+{
+    if let CallbackErrorPolicy::Throw = policy {
+        return env.create_threadsafe_function(&callback, max_queue_size, move |ctx: ThreadSafeCallContext<T>| args(&ctx.env, ctx.value));
+    }
+    let callback = FunctionRef::retain(env, callback)?;
+    // 真正的调用在下面的回调里完成，线程安全函数本身挂一个空函数
+    let noop = env.create_function("recvCallback", noop)?;
+    env.create_threadsafe_function(&noop, max_queue_size, move |ctx: ThreadSafeCallContext<T>| {
+        let mut argv = vec![ctx.env.get_null()?.into_unknown()];
+        argv.extend(args(&ctx.env, ctx.value)?);
+        let failed = hooks.take_failed();
+        match callback.call(&ctx.env, &argv) {
+            Err(err) => fail(policy, &events, &hooks, err.reason, failed),
+            Ok(result) if result.is_promise()? => {
+                let (events, hooks) = (events.clone(), hooks.clone());
+                on_settle(&ctx.env, result, move |outcome| {
+                    if let Err(reason) = outcome {
+                        fail(policy, &events, &hooks, reason, failed);
+                    }
+                })?;
+            }
+            Ok(_) => {}
+        }
+        Ok(Vec::<JsUnknown>::new())
+    })
+}
+
+fn fail(policy: CallbackErrorPolicy, events: &EventEmitter, hooks: &RecvHooks, reason: String, failed: Option<Failed>) {
+    match policy {
+        CallbackErrorPolicy::Log => events.warn("callbackThrew", format!("Recv callback threw: {}", reason)),
+        CallbackErrorPolicy::Stop => {
+            events.emit(SocketEvent::new("callbackError").code("stopped").message(reason));
+            hooks.stop();
+        }
+        _ => events.emit(SocketEvent::new("callbackError").message(reason)),
+    }
+    if let Some(failed) = failed {
+        failed();
+    }
+}
+
+// 和 on_abort 一样，把回调放进 External 再 bind 到固定的 JS 函数上，挂到 promise.then：
+// fulfilled 时回调 Ok，rejected 时回调 Err（原因转成字符串）
+pub fn on_settle<F>(env: &Env, promise: JsUnknown, callback: F) -> Result<()>
+where
+    F: FnOnce(std::result::Result<(), String>) + Send + 'static,
+{
+    let external: Settled = Mutex::new(Some(Box::new(callback)));
+    let external = unsafe { External::to_napi_value(env.raw(), External::new(external))? };
+    let bind = |function: JsFunction| -> Result<JsUnknown> {
+        let function = function.coerce_to_object()?;
+        let bind: JsFunction = function.get_named_property("bind")?;
+        let external = unsafe { JsUnknown::from_raw_unchecked(env.raw(), external) };
+        bind.call(Some(&function), &[env.get_null()?.into_unknown(), external])
+    };
+    let fulfilled = bind(env.create_function("onFulfilled", fulfilled)?)?;
+    let rejected = bind(env.create_function("onRejected", rejected)?)?;
+    let promise = promise.coerce_to_object()?;
+    let then: JsFunction = promise.get_named_property("then")?;
+    then.call(Some(&promise), &[fulfilled, rejected])?;
+    Ok(())
+}
+
+fn settle(ctx: &CallContext, outcome: std::result::Result<(), String>) -> Result<JsUndefined> {
+    let external = ctx.get::<JsUnknown>(0)?;
+    let callback = unsafe { External::<Settled>::from_napi_value(ctx.env.raw(), external.raw())? };
+    if let Some(callback) = callback.lock().unwrap().take() {
+        callback(outcome);
+    }
+    ctx.env.get_undefined()
+}
+
+#[js_function(2)]
+fn fulfilled(ctx: CallContext) -> Result<JsUndefined> {
+    settle(&ctx, Ok(()))
+}
+
+#[js_function(2)]
+fn rejected(ctx: CallContext) -> Result<JsUndefined> {
+    let reason = ctx.get::<JsUnknown>(1)?.coerce_to_string()?.into_utf8()?.into_owned()?;
+    settle(&ctx, Err(reason))
+}
+
+#[js_function]
+pub fn noop(ctx: CallContext) -> Result<JsUndefined> {
+    ctx.env.get_undefined()
+}
@@ -0,0 +1,155 @@
+use napi::bindgen_prelude::Buffer;
+use napi_derive::napi;
+use nng::Message;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use crate::pipes::now_ms;
+use crate::recording::Recorder;
+
+// 抓包记录的一条消息。direction 为 "sent" 或 "received"；raw socket 的消息头单独放在 header 里，
+// 接收的消息带上来源 pipe。at 是 Unix 毫秒
+#[napi(object)]
+pub struct CapturedMessage {
+    pub direction: String,
+    pub data: Buffer,
+    pub header: Option<Buffer>,
+    pub pipe: Option<u32>,
+    pub at: f64,
+}
+
+// 复制出来的消息内容，交给 napi 之前不持有 JS 的内存
+pub struct Entry {
+    pub sent: bool,
+    pub data: Vec<u8>,
+    pub header: Vec<u8>,
+    pub pipe: Option<u32>,
+    pub at: f64,
+}
+
+#[derive(Default)]
+struct Ring {
+    limit: usize,
+    entries: VecDeque<Entry>,
+}
+
+#[derive(Default)]
+struct Sinks {
+    ring: Option<Ring>,
+    recorder: Option<Recorder>, // record() 写的录制文件
+}
+
+impl Sinks {
+    fn enabled(&self) -> bool {
+        self.ring.is_some() || self.recorder.is_some()
+    }
+}
+
+// 最近收发的消息，环形保留 limit 条，用来事后排查协议问题；也可以同时写进录制文件。探测帧不记录
+#[derive(Clone, Default)]
+pub struct Capture {
+    sinks: Arc<Mutex<Sinks>>,
+}
+
+// 开启抓包时发送前复制的消息，发送成功后交给 Capture::sent
+pub struct Snapshot(Entry);
+
+impl Capture {
+    // 0 关闭并清空；缩小时丢掉最旧的
+    pub fn set_limit(&self, limit: usize) {
+        let mut sinks = self.sinks.lock().unwrap();
+        if limit == 0 {
+            sinks.ring = None;
+            return;
+        }
+        let ring = sinks.ring.get_or_insert_with(Ring::default);
+        ring.limit = limit;
+        while ring.entries.len() > limit {
+            ring.entries.pop_front();
+        }
+    }
+
+    // 没有开启时不复制
+    pub fn snapshot(&self, message: &Message) -> Option<Snapshot> {
+        if !self.sinks.lock().unwrap().enabled() {
+            return None;
+        }
+        Some(Snapshot(entry(true, message)))
+    }
+
+    pub fn sent(&self, snapshot: Option<Snapshot>) {
+        if let Some(Snapshot(entry)) = snapshot {
+            self.push(entry);
+        }
+    }
+
+    pub fn received(&self, message: &Message) {
+        if self.sinks.lock().unwrap().enabled() {
+            self.push(entry(false, message));
+        }
+    }
+
+    // 按时间顺序，最旧的在前
+    pub fn messages(&self) -> Vec<CapturedMessage> {
+        let sinks = self.sinks.lock().unwrap();
+        let entries = match sinks.ring.as_ref() {
+            Some(ring) => &ring.entries,
+            None => return Vec::new(),
+        };
+        entries
+            .iter()
+            .map(|entry| CapturedMessage {
+                direction: if entry.sent { "sent" } else { "received" }.to_string(),
+                data: entry.data.clone().into(),
+                header: (!entry.header.is_empty()).then(|| entry.header.clone().into()),
+                pipe: entry.pipe,
+                at: entry.at,
+            })
+            .collect()
+    }
+
+    // 开始录制，替换正在进行的录制
+    pub fn record(&self, recorder: Recorder) {
+        let previous = self.sinks.lock().unwrap().recorder.replace(recorder);
+        if let Some(previous) = previous {
+            previous.finish();
+        }
+    }
+
+    // 停止录制，返回前文件已经写完
+    pub fn stop_recording(&self) {
+        let recorder = self.sinks.lock().unwrap().recorder.take();
+        if let Some(recorder) = recorder {
+            recorder.finish();
+        }
+    }
+
+    fn push(&self, entry: Entry) {
+        let mut sinks = self.sinks.lock().unwrap();
+        if let Some(recorder) = &sinks.recorder {
+            recorder.write(&entry);
+        }
+        if let Some(ring) = sinks.ring.as_mut() {
+            if ring.entries.len() >= ring.limit {
+                ring.entries.pop_front();
+            }
+            ring.entries.push_back(entry);
+        }
+    }
+}
+
+fn entry(sent: bool, message: &Message) -> Entry {
+    Entry {
+        sent,
+        data: message.as_slice().to_vec(),
+        header: message.as_header().as_slice().to_vec(),
+        pipe: if sent { None } else { received_from(message) },
+        at: now_ms(),
+    }
+}
+
+// Message::pipe 要 &mut，这里只读
+pub fn received_from(message: &Message) -> Option<u32> {
+    let id = unsafe { nng::ffi::nng_pipe_id(nng::ffi::nng_msg_get_pipe(message.nng_msg())) };
+    (id > 0).then_some(id as u32)
+}
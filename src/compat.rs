@@ -0,0 +1,177 @@
+use napi::bindgen_prelude::{Buffer, Either, Either4};
+use napi::Result;
+use nng::options::protocol::pubsub::{Subscribe, Unsubscribe};
+use nng::options::protocol::reqrep::ResendTime;
+use nng::options::protocol::survey::SurveyTime;
+use nng::options::transport::tcp::NoDelay;
+use nng::options::{
+    MaxTtl, Options, ReconnectMaxTime, ReconnectMinTime, RecvBufferSize, RecvMaxSize, RecvTimeout, SendBufferSize,
+    SendTimeout, SocketName,
+};
+use nng::Socket;
+use std::time::Duration;
+
+// 旧 nanomsg 的选项名。接受 "NN_SNDTIMEO"、"SNDTIMEO"，以及 nanomsg npm 包的方法名（sndtimeo、chan 等）
+#[derive(Clone, Copy)]
+enum NnOption {
+    SndTimeo,
+    RcvTimeo,
+    SndBuf,
+    RcvBuf,
+    RcvMaxSize,
+    ReconnectIvl,
+    ReconnectIvlMax,
+    Linger,
+    MaxTtl,
+    SocketName,
+    SubSubscribe,
+    SubUnsubscribe,
+    ReqResendIvl,
+    SurveyorDeadline,
+    TcpNoDelay,
+    SndPrio,
+    RcvPrio,
+    Ipv4Only,
+}
+
+// nanomsg 的 NN_SNDBUF/NN_RCVBUF 以字节计，nng 的队列以消息数计，上限 8192
+const NNG_MAX_BUFFER: i64 = 8192;
+
+fn lookup(name: &str) -> Option<NnOption> {
+    let upper = name.to_ascii_uppercase();
+    let key = upper.strip_prefix("NN_").unwrap_or(&upper);
+    let option = match key {
+        "SNDTIMEO" => NnOption::SndTimeo,
+        "RCVTIMEO" => NnOption::RcvTimeo,
+        "SNDBUF" => NnOption::SndBuf,
+        "RCVBUF" => NnOption::RcvBuf,
+        "RCVMAXSIZE" => NnOption::RcvMaxSize,
+        "RECONNECT_IVL" | "RECONN" => NnOption::ReconnectIvl,
+        "RECONNECT_IVL_MAX" | "MAXRECONN" => NnOption::ReconnectIvlMax,
+        "LINGER" => NnOption::Linger,
+        "MAXTTL" => NnOption::MaxTtl,
+        "SOCKET_NAME" => NnOption::SocketName,
+        "SUB_SUBSCRIBE" | "CHAN" => NnOption::SubSubscribe,
+        "SUB_UNSUBSCRIBE" | "RMCHAN" => NnOption::SubUnsubscribe,
+        "REQ_RESEND_IVL" => NnOption::ReqResendIvl,
+        "SURVEYOR_DEADLINE" => NnOption::SurveyorDeadline,
+        "TCP_NODELAY" | "TCPNODELAY" => NnOption::TcpNoDelay,
+        "SNDPRIO" => NnOption::SndPrio,
+        "RCVPRIO" => NnOption::RcvPrio,
+        "IPV4ONLY" | "IPV6" => NnOption::Ipv4Only,
+        _ => return None,
+    };
+    Some(option)
+}
+
+pub type NnValue = Either4<f64, bool, String, Buffer>;
+
+fn invalid(reason: String) -> napi::Error {
+    napi::Error::new(napi::Status::InvalidArg, reason)
+}
+
+fn unsupported(name: &str, reason: &str) -> napi::Error {
+    napi::Error::new(napi::Status::GenericFailure, format!("{} is not supported: {}", name, reason))
+}
+
+fn failed(name: &str, err: nng::Error) -> napi::Error {
+    napi::Error::new(napi::Status::GenericFailure, format!("Failed to set {}: {:?}", name, err))
+}
+
+fn number(name: &str, value: &NnValue) -> Result<i64> {
+    match value {
+        Either4::A(number) if number.fract() == 0.0 => Ok(*number as i64),
+        _ => Err(invalid(format!("{} takes an integer", name))),
+    }
+}
+
+// nanomsg 用 -1 表示不超时，nng 用 None
+fn millis(name: &str, value: &NnValue) -> Result<Option<Duration>> {
+    match number(name, value)? {
+        -1 => Ok(None),
+        ms if ms >= 0 => Ok(Some(Duration::from_millis(ms as u64))),
+        ms => Err(invalid(format!("{} must be -1 or a non-negative number of milliseconds, got {}", name, ms))),
+    }
+}
+
+fn to_millis(duration: Option<Duration>) -> f64 {
+    duration.map_or(-1.0, |duration| duration.as_millis() as f64)
+}
+
+fn bytes(name: &str, value: NnValue) -> Result<Vec<u8>> {
+    match value {
+        Either4::C(text) => Ok(text.into_bytes()),
+        Either4::D(buffer) => Ok(buffer.to_vec()),
+        _ => Err(invalid(format!("{} takes a string or Buffer", name))),
+    }
+}
+
+// 数值直接当作消息数，再限制在 nng 的范围内
+fn buffer_len(name: &str, value: &NnValue) -> Result<i32> {
+    let len = number(name, value)?;
+    if len < 0 {
+        return Err(invalid(format!("{} must not be negative, got {}", name, len)));
+    }
+    Ok(len.min(NNG_MAX_BUFFER) as i32)
+}
+
+pub fn set(socket: &Socket, name: &str, value: NnValue) -> Result<()> {
+    let option = lookup(name).ok_or_else(|| invalid(format!("Unknown nanomsg option: {}", name)))?;
+    match option {
+        NnOption::SndTimeo => socket.set_opt::<SendTimeout>(millis(name, &value)?),
+        NnOption::RcvTimeo => socket.set_opt::<RecvTimeout>(millis(name, &value)?),
+        NnOption::SndBuf => socket.set_opt::<SendBufferSize>(buffer_len(name, &value)?),
+        NnOption::RcvBuf => socket.set_opt::<RecvBufferSize>(buffer_len(name, &value)?),
+        // nanomsg 的 -1 和 nng 的 0 都表示不限
+        NnOption::RcvMaxSize => socket.set_opt::<RecvMaxSize>(number(name, &value)?.max(0) as usize),
+        NnOption::ReconnectIvl => socket.set_opt::<ReconnectMinTime>(millis(name, &value)?),
+        NnOption::ReconnectIvlMax => socket.set_opt::<ReconnectMaxTime>(millis(name, &value)?),
+        // nng 关闭 socket 时不等待未发送的消息，需要时用 drain
+        NnOption::Linger => {
+            millis(name, &value)?;
+            Ok(())
+        }
+        NnOption::MaxTtl => {
+            let ttl = number(name, &value)?;
+            if !(1..=255).contains(&ttl) {
+                return Err(invalid(format!("{} must be between 1 and 255, got {}", name, ttl)));
+            }
+            socket.set_opt::<MaxTtl>(ttl as u8)
+        }
+        NnOption::SocketName => match value {
+            Either4::C(text) => socket.set_opt::<SocketName>(text),
+            _ => return Err(invalid(format!("{} takes a string", name))),
+        },
+        NnOption::SubSubscribe => socket.set_opt::<Subscribe>(bytes(name, value)?),
+        NnOption::SubUnsubscribe => socket.set_opt::<Unsubscribe>(bytes(name, value)?),
+        NnOption::ReqResendIvl => socket.set_opt::<ResendTime>(millis(name, &value)?),
+        NnOption::SurveyorDeadline => socket.set_opt::<SurveyTime>(millis(name, &value)?),
+        NnOption::TcpNoDelay => match value {
+            Either4::B(flag) => socket.set_opt::<NoDelay>(flag),
+            Either4::A(number) => socket.set_opt::<NoDelay>(number != 0.0),
+            _ => return Err(invalid(format!("{} takes a boolean", name))),
+        },
+        NnOption::SndPrio | NnOption::RcvPrio => return Err(unsupported(name, "nng has no endpoint priorities")),
+        NnOption::Ipv4Only => return Err(unsupported(name, "use tcp4:// or tcp6:// URLs instead")),
+    }
+    .map_err(|err| failed(name, err))
+}
+
+// 只有 nng 能读出来的选项可以读；时间以毫秒返回，不超时为 -1
+pub fn get(socket: &Socket, name: &str) -> Result<Either<f64, String>> {
+    let option = lookup(name).ok_or_else(|| invalid(format!("Unknown nanomsg option: {}", name)))?;
+    let read = |err: nng::Error| napi::Error::new(napi::Status::GenericFailure, format!("Failed to read {}: {:?}", name, err));
+    let value = match option {
+        NnOption::SndTimeo => to_millis(socket.get_opt::<SendTimeout>().map_err(read)?),
+        NnOption::RcvTimeo => to_millis(socket.get_opt::<RecvTimeout>().map_err(read)?),
+        NnOption::SndBuf => socket.get_opt::<SendBufferSize>().map_err(read)? as f64,
+        NnOption::RcvBuf => socket.get_opt::<RecvBufferSize>().map_err(read)? as f64,
+        NnOption::MaxTtl => socket.get_opt::<MaxTtl>().map_err(read)? as f64,
+        NnOption::ReqResendIvl => to_millis(socket.get_opt::<ResendTime>().map_err(read)?),
+        NnOption::SurveyorDeadline => to_millis(socket.get_opt::<SurveyTime>().map_err(read)?),
+        NnOption::Linger => 0.0,
+        NnOption::SocketName => return Ok(Either::B(socket.get_opt::<SocketName>().map_err(read)?)),
+        _ => return Err(unsupported(name, "nng cannot read this option back")),
+    };
+    Ok(Either::A(value))
+}
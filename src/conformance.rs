@@ -0,0 +1,218 @@
+use napi::bindgen_prelude::*;
+use napi::JsObject;
+use napi_derive::napi;
+use nng::options::protocol::pubsub::Subscribe;
+use nng::options::{Options, RecvTimeout, SendTimeout};
+use nng::{ffi, Listener, Message, Protocol, Socket};
+use std::ffi::{c_char, c_int};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::events::EventEmitter;
+use crate::guard;
+use crate::transport::Transport;
+
+type Resolver = Box<dyn FnOnce(Env) -> Result<Vec<ConformanceCheck>> + Send>;
+
+// 单步收发的超时，对端没按协议回应时不会一直卡住
+const STEP_TIMEOUT: Duration = Duration::from_secs(1);
+// 等对端的 pipe 挂上，pub/sub 和 bus 在此之前发出的消息会被直接丢掉
+const ATTACH_WAIT: Duration = Duration::from_millis(50);
+
+// 一组协议的检查结果，detail 为失败原因
+#[napi(object)]
+pub struct ConformanceCheck {
+    pub protocol: String, // 如 "Req0/Rep0"
+    pub passed: bool,
+    pub detail: Option<String>,
+}
+
+type Check = fn(&str) -> std::result::Result<(), String>;
+
+const CHECKS: &[(&str, Check)] = &[
+    ("Req0/Rep0", req_rep),
+    ("Push0/Pull0", push_pull),
+    ("Pub0/Sub0", pub_sub),
+    ("Pair0", pair0),
+    ("Pair1", pair1),
+    ("Surveyor0/Respondent0", survey),
+    ("Bus0", bus),
+];
+
+// 用本模块链接的 nng 在两端各开一个参考 socket，按每种协议的语义走一遍，
+// 升级 nng 或改动传输层后可以在 CI 里发现协议行为的回归。
+// transport 支持 Inproc（默认）、Tcp（回环地址的随机端口）和 Ipc（临时目录下的文件）
+#[napi(ts_return_type = "Promise<Array<ConformanceCheck>>")]
+pub fn run_conformance(env: Env, transport: Option<Transport>) -> Result<JsObject> {
+    let transport = transport.unwrap_or(Transport::Inproc);
+    if !matches!(transport, Transport::Inproc | Transport::Tcp | Transport::Ipc) {
+        return Err(napi::Error::new(
+            napi::Status::InvalidArg,
+            "Conformance checks only run over Inproc, Tcp and Ipc".to_string(),
+        ));
+    }
+    let (deferred, promise) = env.create_deferred::<Vec<ConformanceCheck>, Resolver>()?;
+    guard::spawn(EventEmitter::default(), "Conformance", move || {
+        let checks = CHECKS
+            .iter()
+            .map(|(protocol, check)| {
+                let result = check(&scratch_url(transport));
+                ConformanceCheck { protocol: protocol.to_string(), passed: result.is_ok(), detail: result.err() }
+            })
+            .collect();
+        deferred.resolve(Box::new(move |_| Ok(checks)));
+    });
+    Ok(promise)
+}
+
+fn scratch_url(transport: Transport) -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let count = COUNTER.fetch_add(1, Ordering::SeqCst);
+    match transport {
+        Transport::Tcp => "tcp://127.0.0.1:0".to_string(),
+        Transport::Ipc => {
+            let path = std::env::temp_dir().join(format!("napi-nng-conformance-{}-{}", std::process::id(), count));
+            format!("ipc://{}", path.display())
+        }
+        _ => format!("inproc://napi-nng-conformance-{}-{}", std::process::id(), count),
+    }
+}
+
+fn open(protocol: Protocol) -> std::result::Result<Socket, String> {
+    let socket = Socket::new(protocol).map_err(|err| format!("Failed to open {:?}: {:?}", protocol, err))?;
+    let _ = socket.set_opt::<RecvTimeout>(Some(STEP_TIMEOUT));
+    let _ = socket.set_opt::<SendTimeout>(Some(STEP_TIMEOUT));
+    Ok(socket)
+}
+
+// 两端连好的 socket，关闭交给 Drop
+struct Peers {
+    listener: Socket,
+    dialer: Socket,
+}
+
+impl Drop for Peers {
+    fn drop(&mut self) {
+        self.dialer.close();
+        self.listener.close();
+    }
+}
+
+fn connect(listen: Protocol, dial: Protocol, url: &str) -> std::result::Result<Peers, String> {
+    let peers = Peers { listener: open(listen)?, dialer: open(dial)? };
+    let listener = Listener::new(&peers.listener, url).map_err(|err| format!("Failed to listen on {}: {:?}", url, err))?;
+    let url = bound_url(listener, url);
+    peers.dialer.dial(&url).map_err(|err| format!("Failed to dial {}: {:?}", url, err))?;
+    std::thread::sleep(ATTACH_WAIT);
+    Ok(peers)
+}
+
+// tcp 监听端口 0 时换成实际分配的端口
+fn bound_url(listener: Listener, url: &str) -> String {
+    let Some(host) = url.strip_prefix("tcp://").and_then(|rest| rest.strip_suffix(":0")) else {
+        return url.to_string();
+    };
+    let mut port: c_int = 0;
+    let name = ffi::NNG_OPT_TCP_BOUND_PORT.as_ptr() as *const c_char;
+    unsafe { ffi::nng_listener_get_int(listener.nng_listener(), name, &mut port) };
+    format!("tcp://{}:{}", host, port)
+}
+
+fn send(socket: &Socket, data: &[u8]) -> std::result::Result<(), String> {
+    socket.send(Message::from(data)).map_err(|(_, err)| format!("Send {:?} failed: {:?}", String::from_utf8_lossy(data), err))
+}
+
+fn expect(socket: &Socket, expected: &[u8]) -> std::result::Result<(), String> {
+    let message = socket.recv().map_err(|err| format!("Expected {:?}, recv failed: {:?}", String::from_utf8_lossy(expected), err))?;
+    if message.as_slice() != expected {
+        return Err(format!(
+            "Expected {:?}, received {:?}",
+            String::from_utf8_lossy(expected),
+            String::from_utf8_lossy(message.as_slice())
+        ));
+    }
+    Ok(())
+}
+
+fn expect_nothing(socket: &Socket) -> std::result::Result<(), String> {
+    match socket.recv() {
+        Err(nng::Error::TimedOut) => Ok(()),
+        Ok(message) => Err(format!("Unexpected message {:?}", String::from_utf8_lossy(message.as_slice()))),
+        Err(err) => Err(format!("Recv failed: {:?}", err)),
+    }
+}
+
+// 请求之后必须先收回复才能再发下一条请求，回复对得上请求
+fn req_rep(url: &str) -> std::result::Result<(), String> {
+    let peers = connect(Protocol::Rep0, Protocol::Req0, url)?;
+    for request in [&b"ping-1"[..], b"ping-2"] {
+        send(&peers.dialer, request)?;
+        expect(&peers.listener, request)?;
+        send(&peers.listener, b"pong")?;
+        expect(&peers.dialer, b"pong")?;
+    }
+    // Rep 没有待回复的请求时不能发送
+    match peers.listener.send(Message::from(&b"stray"[..])) {
+        Err((_, nng::Error::IncorrectState)) => Ok(()),
+        other => Err(format!("Rep sent without a pending request: {:?}", other.map_err(|(_, err)| err))),
+    }
+}
+
+// 单向按顺序送达，Pull 不能发送
+fn push_pull(url: &str) -> std::result::Result<(), String> {
+    let peers = connect(Protocol::Pull0, Protocol::Push0, url)?;
+    // inproc 没有缓冲，一边发一边收，不然 Push 会等到超时
+    for data in [&b"1"[..], b"2", b"3"] {
+        send(&peers.dialer, data)?;
+        expect(&peers.listener, data)?;
+    }
+    match peers.listener.send(Message::from(&b"back"[..])) {
+        Err((_, nng::Error::NotSupported)) => Ok(()),
+        other => Err(format!("Pull accepted a send: {:?}", other.map_err(|(_, err)| err))),
+    }
+}
+
+// 只收到订阅前缀匹配的消息
+fn pub_sub(url: &str) -> std::result::Result<(), String> {
+    let peers = connect(Protocol::Pub0, Protocol::Sub0, url)?;
+    peers.dialer.set_opt::<Subscribe>(b"a".to_vec()).map_err(|err| format!("Subscribe failed: {:?}", err))?;
+    send(&peers.listener, b"b-1")?;
+    send(&peers.listener, b"a-1")?;
+    expect(&peers.dialer, b"a-1")?;
+    expect_nothing(&peers.dialer)
+}
+
+fn ping_pong(peers: &Peers) -> std::result::Result<(), String> {
+    send(&peers.dialer, b"ping")?;
+    expect(&peers.listener, b"ping")?;
+    send(&peers.listener, b"pong")?;
+    expect(&peers.dialer, b"pong")
+}
+
+fn pair0(url: &str) -> std::result::Result<(), String> {
+    ping_pong(&connect(Protocol::Pair0, Protocol::Pair0, url)?)
+}
+
+fn pair1(url: &str) -> std::result::Result<(), String> {
+    ping_pong(&connect(Protocol::Pair1, Protocol::Pair1, url)?)
+}
+
+// 调查发出后收到应答，应答者不能主动发送
+fn survey(url: &str) -> std::result::Result<(), String> {
+    let peers = connect(Protocol::Surveyor0, Protocol::Respondent0, url)?;
+    match peers.dialer.send(Message::from(&b"unsolicited"[..])) {
+        Err((_, nng::Error::IncorrectState)) => {}
+        other => return Err(format!("Respondent sent without a survey: {:?}", other.map_err(|(_, err)| err))),
+    }
+    send(&peers.listener, b"survey")?;
+    expect(&peers.dialer, b"survey")?;
+    send(&peers.dialer, b"answer")?;
+    expect(&peers.listener, b"answer")
+}
+
+// 双向广播，发送方自己收不到
+fn bus(url: &str) -> std::result::Result<(), String> {
+    let peers = connect(Protocol::Bus0, Protocol::Bus0, url)?;
+    ping_pong(&peers)?;
+    expect_nothing(&peers.listener)
+}
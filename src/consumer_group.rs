@@ -0,0 +1,280 @@
+use napi::bindgen_prelude::*;
+use napi::threadsafe_function::{ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use napi_derive::napi;
+use nng::options::protocol::pubsub::{Subscribe, Unsubscribe};
+use nng::options::{Options, RecvBufferSize, SendBufferSize};
+use nng::{Error as NngError, PipeEvent, Protocol, Socket};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::Arc;
+
+use crate::events::{EventEmitter, SocketEvent};
+use crate::frames::{FrameErrors, MalformedFrames};
+use crate::guard;
+use crate::topic::{self, TopicMessage};
+
+// 中间阶段两头的缓冲区默认能放下的消息数，nng 最多 8192
+const DEFAULT_BUFFER_SIZE: u32 = 1024;
+
+fn failed(reason: String) -> napi::Error {
+    napi::Error::new(napi::Status::GenericFailure, reason)
+}
+
+#[napi(object)]
+pub struct ConsumerGroupOptions {
+    pub buffer_size: Option<u32>, // Sub 的接收缓冲区和 Push 的发送缓冲区（条数），默认 1024
+}
+
+#[napi(object)]
+pub struct ConsumerGroupStats {
+    pub members: i64,   // 当前连着 groupUrl 的成员
+    pub received: i64,  // 从发布端收到的
+    pub forwarded: i64, // 交给成员的
+}
+
+#[derive(Default)]
+struct Counters {
+    members: AtomicI64,
+    received: AtomicI64,
+    forwarded: AtomicI64,
+}
+
+// 消费组：中间的 Push 阶段订阅发布端的主题，经 Push 把每条消息只分给组里的一个成员（空闲的成员轮流拿），
+// 不同的消费组各自收到全部消息。成员用 GroupConsumer 连上 groupUrl。
+// 没有成员时 Push 阻塞，Sub 的接收缓冲区满了以后按 pub/sub 的规则丢弃，和普通订阅端跟不上时一样
+#[napi]
+pub struct ConsumerGroup {
+    upstream: Option<Socket>, // Sub0，连发布端
+    members: Option<Socket>,  // Push0，监听 groupUrl
+    counters: Arc<Counters>,
+    events: EventEmitter,
+    malformed: FrameErrors,
+    is_closing: Arc<AtomicBool>,
+}
+
+#[napi]
+impl ConsumerGroup {
+    #[napi(constructor)]
+    pub fn new(group_url: String, options: Option<ConsumerGroupOptions>) -> Result<Self> {
+        let buffer_size = options.and_then(|options| options.buffer_size).unwrap_or(DEFAULT_BUFFER_SIZE);
+        let upstream = Socket::new(Protocol::Sub0).map_err(|err| failed(format!("Socket creation failed: {:?}", err)))?;
+        let members = Socket::new(Protocol::Push0).map_err(|err| {
+            upstream.close();
+            failed(format!("Socket creation failed: {:?}", err))
+        })?;
+        let counters = Arc::new(Counters::default());
+        let watched = counters.clone();
+        let watch = move |_, event| match event {
+            PipeEvent::AddPost => {
+                watched.members.fetch_add(1, Ordering::SeqCst);
+            }
+            PipeEvent::RemovePost => {
+                watched.members.fetch_sub(1, Ordering::SeqCst);
+            }
+            _ => {}
+        };
+        let listened = upstream
+            .set_opt::<RecvBufferSize>(buffer_size as i32)
+            .and_then(|_| members.set_opt::<SendBufferSize>(buffer_size as i32))
+            .map_err(|err| napi::Error::new(napi::Status::InvalidArg, format!("Invalid bufferSize: {:?}", err)))
+            .and_then(|_| members.pipe_notify(watch).map_err(|err| failed(format!("Failed to watch pipes: {:?}", err))))
+            .and_then(|_| members.listen(&group_url).map_err(|err| failed(format!("Listen failed: {:?}", err))));
+        if let Err(err) = listened {
+            upstream.close();
+            members.close();
+            return Err(err);
+        }
+
+        let group = ConsumerGroup {
+            upstream: Some(upstream.clone()),
+            members: Some(members.clone()),
+            counters,
+            events: EventEmitter::default(),
+            malformed: FrameErrors::default(),
+            is_closing: Arc::new(AtomicBool::new(false)),
+        };
+        let (counters, events, malformed, is_closing) =
+            (group.counters.clone(), group.events.clone(), group.malformed.clone(), group.is_closing.clone());
+        guard::spawn(group.events.clone(), "Consumer group", move || {
+            // 已经收到实时消息的主题，之后的重放是给其他新订阅者的，不再分给成员
+            let mut live_topics: HashSet<String> = HashSet::new();
+            loop {
+                let message = match upstream.recv() {
+                    Ok(message) => message,
+                    Err(_) if is_closing.load(Ordering::SeqCst) => return,
+                    Err(err) => {
+                        events.warn("recvFailed", format!("Error receiving message: {:?}", err));
+                        continue;
+                    }
+                };
+                match topic::decode(message.as_slice()) {
+                    Ok(frame) => {
+                        let replayed = frame.flags & topic::FLAG_REPLAYED != 0;
+                        if !replayed {
+                            live_topics.insert(frame.topic.to_string());
+                        } else if live_topics.contains(frame.topic) {
+                            continue;
+                        }
+                    }
+                    Err(err) => {
+                        malformed.record(err, &events);
+                        continue;
+                    }
+                }
+                counters.received.fetch_add(1, Ordering::SeqCst);
+                match members.send(message) {
+                    Ok(()) => {
+                        counters.forwarded.fetch_add(1, Ordering::SeqCst);
+                    }
+                    Err((_, NngError::Closed)) => return,
+                    Err((_, err)) => events.warn("sendFailed", format!("Failed to forward message: {:?}", err)),
+                }
+            }
+        });
+        Ok(group)
+    }
+
+    // 异步拨号，发布端晚于消费组启动时会自动重连
+    #[napi]
+    pub fn connect(&self, url: String) -> Result<()> {
+        self.upstream()?.dial_async(&url).map_err(|err| failed(format!("Connection failed: {:?}", err)))
+    }
+
+    #[napi]
+    pub fn subscribe(&self, topic: String) -> Result<()> {
+        topic::check_topic(&topic)?;
+        self.upstream()?
+            .set_opt::<Subscribe>(topic::subscription(&topic))
+            .map_err(|err| failed(format!("Failed to subscribe: {:?}", err)))
+    }
+
+    #[napi]
+    pub fn unsubscribe(&self, topic: String) -> Result<()> {
+        topic::check_topic(&topic)?;
+        self.upstream()?
+            .set_opt::<Unsubscribe>(topic::subscription(&topic))
+            .map_err(|err| failed(format!("Failed to unsubscribe: {:?}", err)))
+    }
+
+    #[napi]
+    pub fn stats(&self) -> ConsumerGroupStats {
+        ConsumerGroupStats {
+            members: self.counters.members.load(Ordering::SeqCst),
+            received: self.counters.received.load(Ordering::SeqCst),
+            forwarded: self.counters.forwarded.load(Ordering::SeqCst),
+        }
+    }
+
+    // 收到后丢弃的畸形主题帧，按原因计数
+    #[napi]
+    pub fn malformed_frames(&self) -> MalformedFrames {
+        self.malformed.snapshot()
+    }
+
+    // recvFailed、sendFailed 和畸形帧事件
+    #[napi]
+    pub fn on_event(&self, callback: ThreadsafeFunction<SocketEvent>) {
+        self.events.set(callback);
+    }
+
+    // 还没分给成员的消息随之丢弃
+    #[napi]
+    pub fn close(&mut self) {
+        self.is_closing.store(true, Ordering::SeqCst);
+        for socket in [self.upstream.take(), self.members.take()].into_iter().flatten() {
+            socket.close();
+        }
+        self.events.clear();
+    }
+
+    fn upstream(&self) -> Result<&Socket> {
+        self.upstream.as_ref().ok_or_else(|| failed("Consumer group closed".to_string()))
+    }
+}
+
+// 消费组的成员：连上 ConsumerGroup 的 groupUrl，和同组的其他成员分摊消息
+#[napi]
+pub struct GroupConsumer {
+    socket: Option<Socket>,
+    receiving: Arc<AtomicBool>,
+    is_closing: Arc<AtomicBool>,
+    events: EventEmitter,
+    malformed: FrameErrors,
+    received: Arc<AtomicI64>,
+}
+
+#[napi]
+impl GroupConsumer {
+    #[napi(constructor)]
+    pub fn new() -> Self {
+        GroupConsumer {
+            socket: None,
+            receiving: Arc::new(AtomicBool::new(false)),
+            is_closing: Arc::new(AtomicBool::new(false)),
+            events: EventEmitter::default(),
+            malformed: FrameErrors::default(),
+            received: Arc::default(),
+        }
+    }
+
+    // 异步拨号，消费组晚于成员启动时会自动重连
+    #[napi]
+    pub fn connect(&mut self, group_url: String) -> Result<()> {
+        let socket = match &self.socket {
+            Some(socket) => socket.clone(),
+            None => Socket::new(Protocol::Pull0).map_err(|err| failed(format!("Socket creation failed: {:?}", err)))?,
+        };
+        socket.dial_async(&group_url).map_err(|err| failed(format!("Connection failed: {:?}", err)))?;
+        self.socket = Some(socket);
+        Ok(())
+    }
+
+    #[napi]
+    pub fn recv(&self, callback: ThreadsafeFunction<TopicMessage>) -> Result<()> {
+        let socket = self.socket.as_ref().ok_or_else(|| failed("Socket not connected".to_string()))?.clone();
+        if self.receiving.swap(true, Ordering::SeqCst) {
+            return Err(failed("Already receiving".to_string()));
+        }
+        let (is_closing, events, malformed, received) =
+            (self.is_closing.clone(), self.events.clone(), self.malformed.clone(), self.received.clone());
+        guard::spawn(self.events.clone(), "Receive loop", move || loop {
+            match socket.recv() {
+                Ok(message) => match topic::decode(message.as_slice()) {
+                    Ok(frame) => {
+                        received.fetch_add(1, Ordering::SeqCst);
+                        let _ = callback.call(Ok(frame.to_message()), ThreadsafeFunctionCallMode::NonBlocking);
+                    }
+                    Err(err) => malformed.record(err, &events),
+                },
+                Err(_) if is_closing.load(Ordering::SeqCst) => return, // 主动关闭时不报错
+                Err(err) => events.warn("recvFailed", format!("Error receiving message: {:?}", err)),
+            }
+        });
+        Ok(())
+    }
+
+    // 这个成员分到的消息数
+    #[napi]
+    pub fn received(&self) -> i64 {
+        self.received.load(Ordering::SeqCst)
+    }
+
+    #[napi]
+    pub fn malformed_frames(&self) -> MalformedFrames {
+        self.malformed.snapshot()
+    }
+
+    #[napi]
+    pub fn on_event(&self, callback: ThreadsafeFunction<SocketEvent>) {
+        self.events.set(callback);
+    }
+
+    #[napi]
+    pub fn close(&mut self) {
+        if let Some(socket) = self.socket.take() {
+            self.is_closing.store(true, Ordering::SeqCst);
+            self.events.clear();
+            socket.close();
+        }
+    }
+}
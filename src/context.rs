@@ -0,0 +1,98 @@
+use napi::{CleanupEnvHook, Env, Result};
+use nng::Socket;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::keep_alive::KeepAlive;
+use crate::outbox::Outbox;
+
+// env 销毁时最多等这么久让接收线程退出
+const TEARDOWN_WAIT: Duration = Duration::from_secs(1);
+const TEARDOWN_POLL: Duration = Duration::from_millis(1);
+
+// 打开着的 SocketWrapper 在关闭流程里要用到的部分
+pub struct Closing {
+    pub socket: Socket,
+    pub outbox: Outbox,
+    pub recv_paused: Arc<AtomicBool>,
+    pub is_closing: Arc<AtomicBool>,
+    pub keep_alive: KeepAlive, // 正在运行的接收循环
+}
+
+// 同一个进程里可以有多个 env：主线程、每个 worker_threads、Electron 的每个渲染进程，
+// 有的嵌入方还会对同一个 env 重复加载插件。打开着的 socket 按 env 分开登记在 env 的 instance data 上
+// （nng 的 socket id -> Closing），某个 env 第一次打开 socket 时才建表并挂上 env 的清理钩子，
+// env 销毁时只清掉它自己的，不影响其它 env
+#[derive(Clone, Default)]
+pub struct Registry {
+    sockets: Arc<Mutex<BTreeMap<i32, Closing>>>,
+}
+
+impl Registry {
+    // env 的登记表，第一次用到时创建
+    pub fn of(env: &Env) -> Result<Registry> {
+        if let Some(registry) = env.get_instance_data::<Registry>()? {
+            return Ok(registry.clone());
+        }
+        let registry = Registry::default();
+        env.set_instance_data(registry.clone(), (), |_| {})?;
+        let mut env = *env;
+        env.add_env_cleanup_hook(registry.clone(), tear_down_env)?;
+        Ok(registry)
+    }
+
+    pub fn untrack(&self, socket: &Socket) {
+        self.sockets.lock().unwrap().remove(&socket_id(socket));
+    }
+
+    // 这个 env 里打开着的 socket
+    pub fn for_each<F: FnMut(&Closing)>(&self, f: F) {
+        self.sockets.lock().unwrap().values().for_each(f);
+    }
+
+    pub fn same(&self, other: &Registry) -> bool {
+        Arc::ptr_eq(&self.sockets, &other.sockets)
+    }
+}
+
+fn socket_id(socket: &Socket) -> i32 {
+    unsafe { nng::ffi::nng_socket_id(socket.nng_socket()) }
+}
+
+// 返回 env 的登记表，socket 关闭时从里面取消登记
+pub fn track(env: &Env, socket: &Socket, closing: Closing) -> Result<Registry> {
+    let registry = Registry::of(env)?;
+    registry.sockets.lock().unwrap().insert(socket_id(socket), closing);
+    Ok(registry)
+}
+
+// 接收循环启动时再挂一个同样的钩子。Node 按注册的相反顺序执行清理钩子，线程安全函数也是在清理钩子里关闭的；
+// 这个钩子晚于循环用到的线程安全函数注册，会先执行，接收线程放掉它们以后 Node 才去关闭，不会在释放时出错。
+// 循环正常退出时由调用方取消
+pub fn guard_loop(env: &Env) -> Result<CleanupEnvHook<Registry>> {
+    let registry = Registry::of(env)?;
+    let mut env = *env;
+    env.add_env_cleanup_hook(registry, tear_down_env)
+}
+
+fn tear_down_env(registry: Registry) {
+    let sockets = std::mem::take(&mut *registry.sockets.lock().unwrap());
+    tear_down(sockets.into_values().collect());
+}
+
+// env 销毁（Electron 刷新窗口、worker 退出）时 JS 里没有关掉的 socket：关闭后 nng 取消它们上面所有进行中的 aio，
+// 再等接收线程退出，之后不会再有线程调用这个 env 的回调；同时释放地址，刷新后的页面可以重新监听
+fn tear_down(sockets: Vec<Closing>) {
+    for entry in &sockets {
+        entry.recv_paused.store(true, Ordering::SeqCst);
+        entry.is_closing.store(true, Ordering::SeqCst);
+        entry.outbox.close();
+        entry.socket.close();
+    }
+    let deadline = Instant::now() + TEARDOWN_WAIT;
+    while sockets.iter().any(|entry| entry.keep_alive.running() > 0) && Instant::now() < deadline {
+        std::thread::sleep(TEARDOWN_POLL);
+    }
+}
@@ -0,0 +1,162 @@
+use napi::bindgen_prelude::Either3;
+use nng::{Protocol, Socket};
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use crate::capture::Capture;
+use crate::memory::MemoryAccount;
+use crate::option_dump::{self, OptionEntry};
+use crate::outbox::Outbox;
+use crate::pipes::{now_ms, PipeHooks};
+
+const MESSAGE_PREVIEW: usize = 64; // 每条抓到的消息最多写出这么多字节
+
+// 写快照时要读的状态，open 时接上、close 时断开，后台线程里也能读
+pub struct Source {
+    pub socket: Socket,
+    pub protocol: Protocol,
+    pub raw: bool,
+    pub outbox: Outbox,
+    pub pipes: PipeHooks,
+    pub capture: Capture,
+    pub memory: MemoryAccount,
+}
+
+// setCrashDump：接收循环的 aio 失败、后台线程 panic 之类意外的致命错误发生时，
+// 往 dir 里写一份文本快照：socket 选项、当前的 pipe、各端点的连接历史、队列和内存计数、最近抓到的消息
+#[derive(Clone, Default)]
+pub struct CrashDump {
+    dir: Arc<Mutex<Option<PathBuf>>>,
+    source: Arc<Mutex<Option<Source>>>,
+}
+
+impl CrashDump {
+    pub fn set_dir(&self, dir: Option<String>) {
+        *self.dir.lock().unwrap() = dir.map(PathBuf::from);
+    }
+
+    pub fn attach(&self, source: Source) {
+        *self.source.lock().unwrap() = Some(source);
+    }
+
+    pub fn detach(&self) {
+        self.source.lock().unwrap().take();
+    }
+
+    // 返回写出的文件路径；没有开启或 socket 没打开时不写
+    pub fn write(&self, reason: &str, labels: &str) -> Option<Result<String, String>> {
+        let dir = self.dir.lock().unwrap().clone()?;
+        let (socket_id, report) = {
+            let source = self.source.lock().unwrap();
+            let source = source.as_ref()?;
+            (socket_id(&source.socket), report(source, reason, labels))
+        };
+        let path = dir.join(format!("napi-nng-crash-{}-{}-{}.txt", std::process::id(), socket_id, now_ms() as u64));
+        let written = std::fs::create_dir_all(&dir).and_then(|_| std::fs::write(&path, report));
+        Some(match written {
+            Ok(()) => Ok(path.display().to_string()),
+            Err(err) => Err(format!("Failed to write crash dump {}: {}", path.display(), err)),
+        })
+    }
+}
+
+fn report(source: &Source, reason: &str, labels: &str) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "napi-nng crash dump");
+    let _ = writeln!(out, "reason: {}", reason);
+    let _ = writeln!(out, "at: {}", now_ms() as u64);
+    let _ = writeln!(out, "pid: {}", std::process::id());
+    let _ = writeln!(out, "socket: {} {:?}{}", socket_id(&source.socket), source.protocol, if source.raw { " raw" } else { "" });
+    if !labels.is_empty() {
+        let _ = writeln!(out, "labels: {}", labels);
+    }
+
+    let _ = writeln!(out, "\n[options]");
+    for entry in option_dump::dump(&source.socket, source.protocol, source.raw, Vec::new()).socket {
+        let _ = writeln!(out, "{}", option_line(&entry));
+    }
+
+    let _ = writeln!(out, "\n[pipes]");
+    for pipe in source.pipes.pipes() {
+        let _ = writeln!(
+            out,
+            "pipe {} {} url={} remote={} local={}",
+            pipe.pipe_id,
+            pipe.transport,
+            pipe.url.as_deref().unwrap_or("-"),
+            pipe.remote_address.as_deref().unwrap_or("-"),
+            pipe.local_address.as_deref().unwrap_or("-"),
+        );
+    }
+
+    let _ = writeln!(out, "\n[endpoints]");
+    for endpoint in source.pipes.endpoints() {
+        let _ = writeln!(
+            out,
+            "{} {} url={} connected={} connects={} disconnects={} reconnects={}",
+            endpoint.kind,
+            endpoint.id,
+            endpoint.url.as_deref().unwrap_or("-"),
+            endpoint.connected,
+            endpoint.connects,
+            endpoint.disconnects,
+            endpoint.reconnects,
+        );
+    }
+
+    let _ = writeln!(out, "\n[counters]");
+    let depths = source.outbox.depths();
+    let _ = writeln!(out, "handed: {}", source.outbox.handed());
+    let _ = writeln!(out, "dropped: {}", source.outbox.dropped());
+    let _ = writeln!(out, "outbox depth: {}", depths.outbox);
+    let _ = writeln!(out, "send queue depth: {}", optional(depths.send_pending));
+    let _ = writeln!(out, "recv queue depth: {}", optional(depths.recv_pending));
+    let memory = source.memory.usage();
+    let _ = writeln!(
+        out,
+        "memory: queued={} inFlight={} retained={} total={} limit={}",
+        memory.queued_bytes,
+        memory.in_flight_bytes,
+        memory.retained_bytes,
+        memory.total_bytes,
+        optional(memory.limit_bytes),
+    );
+
+    // 只有 setCapture 开启时才有
+    let _ = writeln!(out, "\n[recent messages]");
+    for message in source.capture.messages() {
+        let preview: String = message.data.iter().take(MESSAGE_PREVIEW).map(|byte| format!("{:02x}", byte)).collect();
+        let _ = writeln!(
+            out,
+            "{} {} pipe={} {} bytes: {}{}",
+            message.at as u64,
+            message.direction,
+            optional(message.pipe),
+            message.data.len(),
+            preview,
+            if message.data.len() > MESSAGE_PREVIEW { "…" } else { "" },
+        );
+    }
+    out
+}
+
+fn socket_id(socket: &Socket) -> u32 {
+    unsafe { nng::ffi::nng_socket_id(socket.nng_socket()) as u32 }
+}
+
+fn option_line(entry: &OptionEntry) -> String {
+    let value = |value: &Either3<f64, bool, String>| match value {
+        Either3::A(number) => number.to_string(),
+        Either3::B(flag) => flag.to_string(),
+        Either3::C(text) => text.clone(),
+    };
+    match &entry.default_value {
+        Some(default) => format!("{} = {} (default {})", entry.name, value(&entry.value), value(default)),
+        None => format!("{} = {}", entry.name, value(&entry.value)),
+    }
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map_or_else(|| "-".to_string(), |value| value.to_string())
+}
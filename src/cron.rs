@@ -0,0 +1,275 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, SystemTime};
+
+use crate::events::EventEmitter;
+use crate::guard;
+
+const DAY: u64 = 86_400;
+// 最多往后找这么久，比如 2 月 30 日永远不会到
+const HORIZON: u64 = 5 * 366 * DAY;
+// 最多睡这么久就重新看一次时钟，系统时间被调整后不会错过太久
+const MAX_SLEEP: Duration = Duration::from_secs(60);
+
+const MONTHS: [&str; 12] = ["JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC"];
+const WEEKDAYS: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+// cron 表达式：5 段（分 时 日 月 星期）或者 6 段（秒 分 时 日 月 星期），按 UTC 计算。
+// 每段支持 *、?、数字、a-b、列表和 /步长，月份和星期也可以写英文缩写，星期 0 和 7 都是星期日；
+// 还支持 @yearly、@monthly、@weekly、@daily、@hourly。日和星期都限定时满足其一即可，和 Vixie cron 一致
+pub struct Cron {
+    seconds: u64, // 每段一个位图，第 n 位表示取值 n
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Cron {
+    pub fn parse(expression: &str) -> std::result::Result<Cron, String> {
+        let expression = match expression.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            other => other,
+        };
+        let mut fields: Vec<&str> = expression.split_whitespace().collect();
+        match fields.len() {
+            5 => fields.insert(0, "0"),
+            6 => {}
+            n => return Err(format!("Expected 5 or 6 fields, got {}", n)),
+        }
+        let weekdays = field(fields[5], 0, 7, &WEEKDAYS)?;
+        Ok(Cron {
+            seconds: field(fields[0], 0, 59, &[])?,
+            minutes: field(fields[1], 0, 59, &[])?,
+            hours: field(fields[2], 0, 23, &[])?,
+            days: field(fields[3], 1, 31, &[])?,
+            months: field(fields[4], 1, 12, &MONTHS)?,
+            weekdays: (weekdays | (weekdays >> 7)) & 0x7f,
+            any_day: fields[3].starts_with(['*', '?']),
+            any_weekday: fields[5].starts_with(['*', '?']),
+        })
+    }
+
+    // after 之后（不含）下一次触发的时间，单位是秒（Unix 时间）；找不到时为 None
+    pub fn next_after(&self, after: u64) -> Option<u64> {
+        let mut t = after + 1;
+        while t < after + HORIZON {
+            let (days, secs) = (t / DAY, t % DAY);
+            let (year, month, day) = civil(days as i64);
+            // 1970-01-01 是星期四
+            let weekday = (days + 4) % 7;
+            if !has(self.months, month as u64) {
+                let (year, month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+                t = days_from_civil(year, month, 1) as u64 * DAY;
+            } else if !self.day_matches(day as u64, weekday) {
+                t = (days + 1) * DAY;
+            } else if !has(self.hours, secs / 3600) {
+                t = days * DAY + (secs / 3600 + 1) * 3600;
+            } else if !has(self.minutes, secs / 60 % 60) {
+                t = t - secs % 60 + 60;
+            } else if !has(self.seconds, secs % 60) {
+                t += 1;
+            } else {
+                return Some(t);
+            }
+        }
+        None
+    }
+
+    fn day_matches(&self, day: u64, weekday: u64) -> bool {
+        let (day, weekday) = (has(self.days, day), has(self.weekdays, weekday));
+        if self.any_day || self.any_weekday {
+            day && weekday
+        } else {
+            day || weekday
+        }
+    }
+}
+
+fn has(bits: u64, value: u64) -> bool {
+    bits & (1 << value) != 0
+}
+
+fn field(spec: &str, min: u32, max: u32, names: &[&str]) -> std::result::Result<u64, String> {
+    let mut bits = 0u64;
+    for item in spec.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => {
+                let step = step.parse::<u32>().ok().filter(|step| *step > 0);
+                (range, step.ok_or_else(|| format!("Invalid step in '{}'", item))?)
+            }
+            None => (item, 1),
+        };
+        let (low, high) = match range {
+            "*" | "?" => (min, max),
+            _ => match range.split_once('-') {
+                Some((low, high)) => (value(low, min, max, names)?, value(high, min, max, names)?),
+                // a/n 表示从 a 开始每隔 n 个直到最大值
+                None => {
+                    let low = value(range, min, max, names)?;
+                    (low, if item.contains('/') { max } else { low })
+                }
+            },
+        };
+        if low > high {
+            return Err(format!("Invalid range '{}'", item));
+        }
+        for value in (low..=high).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+fn value(text: &str, min: u32, max: u32, names: &[&str]) -> std::result::Result<u32, String> {
+    let parsed = match names.iter().position(|name| name.eq_ignore_ascii_case(text)) {
+        Some(index) => Some(min + index as u32),
+        None => text.parse::<u32>().ok(),
+    };
+    parsed
+        .filter(|value| (min..=max).contains(value))
+        .ok_or_else(|| format!("'{}' is not in {}-{}", text, min, max))
+}
+
+// 从 1970-01-01 起的天数换算成公历日期，见 Howard Hinnant 的 civil_from_days
+fn civil(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let month = month as i64;
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64)
+}
+
+struct Entry<T> {
+    cron: Cron,
+    next: u64, // 下一次触发的时间，Unix 秒
+    job: T,
+}
+
+struct Table<T> {
+    entries: HashMap<String, Entry<T>>,
+    stopped: bool,
+}
+
+struct Shared<T> {
+    table: Mutex<Table<T>>,
+    wake: Condvar,
+}
+
+type Fire<T> = Box<dyn Fn(T) + Send>;
+
+// 按名字登记的周期任务，由一个后台线程在到点时交给 fire。线程在第一次 add 时启动；
+// 进程挂起等原因错过的触发只补一次
+pub struct Crontab<T: Clone + Send + 'static> {
+    shared: Arc<Shared<T>>,
+    events: EventEmitter,
+    fire: Option<Fire<T>>, // 线程启动时取走
+}
+
+impl<T: Clone + Send + 'static> Crontab<T> {
+    pub fn new<F>(events: EventEmitter, fire: F) -> Self
+    where
+        F: Fn(T) + Send + 'static,
+    {
+        let table = Table { entries: HashMap::new(), stopped: false };
+        Crontab {
+            shared: Arc::new(Shared { table: Mutex::new(table), wake: Condvar::new() }),
+            events,
+            fire: Some(Box::new(fire)),
+        }
+    }
+
+    // 同名的替换掉原来的；表达式永远不会触发时返回 Err
+    pub fn add(&mut self, name: String, cron: Cron, job: T) -> std::result::Result<(), String> {
+        let next = cron.next_after(unix_millis() / 1000).ok_or_else(|| "Expression never fires".to_string())?;
+        if let Some(fire) = self.fire.take() {
+            let shared = self.shared.clone();
+            guard::spawn(self.events.clone(), "Job cron", move || run(shared, fire));
+        }
+        self.shared.table.lock().unwrap().entries.insert(name, Entry { cron, next, job });
+        self.shared.wake.notify_one();
+        Ok(())
+    }
+
+    pub fn remove(&self, name: &str) -> bool {
+        self.shared.table.lock().unwrap().entries.remove(name).is_some()
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.shared.table.lock().unwrap().entries.keys().cloned().collect()
+    }
+}
+
+impl<T: Clone + Send + 'static> Drop for Crontab<T> {
+    fn drop(&mut self) {
+        let mut table = self.shared.table.lock().unwrap();
+        table.stopped = true;
+        table.entries.clear();
+        self.shared.wake.notify_one();
+    }
+}
+
+fn run<T: Clone + Send + 'static>(shared: Arc<Shared<T>>, fire: Fire<T>) {
+    let mut table = shared.table.lock().unwrap();
+    loop {
+        if table.stopped {
+            return;
+        }
+        let now = unix_millis();
+        let mut due = Vec::new();
+        table.entries.retain(|_, entry| {
+            if entry.next * 1000 > now {
+                return true;
+            }
+            due.push(entry.job.clone());
+            match entry.cron.next_after(now / 1000) {
+                Some(next) => {
+                    entry.next = next;
+                    true
+                }
+                None => false,
+            }
+        });
+        if !due.is_empty() {
+            drop(table);
+            for job in due {
+                fire(job);
+            }
+            table = shared.table.lock().unwrap();
+            continue;
+        }
+        table = match table.entries.values().map(|entry| entry.next * 1000).min() {
+            Some(next) => {
+                let wait = Duration::from_millis(next.saturating_sub(now)).min(MAX_SLEEP);
+                shared.wake.wait_timeout(table, wait).unwrap().0
+            }
+            None => shared.wake.wait(table).unwrap(),
+        };
+    }
+}
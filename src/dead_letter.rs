@@ -0,0 +1,65 @@
+use napi::bindgen_prelude::Buffer;
+use napi::threadsafe_function::{ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use napi_derive::napi;
+use nng::{Message, Socket};
+use std::sync::{Arc, Mutex};
+
+use crate::events::EventEmitter;
+
+// 交给死信出口的消息。reason 为 sendFailed（nng 拒绝发送，error 带原因）、
+// memoryLimit（超过内存上限没能排队）、queueFull（断线期间的队列已满）、closed（socket 关闭时还在队列里），
+// 或者收到的消息被 nack(false)（nacked）、重新投递次数超过上限（redeliveryLimit）、
+// 重新排队失败次数超过上限（retryLimit）或没能推给重试 socket（requeueFailed）
+#[napi(object)]
+pub struct DeadLetter {
+    pub message: Buffer,
+    pub reason: String,
+    pub error: Option<String>,
+}
+
+enum Sink {
+    Callback(ThreadsafeFunction<DeadLetter>),
+    Socket(Socket),
+}
+
+// sendAsync/sendReliable 没能送出的消息的去处：回调，或者另一个 socket。
+// 没有设置时只有 Promise 被 reject，消息本身丢弃
+#[derive(Clone, Default)]
+pub struct DeadLetters {
+    sink: Arc<Mutex<Option<Sink>>>,
+}
+
+impl DeadLetters {
+    pub fn set_callback(&self, callback: ThreadsafeFunction<DeadLetter>) {
+        *self.sink.lock().unwrap() = Some(Sink::Callback(callback));
+    }
+
+    pub fn set_socket(&self, socket: Socket) {
+        *self.sink.lock().unwrap() = Some(Sink::Socket(socket));
+    }
+
+    pub fn clear(&self) {
+        *self.sink.lock().unwrap() = None;
+    }
+
+    pub fn deliver(&self, events: &EventEmitter, message: Message, reason: &str, error: Option<String>) {
+        let sink = self.sink.lock().unwrap();
+        match sink.as_ref() {
+            Some(Sink::Callback(callback)) => {
+                let letter = DeadLetter {
+                    message: message.as_slice().to_vec().into(),
+                    reason: reason.to_string(),
+                    error,
+                };
+                callback.call(Ok(letter), ThreadsafeFunctionCallMode::NonBlocking);
+            }
+            // 死信 socket 满了或已关闭时不再重试，只发出警告
+            Some(Sink::Socket(socket)) => {
+                if let Err((_, err)) = socket.try_send(message) {
+                    events.warn("deadLetterFailed", format!("Failed to forward dead letter ({}): {:?}", reason, err));
+                }
+            }
+            None => {}
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use napi::Result;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, ErrorKind, Read, Write};
+use std::time::{Duration, SystemTime};
+
+// 持久化文件格式：8 字节文件头，之后每条记录为 过期时间(8, Unix 毫秒, 大端) + key 长度(4, 大端) + key。
+// 只追加，打开时和记录数超过存活条目两倍时丢掉过期的、重写一遍
+const MAGIC: &[u8; 8] = b"NNGSEEN\x01";
+// 存活条目很少时不值得频繁重写
+const COMPACT_SLACK: usize = 1024;
+
+fn failed(reason: String) -> napi::Error {
+    napi::Error::new(napi::Status::GenericFailure, reason)
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64)
+}
+
+fn encode(key: &str, expires: u64) -> Vec<u8> {
+    let mut record = Vec::with_capacity(12 + key.len());
+    record.extend_from_slice(&expires.to_be_bytes());
+    record.extend_from_slice(&(key.len() as u32).to_be_bytes());
+    record.extend_from_slice(key.as_bytes());
+    record
+}
+
+// 读下一条记录，文件正好结束时返回 None；最后一条没写完整（比如写到一半进程退出）时也当作结束
+fn decode(reader: &mut impl Read) -> std::io::Result<Option<(String, u64)>> {
+    let mut fixed = [0u8; 12];
+    match reader.read_exact(&mut fixed) {
+        Ok(()) => {}
+        Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    }
+    let expires = u64::from_be_bytes(fixed[..8].try_into().unwrap());
+    let mut key = vec![0u8; u32::from_be_bytes(fixed[8..].try_into().unwrap()) as usize];
+    match reader.read_exact(&mut key) {
+        Ok(()) => Ok(Some((String::from_utf8_lossy(&key).into_owned(), expires))),
+        Err(err) if err.kind() == ErrorKind::UnexpectedEof => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+// 已经完成的任务 key，在 ttl 内再次入队同一个 key 时不再分发
+pub struct SeenSet {
+    ttl: Duration,
+    entries: HashMap<String, u64>, // key -> 过期时间，Unix 毫秒
+    path: Option<String>,
+    file: Option<File>,
+    written: usize, // 文件里的记录数，包括过期的
+}
+
+impl SeenSet {
+    pub fn open(path: Option<String>, ttl: Duration) -> Result<Self> {
+        let mut seen = SeenSet { ttl, entries: HashMap::new(), path, file: None, written: 0 };
+        let Some(path) = seen.path.clone() else {
+            return Ok(seen);
+        };
+        match File::open(&path) {
+            Ok(file) => {
+                let mut reader = BufReader::new(file);
+                let mut magic = [0u8; 8];
+                if reader.read_exact(&mut magic).is_err() || &magic != MAGIC {
+                    return Err(failed(format!("Not a dedup store: {}", path)));
+                }
+                let now = unix_millis();
+                while let Some((key, expires)) =
+                    decode(&mut reader).map_err(|err| failed(format!("Failed to read {}: {}", path, err)))?
+                {
+                    if expires > now {
+                        seen.entries.insert(key, expires);
+                    }
+                }
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => return Err(failed(format!("Failed to open {}: {}", path, err))),
+        }
+        seen.compact().map_err(|err| failed(format!("Failed to write {}: {}", path, err)))?;
+        Ok(seen)
+    }
+
+    pub fn contains(&mut self, key: &str) -> bool {
+        match self.entries.get(key) {
+            Some(expires) if *expires > unix_millis() => true,
+            Some(_) => {
+                self.entries.remove(key);
+                false
+            }
+            None => false,
+        }
+    }
+
+    // 写文件失败时返回错误，内存里的记录照样生效
+    pub fn insert(&mut self, key: String) -> std::io::Result<()> {
+        let expires = unix_millis() + self.ttl.as_millis() as u64;
+        let record = encode(&key, expires);
+        self.entries.insert(key, expires);
+        let Some(file) = self.file.as_mut() else {
+            return Ok(());
+        };
+        file.write_all(&record)?;
+        self.written += 1;
+        if self.written > self.entries.len() * 2 + COMPACT_SLACK {
+            self.prune();
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    pub fn prune(&mut self) {
+        let now = unix_millis();
+        self.entries.retain(|_, expires| *expires > now);
+    }
+
+    // 写到临时文件再改名，重写到一半出错时原来的文件还在
+    fn compact(&mut self) -> std::io::Result<()> {
+        let Some(path) = self.path.as_ref() else {
+            return Ok(());
+        };
+        let temporary = format!("{}.tmp", path);
+        let mut contents = MAGIC.to_vec();
+        for (key, expires) in &self.entries {
+            contents.extend_from_slice(&encode(key, *expires));
+        }
+        fs::write(&temporary, &contents)?;
+        fs::rename(&temporary, path)?;
+        self.file = Some(OpenOptions::new().append(true).open(path)?);
+        self.written = self.entries.len();
+        Ok(())
+    }
+}
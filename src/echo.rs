@@ -0,0 +1,75 @@
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+use nng::{Protocol, Socket};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::events::EventEmitter;
+use crate::guard;
+use crate::nanomsg::ProtocolType;
+
+// 完全在 Rust 侧运行的对端，集成测试和压测不用再起一个 Node 进程。
+// Rep0 / Respondent0 / Pair0 / Pair1 把收到的消息原样发回，Pull0 只接收后丢弃
+#[napi]
+pub struct EchoServer {
+    socket: Option<Socket>,
+    received: Arc<AtomicU64>,
+}
+
+#[napi]
+impl EchoServer {
+    // 收到的消息数
+    #[napi]
+    pub fn received(&self) -> i64 {
+        self.received.load(Ordering::SeqCst) as i64
+    }
+
+    #[napi]
+    pub fn close(&mut self) {
+        if let Some(socket) = self.socket.take() {
+            socket.close();
+        }
+    }
+}
+
+#[napi]
+pub fn start_echo_server(protocol: ProtocolType, url: String) -> Result<EchoServer> {
+    let protocol: Protocol = protocol.into();
+    let echo = match protocol {
+        Protocol::Rep0 | Protocol::Respondent0 | Protocol::Pair0 | Protocol::Pair1 => true,
+        Protocol::Pull0 => false,
+        _ => {
+            return Err(napi::Error::new(
+                napi::Status::InvalidArg,
+                "Echo server only supports Rep0, Respondent0, Pull0, Pair0 and Pair1".to_string(),
+            ))
+        }
+    };
+    let socket = Socket::new(protocol)
+        .map_err(|err| napi::Error::new(napi::Status::GenericFailure, format!("Socket creation failed: {:?}", err)))?;
+    if let Err(err) = socket.listen(&url) {
+        socket.close();
+        return Err(napi::Error::new(napi::Status::GenericFailure, format!("Listen failed: {:?}", err)));
+    }
+
+    let received = Arc::new(AtomicU64::new(0));
+    let events = EventEmitter::default();
+    let task_socket = socket.clone();
+    let task_received = received.clone();
+    guard::spawn(events, "Echo", move || loop {
+        let message = match task_socket.recv() {
+            Ok(message) => message,
+            Err(nng::Error::Closed) => return,
+            Err(_) => continue,
+        };
+        task_received.fetch_add(1, Ordering::SeqCst);
+        if echo {
+            // 其他发送错误只丢掉这一条，socket 关闭时退出
+            if let Err((_, nng::Error::Closed)) = task_socket.send(message) {
+                return;
+            }
+        }
+    });
+
+    Ok(EchoServer { socket: Some(socket), received })
+}
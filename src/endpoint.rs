@@ -0,0 +1,135 @@
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+use nng::options::transport::tcp::{KeepAlive, NoDelay};
+use nng::options::transport::tls::{CaFile, CertKeyFile};
+use nng::options::{Options, ReconnectMaxTime, ReconnectMinTime, RecvMaxSize, SetOpt};
+use nng::{ffi, DialerBuilder, ListenerBuilder, Socket};
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::time::Duration;
+
+use crate::tls::{check, TlsAuthMode};
+
+// 端点启动前设置的选项，只对这一个 listener/dialer 生效，覆盖从 socket 继承来的值。
+// 同一个 socket 上的 ipc 和 tls+tcp 端点可以各用各的设置。
+// 收发队列长度和收发超时在 nng 里只有 socket 级别，不能按端点设置
+#[napi(object)]
+pub struct EndpointOptions {
+    pub recv_max_size: Option<u32>, // 0 表示不限制
+    pub tcp_no_delay: Option<bool>,
+    pub tcp_keep_alive: Option<bool>,
+    pub tls_cert_key_file: Option<String>, // 需要启用 cargo feature "tls"
+    pub tls_ca_file: Option<String>,
+    pub tls_auth_mode: Option<TlsAuthMode>, // 是否校验对端证书；nng 默认 listener 不校验、dialer 要求校验
+    pub tls_server_name: Option<String>, // 仅 dialer，校验服务端证书用的主机名，默认取 URL 里的主机
+    pub reconnect_min_ms: Option<u32>, // 仅 dialer
+    pub reconnect_max_ms: Option<u32>, // 仅 dialer
+}
+
+// socket 上 dialer 断线后重新拨号的间隔：先等 minMs，之后每次翻倍，直到 maxMs。
+// 设在 socket 上，之后创建的 dialer 继承；createDialer 的 reconnectMinMs/reconnectMaxMs 可以单独覆盖
+#[napi(object)]
+pub struct ReconnectBackoff {
+    pub min_ms: Option<u32>, // nng 默认 1000
+    pub max_ms: Option<u32>, // 0 表示不翻倍，总是等 minMs；nng 默认 0
+}
+
+pub fn apply_backoff(socket: &Socket, backoff: &ReconnectBackoff) -> Result<()> {
+    if let (Some(min), Some(max)) = (backoff.min_ms, backoff.max_ms) {
+        if max != 0 && max < min {
+            return Err(napi::Error::new(napi::Status::InvalidArg, "maxMs must be 0 or at least minMs".to_string()));
+        }
+    }
+    if let Some(ms) = backoff.min_ms {
+        socket
+            .set_opt::<ReconnectMinTime>(Some(Duration::from_millis(ms as u64)))
+            .map_err(|err| fail("reconnect minMs", err))?;
+    }
+    if let Some(ms) = backoff.max_ms {
+        socket
+            .set_opt::<ReconnectMaxTime>(Some(Duration::from_millis(ms as u64)))
+            .map_err(|err| fail("reconnect maxMs", err))?;
+    }
+    Ok(())
+}
+
+fn fail(what: &str, err: nng::Error) -> napi::Error {
+    napi::Error::new(napi::Status::GenericFailure, format!("Failed to set {}: {:?}", what, err))
+}
+
+fn apply_common<T>(target: &T, options: &EndpointOptions) -> Result<()>
+where
+    T: Options + SetOpt<RecvMaxSize> + SetOpt<NoDelay> + SetOpt<KeepAlive> + SetOpt<CaFile> + SetOpt<CertKeyFile>,
+{
+    if let Some(size) = options.recv_max_size {
+        target.set_opt::<RecvMaxSize>(size as usize).map_err(|err| fail("recvMaxSize", err))?;
+    }
+    if let Some(enabled) = options.tcp_no_delay {
+        target.set_opt::<NoDelay>(enabled).map_err(|err| fail("tcpNoDelay", err))?;
+    }
+    if let Some(enabled) = options.tcp_keep_alive {
+        target.set_opt::<KeepAlive>(enabled).map_err(|err| fail("tcpKeepAlive", err))?;
+    }
+    // CA 要先于证书设置，nng 才会用它校验对端
+    if let Some(path) = &options.tls_ca_file {
+        target.set_opt::<CaFile>(path.clone()).map_err(|err| fail("tlsCaFile", err))?;
+    }
+    if let Some(path) = &options.tls_cert_key_file {
+        target.set_opt::<CertKeyFile>(path.clone()).map_err(|err| fail("tlsCertKeyFile", err))?;
+    }
+    Ok(())
+}
+
+fn option_name(name: &[u8]) -> *const c_char {
+    name.as_ptr() as *const c_char
+}
+
+pub fn apply_listener(listener: &ListenerBuilder, options: &EndpointOptions) -> Result<()> {
+    if options.reconnect_min_ms.is_some() || options.reconnect_max_ms.is_some() {
+        return Err(napi::Error::new(
+            napi::Status::InvalidArg,
+            "Reconnect times only apply to dialers".to_string(),
+        ));
+    }
+    if options.tls_server_name.is_some() {
+        return Err(napi::Error::new(
+            napi::Status::InvalidArg,
+            "tlsServerName only applies to dialers".to_string(),
+        ));
+    }
+    apply_common(listener, options)?;
+    if let Some(mode) = options.tls_auth_mode {
+        let name = option_name(ffi::NNG_OPT_TLS_AUTH_MODE);
+        check(unsafe { ffi::nng_listener_set_int(listener.nng_listener(), name, mode.nng_mode()) })
+            .map_err(|err| fail("tlsAuthMode", err))?;
+    }
+    Ok(())
+}
+
+pub fn apply_dialer(dialer: &DialerBuilder, options: &EndpointOptions) -> Result<()> {
+    if let Some(ms) = options.reconnect_min_ms {
+        dialer
+            .set_opt::<ReconnectMinTime>(Some(Duration::from_millis(ms as u64)))
+            .map_err(|err| fail("reconnectMinMs", err))?;
+    }
+    if let Some(ms) = options.reconnect_max_ms {
+        dialer
+            .set_opt::<ReconnectMaxTime>(Some(Duration::from_millis(ms as u64)))
+            .map_err(|err| fail("reconnectMaxMs", err))?;
+    }
+    apply_common(dialer, options)?;
+    if let Some(mode) = options.tls_auth_mode {
+        let name = option_name(ffi::NNG_OPT_TLS_AUTH_MODE);
+        check(unsafe { ffi::nng_dialer_set_int(dialer.nng_dialer(), name, mode.nng_mode()) })
+            .map_err(|err| fail("tlsAuthMode", err))?;
+    }
+    if let Some(server_name) = &options.tls_server_name {
+        let value = CString::new(server_name.as_str()).map_err(|_| {
+            napi::Error::new(napi::Status::InvalidArg, format!("Invalid tlsServerName: {:?}", server_name))
+        })?;
+        let name = option_name(ffi::NNG_OPT_TLS_SERVER_NAME);
+        check(unsafe { ffi::nng_dialer_set_string(dialer.nng_dialer(), name, value.as_ptr()) })
+            .map_err(|err| fail("tlsServerName", err))?;
+    }
+    Ok(())
+}
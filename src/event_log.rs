@@ -0,0 +1,442 @@
+use napi::bindgen_prelude::*;
+use napi::threadsafe_function::{ThreadSafeCallContext, ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use napi::{Env, JsFunction};
+use napi_derive::napi;
+use nng::{Error as NngError, Protocol, Socket};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::SystemTime;
+
+use crate::events::{EventEmitter, SocketEvent};
+use crate::guard;
+use crate::payload::Payload;
+use crate::topic;
+
+// 日志文件格式：8 字节文件头，之后每条记录为
+// offset(8, 大端) + 时间(8，Unix 毫秒的 f64 大端) + topic 长度(4, 大端) + 消息长度(4, 大端) + topic + 消息。
+// offset 从 0 开始连续编号，只追加；打开时截掉最后一条没写完整的记录
+const MAGIC: &[u8; 8] = b"NNGELOG\x01";
+const RECORD_HEADER_LEN: usize = 24;
+const DEFAULT_READ_LIMIT: u32 = 1000;
+// follow 每次从文件读多少条，回调队列满时读线程等 JS 线程
+const FOLLOW_BATCH: usize = 256;
+const FOLLOW_QUEUE: usize = 1024;
+// 追赶应答每批最多扫描的记录数
+const CATCH_UP_LIMIT: u32 = 1000;
+
+fn failed(reason: String) -> napi::Error {
+    napi::Error::new(napi::Status::GenericFailure, reason)
+}
+
+#[napi(object)]
+pub struct EventLogOptions {
+    pub sync: Option<bool>, // 每条记录写完都 fsync，默认 false（只保证写进操作系统）
+}
+
+#[napi(object)]
+pub struct LogRecord {
+    pub offset: i64,
+    pub topic: String,
+    pub data: Buffer,
+    pub timestamp: f64, // 追加时的 Unix 毫秒
+}
+
+pub struct Stored {
+    pub offset: u64,
+    pub timestamp: f64,
+    pub topic: String,
+    pub data: Vec<u8>,
+}
+
+impl From<Stored> for LogRecord {
+    fn from(stored: Stored) -> Self {
+        LogRecord { offset: stored.offset as i64, topic: stored.topic, data: stored.data.into(), timestamp: stored.timestamp }
+    }
+}
+
+fn encode(stored: &Stored) -> Vec<u8> {
+    let mut record = Vec::with_capacity(RECORD_HEADER_LEN + stored.topic.len() + stored.data.len());
+    record.extend_from_slice(&stored.offset.to_be_bytes());
+    record.extend_from_slice(&stored.timestamp.to_be_bytes());
+    record.extend_from_slice(&(stored.topic.len() as u32).to_be_bytes());
+    record.extend_from_slice(&(stored.data.len() as u32).to_be_bytes());
+    record.extend_from_slice(stored.topic.as_bytes());
+    record.extend_from_slice(&stored.data);
+    record
+}
+
+// 读下一条记录，文件结束或者最后一条不完整时返回 None
+fn decode(reader: &mut impl Read) -> std::io::Result<Option<Stored>> {
+    let mut fixed = [0u8; RECORD_HEADER_LEN];
+    match reader.read_exact(&mut fixed) {
+        Ok(()) => {}
+        Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    }
+    let offset = u64::from_be_bytes(fixed[..8].try_into().unwrap());
+    let timestamp = f64::from_be_bytes(fixed[8..16].try_into().unwrap());
+    let topic_len = u32::from_be_bytes(fixed[16..20].try_into().unwrap()) as usize;
+    let data_len = u32::from_be_bytes(fixed[20..24].try_into().unwrap()) as usize;
+    let mut body = vec![0u8; topic_len + data_len];
+    match reader.read_exact(&mut body) {
+        Ok(()) => {}
+        Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    }
+    let data = body.split_off(topic_len);
+    let topic = String::from_utf8(body).map_err(|_| std::io::Error::new(ErrorKind::InvalidData, "Topic is not UTF-8"))?;
+    Ok(Some(Stored { offset, timestamp, topic, data }))
+}
+
+// 追赶协议（Req/Rep）。请求：fromOffset(8, 大端) + limit(4, 大端) + 以 0 分隔的主题列表，空表示全部主题；
+// 应答：next(8, 大端) + end(8, 大端) + 若干条记录（格式同日志文件）。next 是这一批扫描过的最后一条之后的 offset，
+// 不属于所请求主题的记录也算扫描过；end 是应答时日志的末尾，next < end 时订阅端接着请求
+pub fn encode_request(from: u64, limit: u32, topics: &[String]) -> Vec<u8> {
+    let mut request = Vec::with_capacity(12);
+    request.extend_from_slice(&from.to_be_bytes());
+    request.extend_from_slice(&limit.to_be_bytes());
+    request.extend_from_slice(topics.join("\0").as_bytes());
+    request
+}
+
+fn decode_request(data: &[u8]) -> Option<(u64, u32, Vec<String>)> {
+    let from = u64::from_be_bytes(data.get(..8)?.try_into().ok()?);
+    let limit = u32::from_be_bytes(data.get(8..12)?.try_into().ok()?);
+    let topics = std::str::from_utf8(&data[12..]).ok()?;
+    let topics = topics.split('\0').filter(|topic| !topic.is_empty()).map(str::to_string).collect();
+    Some((from, limit, topics))
+}
+
+fn encode_batch(next: u64, end: u64, records: &[Stored]) -> Vec<u8> {
+    let mut batch = Vec::new();
+    batch.extend_from_slice(&next.to_be_bytes());
+    batch.extend_from_slice(&end.to_be_bytes());
+    for stored in records {
+        batch.extend_from_slice(&encode(stored));
+    }
+    batch
+}
+
+// (next, end, 记录)，格式不对时为 None
+pub fn decode_batch(data: &[u8]) -> Option<(u64, u64, Vec<Stored>)> {
+    let next = u64::from_be_bytes(data.get(..8)?.try_into().ok()?);
+    let end = u64::from_be_bytes(data.get(8..16)?.try_into().ok()?);
+    let mut reader = data.get(16..)?;
+    let mut records = Vec::new();
+    while !reader.is_empty() {
+        records.push(decode(&mut reader).ok()??);
+    }
+    Some((next, end, records))
+}
+
+struct Log {
+    file: File,
+    positions: Vec<u64>, // 每个 offset 的记录在文件里的位置
+    end: u64,
+    closed: bool,
+}
+
+// 追加端、读取端和 follow 线程共用；追加完整写入后才登记位置，读的一方不会读到半条记录
+pub struct Store {
+    path: String,
+    sync: bool,
+    log: Mutex<Log>,
+    appended: Condvar,
+}
+
+impl Store {
+    pub fn open(path: &str, sync: bool) -> Result<Self> {
+        let io_failed = |err: std::io::Error| failed(format!("Failed to open {}: {}", path, err));
+        let mut file = OpenOptions::new().read(true).append(true).create(true).open(path).map_err(io_failed)?;
+        let mut positions = Vec::new();
+        let mut end = MAGIC.len() as u64;
+        if file.metadata().map_err(io_failed)?.len() == 0 {
+            file.write_all(MAGIC).map_err(io_failed)?;
+        } else {
+            let mut reader = BufReader::new(File::open(path).map_err(io_failed)?);
+            let mut magic = [0u8; 8];
+            if reader.read_exact(&mut magic).is_err() || &magic != MAGIC {
+                return Err(failed(format!("Not an event log: {}", path)));
+            }
+            while let Some(stored) = decode(&mut reader).map_err(io_failed)? {
+                if stored.offset != positions.len() as u64 {
+                    return Err(failed(format!("Corrupt event log {}: offset {} out of order", path, stored.offset)));
+                }
+                positions.push(end);
+                end += (RECORD_HEADER_LEN + stored.topic.len() + stored.data.len()) as u64;
+            }
+            // 上次写到一半退出留下的残缺记录
+            if file.metadata().map_err(io_failed)?.len() > end {
+                file.set_len(end).map_err(io_failed)?;
+            }
+        }
+        let log = Log { file, positions, end, closed: false };
+        Ok(Store { path: path.to_string(), sync, log: Mutex::new(log), appended: Condvar::new() })
+    }
+
+    pub fn append(&self, topic: &str, data: &[u8]) -> std::io::Result<Stored> {
+        let mut log = self.log.lock().unwrap();
+        if log.closed {
+            return Err(std::io::Error::new(ErrorKind::BrokenPipe, "Event log closed"));
+        }
+        let timestamp = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map_or(0.0, |d| d.as_secs_f64() * 1000.0);
+        let stored = Stored { offset: log.positions.len() as u64, timestamp, topic: topic.to_string(), data: data.to_vec() };
+        let record = encode(&stored);
+        if let Err(err) = log.file.write_all(&record).and_then(|_| if self.sync { log.file.sync_data() } else { Ok(()) }) {
+            // 写了一部分时截回去，下一条接着写在完整的记录后面
+            let end = log.end;
+            let _ = log.file.set_len(end);
+            return Err(err);
+        }
+        let position = log.end;
+        log.positions.push(position);
+        log.end += record.len() as u64;
+        self.appended.notify_all();
+        Ok(stored)
+    }
+
+    // 从 from 开始最多 limit 条，from 超出末尾时为空
+    pub fn read(&self, from: u64, limit: usize) -> std::io::Result<Vec<Stored>> {
+        let (position, count) = {
+            let log = self.log.lock().unwrap();
+            let Some(position) = log.positions.get(from as usize) else {
+                return Ok(Vec::new());
+            };
+            (*position, (log.positions.len() - from as usize).min(limit))
+        };
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(position))?;
+        let mut reader = BufReader::new(file);
+        let mut records = Vec::with_capacity(count);
+        while records.len() < count {
+            match decode(&mut reader)? {
+                Some(stored) => records.push(stored),
+                None => break,
+            }
+        }
+        Ok(records)
+    }
+
+    pub fn next_offset(&self) -> u64 {
+        self.log.lock().unwrap().positions.len() as u64
+    }
+
+    // 等到 offset 已经写入，返回 false 表示日志关闭或者 stopped
+    pub fn wait_for(&self, offset: u64, stopped: &AtomicBool) -> bool {
+        let mut log = self.log.lock().unwrap();
+        loop {
+            if log.closed || stopped.load(Ordering::SeqCst) {
+                return false;
+            }
+            if (offset as usize) < log.positions.len() {
+                return true;
+            }
+            log = self.appended.wait(log).unwrap();
+        }
+    }
+
+    pub fn wake(&self) {
+        let _log = self.log.lock().unwrap();
+        self.appended.notify_all();
+    }
+
+    pub fn close(&self) {
+        self.log.lock().unwrap().closed = true;
+        self.appended.notify_all();
+    }
+}
+
+// 事件日志：发布的每条消息先追加到本地日志、分配 offset，再经 Pub socket 发出，主题帧的序号就是 offset。
+// 订阅端记下处理到的 offset，断开或重启后用 read/follow 或者 LogSubscriber 从那里接着读，像一个单机的 Kafka
+#[napi]
+pub struct EventLog {
+    store: Arc<Store>,
+    socket: Option<Socket>,
+    catch_up: Option<Socket>, // listenCatchUp 之后的 Rep socket
+    followers: Mutex<HashMap<u32, Arc<AtomicBool>>>,
+    next_follower: AtomicU32,
+    events: EventEmitter,
+}
+
+#[napi]
+impl EventLog {
+    #[napi(constructor)]
+    pub fn new(path: String, options: Option<EventLogOptions>) -> Result<Self> {
+        let sync = options.and_then(|options| options.sync).unwrap_or(false);
+        let store = Arc::new(Store::open(&path, sync)?);
+        let socket = Socket::new(Protocol::Pub0).map_err(|err| failed(format!("Socket creation failed: {:?}", err)))?;
+        Ok(EventLog {
+            store,
+            socket: Some(socket),
+            catch_up: None,
+            followers: Mutex::new(HashMap::new()),
+            next_follower: AtomicU32::new(1),
+            events: EventEmitter::default(),
+        })
+    }
+
+    // 实时消息的发布地址，Subscriber 连上来按主题订阅
+    #[napi]
+    pub fn listen(&self, url: String) -> Result<()> {
+        self.socket()?.listen(&url).map_err(|err| failed(format!("Listen failed: {:?}", err)))
+    }
+
+    // 追赶地址：LogSubscriber 在这里按 offset 补读错过的记录，之后再接上实时消息
+    #[napi]
+    pub fn listen_catch_up(&mut self, url: String) -> Result<()> {
+        self.socket()?;
+        if let Some(socket) = &self.catch_up {
+            return socket.listen(&url).map_err(|err| failed(format!("Listen failed: {:?}", err)));
+        }
+        let socket = Socket::new(Protocol::Rep0).map_err(|err| failed(format!("Socket creation failed: {:?}", err)))?;
+        if let Err(err) = socket.listen(&url) {
+            socket.close();
+            return Err(failed(format!("Listen failed: {:?}", err)));
+        }
+
+        let (server, store, events) = (socket.clone(), self.store.clone(), self.events.clone());
+        guard::spawn(self.events.clone(), "Event log catch-up", move || loop {
+            let request = match server.recv() {
+                Ok(request) => request,
+                Err(NngError::Closed) => return,
+                Err(err) => {
+                    events.warn("recvFailed", format!("Error receiving catch-up request: {:?}", err));
+                    continue;
+                }
+            };
+            // 不回应答，请求端超时后重发
+            let Some((from, limit, topics)) = decode_request(request.as_slice()) else {
+                events.warn("malformedRequest", "Dropped a malformed catch-up request");
+                continue;
+            };
+            let end = store.next_offset();
+            let scanned = match store.read(from, limit.clamp(1, CATCH_UP_LIMIT) as usize) {
+                Ok(scanned) => scanned,
+                Err(err) => {
+                    events.warn("readFailed", format!("Failed to read {} at offset {}: {}", store.path, from, err));
+                    continue;
+                }
+            };
+            let next = scanned.last().map_or(from, |stored| stored.offset + 1);
+            let records: Vec<Stored> =
+                scanned.into_iter().filter(|stored| topics.is_empty() || topics.contains(&stored.topic)).collect();
+            match server.send(&encode_batch(next, end, &records)[..]) {
+                Ok(()) => {}
+                Err((_, NngError::Closed)) => return,
+                Err((_, err)) => events.warn("sendFailed", format!("Failed to answer catch-up request: {:?}", err)),
+            }
+        });
+        self.catch_up = Some(socket);
+        Ok(())
+    }
+
+    // 追加并发布，返回分配的 offset；写日志失败时不发布
+    #[napi(ts_args_type = "topic: string, message: Buffer | Uint8Array | string | ArrayBuffer")]
+    pub fn publish(&self, topic: String, message: Payload) -> Result<i64> {
+        topic::check_topic(&topic)?;
+        let socket = self.socket()?;
+        let stored = self
+            .store
+            .append(&topic, &message)
+            .map_err(|err| failed(format!("Failed to append to {}: {}", self.store.path, err)))?;
+        let frame = topic::encode(&topic, 0, Some(stored.offset), &stored.data);
+        if let Err((_, err)) = socket.send(&frame[..]) {
+            self.events.warn("sendFailed", format!("Failed to publish offset {}: {:?}", stored.offset, err));
+        }
+        Ok(stored.offset as i64)
+    }
+
+    // 从 fromOffset 开始读最多 limit 条（默认 1000）
+    #[napi]
+    pub fn read(&self, from_offset: i64, limit: Option<u32>) -> Result<Vec<LogRecord>> {
+        let from = check_offset(from_offset)?;
+        let limit = limit.unwrap_or(DEFAULT_READ_LIMIT) as usize;
+        let records = self.store.read(from, limit).map_err(|err| failed(format!("Failed to read {}: {}", self.store.path, err)))?;
+        Ok(records.into_iter().map(LogRecord::from).collect())
+    }
+
+    // 下一条记录会分配到的 offset，也就是已有的记录数
+    #[napi]
+    pub fn next_offset(&self) -> i64 {
+        self.store.next_offset() as i64
+    }
+
+    // 从 fromOffset 开始按顺序把记录交给回调，读完已有的之后继续等新的，直到 unfollow 或 close。返回取消用的 id
+    #[napi(ts_args_type = "fromOffset: number, callback: (err: Error | null, record: LogRecord) => any")]
+    pub fn follow(&self, env: Env, from_offset: i64, callback: JsFunction) -> Result<u32> {
+        let mut next = check_offset(from_offset)?;
+        self.socket()?;
+        let callback = env.create_threadsafe_function(&callback, FOLLOW_QUEUE, |ctx: ThreadSafeCallContext<LogRecord>| {
+            Ok(vec![ctx.value])
+        })?;
+        let stopped = Arc::new(AtomicBool::new(false));
+        let id = self.next_follower.fetch_add(1, Ordering::SeqCst);
+        self.followers.lock().unwrap().insert(id, stopped.clone());
+
+        let (store, events) = (self.store.clone(), self.events.clone());
+        guard::spawn(self.events.clone(), "Event log follower", move || {
+            while store.wait_for(next, &stopped) {
+                let records = match store.read(next, FOLLOW_BATCH) {
+                    Ok(records) => records,
+                    Err(err) => {
+                        events.warn("readFailed", format!("Failed to read {} at offset {}: {}", store.path, next, err));
+                        return;
+                    }
+                };
+                for stored in records {
+                    if stopped.load(Ordering::SeqCst) {
+                        return;
+                    }
+                    next = stored.offset + 1;
+                    if callback.call(Ok(stored.into()), ThreadsafeFunctionCallMode::Blocking) != napi::Status::Ok {
+                        return;
+                    }
+                }
+            }
+        });
+        Ok(id)
+    }
+
+    #[napi]
+    pub fn unfollow(&self, id: u32) -> bool {
+        let Some(stopped) = self.followers.lock().unwrap().remove(&id) else {
+            return false;
+        };
+        stopped.store(true, Ordering::SeqCst);
+        self.store.wake();
+        true
+    }
+
+    // sendFailed、readFailed、recvFailed、malformedRequest
+    #[napi]
+    pub fn on_event(&self, callback: ThreadsafeFunction<SocketEvent>) {
+        self.events.set(callback);
+    }
+
+    // 停止 follow，日志文件保留，下次用同一个路径打开时接着编号
+    #[napi]
+    pub fn close(&mut self) {
+        if let Some(socket) = self.socket.take() {
+            for (_, stopped) in self.followers.lock().unwrap().drain() {
+                stopped.store(true, Ordering::SeqCst);
+            }
+            self.store.close();
+            socket.close();
+            if let Some(catch_up) = self.catch_up.take() {
+                catch_up.close();
+            }
+            self.events.clear();
+        }
+    }
+
+    fn socket(&self) -> Result<&Socket> {
+        self.socket.as_ref().ok_or_else(|| failed("Event log closed".to_string()))
+    }
+}
+
+pub fn check_offset(offset: i64) -> Result<u64> {
+    u64::try_from(offset).map_err(|_| napi::Error::new(napi::Status::InvalidArg, "offset must not be negative".to_string()))
+}
@@ -0,0 +1,111 @@
+use napi::threadsafe_function::{ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use napi::Env;
+use napi_derive::napi;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::crash_dump::CrashDump;
+use crate::labels::Labels;
+
+// 通过 onEvent 回调推给 JS 的事件，name 区分事件类型，其余字段按事件选填
+#[napi(object)]
+pub struct SocketEvent {
+    pub name: String,
+    pub pipe_id: Option<u32>,
+    pub message: Option<String>,
+    pub value: Option<i64>,
+    pub code: Option<String>, // warning 和 info 事件的分类，如 recvTimedOut、recvFailed、closed
+    pub labels: Option<HashMap<String, String>>, // 所属 socket 的标签
+}
+
+impl SocketEvent {
+    pub fn new(name: &str) -> Self {
+        SocketEvent {
+            name: name.to_string(),
+            pipe_id: None,
+            message: None,
+            value: None,
+            code: None,
+            labels: None,
+        }
+    }
+
+    pub fn pipe(mut self, pipe_id: u32) -> Self {
+        self.pipe_id = Some(pipe_id);
+        self
+    }
+
+    pub fn message<S: Into<String>>(mut self, message: S) -> Self {
+        self.message = Some(message.into());
+        self
+    }
+
+    pub fn value(mut self, value: i64) -> Self {
+        self.value = Some(value);
+        self
+    }
+
+    pub fn code(mut self, code: &str) -> Self {
+        self.code = Some(code.to_string());
+        self
+    }
+}
+
+// 可以在多个线程间共享的事件出口，没有注册回调时事件直接丢弃
+#[derive(Clone, Default)]
+pub struct EventEmitter {
+    callback: Arc<Mutex<Option<ThreadsafeFunction<SocketEvent>>>>,
+    labels: Labels,
+    crash_dump: CrashDump, // setCrashDump 的目录和要写的状态，clear 时保留
+}
+
+impl EventEmitter {
+    pub fn labels(&self) -> &Labels {
+        &self.labels
+    }
+
+    pub fn crash_dump(&self) -> &CrashDump {
+        &self.crash_dump
+    }
+
+    pub fn set(&self, callback: ThreadsafeFunction<SocketEvent>) {
+        *self.callback.lock().unwrap() = Some(callback);
+    }
+
+    pub fn emit(&self, mut event: SocketEvent) {
+        if let Some(callback) = self.callback.lock().unwrap().as_ref() {
+            if !self.labels.is_empty() {
+                event.labels = Some(self.labels.get());
+            }
+            let _ = callback.call(Ok(event), ThreadsafeFunctionCallMode::NonBlocking);
+        }
+    }
+
+    // 内部警告作为 warning 事件交给 JS，由调用方决定怎么记录；没有注册 onEvent 时不输出
+    pub fn warn<S: Into<String>>(&self, code: &str, message: S) {
+        self.emit(SocketEvent::new("warning").code(code).message(message));
+    }
+
+    // 意外的致命错误：开启了 setCrashDump 时先写快照，crashDump 事件带着文件路径，再发 error 事件
+    pub fn fatal(&self, reason: String) {
+        match self.crash_dump.write(&reason, &self.labels.prefix()) {
+            Some(Ok(path)) => self.emit(SocketEvent::new("crashDump").message(path)),
+            Some(Err(message)) => self.warn("crashDumpFailed", message),
+            None => {}
+        }
+        self.emit(SocketEvent::new("error").message(reason));
+    }
+
+    pub fn clear(&self) {
+        self.callback.lock().unwrap().take();
+    }
+
+    // 保留回调，只改它是否让进程保持运行
+    pub fn hold(&self, env: &Env, referenced: bool) -> napi::Result<()> {
+        match self.callback.lock().unwrap().as_mut() {
+            Some(callback) if referenced => callback.refer(env),
+            Some(callback) => callback.unref(env),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use napi::bindgen_prelude::*;
+use napi::threadsafe_function::{ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use napi_derive::napi;
+use nng::options::protocol::pubsub::Subscribe;
+use nng::options::Options;
+use nng::{Error as NngError, Protocol, Socket};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use crate::events::{EventEmitter, SocketEvent};
+use crate::guard;
+use crate::nanomsg::ProtocolType;
+
+const DEFAULT_QUEUE_SIZE: u32 = 1024;
+
+fn failed(reason: String) -> napi::Error {
+    napi::Error::new(napi::Status::GenericFailure, reason)
+}
+
+fn invalid(reason: String) -> napi::Error {
+    napi::Error::new(napi::Status::InvalidArg, reason)
+}
+
+#[napi(object)]
+pub struct FanInOptions {
+    pub queue_size: Option<u32>, // 汇总后等待交付的消息数，满了以后各个上游停止接收，默认 1024
+}
+
+#[napi(object)]
+pub struct UpstreamOptions {
+    pub url: String,
+    pub protocol: Option<ProtocolType>, // Pull0（默认）、Sub0（收全部主题）、Pair0、Pair1 或 Bus0
+    pub dial: Option<bool>,             // 默认在 url 上监听，等边缘节点连上来
+}
+
+#[napi(object)]
+pub struct SourcedMessage {
+    pub source: String,   // addUpstream 时的名字
+    pub sequence: i64,    // 汇总后的序号，从 1 开始，按交付顺序递增
+    pub data: Buffer,
+    pub received_at: f64, // 上游收到的时间，Unix 毫秒
+}
+
+#[napi(object)]
+pub struct UpstreamStats {
+    pub name: String,
+    pub received: i64,
+}
+
+#[napi(object)]
+pub struct FanInStats {
+    pub delivered: i64,
+    pub queued: i64,
+    pub upstreams: Vec<UpstreamStats>,
+}
+
+struct Arrival {
+    source: Arc<str>,
+    data: Vec<u8>,
+    received_at: f64,
+}
+
+struct Upstream {
+    socket: Socket,
+    received: Arc<AtomicI64>,
+}
+
+// 汇聚：在多个上游 socket 上收消息，合成一条按到达先后排好序的流，每条带上来源的名字和全局序号。
+// 汇总队列满了（比如 JS 线程处理不过来）时各个上游停止接收，压力沿着 nng 的缓冲区传回发送方
+#[napi]
+pub struct FanIn {
+    upstreams: Mutex<HashMap<String, Upstream>>,
+    queue: Mutex<Option<SyncSender<Arrival>>>, // 关闭时取走，上游线程都退出后交付线程随之退出
+    arrivals: Mutex<Option<Receiver<Arrival>>>, // recv 时交给交付线程
+    queued: Arc<AtomicI64>,
+    delivered: Arc<AtomicI64>,
+    events: EventEmitter,
+    is_closing: Arc<AtomicBool>,
+}
+
+#[napi]
+impl FanIn {
+    #[napi(constructor)]
+    pub fn new(options: Option<FanInOptions>) -> Result<Self> {
+        let queue_size = options.and_then(|options| options.queue_size).unwrap_or(DEFAULT_QUEUE_SIZE);
+        if queue_size == 0 {
+            return Err(invalid("queueSize must be greater than 0".to_string()));
+        }
+        let (queue, arrivals) = mpsc::sync_channel(queue_size as usize);
+        Ok(FanIn {
+            upstreams: Mutex::new(HashMap::new()),
+            queue: Mutex::new(Some(queue)),
+            arrivals: Mutex::new(Some(arrivals)),
+            queued: Arc::default(),
+            delivered: Arc::default(),
+            events: EventEmitter::default(),
+            is_closing: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    // 同名的上游先关掉再替换
+    #[napi]
+    pub fn add_upstream(&self, name: String, options: UpstreamOptions) -> Result<()> {
+        let Some(queue) = self.queue.lock().unwrap().clone() else {
+            return Err(failed("Fan-in closed".to_string()));
+        };
+        let protocol = options.protocol.map(Protocol::from).unwrap_or(Protocol::Pull0);
+        if !matches!(protocol, Protocol::Pull0 | Protocol::Sub0 | Protocol::Pair0 | Protocol::Pair1 | Protocol::Bus0) {
+            return Err(invalid("Upstreams receive on Pull0, Sub0, Pair0, Pair1 or Bus0".to_string()));
+        }
+        let socket = Socket::new(protocol).map_err(|err| failed(format!("Socket creation failed: {:?}", err)))?;
+        let subscribed = if protocol == Protocol::Sub0 {
+            socket.set_opt::<Subscribe>(Vec::new()).map_err(|err| failed(format!("Failed to subscribe: {:?}", err)))
+        } else {
+            Ok(())
+        };
+        let opened = subscribed.and_then(|_| {
+            if options.dial.unwrap_or(false) {
+                socket.dial_async(&options.url).map_err(|err| failed(format!("Connection failed: {:?}", err)))
+            } else {
+                socket.listen(&options.url).map_err(|err| failed(format!("Listen failed: {:?}", err)))
+            }
+        });
+        if let Err(err) = opened {
+            socket.close();
+            return Err(err);
+        }
+
+        let upstream = Upstream { socket: socket.clone(), received: Arc::default() };
+        let (received, queued, events, is_closing) =
+            (upstream.received.clone(), self.queued.clone(), self.events.clone(), self.is_closing.clone());
+        let source: Arc<str> = Arc::from(name.as_str());
+        guard::spawn(self.events.clone(), "Fan-in upstream", move || loop {
+            let message = match socket.recv() {
+                Ok(message) => message,
+                Err(NngError::Closed) => return,
+                Err(_) if is_closing.load(Ordering::SeqCst) => return,
+                Err(err) => {
+                    events.warn("recvFailed", format!("Error receiving from {}: {:?}", source, err));
+                    continue;
+                }
+            };
+            received.fetch_add(1, Ordering::SeqCst);
+            let received_at = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map_or(0.0, |d| d.as_secs_f64() * 1000.0);
+            queued.fetch_add(1, Ordering::SeqCst);
+            // 队列满时在这里等，不再从 socket 收
+            if queue.send(Arrival { source: source.clone(), data: message.as_slice().to_vec(), received_at }).is_err() {
+                return;
+            }
+        });
+        if let Some(previous) = self.upstreams.lock().unwrap().insert(name, upstream) {
+            previous.socket.close();
+        }
+        Ok(())
+    }
+
+    // 已经进了汇总队列的消息照样交付
+    #[napi]
+    pub fn remove_upstream(&self, name: String) -> bool {
+        match self.upstreams.lock().unwrap().remove(&name) {
+            Some(upstream) => {
+                upstream.socket.close();
+                true
+            }
+            None => false,
+        }
+    }
+
+    // 按汇总顺序逐条交给 callback，只能调用一次
+    #[napi(ts_args_type = "callback: (err: Error | null, message: SourcedMessage) => any")]
+    pub fn recv(&self, callback: ThreadsafeFunction<SourcedMessage>) -> Result<()> {
+        let Some(arrivals) = self.arrivals.lock().unwrap().take() else {
+            return Err(failed("Already receiving".to_string()));
+        };
+        let (queued, delivered, is_closing) = (self.queued.clone(), self.delivered.clone(), self.is_closing.clone());
+        guard::spawn(self.events.clone(), "Fan-in", move || {
+            let mut sequence = 0i64;
+            while let Ok(arrival) = arrivals.recv() {
+                if is_closing.load(Ordering::SeqCst) {
+                    return; // 丢掉接收端，等在队列上的上游线程随之退出
+                }
+                queued.fetch_sub(1, Ordering::SeqCst);
+                sequence += 1;
+                let message = SourcedMessage {
+                    source: arrival.source.to_string(),
+                    sequence,
+                    data: arrival.data.into(),
+                    received_at: arrival.received_at,
+                };
+                if callback.call(Ok(message), ThreadsafeFunctionCallMode::Blocking) != napi::Status::Ok {
+                    return;
+                }
+                delivered.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        Ok(())
+    }
+
+    #[napi]
+    pub fn stats(&self) -> FanInStats {
+        let mut upstreams: Vec<UpstreamStats> = self
+            .upstreams
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, upstream)| UpstreamStats { name: name.clone(), received: upstream.received.load(Ordering::SeqCst) })
+            .collect();
+        upstreams.sort_by(|a, b| a.name.cmp(&b.name));
+        FanInStats {
+            delivered: self.delivered.load(Ordering::SeqCst),
+            queued: self.queued.load(Ordering::SeqCst),
+            upstreams,
+        }
+    }
+
+    // recvFailed，message 里带着上游的名字
+    #[napi]
+    pub fn on_event(&self, callback: ThreadsafeFunction<SocketEvent>) {
+        self.events.set(callback);
+    }
+
+    // 还没交付的消息随之丢弃
+    #[napi]
+    pub fn close(&self) {
+        self.is_closing.store(true, Ordering::SeqCst);
+        self.queue.lock().unwrap().take();
+        self.arrivals.lock().unwrap().take();
+        for (_, upstream) in self.upstreams.lock().unwrap().drain() {
+            upstream.socket.close();
+        }
+        self.events.clear();
+    }
+}
@@ -0,0 +1,298 @@
+use napi::bindgen_prelude::*;
+use napi::threadsafe_function::ThreadsafeFunction;
+use napi_derive::napi;
+use nng::options::protocol::pubsub::Subscribe;
+use nng::options::{Options, SendTimeout};
+use nng::{Error as NngError, Message, Protocol, Socket};
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::events::{EventEmitter, SocketEvent};
+use crate::guard;
+use crate::nanomsg::ProtocolType;
+use crate::routing::{self, Rule, RoutingRule};
+
+const DEFAULT_QUEUE_SIZE: u32 = 1024;
+const DEFAULT_SEND_TIMEOUT_MS: u32 = 1000;
+
+fn failed(reason: String) -> napi::Error {
+    napi::Error::new(napi::Status::GenericFailure, reason)
+}
+
+fn invalid(reason: String) -> napi::Error {
+    napi::Error::new(napi::Status::InvalidArg, reason)
+}
+
+#[napi(object)]
+pub struct FanOutOptions {
+    pub protocol: Option<ProtocolType>, // 接收端的协议：Pull0（默认）、Sub0（收全部主题）、Pair0、Pair1 或 Bus0
+    pub dial: Option<bool>,             // 默认在 url 上监听
+    pub first_match: Option<bool>,      // 为 true 时每条消息只交给按添加顺序第一个规则匹配的下游，默认交给所有匹配的
+}
+
+#[napi(object)]
+pub struct DownstreamOptions {
+    pub url: String,
+    pub protocol: ProtocolType,          // Push0、Pub0、Pair0、Pair1 或 Bus0
+    pub listen: Option<bool>,            // 默认拨号到 url，下游晚启动时自动重连
+    pub queue_size: Option<u32>,         // 这个下游排队等发的消息数，满了丢弃新消息，默认 1024
+    pub send_timeout_ms: Option<u32>,    // 单条消息最多等这么久，超时算一次失败，默认 1000
+    pub rules: Option<Vec<RoutingRule>>, // 只转发匹配其中一条规则的消息，不给时转发全部
+}
+
+#[napi(object)]
+pub struct DownstreamStats {
+    pub name: String,
+    pub sent: i64,
+    pub dropped: i64, // 队列满了丢掉的
+    pub failed: i64,  // 发送出错或超时的
+    pub queued: i64,
+}
+
+#[napi(object)]
+pub struct FanOutStats {
+    pub received: i64,
+    pub unrouted: i64, // 没有任何下游的规则匹配、直接丢弃的
+    pub downstreams: Vec<DownstreamStats>,
+}
+
+#[derive(Default)]
+struct Counters {
+    sent: AtomicI64,
+    dropped: AtomicI64,
+    failed: AtomicI64,
+    queued: AtomicI64,
+}
+
+// 一个下游：自己的 socket、路由规则、有界队列和发送线程
+struct Downstream {
+    socket: Socket,
+    rules: Vec<Rule>,
+    queue: SyncSender<Arc<[u8]>>,
+    counters: Arc<Counters>,
+}
+
+// 按添加顺序，firstMatch 时依次匹配
+type Downstreams = Arc<Mutex<Vec<(String, Downstream)>>>;
+
+// 扇出：从一个 socket 收消息，原样转给每个下游。每个下游有独立的队列和发送线程，
+// 某个下游断开、变慢或者出错时只影响它自己：队列满了丢它的消息，其余下游照常收到。
+// 下游可以带路由规则，按消息开头的字节、主题和大小决定转给谁，路由在原生线程里完成
+#[napi]
+pub struct FanOut {
+    upstream: Option<Socket>,
+    downstreams: Downstreams,
+    received: Arc<AtomicI64>,
+    unrouted: Arc<AtomicI64>,
+    events: EventEmitter,
+    is_closing: Arc<AtomicBool>,
+}
+
+#[napi]
+impl FanOut {
+    #[napi(constructor)]
+    pub fn new(url: String, options: Option<FanOutOptions>) -> Result<Self> {
+        let (protocol, dial, first_match) = match options {
+            Some(options) => (
+                options.protocol.map(Protocol::from).unwrap_or(Protocol::Pull0),
+                options.dial.unwrap_or(false),
+                options.first_match.unwrap_or(false),
+            ),
+            None => (Protocol::Pull0, false, false),
+        };
+        if !matches!(protocol, Protocol::Pull0 | Protocol::Sub0 | Protocol::Pair0 | Protocol::Pair1 | Protocol::Bus0) {
+            return Err(invalid("Fan-out receives on Pull0, Sub0, Pair0, Pair1 or Bus0".to_string()));
+        }
+        let upstream = Socket::new(protocol).map_err(|err| failed(format!("Socket creation failed: {:?}", err)))?;
+        let opened = if protocol == Protocol::Sub0 {
+            upstream.set_opt::<Subscribe>(Vec::new()).map_err(|err| failed(format!("Failed to subscribe: {:?}", err)))
+        } else {
+            Ok(())
+        }
+        .and_then(|_| attach(&upstream, &url, dial));
+        if let Err(err) = opened {
+            upstream.close();
+            return Err(err);
+        }
+
+        let fan_out = FanOut {
+            upstream: Some(upstream.clone()),
+            downstreams: Arc::default(),
+            received: Arc::default(),
+            unrouted: Arc::default(),
+            events: EventEmitter::default(),
+            is_closing: Arc::new(AtomicBool::new(false)),
+        };
+        let (downstreams, received, unrouted, events, is_closing) = (
+            fan_out.downstreams.clone(),
+            fan_out.received.clone(),
+            fan_out.unrouted.clone(),
+            fan_out.events.clone(),
+            fan_out.is_closing.clone(),
+        );
+        guard::spawn(fan_out.events.clone(), "Fan-out", move || loop {
+            let message = match upstream.recv() {
+                Ok(message) => message,
+                Err(NngError::Closed) => return,
+                Err(_) if is_closing.load(Ordering::SeqCst) => return,
+                Err(err) => {
+                    events.warn("recvFailed", format!("Error receiving message: {:?}", err));
+                    continue;
+                }
+            };
+            received.fetch_add(1, Ordering::SeqCst);
+            // 只转发正文，各个下游共用一份
+            let body: Arc<[u8]> = Arc::from(message.as_slice());
+            let mut routed = false;
+            for (_, downstream) in downstreams.lock().unwrap().iter() {
+                if !routing::any_matches(&downstream.rules, &body) {
+                    continue;
+                }
+                routed = true;
+                // 先计数再入队，发送线程取走时减掉，不会出现负数
+                downstream.counters.queued.fetch_add(1, Ordering::SeqCst);
+                if let Err(TrySendError::Full(_) | TrySendError::Disconnected(_)) = downstream.queue.try_send(body.clone()) {
+                    downstream.counters.queued.fetch_sub(1, Ordering::SeqCst);
+                    downstream.counters.dropped.fetch_add(1, Ordering::SeqCst);
+                }
+                if first_match {
+                    break;
+                }
+            }
+            if !routed {
+                unrouted.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+        Ok(fan_out)
+    }
+
+    // 同名的下游先关掉再替换，位置不变
+    #[napi]
+    pub fn add_downstream(&self, name: String, options: DownstreamOptions) -> Result<()> {
+        if self.upstream.is_none() {
+            return Err(failed("Fan-out closed".to_string()));
+        }
+        let protocol: Protocol = options.protocol.into();
+        if !matches!(protocol, Protocol::Push0 | Protocol::Pub0 | Protocol::Pair0 | Protocol::Pair1 | Protocol::Bus0) {
+            return Err(invalid("Downstreams send on Push0, Pub0, Pair0, Pair1 or Bus0".to_string()));
+        }
+        let queue_size = options.queue_size.unwrap_or(DEFAULT_QUEUE_SIZE);
+        if queue_size == 0 {
+            return Err(invalid("queueSize must be greater than 0".to_string()));
+        }
+        let rules = options.rules.unwrap_or_default().iter().map(Rule::compile).collect::<Result<Vec<_>>>()?;
+        let timeout = Duration::from_millis(options.send_timeout_ms.unwrap_or(DEFAULT_SEND_TIMEOUT_MS) as u64);
+        let socket = Socket::new(protocol).map_err(|err| failed(format!("Socket creation failed: {:?}", err)))?;
+        let opened = socket
+            .set_opt::<SendTimeout>(Some(timeout))
+            .map_err(|err| invalid(format!("Invalid sendTimeoutMs: {:?}", err)))
+            .and_then(|_| attach(&socket, &options.url, !options.listen.unwrap_or(false)));
+        if let Err(err) = opened {
+            socket.close();
+            return Err(err);
+        }
+
+        let (queue, messages) = mpsc::sync_channel(queue_size as usize);
+        let downstream = Downstream { socket: socket.clone(), rules, queue, counters: Arc::default() };
+        let counters = downstream.counters.clone();
+        let events = self.events.clone();
+        let thread_name = name.clone();
+        guard::spawn(self.events.clone(), "Fan-out downstream", move || {
+            deliver(&thread_name, socket, messages, counters, events)
+        });
+        let mut downstreams = self.downstreams.lock().unwrap();
+        match downstreams.iter_mut().find(|(existing, _)| *existing == name) {
+            Some((_, previous)) => std::mem::replace(previous, downstream).socket.close(),
+            None => downstreams.push((name, downstream)),
+        }
+        Ok(())
+    }
+
+    // 还在队列里的消息随之丢弃
+    #[napi]
+    pub fn remove_downstream(&self, name: String) -> bool {
+        let mut downstreams = self.downstreams.lock().unwrap();
+        match downstreams.iter().position(|(existing, _)| *existing == name) {
+            Some(index) => {
+                downstreams.remove(index).1.socket.close();
+                true
+            }
+            None => false,
+        }
+    }
+
+    #[napi]
+    pub fn stats(&self) -> FanOutStats {
+        let downstreams: Vec<DownstreamStats> = self
+            .downstreams
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, downstream)| DownstreamStats {
+                name: name.clone(),
+                sent: downstream.counters.sent.load(Ordering::SeqCst),
+                dropped: downstream.counters.dropped.load(Ordering::SeqCst),
+                failed: downstream.counters.failed.load(Ordering::SeqCst),
+                queued: downstream.counters.queued.load(Ordering::SeqCst),
+            })
+            .collect();
+        FanOutStats {
+            received: self.received.load(Ordering::SeqCst),
+            unrouted: self.unrouted.load(Ordering::SeqCst),
+            downstreams,
+        }
+    }
+
+    // recvFailed；某个下游开始出错时 downstreamFailed、恢复时 downstreamRecovered，message 以下游的名字开头
+    #[napi]
+    pub fn on_event(&self, callback: ThreadsafeFunction<SocketEvent>) {
+        self.events.set(callback);
+    }
+
+    #[napi]
+    pub fn close(&mut self) {
+        self.is_closing.store(true, Ordering::SeqCst);
+        if let Some(upstream) = self.upstream.take() {
+            upstream.close();
+        }
+        for (_, downstream) in self.downstreams.lock().unwrap().drain(..) {
+            downstream.socket.close();
+        }
+        self.events.clear();
+    }
+}
+
+fn attach(socket: &Socket, url: &str, dial: bool) -> Result<()> {
+    if dial {
+        socket.dial_async(url).map_err(|err| failed(format!("Connection failed: {:?}", err)))
+    } else {
+        socket.listen(url).map_err(|err| failed(format!("Listen failed: {:?}", err)))
+    }
+}
+
+// 下游的发送线程，连续出错只在开始和恢复时各报一次
+fn deliver(name: &str, socket: Socket, messages: Receiver<Arc<[u8]>>, counters: Arc<Counters>, events: EventEmitter) {
+    let mut failing = false;
+    while let Ok(body) = messages.recv() {
+        counters.queued.fetch_sub(1, Ordering::SeqCst);
+        match socket.send(Message::from(&body[..])) {
+            Ok(()) => {
+                counters.sent.fetch_add(1, Ordering::SeqCst);
+                if failing {
+                    failing = false;
+                    events.emit(SocketEvent::new("downstreamRecovered").message(name));
+                }
+            }
+            Err((_, NngError::Closed)) => return,
+            Err((_, err)) => {
+                counters.failed.fetch_add(1, Ordering::SeqCst);
+                if !failing {
+                    failing = true;
+                    events.emit(SocketEvent::new("downstreamFailed").message(format!("{}: {:?}", name, err)));
+                }
+            }
+        }
+    }
+}
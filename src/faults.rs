@@ -0,0 +1,225 @@
+use napi::Result;
+use napi_derive::napi;
+use std::cmp::Ordering as CmpOrdering;
+use std::collections::hash_map::RandomState;
+use std::collections::BinaryHeap;
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::events::EventEmitter;
+use crate::guard;
+use crate::sampling::mix;
+
+const DEFAULT_REORDER_DELAY_MS: u32 = 20;
+
+// 故障注入的配置，只用于测试。几种故障相互独立：先决定是否丢弃，再决定是否重复、是否压后
+#[napi(object)]
+pub struct FaultOptions {
+    pub drop_rate: Option<f64>,      // 丢弃的概率，[0, 1]
+    pub duplicate_rate: Option<f64>, // 多交付一份的概率
+    pub reorder_rate: Option<f64>,   // 额外延迟 reorderDelayMs、让后面的消息先到的概率
+    pub reorder_delay_ms: Option<u32>, // 默认 20
+    pub latency_ms: Option<u32>,     // 每条消息的固定延迟
+    pub jitter_ms: Option<u32>,      // 在固定延迟上再加 [0, jitterMs) 的随机延迟，本身也会造成乱序
+    pub direction: Option<String>,   // "send"、"recv" 或 "both"（默认）
+    pub seed: Option<u32>,           // 固定随机种子，重复运行得到相同的故障序列
+}
+
+struct Config {
+    drop: f64,
+    duplicate: f64,
+    reorder: f64,
+    reorder_delay: Duration,
+    latency: Duration,
+    jitter_ms: u32,
+    send: bool,
+    recv: bool,
+}
+
+// 一条消息的命运：copies 为 0 表示丢弃
+pub struct Fate {
+    pub copies: u32,
+    pub delay: Duration,
+}
+
+fn invalid(reason: String) -> napi::Error {
+    napi::Error::new(napi::Status::InvalidArg, reason)
+}
+
+fn rate(name: &str, value: Option<f64>) -> Result<f64> {
+    let value = value.unwrap_or(0.0);
+    if !(0.0..=1.0).contains(&value) {
+        return Err(invalid(format!("{} must be between 0 and 1", name)));
+    }
+    Ok(value)
+}
+
+// 每个 socket 一份，收发两个方向共用配置和随机数
+#[derive(Clone, Default)]
+pub struct Faults {
+    config: Arc<Mutex<Option<Config>>>,
+    random: Arc<AtomicU64>,
+}
+
+impl Faults {
+    // None 关闭故障注入
+    pub fn configure(&self, options: Option<FaultOptions>) -> Result<()> {
+        let options = match options {
+            Some(options) => options,
+            None => {
+                *self.config.lock().unwrap() = None;
+                return Ok(());
+            }
+        };
+        let (send, recv) = match options.direction.as_deref() {
+            None | Some("both") => (true, true),
+            Some("send") => (true, false),
+            Some("recv") => (false, true),
+            Some(other) => return Err(invalid(format!("direction must be \"send\", \"recv\" or \"both\", got {:?}", other))),
+        };
+        let config = Config {
+            drop: rate("dropRate", options.drop_rate)?,
+            duplicate: rate("duplicateRate", options.duplicate_rate)?,
+            reorder: rate("reorderRate", options.reorder_rate)?,
+            reorder_delay: Duration::from_millis(options.reorder_delay_ms.unwrap_or(DEFAULT_REORDER_DELAY_MS) as u64),
+            latency: Duration::from_millis(options.latency_ms.unwrap_or(0) as u64),
+            jitter_ms: options.jitter_ms.unwrap_or(0),
+            send,
+            recv,
+        };
+        let seed = match options.seed {
+            Some(seed) => seed as u64,
+            None => RandomState::new().hash_one(0u64),
+        };
+        let mut current = self.config.lock().unwrap();
+        self.random.store(seed, Ordering::SeqCst);
+        *current = Some(config);
+        Ok(())
+    }
+
+    // 没有开启或这个方向不注入时返回 None，消息照常处理
+    pub fn decide(&self, send: bool) -> Option<Fate> {
+        let config = self.config.lock().unwrap();
+        let config = config.as_ref().filter(|config| if send { config.send } else { config.recv })?;
+        if self.chance(config.drop) {
+            return Some(Fate { copies: 0, delay: Duration::ZERO });
+        }
+        let copies = if self.chance(config.duplicate) { 2 } else { 1 };
+        let mut delay = config.latency;
+        if config.jitter_ms > 0 {
+            delay += Duration::from_millis((self.next() * config.jitter_ms as f64) as u64);
+        }
+        if self.chance(config.reorder) {
+            delay += config.reorder_delay;
+        }
+        Some(Fate { copies, delay })
+    }
+
+    fn chance(&self, rate: f64) -> bool {
+        rate > 0.0 && self.next() < rate
+    }
+
+    // [0, 1) 的均匀分布
+    fn next(&self) -> f64 {
+        let state = self.random.fetch_add(0x9e3779b97f4a7c15, Ordering::SeqCst);
+        (mix(state) >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+struct Delayed<T> {
+    due: Instant,
+    seq: u64,
+    item: T,
+}
+
+// BinaryHeap 是大顶堆，反过来比较，最早到期的在堆顶；同时到期的按放入顺序
+impl<T> Ord for Delayed<T> {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        (other.due, other.seq).cmp(&(self.due, self.seq))
+    }
+}
+
+impl<T> PartialOrd for Delayed<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> PartialEq for Delayed<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == CmpOrdering::Equal
+    }
+}
+
+impl<T> Eq for Delayed<T> {}
+
+struct Line<T> {
+    items: BinaryHeap<Delayed<T>>,
+    next_seq: u64,
+    closed: bool,
+}
+
+struct LineShared<T> {
+    line: Mutex<Line<T>>,
+    wake: Condvar,
+}
+
+// 注入延迟的消息按到期时间交给 sink。释放时剩下的消息立即交出去，不会丢
+pub struct DelayLine<T> {
+    shared: Arc<LineShared<T>>,
+}
+
+impl<T: Send + 'static> DelayLine<T> {
+    pub fn start<F>(events: EventEmitter, mut sink: F) -> Self
+    where
+        F: FnMut(T) + Send + 'static,
+    {
+        let shared = Arc::new(LineShared {
+            line: Mutex::new(Line { items: BinaryHeap::new(), next_seq: 0, closed: false }),
+            wake: Condvar::new(),
+        });
+        let worker = shared.clone();
+        guard::spawn(events, "Fault delay", move || {
+            let mut line = worker.line.lock().unwrap();
+            loop {
+                let now = Instant::now();
+                let due = match line.items.peek() {
+                    Some(next) if next.due <= now || line.closed => None,
+                    Some(next) => Some(next.due - now),
+                    None if line.closed => return,
+                    None => {
+                        line = worker.wake.wait(line).unwrap();
+                        continue;
+                    }
+                };
+                match due {
+                    Some(wait) => line = worker.wake.wait_timeout(line, wait).unwrap().0,
+                    None => {
+                        let delayed = line.items.pop().expect("peeked");
+                        drop(line);
+                        sink(delayed.item);
+                        line = worker.line.lock().unwrap();
+                    }
+                }
+            }
+        });
+        DelayLine { shared }
+    }
+
+    pub fn push(&self, item: T, delay: Duration) {
+        let mut line = self.shared.line.lock().unwrap();
+        let seq = line.next_seq;
+        line.next_seq += 1;
+        line.items.push(Delayed { due: Instant::now() + delay, seq, item });
+        self.shared.wake.notify_one();
+    }
+}
+
+impl<T> Drop for DelayLine<T> {
+    fn drop(&mut self) {
+        self.shared.line.lock().unwrap().closed = true;
+        self.shared.wake.notify_one();
+    }
+}
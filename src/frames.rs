@@ -0,0 +1,97 @@
+use napi_derive::napi;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::events::{EventEmitter, SocketEvent};
+
+// 解析对端发来的分层协议帧（主题帧、RPC 帧、握手、主题 RPC 的信封）时的错误。
+// 对端不可信：长度对不上、编码不对或带了未定义字段的帧整条丢弃，不去猜测它的意思
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameError {
+    Truncated,        // 比固定头部或声明的长度短
+    MissingDelimiter, // 主题帧没有 NUL 分隔符
+    InvalidUtf8,      // 主题、方法名、地址或元数据不是合法的 UTF-8
+    UnknownKind,      // 不认识的 RPC 帧类型
+    UnknownFlags,     // 主题帧带了未定义的 flag 位
+}
+
+const KINDS: usize = 5;
+
+impl FrameError {
+    fn index(self) -> usize {
+        match self {
+            FrameError::Truncated => 0,
+            FrameError::MissingDelimiter => 1,
+            FrameError::InvalidUtf8 => 2,
+            FrameError::UnknownKind => 3,
+            FrameError::UnknownFlags => 4,
+        }
+    }
+
+    pub fn code(self) -> &'static str {
+        match self {
+            FrameError::Truncated => "truncated",
+            FrameError::MissingDelimiter => "missingDelimiter",
+            FrameError::InvalidUtf8 => "invalidUtf8",
+            FrameError::UnknownKind => "unknownKind",
+            FrameError::UnknownFlags => "unknownFlags",
+        }
+    }
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            FrameError::Truncated => "frame is shorter than its declared length",
+            FrameError::MissingDelimiter => "topic frame has no delimiter",
+            FrameError::InvalidUtf8 => "frame field is not valid UTF-8",
+            FrameError::UnknownKind => "unknown frame kind",
+            FrameError::UnknownFlags => "topic frame has undefined flags",
+        };
+        f.write_str(reason)
+    }
+}
+
+// 读一段 2 字节大端长度前缀的字符串，返回字符串和剩下的字节
+pub fn take_str(data: &[u8]) -> Result<(&str, &[u8]), FrameError> {
+    let len = u16::from_be_bytes(data.get(..2).ok_or(FrameError::Truncated)?.try_into().unwrap()) as usize;
+    let value = data.get(2..2 + len).ok_or(FrameError::Truncated)?;
+    let value = std::str::from_utf8(value).map_err(|_| FrameError::InvalidUtf8)?;
+    Ok((value, &data[2 + len..]))
+}
+
+// 丢弃的畸形帧按原因计数
+#[napi(object)]
+pub struct MalformedFrames {
+    pub truncated: i64,
+    pub missing_delimiter: i64,
+    pub invalid_utf8: i64,
+    pub unknown_kind: i64,
+    pub unknown_flags: i64,
+}
+
+#[derive(Clone, Default)]
+pub struct FrameErrors {
+    counts: Arc<[AtomicU64; KINDS]>,
+}
+
+impl FrameErrors {
+    // 每种原因第一次出现时发 malformedFrame 事件，之后只计数，对端持续发垃圾时不会刷屏
+    pub fn record(&self, error: FrameError, events: &EventEmitter) {
+        if self.counts[error.index()].fetch_add(1, Ordering::SeqCst) == 0 {
+            events.emit(SocketEvent::new("malformedFrame").code(error.code()).message(format!("Dropped malformed frame: {}", error)));
+        }
+    }
+
+    pub fn snapshot(&self) -> MalformedFrames {
+        let count = |error: FrameError| self.counts[error.index()].load(Ordering::SeqCst) as i64;
+        MalformedFrames {
+            truncated: count(FrameError::Truncated),
+            missing_delimiter: count(FrameError::MissingDelimiter),
+            invalid_utf8: count(FrameError::InvalidUtf8),
+            unknown_kind: count(FrameError::UnknownKind),
+            unknown_flags: count(FrameError::UnknownFlags),
+        }
+    }
+}
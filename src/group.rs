@@ -0,0 +1,146 @@
+use napi::bindgen_prelude::*;
+use napi::{Env, JsObject, NapiRaw, NapiValue, Ref};
+use napi_derive::napi;
+use std::time::Duration;
+
+use crate::events::EventEmitter;
+use crate::nanomsg::{SocketStats, SocketWrapper};
+use crate::outbox;
+
+// 整组的统计：各项深度是成员之和，没有打开的成员不计
+#[napi(object)]
+pub struct GroupStats {
+    pub sockets: u32, // 成员数，包括已经关闭的
+    pub open: u32,
+    pub outbox_depth: i64,
+    pub send_queue_depth: i64, // 读不到的成员（走过 inproc）不计
+    pub recv_queue_depth: i64,
+    pub members: Vec<SocketStats>, // 打开的成员各自的统计，按加入顺序
+}
+
+// 一组 SocketWrapper，一次关闭、暂停接收、等发送队列清空或读统计，
+// 适合按租户管理几十个 socket 的服务。组只持有引用，成员仍然可以单独使用
+#[napi(custom_finalize)]
+pub struct SocketGroup {
+    members: Vec<Ref<()>>,
+}
+
+// 和 ClassInstance 一样，成员由 JS 持有，组的引用保证它在这期间不会被回收
+fn member(env: Env, reference: &Ref<()>) -> Result<&'static mut SocketWrapper> {
+    let object: JsObject = env.get_reference_value(reference)?;
+    unsafe { <&mut SocketWrapper>::from_napi_value(env.raw(), object.raw()) }
+}
+
+#[napi]
+impl SocketGroup {
+    #[napi(constructor)]
+    pub fn new() -> Self {
+        SocketGroup { members: Vec::new() }
+    }
+
+    // 已经在组里时返回 false
+    #[napi]
+    pub fn add(&mut self, env: Env, socket: ClassInstance<SocketWrapper>) -> Result<bool> {
+        if self.position(env, &*socket)?.is_some() {
+            return Ok(false);
+        }
+        let object = unsafe { JsObject::from_raw_unchecked(env.raw(), socket.value) };
+        self.members.push(env.create_reference(object)?);
+        Ok(true)
+    }
+
+    // 只移出组，不关闭；不在组里时返回 false
+    #[napi]
+    pub fn remove(&mut self, env: Env, socket: ClassInstance<SocketWrapper>) -> Result<bool> {
+        match self.position(env, &*socket)? {
+            Some(index) => {
+                self.members.remove(index).unref(env)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    #[napi]
+    pub fn size(&self) -> u32 {
+        self.members.len() as u32
+    }
+
+    // 关闭所有成员并清空组
+    #[napi]
+    pub fn close(&mut self, env: Env) -> Result<()> {
+        for mut reference in self.members.drain(..) {
+            let socket = member(env, &reference)?;
+            if socket.is_open() {
+                socket.close(env);
+            }
+            reference.unref(env)?;
+        }
+        Ok(())
+    }
+
+    #[napi]
+    pub fn pause_recv(&self, env: Env) -> Result<()> {
+        for reference in &self.members {
+            member(env, reference)?.pause_recv();
+        }
+        Ok(())
+    }
+
+    #[napi]
+    pub fn resume_recv(&self, env: Env) -> Result<()> {
+        for reference in &self.members {
+            member(env, reference)?.resume_recv();
+        }
+        Ok(())
+    }
+
+    // 等所有能发送的成员的发送队列都清空，语义和 SocketWrapper.drain 相同；只能接收或没打开的成员跳过
+    #[napi(ts_return_type = "Promise<void>")]
+    pub fn drain(&self, env: Env, timeout_ms: Option<u32>) -> Result<JsObject> {
+        let timeout = timeout_ms.filter(|ms| *ms > 0).map(|ms| Duration::from_millis(ms as u64));
+        let mut outboxes = Vec::new();
+        for reference in &self.members {
+            outboxes.extend(member(env, reference)?.send_outbox());
+        }
+        outbox::drain_all(env, outboxes, EventEmitter::default(), timeout)
+    }
+
+    #[napi]
+    pub fn stats(&self, env: Env) -> Result<GroupStats> {
+        let mut members = Vec::new();
+        for reference in &self.members {
+            let socket = member(env, reference)?;
+            if socket.is_open() {
+                members.push(socket.stats()?);
+            }
+        }
+        Ok(GroupStats {
+            sockets: self.members.len() as u32,
+            open: members.len() as u32,
+            outbox_depth: members.iter().map(|stats| stats.outbox_depth).sum(),
+            send_queue_depth: members.iter().filter_map(|stats| stats.send_queue_depth).sum(),
+            recv_queue_depth: members.iter().filter_map(|stats| stats.recv_queue_depth).sum(),
+            members,
+        })
+    }
+
+    fn position(&self, env: Env, target: *const SocketWrapper) -> Result<Option<usize>> {
+        for (index, reference) in self.members.iter().enumerate() {
+            if std::ptr::eq(member(env, reference)?, target) {
+                return Ok(Some(index));
+            }
+        }
+        Ok(None)
+    }
+}
+
+// 组被回收时释放对成员的引用，成员本身不受影响
+impl ObjectFinalize for SocketGroup {
+    fn finalize(mut self, env: Env) -> Result<()> {
+        for mut reference in self.members.drain(..) {
+            reference.unref(env)?;
+        }
+        Ok(())
+    }
+}
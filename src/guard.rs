@@ -0,0 +1,31 @@
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::events::EventEmitter;
+
+// 后台线程里的 panic：拦下来变成 error 事件（开启了 setCrashDump 时先写快照），线程随后正常收尾退出。
+// 不拦的话线程带着没 settle 的 Promise 和中毒的锁消失，JS 线程之后再碰这些锁就会让整个进程退出。
+pub fn contain<F: FnOnce()>(events: &EventEmitter, task: &str, f: F) {
+    if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(f)) {
+        let reason = format!("{} panicked: {}", task, describe(&payload));
+        events.fatal(reason);
+    }
+}
+
+// 和 std::thread::spawn 一样，线程体用 contain 包起来
+pub fn spawn<F>(events: EventEmitter, task: &'static str, f: F)
+where
+    F: FnOnce() + Send + 'static,
+{
+    std::thread::spawn(move || contain(&events, task, f));
+}
+
+fn describe(payload: &Box<dyn Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
@@ -0,0 +1,83 @@
+use napi_derive::napi;
+use std::collections::HashMap;
+
+use crate::frames::{take_str, FrameError};
+
+// pipe 建立后双方交换的握手参数，版本区间没有交集的对端会被断开
+#[napi(object)]
+pub struct HandshakeOptions {
+    pub version: u32,
+    pub min_version: Option<u32>, // 能接受的最低版本，默认等于 version
+    pub metadata: Option<HashMap<String, String>>,
+}
+
+#[napi(object)]
+pub struct PeerInfo {
+    pub peer_id: u32,
+    pub version: u32, // 协商后双方使用的版本
+    pub metadata: HashMap<String, String>,
+}
+
+// 握手帧格式：version(4) + min_version(4) + 若干组 key/value，每段前面带 2 字节长度
+#[derive(Clone)]
+pub struct Hello {
+    pub version: u32,
+    pub min_version: u32,
+    pub metadata: HashMap<String, String>,
+}
+
+impl Hello {
+    pub fn from_options(options: HandshakeOptions) -> Self {
+        Hello {
+            version: options.version,
+            min_version: options.min_version.unwrap_or(options.version).min(options.version),
+            metadata: options.metadata.unwrap_or_default(),
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&self.version.to_be_bytes());
+        data.extend_from_slice(&self.min_version.to_be_bytes());
+        for (key, value) in &self.metadata {
+            for part in [key, value] {
+                data.extend_from_slice(&(part.len() as u16).to_be_bytes());
+                data.extend_from_slice(part.as_bytes());
+            }
+        }
+        data
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, FrameError> {
+        if data.len() < 8 {
+            return Err(FrameError::Truncated);
+        }
+        let version = u32::from_be_bytes(data[0..4].try_into().unwrap());
+        let min_version = u32::from_be_bytes(data[4..8].try_into().unwrap());
+        let mut metadata = HashMap::new();
+        let mut rest = &data[8..];
+        while !rest.is_empty() {
+            let (key, after_key) = take_str(rest)?;
+            let (value, after_value) = take_str(after_key)?;
+            metadata.insert(key.to_string(), value.to_string());
+            rest = after_value;
+        }
+        Ok(Hello {
+            version,
+            min_version,
+            metadata,
+        })
+    }
+
+    // 双方都按同样的规则计算，结果一致：取较低的版本，且不能低于任一方的下限
+    pub fn negotiate(&self, remote: &Hello) -> Result<u32, String> {
+        let version = self.version.min(remote.version);
+        if version < self.min_version || version < remote.min_version {
+            return Err(format!(
+                "Incompatible protocol version: local {}..={}, peer {}..={}",
+                self.min_version, self.version, remote.min_version, remote.version
+            ));
+        }
+        Ok(version)
+    }
+}
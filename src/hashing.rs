@@ -0,0 +1,70 @@
+use std::collections::BTreeMap;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+// 每个目标在环上放的虚拟节点数
+const DEFAULT_REPLICAS: u32 = 64;
+
+// FNV-1a 64 位哈希，结果跨进程、跨平台稳定
+pub fn fnv1a(data: &[u8]) -> u64 {
+    let mut hash = FNV_OFFSET;
+    for byte in data {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+// splitmix64 的收尾混合，短 key 的 FNV 结果高位区分度不够，环上会扎堆
+fn mix(mut hash: u64) -> u64 {
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    hash ^ (hash >> 31)
+}
+
+// 一致性哈希环，目标用 u32 id 表示
+pub struct HashRing {
+    replicas: u32,
+    ring: BTreeMap<u64, u32>,
+}
+
+impl HashRing {
+    pub fn new() -> Self {
+        HashRing {
+            replicas: DEFAULT_REPLICAS,
+            ring: BTreeMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, target: u32) {
+        for replica in 0..self.replicas {
+            self.ring.insert(Self::node_hash(target, replica), target);
+        }
+    }
+
+    pub fn remove(&mut self, target: u32) {
+        self.ring.retain(|_, t| *t != target);
+    }
+
+    // 找到 key 顺时针方向的第一个节点
+    pub fn get(&self, key: &[u8]) -> Option<u32> {
+        let hash = mix(fnv1a(key));
+        self.ring
+            .range(hash..)
+            .next()
+            .or_else(|| self.ring.iter().next())
+            .map(|(_, target)| *target)
+    }
+
+    pub fn len(&self) -> usize {
+        self.ring.len() / self.replicas as usize
+    }
+
+    fn node_hash(target: u32, replica: u32) -> u64 {
+        let mut bytes = [0u8; 8];
+        bytes[..4].copy_from_slice(&target.to_be_bytes());
+        bytes[4..].copy_from_slice(&replica.to_be_bytes());
+        mix(fnv1a(&bytes))
+    }
+}
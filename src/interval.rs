@@ -0,0 +1,103 @@
+use napi::threadsafe_function::{ThreadSafeCallContext, ThreadsafeFunction};
+use napi::{CallContext, Env, JsFunction, JsUndefined, JsUnknown, NapiRaw, Result};
+use napi_derive::js_function;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::callback_error::FunctionRef;
+use crate::events::EventEmitter;
+use crate::guard;
+use crate::payload::Payload;
+
+// 原生定时器：一个线程按固定节拍调用 tick，下一次的时间从起点按周期推算，不随 tick 的耗时漂移。
+// 落后超过一个周期时（比如进程被挂起）跳过错过的节拍，不会集中补发
+pub struct Interval {
+    stop: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl Interval {
+    pub fn start<F>(events: EventEmitter, period: Duration, mut tick: F) -> Self
+    where
+        F: FnMut() + Send + 'static,
+    {
+        let stop: Arc<(Mutex<bool>, Condvar)> = Arc::default();
+        let signal = stop.clone();
+        guard::spawn(events, "Interval", move || {
+            let (stopped, wake) = &*signal;
+            let mut next = Instant::now() + period;
+            let mut guard = stopped.lock().unwrap();
+            loop {
+                let now = Instant::now();
+                if now < next {
+                    guard = wake.wait_timeout(guard, next - now).unwrap().0;
+                    if *guard {
+                        return;
+                    }
+                    continue;
+                }
+                drop(guard);
+                tick();
+                next += period;
+                let now = Instant::now();
+                if next <= now {
+                    next = now + period;
+                }
+                guard = stopped.lock().unwrap();
+                if *guard {
+                    return;
+                }
+            }
+        });
+        Interval { stop }
+    }
+}
+
+impl Drop for Interval {
+    fn drop(&mut self) {
+        let (stopped, wake) = &*self.stop;
+        *stopped.lock().unwrap() = true;
+        wake.notify_one();
+    }
+}
+
+// 把 JS 生产函数包装成可以从其他线程触发的调用：在 JS 线程上调用 producer，
+// 返回值连同触发时带的 value 交给 deliver；返回 undefined 或 null 表示这一次不发。
+// queue 是回调队列长度，0 表示不限；队列满时多出的调用直接跳过。name 用在警告里
+pub fn producer<T, F>(
+    env: &Env,
+    name: &'static str,
+    producer: JsFunction,
+    queue: usize,
+    events: EventEmitter,
+    mut deliver: F,
+) -> Result<ThreadsafeFunction<T>>
+where
+    T: Send + 'static,
+    F: FnMut(T, Payload) + Send + 'static,
+{
+    let producer = FunctionRef::retain(env, producer)?;
+    // 真正的调用在下面的回调里完成，线程安全函数本身挂一个空函数
+    let noop = env.create_function("publishInterval", noop)?;
+    env.create_threadsafe_function(&noop, queue, move |ctx: ThreadSafeCallContext<T>| {
+        match produce(&ctx.env, &producer) {
+            Ok(Some(payload)) => deliver(ctx.value, payload),
+            Ok(None) => {}
+            Err(err) => events.warn("producerFailed", format!("{} failed: {}", name, err.reason)),
+        }
+        Ok(Vec::<JsUnknown>::new())
+    })
+}
+
+#[js_function]
+fn noop(ctx: CallContext) -> Result<JsUndefined> {
+    ctx.env.get_undefined()
+}
+
+fn produce(env: &Env, producer: &FunctionRef) -> Result<Option<Payload>> {
+    // 生产函数抛出的异常作为警告，不让它变成未捕获异常
+    let result = producer.call(env, &[])?;
+    match result.get_type()? {
+        napi::ValueType::Undefined | napi::ValueType::Null => Ok(None),
+        _ => unsafe { napi::bindgen_prelude::FromNapiValue::from_napi_value(env.raw(), result.raw()) }.map(Some),
+    }
+}
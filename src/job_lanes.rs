@@ -0,0 +1,104 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Default)]
+struct State {
+    by_priority: BTreeMap<(Reverse<u8>, u64), u64>, // (优先级, 序号) -> 任务 id
+    by_age: BTreeMap<u64, (u8, Instant)>,            // 序号 -> 优先级和入队时间
+    delayed: BTreeMap<(Instant, u64), (u64, u8)>,    // (到期时间, 序号) -> 任务 id 和优先级
+    next_seq: u64,
+    closed: bool,
+}
+
+// 生产端等待交给 nng 的任务：优先级大的先发，同一优先级先进先出。
+// 等了超过 starvation 的任务不论优先级先发，积压的低优先级任务不会一直被插队。
+// 延迟发送和等待退避的任务先放在 delayed 里，到期后才开始排队
+pub struct Lanes {
+    state: Mutex<State>,
+    ready: Condvar,
+    starvation: Duration,
+}
+
+impl Lanes {
+    pub fn new(starvation: Duration) -> Self {
+        Lanes { state: Mutex::default(), ready: Condvar::new(), starvation }
+    }
+
+    pub fn push_after(&self, id: u64, priority: u8, delay: Duration) {
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            return;
+        }
+        if delay.is_zero() {
+            state.enqueue(id, priority);
+        } else {
+            let seq = state.next_seq;
+            state.next_seq += 1;
+            state.delayed.insert((Instant::now() + delay, seq), (id, priority));
+        }
+        self.ready.notify_one();
+    }
+
+    // 取下一个要发的任务，没有时等待；关闭后返回 None
+    pub fn pop(&self) -> Option<u64> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if state.closed {
+                return None;
+            }
+            let now = Instant::now();
+            while let Some(entry) = state.delayed.first_entry().filter(|entry| entry.key().0 <= now) {
+                let (id, priority) = entry.remove();
+                state.enqueue(id, priority);
+            }
+            let starved = state
+                .by_age
+                .first_key_value()
+                .filter(|(_, (_, queued_at))| queued_at.elapsed() >= self.starvation)
+                .map(|(seq, (priority, _))| (Reverse(*priority), *seq));
+            let next = match starved {
+                Some(key) => Some(key),
+                None => state.by_priority.first_key_value().map(|(key, _)| *key),
+            };
+            if let Some(key) = next {
+                state.by_age.remove(&key.1);
+                return state.by_priority.remove(&key);
+            }
+            state = match state.delayed.first_key_value() {
+                Some(((due, _), _)) => {
+                    let wait = due.saturating_duration_since(now);
+                    self.ready.wait_timeout(state, wait).unwrap().0
+                }
+                None => self.ready.wait(state).unwrap(),
+            };
+        }
+    }
+
+    pub fn close(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.closed = true;
+        state.by_priority.clear();
+        state.by_age.clear();
+        state.delayed.clear();
+        self.ready.notify_all();
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().by_priority.len()
+    }
+
+    pub fn delayed(&self) -> usize {
+        self.state.lock().unwrap().delayed.len()
+    }
+}
+
+impl State {
+    fn enqueue(&mut self, id: u64, priority: u8) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.by_priority.insert((Reverse(priority), seq), id);
+        self.by_age.insert(seq, (priority, Instant::now()));
+    }
+}
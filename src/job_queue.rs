@@ -0,0 +1,656 @@
+use napi::bindgen_prelude::*;
+use napi::threadsafe_function::{ThreadSafeCallContext, ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use napi::{Env, JsDeferred, JsFunction, JsObject, JsUnknown};
+use napi_derive::napi;
+use nng::{Error as NngError, Message, Protocol, Socket};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::callback_error::{self, FunctionRef};
+use crate::cron::{Cron, Crontab};
+use crate::dead_letter::{DeadLetter, DeadLetters};
+use crate::dedup::SeenSet;
+use crate::events::{EventEmitter, SocketEvent};
+use crate::guard;
+use crate::interval::Interval;
+use crate::job_lanes::Lanes;
+use crate::nanomsg::reject_closed;
+use crate::payload::Payload;
+
+// 基于 Push/Pull 的任务队列：生产端经 Push 把任务分给 worker，worker 处理完经回程的 Push 发确认，
+// 生产端的 Pull 收确认。handler 出错或确认超时算失败一次，重新分发，失败 maxAttempts 次后交给死信。
+// 任务帧：kind(1) + 任务 id(8, 大端) + 已经失败的次数(4, 大端) + 任务数据；
+// 确认帧：kind(1) + 任务 id(8, 大端)，NACK 后面跟失败原因。
+// 延迟发送和失败后的退避都在生产端等待，worker 不需要自己计时。
+// 优先级只在生产端起作用：还没交给 nng 的任务按优先级排队，已经交给 nng 的不会被插队
+const KIND_JOB: u8 = 1;
+const KIND_ACK: u8 = 2;
+const KIND_NACK: u8 = 3;
+
+const DEFAULT_ACK_TIMEOUT_MS: u32 = 30_000;
+const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+const DEFAULT_STARVATION_MS: u32 = 10_000;
+const DEFAULT_MAX_RETRY_DELAY_MS: u32 = 60_000;
+const DEFAULT_DEDUP_TTL_MS: u32 = 86_400_000;
+// 检查确认超时的周期，ackTimeoutMs 更短时按 ackTimeoutMs
+const SWEEP_PERIOD: Duration = Duration::from_millis(100);
+// worker 的槽位全满时多久检查一次是否已经关闭
+const SLOT_POLL: Duration = Duration::from_millis(100);
+
+type Resolver = Box<dyn FnOnce(Env) -> Result<()> + Send>;
+
+fn failed(reason: String) -> napi::Error {
+    napi::Error::new(napi::Status::GenericFailure, reason)
+}
+
+#[napi(object)]
+pub struct JobQueueOptions {
+    pub ack_timeout_ms: Option<u32>, // 任务交给 nng 之后多久没有确认算失败一次，默认 30000
+    pub max_attempts: Option<u32>,   // 失败这么多次后交给死信出口（reason 为 retryLimit），默认 5
+    pub starvation_ms: Option<u32>,  // 排队超过这么久的任务不论优先级先发，默认 10000
+    // 第 n 次失败后等 retryDelayMs * 2^(n-1) 再重新分发，最多等 maxRetryDelayMs（默认 60000）；
+    // 默认 0，失败后立即重新分发
+    pub retry_delay_ms: Option<u32>,
+    pub max_retry_delay_ms: Option<u32>,
+    pub dedup_ttl_ms: Option<u32>,   // 带 jobId 的任务完成后记住多久，默认一天
+    pub dedup_path: Option<String>,  // 把完成过的 jobId 存到这个文件，重启后接着去重；默认只在内存里
+}
+
+#[napi(object)]
+pub struct EnqueueOptions {
+    pub priority: Option<u32>, // 0-255，大的先发，默认 0；重新分发时保持不变
+    pub delay_ms: Option<u32>, // 过这么久才开始排队，默认 0；ackTimeoutMs 从交给 nng 时才开始计时
+    // 调用方给的任务 id：同一个 id 还没完成时再入队只是多等一份结果，dedupTtlMs 内完成过的直接 resolve
+    pub job_id: Option<String>,
+}
+
+// 交给 handler 的任务
+#[napi(object)]
+pub struct Job {
+    pub id: i64,
+    pub data: Buffer,
+    pub attempts: u32, // 之前失败过几次
+}
+
+#[napi(object)]
+pub struct JobQueueStats {
+    pub outstanding: i64,   // 生产端：已入队、还没完成也没进死信的
+    pub waiting: i64,       // 生产端：排队等着交给 nng 的
+    pub delayed: i64,       // 生产端：延迟发送或者失败后等待退避的
+    pub completed: i64,     // 生产端：worker 确认完成的
+    pub retried: i64,       // 生产端：失败后重新分发的次数
+    pub dead_lettered: i64, // 生产端：失败次数到上限、交给死信的
+    pub deduplicated: i64,  // 生产端：jobId 重复、没有再分发的入队
+    pub in_flight: i64,     // worker 端：handler 正在处理的
+    pub processed: i64,     // worker 端：handler 处理完的，不论成败
+}
+
+#[derive(Default)]
+struct Counters {
+    completed: AtomicI64,
+    retried: AtomicI64,
+    dead_lettered: AtomicI64,
+    deduplicated: AtomicI64,
+    in_flight: AtomicI64,
+    processed: AtomicI64,
+}
+
+fn encode_job(id: u64, attempts: u32, data: &[u8]) -> Message {
+    let mut message = Message::with_capacity(13 + data.len());
+    message.push_back(&[KIND_JOB]);
+    message.push_back(&id.to_be_bytes());
+    message.push_back(&attempts.to_be_bytes());
+    message.push_back(data);
+    message
+}
+
+fn encode_ack(id: u64, outcome: &std::result::Result<(), String>) -> Message {
+    let mut message = Message::new();
+    match outcome {
+        Ok(()) => {
+            message.push_back(&[KIND_ACK]);
+            message.push_back(&id.to_be_bytes());
+        }
+        Err(reason) => {
+            message.push_back(&[KIND_NACK]);
+            message.push_back(&id.to_be_bytes());
+            message.push_back(reason.as_bytes());
+        }
+    }
+    message
+}
+
+// kind、任务 id 和剩下的字节；比固定头部短的帧为 None
+fn decode(data: &[u8]) -> Option<(u8, u64, &[u8])> {
+    let id = u64::from_be_bytes(data.get(1..9)?.try_into().ok()?);
+    Some((data[0], id, &data[9..]))
+}
+
+// 已入队、还没完成的任务；deadline 在交给 nng 之后才开始计时。
+// 周期任务没有 deferred，同一个 jobId 重复入队的各有一个
+struct Outstanding {
+    data: Vec<u8>,
+    attempts: u32,
+    priority: u8,
+    key: Option<String>,
+    deadline: Option<Instant>,
+    deferred: Vec<JsDeferred<(), Resolver>>,
+}
+
+// 按 jobId 去重：还没完成的 jobId 对应的任务 id，和完成过的 jobId
+struct Dedup {
+    keys: HashMap<String, u64>,
+    seen: SeenSet,
+}
+
+// schedule 登记的周期任务，每次到点入队一份
+#[derive(Clone)]
+struct Recurring {
+    data: Vec<u8>,
+    priority: u8,
+    delay: Duration,
+}
+
+fn enqueue_options(options: Option<&EnqueueOptions>) -> Result<(u8, Duration)> {
+    let (priority, delay_ms) = options.map_or((None, None), |options| (options.priority, options.delay_ms));
+    let priority = u8::try_from(priority.unwrap_or(0))
+        .map_err(|_| napi::Error::new(napi::Status::InvalidArg, "priority must be between 0 and 255".to_string()))?;
+    Ok((priority, Duration::from_millis(delay_ms.unwrap_or(0) as u64)))
+}
+
+// 生产端的任务表，发送线程、确认线程和超时检查共用。要同时锁 dedup 和 table 时先锁 dedup
+struct Dispatch {
+    table: Mutex<HashMap<u64, Outstanding>>,
+    dedup: Mutex<Dedup>,
+    next_id: AtomicU64,
+    lanes: Lanes, // 等发送线程交给 nng 的任务 id
+    counters: Arc<Counters>,
+    dead_letters: DeadLetters,
+    events: EventEmitter,
+    ack_timeout: Duration,
+    max_attempts: u32,
+    retry_delay: Duration,
+    max_retry_delay: Duration,
+}
+
+impl Dispatch {
+    fn submit(&self, data: Vec<u8>, priority: u8, delay: Duration, key: Option<String>, deferred: Option<JsDeferred<(), Resolver>>) {
+        let mut dedup = self.dedup.lock().unwrap();
+        if let Some(key) = key.as_deref() {
+            if let Some(id) = dedup.keys.get(key) {
+                if let Some(job) = self.table.lock().unwrap().get_mut(id) {
+                    self.counters.deduplicated.fetch_add(1, Ordering::SeqCst);
+                    job.deferred.extend(deferred);
+                    return;
+                }
+            }
+            if dedup.seen.contains(key) {
+                self.counters.deduplicated.fetch_add(1, Ordering::SeqCst);
+                if let Some(deferred) = deferred {
+                    deferred.resolve(Box::new(|_| Ok(())));
+                }
+                return;
+            }
+        }
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        if let Some(key) = key.clone() {
+            dedup.keys.insert(key, id);
+        }
+        let job = Outstanding { data, attempts: 0, priority, key, deadline: None, deferred: deferred.into_iter().collect() };
+        self.table.lock().unwrap().insert(id, job);
+        drop(dedup);
+        self.lanes.push_after(id, priority, delay);
+    }
+
+    // 任务完成或者进了死信，不再占着 jobId；完成的记进 seen
+    fn release(&self, dedup: &mut Dedup, key: Option<String>, completed: bool) {
+        let Some(key) = key else {
+            return;
+        };
+        dedup.keys.remove(&key);
+        if completed {
+            if let Err(err) = dedup.seen.insert(key) {
+                self.events.warn("dedupWriteFailed", format!("Failed to persist completed job id: {}", err));
+            }
+        }
+    }
+
+    fn handed(&self, id: u64) {
+        if let Some(job) = self.table.lock().unwrap().get_mut(&id) {
+            job.deadline = Some(Instant::now() + self.ack_timeout);
+        }
+    }
+
+    fn complete(&self, id: u64) {
+        // 超时后重新分发的任务可能收到多次确认，只算第一次
+        let mut dedup = self.dedup.lock().unwrap();
+        let Some(job) = self.table.lock().unwrap().remove(&id) else {
+            return;
+        };
+        self.release(&mut dedup, job.key, true);
+        drop(dedup);
+        self.counters.completed.fetch_add(1, Ordering::SeqCst);
+        for deferred in job.deferred {
+            deferred.resolve(Box::new(|_| Ok(())));
+        }
+    }
+
+    fn fail(&self, id: u64, reason: &str) {
+        let mut dedup = self.dedup.lock().unwrap();
+        let mut table = self.table.lock().unwrap();
+        let Some(mut job) = table.remove(&id) else {
+            return;
+        };
+        job.attempts += 1;
+        if job.attempts < self.max_attempts {
+            job.deadline = None;
+            let (priority, attempts) = (job.priority, job.attempts);
+            table.insert(id, job);
+            drop(table);
+            drop(dedup);
+            self.counters.retried.fetch_add(1, Ordering::SeqCst);
+            self.lanes.push_after(id, priority, self.backoff(attempts));
+            return;
+        }
+        drop(table);
+        self.release(&mut dedup, job.key, false);
+        drop(dedup);
+        self.counters.dead_lettered.fetch_add(1, Ordering::SeqCst);
+        let error = format!("Job failed {} times: {}", job.attempts, reason);
+        self.dead_letters.deliver(&self.events, Message::from(&job.data[..]), "retryLimit", Some(reason.to_string()));
+        for deferred in job.deferred {
+            deferred.reject(failed(error.clone()));
+        }
+    }
+
+    // 第 attempts 次失败后等多久再重新分发
+    fn backoff(&self, attempts: u32) -> Duration {
+        let factor = 1u32 << (attempts - 1).min(31);
+        self.retry_delay.saturating_mul(factor).min(self.max_retry_delay)
+    }
+
+    fn sweep(&self) {
+        self.dedup.lock().unwrap().seen.prune();
+        let now = Instant::now();
+        let expired: Vec<u64> = self
+            .table
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, job)| job.deadline.is_some_and(|deadline| deadline <= now))
+            .map(|(id, _)| *id)
+            .collect();
+        for id in expired {
+            self.fail(id, "Ack timed out");
+        }
+    }
+}
+
+struct Producer {
+    jobs: Socket,
+    acks: Socket,
+    dispatch: Arc<Dispatch>,
+    crontab: Crontab<Recurring>,
+    _sweeper: Interval,
+}
+
+struct Worker {
+    jobs: Socket,
+    acks: Socket,
+    closing: Arc<AtomicBool>,
+    processing: bool,
+}
+
+// worker 端的并发槽位，handler 处理完（包括返回的 Promise settle）后归还并回确认
+#[derive(Clone)]
+struct Slots {
+    used: Arc<(Mutex<u32>, Condvar)>,
+    acks: Socket,
+    counters: Arc<Counters>,
+    events: EventEmitter,
+}
+
+impl Slots {
+    fn finish(&self, id: u64, outcome: std::result::Result<(), String>) {
+        // 回程满了或生产端已经离开时不等待，生产端按确认超时重新分发
+        if let Err((_, err)) = self.acks.try_send(encode_ack(id, &outcome)) {
+            self.events.warn("ackFailed", format!("Failed to acknowledge job {}: {:?}", id, err));
+        }
+        self.counters.in_flight.fetch_sub(1, Ordering::SeqCst);
+        self.counters.processed.fetch_add(1, Ordering::SeqCst);
+        let (used, freed) = &*self.used;
+        *used.lock().unwrap() -= 1;
+        freed.notify_one();
+    }
+}
+
+// 生产端 listen，worker 端 connect，同一个 JobQueue 也可以两端都用
+#[napi]
+pub struct JobQueue {
+    ack_timeout: Duration,
+    max_attempts: u32,
+    starvation: Duration,
+    retry_delay: Duration,
+    max_retry_delay: Duration,
+    dedup_ttl: Duration,
+    dedup_path: Option<String>,
+    events: EventEmitter,
+    dead_letters: DeadLetters,
+    counters: Arc<Counters>,
+    producer: Option<Producer>,
+    worker: Option<Worker>,
+}
+
+#[napi]
+impl JobQueue {
+    #[napi(constructor)]
+    pub fn new(options: Option<JobQueueOptions>) -> Result<Self> {
+        let ack_timeout_ms = options.as_ref().and_then(|options| options.ack_timeout_ms).unwrap_or(DEFAULT_ACK_TIMEOUT_MS);
+        let max_attempts = options.as_ref().and_then(|options| options.max_attempts).unwrap_or(DEFAULT_MAX_ATTEMPTS);
+        let starvation_ms = options.as_ref().and_then(|options| options.starvation_ms).unwrap_or(DEFAULT_STARVATION_MS);
+        let retry_delay_ms = options.as_ref().and_then(|options| options.retry_delay_ms).unwrap_or(0);
+        let max_retry_delay_ms =
+            options.as_ref().and_then(|options| options.max_retry_delay_ms).unwrap_or(DEFAULT_MAX_RETRY_DELAY_MS);
+        let dedup_ttl_ms = options.as_ref().and_then(|options| options.dedup_ttl_ms).unwrap_or(DEFAULT_DEDUP_TTL_MS);
+        let dedup_path = options.and_then(|options| options.dedup_path);
+        if ack_timeout_ms == 0 || max_attempts == 0 {
+            return Err(napi::Error::new(
+                napi::Status::InvalidArg,
+                "ackTimeoutMs and maxAttempts must be greater than 0".to_string(),
+            ));
+        }
+        Ok(JobQueue {
+            ack_timeout: Duration::from_millis(ack_timeout_ms as u64),
+            max_attempts,
+            starvation: Duration::from_millis(starvation_ms as u64),
+            retry_delay: Duration::from_millis(retry_delay_ms as u64),
+            max_retry_delay: Duration::from_millis(max_retry_delay_ms as u64),
+            dedup_ttl: Duration::from_millis(dedup_ttl_ms as u64),
+            dedup_path,
+            events: EventEmitter::default(),
+            dead_letters: DeadLetters::default(),
+            counters: Arc::default(),
+            producer: None,
+            worker: None,
+        })
+    }
+
+    // 生产端：在 jobsUrl 上分发任务，在 acksUrl 上收 worker 的确认。设置了 dedupPath 时在这里读入完成过的 jobId
+    #[napi]
+    pub fn listen(&mut self, jobs_url: String, acks_url: String) -> Result<()> {
+        if self.producer.is_some() {
+            return Err(failed("Already listening".to_string()));
+        }
+        let seen = SeenSet::open(self.dedup_path.clone(), self.dedup_ttl)?;
+        let (jobs, acks) = open_pair(Protocol::Push0, Protocol::Pull0)?;
+        let listened = jobs
+            .listen(&jobs_url)
+            .and_then(|_| acks.listen(&acks_url))
+            .map_err(|err| failed(format!("Listen failed: {:?}", err)));
+        if let Err(err) = listened {
+            jobs.close();
+            acks.close();
+            return Err(err);
+        }
+
+        let dispatch = Arc::new(Dispatch {
+            table: Mutex::default(),
+            dedup: Mutex::new(Dedup { keys: HashMap::new(), seen }),
+            next_id: AtomicU64::new(1),
+            lanes: Lanes::new(self.starvation),
+            counters: self.counters.clone(),
+            dead_letters: self.dead_letters.clone(),
+            events: self.events.clone(),
+            ack_timeout: self.ack_timeout,
+            max_attempts: self.max_attempts,
+            retry_delay: self.retry_delay,
+            max_retry_delay: self.max_retry_delay,
+        });
+
+        // 发送线程：还没有 worker 连上时 Push 会一直等，不占用 JS 线程
+        let (socket, sender) = (jobs.clone(), dispatch.clone());
+        guard::spawn(self.events.clone(), "Job sender", move || {
+            while let Some(id) = sender.lanes.pop() {
+                let frame = match sender.table.lock().unwrap().get(&id) {
+                    Some(job) => encode_job(id, job.attempts, &job.data),
+                    None => continue,
+                };
+                match socket.send(frame) {
+                    Ok(()) => sender.handed(id),
+                    Err((_, NngError::Closed)) => return,
+                    Err((_, err)) => sender.fail(id, &format!("Send failed: {:?}", err)),
+                }
+            }
+        });
+
+        let (socket, receiver) = (acks.clone(), dispatch.clone());
+        guard::spawn(self.events.clone(), "Job acks", move || loop {
+            match socket.recv() {
+                Ok(message) => match decode(message.as_slice()) {
+                    Some((KIND_ACK, id, _)) => receiver.complete(id),
+                    Some((KIND_NACK, id, reason)) => receiver.fail(id, &String::from_utf8_lossy(reason)),
+                    _ => receiver.events.warn("malformedAck", "Dropped a malformed job acknowledgement"),
+                },
+                Err(NngError::Closed) => return,
+                Err(err) => receiver.events.warn("recvFailed", format!("Error receiving acknowledgement: {:?}", err)),
+            }
+        });
+
+        let weak = Arc::downgrade(&dispatch);
+        let sweeper = Interval::start(self.events.clone(), SWEEP_PERIOD.min(self.ack_timeout), move || {
+            if let Some(dispatch) = weak.upgrade() {
+                dispatch.sweep();
+            }
+        });
+        let submitter = dispatch.clone();
+        let crontab = Crontab::new(self.events.clone(), move |job: Recurring| {
+            submitter.submit(job.data, job.priority, job.delay, None, None);
+        });
+        self.producer = Some(Producer { jobs, acks, dispatch, crontab, _sweeper: sweeper });
+        Ok(())
+    }
+
+    // worker 端：从 jobsUrl 领任务，确认发回 acksUrl
+    #[napi]
+    pub fn connect(&mut self, jobs_url: String, acks_url: String) -> Result<()> {
+        if self.worker.is_some() {
+            return Err(failed("Already connected".to_string()));
+        }
+        let (jobs, acks) = open_pair(Protocol::Pull0, Protocol::Push0)?;
+        let dialed = jobs
+            .dial_async(&jobs_url)
+            .and_then(|_| acks.dial_async(&acks_url))
+            .map_err(|err| failed(format!("Connection failed: {:?}", err)));
+        if let Err(err) = dialed {
+            jobs.close();
+            acks.close();
+            return Err(err);
+        }
+        self.worker = Some(Worker { jobs, acks, closing: Arc::default(), processing: false });
+        Ok(())
+    }
+
+    // 入队一个任务，worker 确认完成后 resolve；失败 maxAttempts 次后 reject，关闭时以 SocketClosed reject。
+    // 生产端重试入队时带上 jobId，同一个任务不会被处理两次
+    #[napi(
+        ts_args_type = "job: Buffer | Uint8Array | string | ArrayBuffer, options?: EnqueueOptions | undefined | null",
+        ts_return_type = "Promise<void>"
+    )]
+    pub fn enqueue(&self, env: Env, job: Payload, options: Option<EnqueueOptions>) -> Result<JsObject> {
+        let producer = self.producer.as_ref().ok_or_else(|| failed("JobQueue is not listening".to_string()))?;
+        let (priority, delay) = enqueue_options(options.as_ref())?;
+        let key = options.and_then(|options| options.job_id);
+        let (deferred, promise) = env.create_deferred::<(), Resolver>()?;
+        producer.dispatch.submit(job.to_vec(), priority, delay, key, Some(deferred));
+        Ok(promise)
+    }
+
+    // 按 cron 表达式周期性地入队 job，同名的替换掉原来的。表达式按 UTC 计算，见 cron.rs；
+    // 周期任务没有 Promise，失败 maxAttempts 次后只交给死信出口；options 里的 jobId 不起作用
+    #[napi(
+        ts_args_type = "name: string, expression: string, job: Buffer | Uint8Array | string | ArrayBuffer, options?: EnqueueOptions | undefined | null"
+    )]
+    pub fn schedule(&mut self, name: String, expression: String, job: Payload, options: Option<EnqueueOptions>) -> Result<()> {
+        let producer = self.producer.as_mut().ok_or_else(|| failed("JobQueue is not listening".to_string()))?;
+        let invalid = |err: String| {
+            napi::Error::new(napi::Status::InvalidArg, format!("Invalid cron expression '{}': {}", expression, err))
+        };
+        let cron = Cron::parse(&expression).map_err(invalid)?;
+        let (priority, delay) = enqueue_options(options.as_ref())?;
+        let job = Recurring { data: job.to_vec(), priority, delay };
+        producer.crontab.add(name, cron, job).map_err(invalid)
+    }
+
+    // 取消周期任务，已经入队的不受影响；没有这个名字时返回 false
+    #[napi]
+    pub fn unschedule(&self, name: String) -> bool {
+        self.producer.as_ref().is_some_and(|producer| producer.crontab.remove(&name))
+    }
+
+    #[napi]
+    pub fn schedules(&self) -> Vec<String> {
+        let mut names = self.producer.as_ref().map_or_else(Vec::new, |producer| producer.crontab.names());
+        names.sort();
+        names
+    }
+
+    // 开始处理任务，最多同时 concurrency 个（默认 1）。handler 正常返回或返回的 Promise resolve 算完成，
+    // 抛出异常或 reject 算失败，由生产端重新分发
+    #[napi(ts_args_type = "handler: (err: Error | null, job: Job) => any, concurrency?: number | undefined | null")]
+    pub fn process(&mut self, env: Env, handler: JsFunction, concurrency: Option<u32>) -> Result<()> {
+        let worker = self.worker.as_mut().ok_or_else(|| failed("JobQueue is not connected".to_string()))?;
+        if worker.processing {
+            return Err(failed("Already processing".to_string()));
+        }
+        let concurrency = concurrency.unwrap_or(1);
+        if concurrency == 0 {
+            return Err(napi::Error::new(napi::Status::InvalidArg, "concurrency must be greater than 0".to_string()));
+        }
+        let slots = Slots {
+            used: Arc::default(),
+            acks: worker.acks.clone(),
+            counters: self.counters.clone(),
+            events: self.events.clone(),
+        };
+
+        let handler = FunctionRef::retain(&env, handler)?;
+        let finisher = slots.clone();
+        let noop = env.create_function("processJob", callback_error::noop)?;
+        let callback: ThreadsafeFunction<(u64, u32, Vec<u8>)> =
+            env.create_threadsafe_function(&noop, 0, move |ctx: ThreadSafeCallContext<(u64, u32, Vec<u8>)>| {
+                let (id, attempts, data) = ctx.value;
+                let job = Job { id: id as i64, data: data.into(), attempts };
+                let mut args = vec![ctx.env.get_null()?.into_unknown()];
+                args.extend(callback_error::single(&ctx.env, job)?);
+                match handler.call(&ctx.env, &args) {
+                    Err(err) => finisher.finish(id, Err(err.reason)),
+                    Ok(result) if result.is_promise()? => {
+                        let finisher = finisher.clone();
+                        callback_error::on_settle(&ctx.env, result, move |outcome| finisher.finish(id, outcome))?;
+                    }
+                    Ok(_) => finisher.finish(id, Ok(())),
+                }
+                Ok(Vec::<JsUnknown>::new())
+            })?;
+
+        let (socket, closing, events) = (worker.jobs.clone(), worker.closing.clone(), self.events.clone());
+        guard::spawn(self.events.clone(), "Job worker", move || loop {
+            {
+                let (used, freed) = &*slots.used;
+                let mut used = used.lock().unwrap();
+                while *used >= concurrency {
+                    if closing.load(Ordering::SeqCst) {
+                        return;
+                    }
+                    used = freed.wait_timeout(used, SLOT_POLL).unwrap().0;
+                }
+            }
+            match socket.recv() {
+                Ok(message) => match decode(message.as_slice()) {
+                    Some((KIND_JOB, id, rest)) if rest.len() >= 4 => {
+                        let attempts = u32::from_be_bytes(rest[..4].try_into().unwrap());
+                        *slots.used.0.lock().unwrap() += 1;
+                        slots.counters.in_flight.fetch_add(1, Ordering::SeqCst);
+                        callback.call(Ok((id, attempts, rest[4..].to_vec())), ThreadsafeFunctionCallMode::NonBlocking);
+                    }
+                    _ => events.warn("malformedJob", "Dropped a malformed job frame"),
+                },
+                Err(NngError::Closed) => return,
+                Err(err) => events.warn("recvFailed", format!("Error receiving job: {:?}", err)),
+            }
+        });
+        worker.processing = true;
+        Ok(())
+    }
+
+    // 失败次数到上限的任务交给回调；传 null 取消
+    #[napi(ts_args_type = "callback?: ((err: Error | null, letter: DeadLetter) => any) | undefined | null")]
+    pub fn set_dead_letter(&self, env: Env, callback: Option<JsFunction>) -> Result<()> {
+        let Some(callback) = callback else {
+            self.dead_letters.clear();
+            return Ok(());
+        };
+        let mut callback = env.create_threadsafe_function(&callback, 0, |ctx: ThreadSafeCallContext<DeadLetter>| Ok(vec![ctx.value]))?;
+        callback.unref(&env)?;
+        self.dead_letters.set_callback(callback);
+        Ok(())
+    }
+
+    // 确认帧损坏、回确认失败等事件：malformedAck、malformedJob、ackFailed、recvFailed、dedupWriteFailed
+    #[napi]
+    pub fn on_event(&self, callback: ThreadsafeFunction<SocketEvent>) {
+        self.events.set(callback);
+    }
+
+    #[napi]
+    pub fn stats(&self) -> JobQueueStats {
+        let (outstanding, waiting, delayed) = self.producer.as_ref().map_or((0, 0, 0), |producer| {
+            let lanes = &producer.dispatch.lanes;
+            (producer.dispatch.table.lock().unwrap().len(), lanes.len(), lanes.delayed())
+        });
+        JobQueueStats {
+            outstanding: outstanding as i64,
+            waiting: waiting as i64,
+            delayed: delayed as i64,
+            completed: self.counters.completed.load(Ordering::SeqCst),
+            retried: self.counters.retried.load(Ordering::SeqCst),
+            dead_lettered: self.counters.dead_lettered.load(Ordering::SeqCst),
+            deduplicated: self.counters.deduplicated.load(Ordering::SeqCst),
+            in_flight: self.counters.in_flight.load(Ordering::SeqCst),
+            processed: self.counters.processed.load(Ordering::SeqCst),
+        }
+    }
+
+    // 还没完成的任务以 SocketClosed reject；worker 正在处理的任务不再回确认，由生产端按超时重新分发
+    #[napi]
+    pub fn close(&mut self) {
+        if let Some(producer) = self.producer.take() {
+            producer.dispatch.lanes.close();
+            producer.jobs.close();
+            producer.acks.close();
+            producer.dispatch.dedup.lock().unwrap().keys.clear();
+            for (_, job) in producer.dispatch.table.lock().unwrap().drain() {
+                for deferred in job.deferred {
+                    reject_closed(deferred, "JobQueue closed");
+                }
+            }
+        }
+        if let Some(worker) = self.worker.take() {
+            worker.closing.store(true, Ordering::SeqCst);
+            worker.jobs.close();
+            worker.acks.close();
+        }
+        self.events.clear();
+    }
+}
+
+fn open_pair(jobs: Protocol, acks: Protocol) -> Result<(Socket, Socket)> {
+    let jobs = Socket::new(jobs).map_err(|err| failed(format!("Socket creation failed: {:?}", err)))?;
+    let acks = Socket::new(acks).map_err(|err| {
+        jobs.close();
+        failed(format!("Socket creation failed: {:?}", err))
+    })?;
+    Ok((jobs, acks))
+}
@@ -0,0 +1,105 @@
+use napi::threadsafe_function::{ThreadSafeCallContext, ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use napi::{check_status, sys, CleanupEnvHook, Env, JsObject, JsUnknown, NapiRaw, NapiValue, Result};
+use std::collections::HashMap;
+use std::ptr;
+use std::sync::{Arc, Mutex};
+
+use crate::callback_error::noop;
+use crate::context::{self, Registry};
+
+// 接收循环是否让进程保持运行，和 net.Server 的 ref()/unref() 一样，默认保持。
+// 接收回调本身不 ref 事件循环，由每个循环用来 resolve 自己 Promise 的线程安全函数代为保持，
+// 这样 ref()/unref() 对已经在运行的循环也立即生效。
+// （env.create_deferred 内部的线程安全函数总是 ref 着事件循环，所以这里自己创建 Promise）
+#[derive(Clone, Default)]
+pub struct KeepAlive {
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    unref: bool,
+    next_id: u64,
+    loops: HashMap<u64, ThreadsafeFunction<()>>, // 正在运行的接收循环
+}
+
+// napi_deferred 和清理钩子只在 JS 线程上使用，这里只是带着它们跨线程
+struct Deferred(sys::napi_deferred);
+struct Hook(CleanupEnvHook<Registry>);
+
+unsafe impl Send for Deferred {}
+unsafe impl Send for Hook {}
+
+// 一个接收循环的 Promise，循环退出时 finish
+pub struct Hold {
+    id: u64,
+    state: Arc<Mutex<State>>,
+    resolve: ThreadsafeFunction<()>,
+}
+
+impl KeepAlive {
+    pub fn hold(&self, env: &Env) -> Result<(Hold, JsObject)> {
+        let mut deferred = ptr::null_mut();
+        let mut promise = ptr::null_mut();
+        check_status!(unsafe { sys::napi_create_promise(env.raw(), &mut deferred, &mut promise) })?;
+        let mut deferred = Some(Deferred(deferred));
+        let hook: Arc<Mutex<Option<Hook>>> = Arc::default();
+        let registered = hook.clone();
+        let noop = env.create_function("recvFinished", noop)?;
+        let mut resolve = env.create_threadsafe_function(&noop, 0, move |ctx: ThreadSafeCallContext<()>| {
+            // 循环已经正常退出，env 销毁时不用再等它
+            if let Some(Hook(hook)) = registered.lock().unwrap().take() {
+                let mut env = ctx.env;
+                env.remove_env_cleanup_hook(hook)?;
+            }
+            if let Some(Deferred(deferred)) = deferred.take() {
+                let undefined = ctx.env.get_undefined()?;
+                check_status!(unsafe { sys::napi_resolve_deferred(ctx.env.raw(), deferred, undefined.raw()) })?;
+            }
+            Ok(Vec::<JsUnknown>::new())
+        })?;
+        *hook.lock().unwrap() = Some(Hook(context::guard_loop(env)?));
+        let mut state = self.state.lock().unwrap();
+        if state.unref {
+            resolve.unref(env)?;
+        }
+        state.next_id += 1;
+        let id = state.next_id;
+        state.loops.insert(id, resolve.clone());
+        let hold = Hold { id, state: self.state.clone(), resolve };
+        Ok((hold, unsafe { JsObject::from_raw_unchecked(env.raw(), promise) }))
+    }
+
+    pub fn set(&self, env: &Env, keep: bool) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.unref = !keep;
+        for resolve in state.loops.values_mut() {
+            if keep {
+                resolve.refer(env)?;
+            } else {
+                resolve.unref(env)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn has_ref(&self) -> bool {
+        !self.state.lock().unwrap().unref
+    }
+
+    pub fn running(&self) -> usize {
+        self.state.lock().unwrap().loops.len()
+    }
+}
+
+impl Hold {
+    // 两份线程安全函数都放掉以后才从 loops 里消失，env 销毁时等的就是这一刻
+    pub fn finish(self) {
+        let Hold { id, state, resolve } = self;
+        resolve.call(Ok(()), ThreadsafeFunctionCallMode::NonBlocking);
+        drop(resolve);
+        // 在锁里放掉，running() 看到 0 时它一定已经释放
+        let mut state = state.lock().unwrap();
+        state.loops.remove(&id);
+    }
+}
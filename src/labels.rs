@@ -0,0 +1,33 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+// 挂在 socket 上的键值标签，会带到事件、统计和日志里，
+// 同一进程里有多个 socket 时可以据此区分遥测数据
+#[derive(Clone, Default)]
+pub struct Labels {
+    labels: Arc<Mutex<BTreeMap<String, String>>>,
+}
+
+impl Labels {
+    pub fn set(&self, labels: HashMap<String, String>) {
+        *self.labels.lock().unwrap() = labels.into_iter().collect();
+    }
+
+    pub fn get(&self) -> HashMap<String, String> {
+        self.labels.lock().unwrap().iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.lock().unwrap().is_empty()
+    }
+
+    // 日志行前缀，按 key 排序，例如 "[role=sub service=pricing] "；没有标签时为空
+    pub fn prefix(&self) -> String {
+        let labels = self.labels.lock().unwrap();
+        if labels.is_empty() {
+            return String::new();
+        }
+        let pairs: Vec<String> = labels.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+        format!("[{}] ", pairs.join(" "))
+    }
+}
@@ -1,5 +1,75 @@
 #![deny(clippy::all)]
+// #[napi] 导出的函数只在 JS 里调用，cargo test 构建时 napi 不会注册它们
+#![cfg_attr(test, allow(dead_code))]
 
+mod abort;
+mod ack;
+mod adaptive;
+mod backtrace;
+mod bridge;
+mod callback_error;
+mod capture;
+mod compat;
+mod conformance;
+mod consumer_group;
+mod context;
+mod crash_dump;
+mod cron;
+mod dead_letter;
+mod dedup;
+mod echo;
+mod endpoint;
+mod event_log;
+mod events;
+mod fan_in;
+mod fan_out;
+mod faults;
+mod frames;
+mod group;
+mod guard;
+mod handshake;
+mod interval;
+mod job_lanes;
+mod job_queue;
+mod keep_alive;
+mod labels;
+mod log_subscriber;
+mod memory;
+mod hashing;
 mod nanomsg;
+mod nng_error;
+mod option_dump;
+mod outbox;
+mod partition;
+mod payload;
+mod pinning;
+mod pipes;
+mod poly;
+mod probe;
+mod psk;
+mod publisher;
+mod recording;
+mod recv_into;
+mod requeue;
+mod routing;
+mod rpc;
+mod sampling;
+mod schedule;
+mod signals;
+mod slab;
+mod slow_consumer;
+mod socket_context;
+mod stats;
+mod sticky;
+mod storm;
+mod subscriber;
+mod tls;
+mod topic;
+mod topic_metrics;
+mod topic_rpc;
+mod transfer;
+mod transport;
+mod warmup;
+mod watchdog;
 
 extern crate napi_derive;
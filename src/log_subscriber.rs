@@ -0,0 +1,241 @@
+use napi::bindgen_prelude::*;
+use napi::threadsafe_function::{ThreadSafeCallContext, ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use napi::{Env, JsFunction};
+use napi_derive::napi;
+use nng::options::protocol::pubsub::Subscribe;
+use nng::options::{Options, RecvBufferSize, RecvTimeout};
+use nng::{Error as NngError, Protocol, Socket};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use crate::event_log::{self, LogRecord, Stored};
+use crate::events::{EventEmitter, SocketEvent};
+use crate::guard;
+use crate::topic;
+
+// 每次追赶请求最多扫描的记录数，等应答超过这么久时重发
+const CATCH_UP_BATCH: u32 = 1000;
+const CATCH_UP_TIMEOUT: Duration = Duration::from_secs(5);
+// 追赶期间实时消息先留在 Sub 的接收缓冲区里，nng 最多 8192 条
+const LIVE_BUFFER: i32 = 8192;
+const CALLBACK_QUEUE: usize = 1024;
+
+fn failed(reason: String) -> napi::Error {
+    napi::Error::new(napi::Status::GenericFailure, reason)
+}
+
+type Topics = Arc<Mutex<HashSet<String>>>;
+
+// 接收线程里的追赶和交付
+struct Cursor {
+    catch_up: Socket,
+    topics: Topics,
+    position: Arc<AtomicU64>, // 下一条要交付的 offset
+    callback: ThreadsafeFunction<LogRecord>,
+    is_closing: Arc<AtomicBool>,
+    events: EventEmitter,
+}
+
+impl Cursor {
+    fn next(&self) -> u64 {
+        self.position.load(Ordering::SeqCst)
+    }
+
+    // 用追赶协议读到日志末尾；关闭时返回 false
+    fn catch_up(&self) -> bool {
+        loop {
+            if self.is_closing.load(Ordering::SeqCst) {
+                return false;
+            }
+            let topics: Vec<String> = self.topics.lock().unwrap().iter().cloned().collect();
+            let request = event_log::encode_request(self.next(), CATCH_UP_BATCH, &topics);
+            let reply = self.catch_up.send(&request[..]).map_err(|(_, err)| err).and_then(|_| self.catch_up.recv());
+            let reply = match reply {
+                Ok(reply) => reply,
+                Err(NngError::Closed) => return false,
+                Err(err) => {
+                    self.events.warn("catchUpFailed", format!("Catch-up from offset {} failed: {:?}", self.next(), err));
+                    continue;
+                }
+            };
+            let Some((next, end, records)) = event_log::decode_batch(reply.as_slice()) else {
+                self.events.warn("malformedReply", "Dropped a malformed catch-up reply");
+                continue;
+            };
+            for stored in records {
+                if !self.deliver(stored) {
+                    return false;
+                }
+            }
+            self.position.fetch_max(next, Ordering::SeqCst);
+            if next >= end {
+                return true;
+            }
+        }
+    }
+
+    fn deliver(&self, stored: Stored) -> bool {
+        if stored.offset < self.next() {
+            return true;
+        }
+        let offset = stored.offset;
+        let wanted = {
+            let topics = self.topics.lock().unwrap();
+            topics.is_empty() || topics.contains(&stored.topic)
+        };
+        if wanted && self.callback.call(Ok(stored.into()), ThreadsafeFunctionCallMode::Blocking) != napi::Status::Ok {
+            return false;
+        }
+        self.position.store(offset + 1, Ordering::SeqCst);
+        true
+    }
+}
+
+// EventLog 的订阅端：从给定的 offset 开始，先经追赶地址补读已有的记录，再接上实时消息。
+// 实时消息订阅全部主题、在本地过滤，offset 出现缺口（比如 Sub 缓冲区溢出）时回到追赶地址补齐，
+// 每条记录按 offset 顺序恰好交付一次。实时消息的 timestamp 是收到的时间
+#[napi]
+pub struct LogSubscriber {
+    live: Option<Socket>,
+    catch_up: Option<Socket>,
+    topics: Topics,
+    position: Arc<AtomicU64>,
+    receiving: Arc<AtomicBool>,
+    is_closing: Arc<AtomicBool>,
+    events: EventEmitter,
+}
+
+#[napi]
+impl LogSubscriber {
+    #[napi(constructor)]
+    pub fn new() -> Self {
+        LogSubscriber {
+            live: None,
+            catch_up: None,
+            topics: Arc::default(),
+            position: Arc::default(),
+            receiving: Arc::new(AtomicBool::new(false)),
+            is_closing: Arc::new(AtomicBool::new(false)),
+            events: EventEmitter::default(),
+        }
+    }
+
+    // liveUrl 为 EventLog.listen 的地址，catchUpUrl 为 listenCatchUp 的地址；异步拨号
+    #[napi]
+    pub fn connect(&mut self, live_url: String, catch_up_url: String) -> Result<()> {
+        if self.live.is_some() {
+            return Err(failed("Already connected".to_string()));
+        }
+        let live = Socket::new(Protocol::Sub0).map_err(|err| failed(format!("Socket creation failed: {:?}", err)))?;
+        let catch_up = Socket::new(Protocol::Req0).map_err(|err| {
+            live.close();
+            failed(format!("Socket creation failed: {:?}", err))
+        })?;
+        let connected = live
+            .set_opt::<RecvBufferSize>(LIVE_BUFFER)
+            .and_then(|_| live.set_opt::<Subscribe>(Vec::new()))
+            .and_then(|_| catch_up.set_opt::<RecvTimeout>(Some(CATCH_UP_TIMEOUT)))
+            .and_then(|_| live.dial_async(&live_url))
+            .and_then(|_| catch_up.dial_async(&catch_up_url))
+            .map_err(|err| failed(format!("Connection failed: {:?}", err)));
+        if let Err(err) = connected {
+            live.close();
+            catch_up.close();
+            return Err(err);
+        }
+        self.live = Some(live);
+        self.catch_up = Some(catch_up);
+        Ok(())
+    }
+
+    // 只交付这些主题的记录，不订阅时交付全部；offset 照样推进
+    #[napi]
+    pub fn subscribe(&self, topic: String) -> Result<()> {
+        topic::check_topic(&topic)?;
+        self.topics.lock().unwrap().insert(topic);
+        Ok(())
+    }
+
+    #[napi]
+    pub fn unsubscribe(&self, topic: String) -> bool {
+        self.topics.lock().unwrap().remove(&topic)
+    }
+
+    // 从 fromOffset（通常是上次处理到的 offset + 1）开始交付
+    #[napi(ts_args_type = "fromOffset: number, callback: (err: Error | null, record: LogRecord) => any")]
+    pub fn recv(&self, env: Env, from_offset: i64, callback: JsFunction) -> Result<()> {
+        let from = event_log::check_offset(from_offset)?;
+        let (Some(live), Some(catch_up)) = (self.live.clone(), self.catch_up.clone()) else {
+            return Err(failed("Socket not connected".to_string()));
+        };
+        if self.receiving.swap(true, Ordering::SeqCst) {
+            return Err(failed("Already receiving".to_string()));
+        }
+        let callback = env.create_threadsafe_function(&callback, CALLBACK_QUEUE, |ctx: ThreadSafeCallContext<LogRecord>| {
+            Ok(vec![ctx.value])
+        })?;
+        self.position.store(from, Ordering::SeqCst);
+        let cursor = Cursor {
+            catch_up,
+            topics: self.topics.clone(),
+            position: self.position.clone(),
+            callback,
+            is_closing: self.is_closing.clone(),
+            events: self.events.clone(),
+        };
+
+        guard::spawn(self.events.clone(), "Log subscriber", move || {
+            if !cursor.catch_up() {
+                return;
+            }
+            loop {
+                let message = match live.recv() {
+                    Ok(message) => message,
+                    Err(_) if cursor.is_closing.load(Ordering::SeqCst) => return, // 主动关闭时不报错
+                    Err(err) => {
+                        cursor.events.warn("recvFailed", format!("Error receiving message: {:?}", err));
+                        continue;
+                    }
+                };
+                let Ok(frame) = topic::decode(message.as_slice()) else {
+                    continue;
+                };
+                let Some(offset) = frame.sequence else {
+                    continue;
+                };
+                if offset > cursor.next() && !cursor.catch_up() {
+                    return;
+                }
+                let timestamp = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map_or(0.0, |d| d.as_secs_f64() * 1000.0);
+                let stored = Stored { offset, timestamp, topic: frame.topic.to_string(), data: frame.payload.to_vec() };
+                if !cursor.deliver(stored) {
+                    return;
+                }
+            }
+        });
+        Ok(())
+    }
+
+    // 下一条要交付的 offset；重启后从这里接着 recv
+    #[napi]
+    pub fn offset(&self) -> i64 {
+        self.position.load(Ordering::SeqCst) as i64
+    }
+
+    // catchUpFailed、malformedReply、recvFailed
+    #[napi]
+    pub fn on_event(&self, callback: ThreadsafeFunction<SocketEvent>) {
+        self.events.set(callback);
+    }
+
+    #[napi]
+    pub fn close(&mut self) {
+        self.is_closing.store(true, Ordering::SeqCst);
+        for socket in [self.live.take(), self.catch_up.take()].into_iter().flatten() {
+            socket.close();
+        }
+        self.events.clear();
+    }
+}
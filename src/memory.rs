@@ -0,0 +1,180 @@
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::Arc;
+
+use crate::events::{EventEmitter, SocketEvent};
+
+#[napi(object)]
+pub struct MemoryUsage {
+    pub queued_bytes: i64,    // 等待交给 nng 的消息（sendAsync 队列、暂停缓冲）
+    pub in_flight_bytes: i64, // 已经交给回调线程、JS 还没拿到的消息
+    pub retained_bytes: i64,  // 保留消息等缓存
+    pub total_bytes: i64,
+    pub limit_bytes: Option<i64>,
+}
+
+#[derive(Clone, Copy)]
+pub enum Pool {
+    Queued,
+    InFlight,
+    Retained,
+}
+
+#[derive(Default)]
+struct Inner {
+    queued: AtomicI64,
+    in_flight: AtomicI64,
+    retained: AtomicI64,
+    limit: AtomicI64, // 0 表示不限制
+    over: AtomicBool,
+    in_flight_messages: AtomicI64, // 已经交给回调线程、JS 还没拿到的消息条数
+    budget: AtomicI64,             // in_flight_messages 的上限，0 表示不限制
+}
+
+// 按 socket 统计内部持有的字节数，超过上限时上报一次 overLimit，回落到上限以下后重新计
+#[derive(Clone)]
+pub struct MemoryAccount {
+    inner: Arc<Inner>,
+    events: EventEmitter,
+}
+
+impl MemoryAccount {
+    pub fn new(events: EventEmitter) -> Self {
+        MemoryAccount {
+            inner: Arc::default(),
+            events,
+        }
+    }
+
+    // 不受上限约束的记账，用于已经收到、必须交付的数据
+    pub fn charge(&self, pool: Pool, bytes: usize) -> Charge {
+        self.counter(pool).fetch_add(bytes as i64, Ordering::SeqCst);
+        if let Pool::InFlight = pool {
+            self.inner.in_flight_messages.fetch_add(1, Ordering::SeqCst);
+        }
+        self.check();
+        Charge {
+            account: self.clone(),
+            pool,
+            bytes: bytes as i64,
+        }
+    }
+
+    // 超过上限时拒绝记账，调用方丢弃或拒绝这份数据
+    pub fn try_charge(&self, pool: Pool, bytes: usize) -> Option<Charge> {
+        let limit = self.inner.limit.load(Ordering::SeqCst);
+        if limit > 0 && self.total() + bytes as i64 > limit {
+            self.report(self.total() + bytes as i64);
+            return None;
+        }
+        Some(self.charge(pool, bytes))
+    }
+
+    pub fn over_limit(&self) -> bool {
+        let limit = self.inner.limit.load(Ordering::SeqCst);
+        limit > 0 && self.total() > limit
+    }
+
+    // 交付预算：同时排在 JS 事件循环里的消息不超过 budget 条
+    pub fn over_budget(&self) -> bool {
+        let budget = self.inner.budget.load(Ordering::SeqCst);
+        budget > 0 && self.inner.in_flight_messages.load(Ordering::SeqCst) >= budget
+    }
+
+    // None 或 0 表示不限制
+    pub fn set_budget(&self, messages: Option<u32>) {
+        self.inner.budget.store(messages.unwrap_or(0) as i64, Ordering::SeqCst);
+    }
+
+    // None 或 0 表示不限制
+    pub fn set_limit(&self, limit: Option<i64>) {
+        self.inner.limit.store(limit.unwrap_or(0).max(0), Ordering::SeqCst);
+        self.check();
+    }
+
+    pub fn usage(&self) -> MemoryUsage {
+        let limit = self.inner.limit.load(Ordering::SeqCst);
+        MemoryUsage {
+            queued_bytes: self.inner.queued.load(Ordering::SeqCst),
+            in_flight_bytes: self.inner.in_flight.load(Ordering::SeqCst),
+            retained_bytes: self.inner.retained.load(Ordering::SeqCst),
+            total_bytes: self.total(),
+            limit_bytes: if limit > 0 { Some(limit) } else { None },
+        }
+    }
+
+    fn total(&self) -> i64 {
+        self.inner.queued.load(Ordering::SeqCst)
+            + self.inner.in_flight.load(Ordering::SeqCst)
+            + self.inner.retained.load(Ordering::SeqCst)
+    }
+
+    fn counter(&self, pool: Pool) -> &AtomicI64 {
+        match pool {
+            Pool::Queued => &self.inner.queued,
+            Pool::InFlight => &self.inner.in_flight,
+            Pool::Retained => &self.inner.retained,
+        }
+    }
+
+    fn check(&self) {
+        if self.over_limit() {
+            self.report(self.total());
+        } else {
+            self.inner.over.store(false, Ordering::SeqCst);
+        }
+    }
+
+    fn report(&self, total: i64) {
+        if !self.inner.over.swap(true, Ordering::SeqCst) {
+            let limit = self.inner.limit.load(Ordering::SeqCst);
+            self.events.emit(
+                SocketEvent::new("overLimit")
+                    .message(format!("memory limit of {} bytes exceeded", limit))
+                    .value(total),
+            );
+        }
+    }
+}
+
+// 释放时归还记账的字节数
+pub struct Charge {
+    account: MemoryAccount,
+    pool: Pool,
+    bytes: i64,
+}
+
+impl Drop for Charge {
+    fn drop(&mut self) {
+        self.account.counter(self.pool).fetch_sub(self.bytes, Ordering::SeqCst);
+        if let Pool::InFlight = self.pool {
+            self.account.inner.in_flight_messages.fetch_sub(1, Ordering::SeqCst);
+        }
+        if !self.account.over_limit() {
+            self.account.inner.over.store(false, Ordering::SeqCst);
+        }
+    }
+}
+
+// 交给 ThreadsafeFunction 的值，转换成 JS 值（或调用失败被丢弃）时释放 in-flight 记账
+pub struct Tracked<T> {
+    value: T,
+    _charge: Charge,
+}
+
+impl<T> Tracked<T> {
+    pub fn new(value: T, charge: Charge) -> Self {
+        Tracked { value, _charge: charge }
+    }
+
+    pub fn get(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: ToNapiValue> ToNapiValue for Tracked<T> {
+    unsafe fn to_napi_value(env: napi::sys::napi_env, val: Self) -> Result<napi::sys::napi_value> {
+        T::to_napi_value(env, val.value)
+    }
+}
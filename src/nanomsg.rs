@@ -1,44 +1,381 @@
 use napi::{
     bindgen_prelude::*,
-    threadsafe_function::{ThreadsafeFunction, ThreadsafeFunctionCallMode},
+    threadsafe_function::{ThreadSafeCallContext, ThreadsafeFunction, ThreadsafeFunctionCallMode},
+    Env, JsDeferred, JsFunction, JsObject, JsUnknown, NapiValue,
 };
-use nng::{ options::{Options},Socket, Protocol, Error as NngError};
+use nng::{ options::{protocol::pubsub::{Subscribe, Unsubscribe}, Options},Aio, AioResult, Dialer, DialerBuilder, Listener, ListenerBuilder, RawSocket, Socket, Protocol, Error as NngError, PipeEvent};
 use napi_derive::napi;
 use core::time::Duration;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::abort::on_abort;
+use crate::ack::{AckOptions, Acks, Envelope};
+use crate::adaptive::{AdaptiveTimeout, AdaptiveTimeoutOptions};
+use crate::backtrace;
+use crate::callback_error::{self, CallbackErrorPolicy, RecvHooks};
+use crate::capture::{self, Capture, CapturedMessage};
+use crate::compat::{self, NnValue};
+use crate::context::{self, Closing, Registry};
+use crate::crash_dump;
+use crate::dead_letter::{DeadLetter, DeadLetters};
+use crate::endpoint::{self, EndpointOptions, ReconnectBackoff};
+use crate::events::{EventEmitter, SocketEvent};
+use crate::faults::{DelayLine, Fate, FaultOptions, Faults};
+use crate::guard;
+use crate::keep_alive::KeepAlive;
+use crate::memory::{MemoryAccount, MemoryUsage, Pool, Tracked};
+use crate::nng_error::{nng_error, reject_nng, NngErrorCode};
+use crate::option_dump::{self, EndpointRef, OptionDump};
+use crate::outbox::Outbox;
+use crate::payload::Payload;
+use crate::pinning::{Pinning, ThreadPinning};
+use crate::pipes::{EndpointStats, PipeEventInfo, PipeHooks, PipeInfo};
+use crate::probe::Probes;
+use crate::recording::{Recorder, Replay, ReplayOptions};
+use crate::recv_into::RecvInto;
+use crate::requeue::{Requeue, RequeueOptions};
+use crate::schedule::Scheduler;
+use crate::slab::{BorrowedSlab, DEFAULT_SLAB_BYTES};
+use crate::socket_context::{self, Context};
+use crate::storm::{DisconnectCoalescing, DisconnectStorm};
+use crate::transfer::TransferableBuffer;
+use crate::transport;
+use crate::warmup::WarmUp;
+use crate::watchdog::{Stage, Watchdog, WatchdogOptions};
+
+// 超过内存上限时接收循环检查的间隔
+const MEMORY_POLL: Duration = Duration::from_millis(10);
+
+// 用完交付预算时接收循环检查的间隔，JS 处理一轮回调通常用不了这么久
+const BUDGET_POLL: Duration = Duration::from_millis(1);
+
+// 没有进行中的调查时，接收循环等待下一次调查的间隔
+const SURVEY_IDLE_POLL: Duration = Duration::from_millis(10);
+
+// recvChunked 的默认分块大小和回调队列长度
+const DEFAULT_CHUNK_BYTES: u32 = 1024 * 1024;
+const CHUNK_QUEUE: usize = 4;
+
+const DEFAULT_WARM_UP_MS: u32 = 10_000;
+
+type Resolver<T = ()> = Box<dyn FnOnce(Env) -> Result<T> + Send>;
+
+// recvChunked 交付的片段：kind 为 "message"、"begin"、"chunk" 或 "end"，
+// begin/end 带整条消息的字节数，message/chunk 带数据
+#[napi(object)]
+pub struct MessagePart {
+    pub kind: String,
+    pub data: Option<Buffer>,
+    pub size: Option<i64>,
+}
+
+impl MessagePart {
+    fn new(kind: &str) -> Self {
+        MessagePart { kind: kind.to_string(), data: None, size: None }
+    }
+
+    fn data(mut self, data: Vec<u8>) -> Self {
+        self.data = Some(data.into());
+        self
+    }
+
+    fn size(mut self, size: usize) -> Self {
+        self.size = Some(size as i64);
+        self
+    }
+}
+
+// 分开的消息头和正文。raw socket 的消息头里是协议的路由信息（REQ/REP 的回溯），
+// 设备和 JS 写的 broker 转发时原样带上，回复才能找回原来的 pipe
+#[napi(object, js_name = "Message")]
+pub struct HeaderedMessage {
+    pub header: Buffer,
+    pub body: Buffer,
+    pub pipe_id: Option<u32>, // 收到这条消息的 pipe，可以用 pipe() 查对端信息；sendMessage 忽略
+}
+
+// sendMsg 的参数，header 和 body 都接受 Buffer | Uint8Array | string | ArrayBuffer
+#[napi(object, object_to_js = false)]
+pub struct OutgoingMessage {
+    #[napi(ts_type = "Buffer | Uint8Array | string | ArrayBuffer")]
+    pub header: Option<Payload>,
+    #[napi(ts_type = "Buffer | Uint8Array | string | ArrayBuffer")]
+    pub body: Payload,
+}
+
+#[napi(object)]
+pub struct SocketCapabilities {
+    pub can_send: bool,
+    pub can_recv: bool,
+    pub raw: bool,
+    pub alternating: bool, // 非 raw 的 Req0/Rep0 必须收发交替进行
+}
 
 #[napi]
 pub struct SocketWrapper {
     socket: Option<Socket>,
+    registry: Option<Registry>, // 登记了这个 socket 的 env，env 销毁时关闭没关掉的 socket
     url: Option<String>, // 用于存储连接的 URL
     receiving: Arc<AtomicBool>, // 控制接收状态
+    recv_paused: Arc<AtomicBool>, // pauseRecv 暂停交付，消息留在 nng 的队列里
     is_closing: Arc<AtomicBool>, // 控制主动关闭状态
+    events: EventEmitter, // 事件回调，标签也挂在这里
+    outbox: Option<Outbox>, // sendAsync 的发送队列
+    raw: bool, // raw 模式下收发的 Buffer 带 REQ/REP 回溯头
+    protocol: Option<Protocol>,
+    memory: MemoryAccount,
+    listeners: BTreeMap<u32, (String, Listener)>, // 按 listener id 保存，可以单独关闭
+    pending_listeners: BTreeMap<u32, (String, ListenerBuilder)>, // createListener 创建、还没启动的
+    pending_dialers: BTreeMap<u32, (String, DialerBuilder)>,
+    dialers: BTreeMap<u32, (String, Dialer)>, // 已启动的 dialer，warmUp 等它们都连上
+    pipes: PipeHooks, // onPipeAdded/onPipeRemoved
+    probes: Probes, // 等待 pong 的 ping
+    adaptive: Option<AdaptiveTimeout>, // 按往返时间自动调整的请求超时
+    slab: BorrowedSlab, // recvBorrowed 复用的 Buffer
+    recv_into: Option<RecvInto>,
+    dead_letters: DeadLetters, // sendAsync/sendReliable 没能送出的消息
+    scheduler: Option<Scheduler>, // sendAfter 的定时器
+    capture: Capture, // setCapture 开启后最近收发的消息
+    requests: Arc<Mutex<()>>, // send 的往返依次进行
+    faults: Faults, // setFaults 注入的丢弃、重复、乱序和延迟
+    pinning: Pinning, // 接收线程绑定的 CPU 核和优先级
+    storm: DisconnectStorm, // setDisconnectCoalescing 汇总的断开
+    namespace: Option<String>, // setNamespace 设置的租户，之后的 inproc/ipc 地址按它改写
+    callback_errors: CallbackErrorPolicy, // 接收回调抛出异常时的处理
+    acks: Acks, // recvEnvelopes 交付的、还没 ack 的消息
+    requeue: Requeue, // setRequeue 设置的重试 socket
+    keep_alive: KeepAlive, // ref()/unref()，跨 open/close 保留
+    watchdog: Watchdog, // setRecvWatchdog 检查卡住的接收循环
+}
+
+// 队列深度都是当前值（消息数）；nng 的两项读不到 pipe 统计（连过 inproc）时为空
+#[napi(object)]
+pub struct SocketStats {
+    pub outbox_depth: i64,             // sendAsync 队列里还没交给 nng 的
+    pub send_queue_depth: Option<i64>, // 已交给 nng、还没写出的
+    pub recv_queue_depth: Option<i64>, // nng 已收到、接收循环还没取走的；Sub 过滤掉的也算在内
+    pub send_buffer: i64, // nng 发送/接收队列的容量
+    pub recv_buffer: i64,
+    pub labels: HashMap<String, String>,
+    pub endpoints: Vec<EndpointStats>, // 各 dialer/listener 的连接次数、时间和累计在线时长
+}
+
+#[napi(object)]
+pub struct ListenerInfo {
+    pub id: u32,
+    pub url: String,
+}
+
+// tryRequest 的结果：ok 为 true 时带回复，否则 reason 说明原因
+#[napi(object)]
+pub struct RequestOutcome {
+    pub ok: bool,
+    pub data: Option<Buffer>,
+    #[napi(ts_type = "'timeout' | 'closed' | 'error'")]
+    pub reason: Option<String>,
+    pub message: Option<String>,        // reason 为 error 时的错误信息
+    pub nng_code: Option<NngErrorCode>, // reason 为 error 时 nng 的错误类别
+}
+
+impl From<std::result::Result<Buffer, NngError>> for RequestOutcome {
+    fn from(result: std::result::Result<Buffer, NngError>) -> Self {
+        let failed = |reason: &str| RequestOutcome { ok: false, data: None, reason: Some(reason.to_string()), message: None, nng_code: None };
+        match result {
+            Ok(data) => RequestOutcome { ok: true, data: Some(data), reason: None, message: None, nng_code: None },
+            Err(NngError::TimedOut) => failed("timeout"),
+            Err(NngError::Canceled | NngError::Closed) => failed("closed"),
+            Err(err) => RequestOutcome {
+                message: Some(format!("Request error: {:?}", err)),
+                nng_code: Some(NngErrorCode::from(&err)),
+                ..failed("error")
+            },
+        }
+    }
 }
 
 #[napi]
 impl SocketWrapper {
     #[napi(constructor)]
     pub fn new() -> Self {
+        let events = EventEmitter::default();
+        let memory = MemoryAccount::new(events.clone());
+        let dead_letters = DeadLetters::default();
+        let requeue = Requeue::default();
         SocketWrapper {
             socket: None,
+            registry: None,
             url: None,
             receiving: Arc::new(AtomicBool::new(false)), // 初始化接收状态
+            recv_paused: Arc::new(AtomicBool::new(false)),
             is_closing: Arc::new(AtomicBool::new(false)), // 初始化关闭状态
+            outbox: None,
+            raw: false,
+            protocol: None,
+            acks: Acks::new(events.clone(), memory.clone(), dead_letters.clone(), requeue.clone()),
+            memory,
+            probes: Probes::new(events.clone()),
+            watchdog: Watchdog::new(events.clone()),
+            events,
+            listeners: BTreeMap::new(),
+            pending_listeners: BTreeMap::new(),
+            pending_dialers: BTreeMap::new(),
+            dialers: BTreeMap::new(),
+            pipes: PipeHooks::default(),
+            adaptive: None,
+            slab: BorrowedSlab::default(),
+            recv_into: None,
+            dead_letters,
+            scheduler: None,
+            capture: Capture::default(),
+            requests: Arc::default(),
+            faults: Faults::default(),
+            pinning: Pinning::default(),
+            storm: DisconnectStorm::default(),
+            namespace: None,
+            callback_errors: CallbackErrorPolicy::Throw,
+            requeue,
+            keep_alive: KeepAlive::default(),
         }
     }
 
+    // 相当于 open + setTimeouts + setReconnectBackoff + dial，失败时不保留 socket
     #[napi]
+    #[allow(clippy::too_many_arguments)]
     pub fn connect(
         &mut self,
+        env: Env,
         protocol: ProtocolType,
         url: String,
         recv_timeout: u32, // 修改为 u32
         send_timeout: u32,
+        raw: Option<bool>,
+        reconnect: Option<ReconnectBackoff>,
     ) -> Result<bool> {
+        self.open(env, protocol, raw)?;
+        let connected = self
+            .set_timeouts(recv_timeout, send_timeout)
+            .and_then(|_| reconnect.map_or(Ok(()), |backoff| self.set_reconnect_backoff(backoff)))
+            .and_then(|_| self.dial(env, url, None));
+        if let Err(err) = connected {
+            self.discard();
+            return Err(err);
+        }
+        Ok(true) // 返回连接成功
+    }
+
+    // 只创建 socket，不建立任何端点；nng 的很多选项要在端点启动前设置，之后再 dial/listen
+    // raw 为 true 时打开 raw socket：
+    // - Req0/Rep0 由调用方自己管理请求 ID，见 requestId/setRequestId
+    // - Sub0 不做主题过滤，收到所有消息，broker 可以在服务端自己过滤、决定怎么扇出；
+    //   nng 的订阅只在订阅端本地生效，不会发给发布端，所以没有 XPUB 那样的订阅控制消息
+    // - Pub0 和非 raw 的行为相同，用来和 raw Sub0 搭配转发
+    #[napi]
+    pub fn open(&mut self, env: Env, protocol: ProtocolType, raw: Option<bool>) -> Result<()> {
+        if self.socket.is_some() {
+            return Err(napi::Error::new(napi::Status::GenericFailure, "Socket already open".to_string()));
+        }
         // 创建新的 socket
-        let socket = Socket::new(protocol.into()).map_err(|err| {
-            napi::Error::new(napi::Status::GenericFailure, format!("Socket creation failed: {:?}", err))
+        let protocol: Protocol = protocol.into();
+        let raw = raw.unwrap_or(false);
+        let socket = if raw {
+            if !matches!(protocol, Protocol::Req0 | Protocol::Rep0 | Protocol::Pub0 | Protocol::Sub0) {
+                return Err(napi::Error::new(napi::Status::InvalidArg, "Raw mode is only supported for Req0, Rep0, Pub0 and Sub0".to_string()));
+            }
+            RawSocket::new(protocol).map(|raw| raw.socket)
+        } else {
+            Socket::new(protocol)
+        }
+        .map_err(|err| nng_error(&env, &err, format!("Socket creation failed: {:?}", err)))?;
+
+        // drain 需要知道 socket 有哪些 pipe
+        let outbox = Outbox::start(
+            socket.clone(),
+            protocol,
+            self.memory.clone(),
+            self.events.clone(),
+            self.dead_letters.clone(),
+            self.capture.clone(),
+            self.faults.clone(),
+        );
+        outbox.set_pause_senders(self.storm.pauses_senders());
+        let notify_outbox = outbox.clone();
+        let pipes = self.pipes.clone();
+        let storm = self.storm.clone();
+        let events = self.events.clone();
+        socket
+            .pipe_notify(move |pipe, event| match event {
+                PipeEvent::AddPre => pipes.pipe_adding(pipe),
+                PipeEvent::AddPost => {
+                    notify_outbox.pipe_added(pipe);
+                    storm.pipe_added(&events);
+                    pipes.pipe_added(pipe);
+                }
+                PipeEvent::RemovePost => {
+                    notify_outbox.pipe_removed(pipe);
+                    let remaining = notify_outbox.clone();
+                    let coalesced = storm.pipe_removed(&events, move || remaining.connected());
+                    pipes.pipe_removed(pipe, !coalesced);
+                }
+                _ => {}
+            })
+            .map_err(|err| napi::Error::new(napi::Status::GenericFailure, format!("Failed to watch pipes: {:?}", err)))?;
+
+        let recv_into = RecvInto::new(socket.clone(), raw, self.probes.clone(), outbox.clone())
+            .map_err(|err| napi::Error::new(napi::Status::GenericFailure, format!("Socket creation failed: {:?}", err)))?;
+
+        let closing = Closing {
+            socket: socket.clone(),
+            outbox: outbox.clone(),
+            recv_paused: self.recv_paused.clone(),
+            is_closing: self.is_closing.clone(),
+            keep_alive: self.keep_alive.clone(),
+        };
+        let registry = match context::track(&env, &socket, closing) {
+            Ok(registry) => registry,
+            Err(err) => {
+                socket.close();
+                return Err(err);
+            }
+        };
+        self.scheduler = Some(Scheduler::new(outbox.clone(), self.events.clone()));
+        self.events.crash_dump().attach(crash_dump::Source {
+            socket: socket.clone(),
+            protocol,
+            raw,
+            outbox: outbox.clone(),
+            pipes: self.pipes.clone(),
+            capture: self.capture.clone(),
+            memory: self.memory.clone(),
+        });
+        self.socket = Some(socket);
+        self.registry = Some(registry);
+        self.outbox = Some(outbox);
+        self.recv_into = Some(recv_into);
+        self.raw = raw;
+        self.protocol = Some(protocol);
+        self.is_closing.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+
+    // 只影响之后 dial 的 dialer，已经在重连的按原来的间隔。nng 没有关闭自动重连的选项，
+    // 不想让它重连时关掉对应的 dialer
+    #[napi]
+    pub fn set_reconnect_backoff(&self, backoff: ReconnectBackoff) -> Result<()> {
+        let socket = self.socket.as_ref().ok_or_else(|| {
+            napi::Error::new(napi::Status::GenericFailure, "Socket not open".to_string())
+        })?;
+        endpoint::apply_backoff(socket, &backoff)
+    }
+
+    // 0 表示不超时
+    #[napi]
+    pub fn set_timeouts(&self, recv_timeout: u32, send_timeout: u32) -> Result<()> {
+        let socket = self.socket.as_ref().ok_or_else(|| {
+            napi::Error::new(napi::Status::GenericFailure, "Socket not open".to_string())
         })?;
 
         // 处理接收超时和发送超时
@@ -54,105 +391,1292 @@ impl SocketWrapper {
             Some(Duration::from_millis(send_timeout as u64))
         };
 
-        if let Some(timeout) = recv_timeout_duration {
-            socket.set_opt::<nng::options::RecvTimeout>(Some(timeout))
-                .map_err(|err| napi::Error::new(napi::Status::GenericFailure, format!("Failed to set receive timeout: {:?}", err)))?;
+        socket.set_opt::<nng::options::RecvTimeout>(recv_timeout_duration)
+            .map_err(|err| napi::Error::new(napi::Status::GenericFailure, format!("Failed to set receive timeout: {:?}", err)))?;
+        socket.set_opt::<nng::options::SendTimeout>(send_timeout_duration)
+            .map_err(|err| napi::Error::new(napi::Status::GenericFailure, format!("Failed to set send timeout: {:?}", err)))?;
+        Ok(())
+    }
+
+    // 只用于非 raw 的 Req：send 的等待时间按最近的往返时间自动调整，
+    // 样本不足时沿用 setTimeouts 的接收超时；不传参数关闭
+    #[napi]
+    pub fn set_adaptive_timeout(&mut self, env: Env, options: Option<AdaptiveTimeoutOptions>) -> Result<()> {
+        let options = match options {
+            Some(options) => options,
+            None => {
+                self.adaptive = None;
+                return Ok(());
+            }
+        };
+        if self.protocol != Some(Protocol::Req0) || self.raw {
+            return Err(protocol_misuse(
+                &env,
+                "Adaptive timeouts are only supported on non-raw Req sockets".to_string(),
+            ));
         }
+        self.adaptive = Some(AdaptiveTimeout::new(options)?);
+        Ok(())
+    }
 
-        if let Some(timeout) = send_timeout_duration {
-            socket.set_opt::<nng::options::SendTimeout>(Some(timeout))
-                .map_err(|err| napi::Error::new(napi::Status::GenericFailure, format!("Failed to set send timeout: {:?}", err)))?;
+    // 当前使用的请求超时（毫秒）；没有开启或样本不足时为空
+    #[napi]
+    pub fn adaptive_timeout(&self) -> Option<u32> {
+        let timeout = self.adaptive.as_ref()?.current()?;
+        Some(timeout.as_millis() as u32)
+    }
+
+    // Sub0 按原始字节前缀订阅，可以匹配其他 nng 发布端常用的二进制主题头；字符串按 UTF-8 编码，空前缀收所有消息
+    #[napi(ts_args_type = "prefix: Buffer | Uint8Array | string | ArrayBuffer")]
+    pub fn subscribe(&self, env: Env, prefix: Payload) -> Result<()> {
+        let socket = self.sub_socket(&env, "subscribe")?;
+        socket.set_opt::<Subscribe>(prefix.to_vec()).map_err(|err| {
+            napi::Error::new(napi::Status::GenericFailure, format!("Failed to subscribe: {:?}", err))
+        })
+    }
+
+    // 一次订阅多个前缀：全部成功或者全部不生效，中途失败时撤掉已经加上的。
+    // 在 dial/listen 之前调用可以保证第一条消息到达时所有前缀都已生效
+    #[napi(ts_args_type = "prefixes: Array<Buffer | Uint8Array | string | ArrayBuffer>")]
+    pub fn subscribe_many(&self, env: Env, prefixes: Vec<Payload>) -> Result<()> {
+        let socket = self.sub_socket(&env, "subscribeMany")?;
+        for (index, prefix) in prefixes.iter().enumerate() {
+            if let Err(err) = socket.set_opt::<Subscribe>(prefix.to_vec()) {
+                for applied in &prefixes[..index] {
+                    let _ = socket.set_opt::<Unsubscribe>(applied.to_vec());
+                }
+                return Err(napi::Error::new(
+                    napi::Status::GenericFailure,
+                    format!("Failed to subscribe to prefix {}: {:?}", index, err),
+                ));
+            }
         }
+        Ok(())
+    }
+
+    #[napi(ts_args_type = "prefix: Buffer | Uint8Array | string | ArrayBuffer")]
+    pub fn unsubscribe(&self, env: Env, prefix: Payload) -> Result<()> {
+        let socket = self.sub_socket(&env, "unsubscribe")?;
+        socket.set_opt::<Unsubscribe>(prefix.to_vec()).map_err(|err| {
+            napi::Error::new(napi::Status::GenericFailure, format!("Failed to unsubscribe: {:?}", err))
+        })
+    }
+
+    // 方便从 nanomsg 迁移：按 nanomsg 的选项名和取值约定设置 socket 选项，
+    // 如 setNanomsgOption('NN_RCVTIMEO', -1)、setNanomsgOption('NN_SUB_SUBSCRIBE', 'topic')
+    #[napi(ts_args_type = "name: string, value: number | boolean | string | Buffer")]
+    pub fn set_nanomsg_option(&self, name: String, value: NnValue) -> Result<()> {
+        let socket = self.socket.as_ref().ok_or_else(|| {
+            napi::Error::new(napi::Status::GenericFailure, "Socket not open".to_string())
+        })?;
+        compat::set(socket, &name, value)
+    }
 
+    #[napi]
+    pub fn get_nanomsg_option(&self, name: String) -> Result<Either<f64, String>> {
+        let socket = self.socket.as_ref().ok_or_else(|| {
+            napi::Error::new(napi::Status::GenericFailure, "Socket not open".to_string())
+        })?;
+        compat::get(socket, &name)
+    }
+
+    // 排查问题用：socket 和各个 listener/dialer 上所有读得到的 nng 选项，附带 nng 的默认值
+    #[napi]
+    pub fn dump_options(&self) -> Result<OptionDump> {
+        let (socket, protocol) = match (&self.socket, self.protocol) {
+            (Some(socket), Some(protocol)) => (socket, protocol),
+            _ => return Err(napi::Error::new(napi::Status::GenericFailure, "Socket not open".to_string())),
+        };
+        let endpoints = self
+            .listeners
+            .iter()
+            .map(|(id, (url, listener))| EndpointRef::listener(*id, url, listener.nng_listener(), true))
+            .chain(self.pending_listeners.iter().map(|(id, (url, builder))| EndpointRef::listener(*id, url, builder.nng_listener(), false)))
+            .chain(self.dialers.iter().map(|(id, (url, dialer))| EndpointRef::dialer(*id, url, dialer.nng_dialer(), true)))
+            .chain(self.pending_dialers.iter().map(|(id, (url, builder))| EndpointRef::dialer(*id, url, builder.nng_dialer(), false)))
+            .collect();
+        let mut dump = option_dump::dump(socket, protocol, self.raw, endpoints);
+        dump.labels = self.events.labels().get();
+        Ok(dump)
+    }
+
+    // nonblocking 为 true 时不等待第一次连接成功，连不上由 nng 在后台重试
+    #[napi]
+    pub fn dial(&mut self, env: Env, url: String, nonblocking: Option<bool>) -> Result<()> {
+        let url = self.resolve_url(url);
+        let socket = self.socket.as_ref().ok_or_else(|| {
+            napi::Error::new(napi::Status::GenericFailure, "Socket not open".to_string())
+        })?;
         // 尝试连接
-        socket.dial(&url).map_err(|err| {
-            napi::Error::new(napi::Status::GenericFailure, format!("Connection failed: {:?}", err))
+        let dialer = Dialer::new(socket, &url, nonblocking.unwrap_or(false))
+            .map_err(|err| nng_error(&env, &err, format!("Connection failed: {:?}", err)))?;
+        let id = unsafe { nng::ffi::nng_dialer_id(dialer.nng_dialer()) } as u32;
+        self.dialers.insert(id, (url.clone(), dialer));
+        self.url = Some(url); // 存储连接的 URL
+        Ok(())
+    }
+
+    // 可以多次调用同时监听多个地址（例如本地 ipc:// 加远程 tcp://），返回的 id 用于 closeListener
+    #[napi]
+    pub fn listen(&mut self, env: Env, url: String) -> Result<u32> {
+        let url = self.resolve_url(url);
+        let socket = self.socket.as_ref().ok_or_else(|| {
+            napi::Error::new(napi::Status::GenericFailure, "Socket not open".to_string())
         })?;
+        let listener = Listener::new(socket, &url).map_err(|err| nng_error(&env, &err, format!("Listen failed: {:?}", err)))?;
+        let id = unsafe { nng::ffi::nng_listener_id(listener.nng_listener()) } as u32;
+        self.listeners.insert(id, (url.clone(), listener));
+        self.url = Some(url);
+        Ok(id)
+    }
 
-        self.socket = Some(socket);
-        self.url = Some(url.clone()); // 存储连接的 URL
-        Ok(true) // 返回连接成功
+    // 只关闭这一个 listener，由它接受的连接也会断开；id 不存在时返回 false
+    #[napi]
+    pub fn close_listener(&mut self, id: u32) -> bool {
+        match self.listeners.remove(&id) {
+            Some((_, listener)) => {
+                listener.close();
+                true
+            }
+            None => false,
+        }
     }
 
     #[napi]
-    pub fn send(&self, message: Buffer) -> Result<Buffer> {
-        if let Some(socket) = &self.socket {
-            let msg = nng::Message::from(&message[..]);
+    pub fn listeners(&self) -> Vec<ListenerInfo> {
+        self.listeners
+            .iter()
+            .map(|(id, (url, _))| ListenerInfo { id: *id, url: url.clone() })
+            .collect()
+    }
 
-            // 添加发送超时错误处理
-            socket.send(msg).map_err(|(_, e)| {
-                eprintln!("Failed to send message: {:?}", e);
-                napi::Error::new(napi::Status::GenericFailure, format!("Send error: {:?}", e))
-            })?;
+    // 两阶段创建：先创建 listener 并设置选项（TLS、接收上限等），startListener 之后才开始监听
+    #[napi]
+    pub fn create_listener(&mut self, env: Env, url: String, options: Option<EndpointOptions>) -> Result<u32> {
+        let url = self.resolve_url(url);
+        let socket = self.socket.as_ref().ok_or_else(|| {
+            napi::Error::new(napi::Status::GenericFailure, "Socket not open".to_string())
+        })?;
+        let builder = ListenerBuilder::new(socket, &url)
+            .map_err(|err| nng_error(&env, &err, format!("Failed to create listener: {:?}", err)))?;
+        if let Some(options) = options {
+            endpoint::apply_listener(&builder, &options)?; // 出错时 builder 被丢弃，listener 随之关闭
+        }
+        let id = unsafe { nng::ffi::nng_listener_id(builder.nng_listener()) } as u32;
+        self.pending_listeners.insert(id, (url, builder));
+        Ok(id)
+    }
 
-            // 添加接收超时错误处理
-            let response = socket.recv().map_err(|e| {
-                match e {
-                    NngError::TimedOut => {
-                        napi::Error::new(napi::Status::GenericFailure, "Receive timeout".to_string())
-                    }
-                    _ => {
-                        napi::Error::new(napi::Status::GenericFailure, format!("Receive error: {:?}", e))
-                    }
+    // 启动后和 listen 返回的 listener 一样，可以用 closeListener 关闭
+    #[napi]
+    pub fn start_listener(&mut self, env: Env, id: u32) -> Result<()> {
+        let (url, builder) = self.pending_listeners.remove(&id).ok_or_else(|| {
+            napi::Error::new(napi::Status::InvalidArg, format!("No pending listener with id {}", id))
+        })?;
+        let listener = builder.start().map_err(|(_, err)| nng_error(&env, &err, format!("Listen failed: {:?}", err)))?;
+        self.listeners.insert(id, (url.clone(), listener));
+        self.url = Some(url);
+        Ok(())
+    }
+
+    // 和 createListener 一样，另外可以设置重连间隔
+    #[napi]
+    pub fn create_dialer(&mut self, env: Env, url: String, options: Option<EndpointOptions>) -> Result<u32> {
+        let url = self.resolve_url(url);
+        let socket = self.socket.as_ref().ok_or_else(|| {
+            napi::Error::new(napi::Status::GenericFailure, "Socket not open".to_string())
+        })?;
+        let builder = DialerBuilder::new(socket, &url)
+            .map_err(|err| nng_error(&env, &err, format!("Failed to create dialer: {:?}", err)))?;
+        if let Some(options) = options {
+            endpoint::apply_dialer(&builder, &options)?;
+        }
+        let id = unsafe { nng::ffi::nng_dialer_id(builder.nng_dialer()) } as u32;
+        self.pending_dialers.insert(id, (url, builder));
+        Ok(id)
+    }
+
+    // nonblocking 的含义同 dial
+    #[napi]
+    pub fn start_dialer(&mut self, env: Env, id: u32, nonblocking: Option<bool>) -> Result<()> {
+        let (url, builder) = self.pending_dialers.remove(&id).ok_or_else(|| {
+            napi::Error::new(napi::Status::InvalidArg, format!("No pending dialer with id {}", id))
+        })?;
+        let dialer = builder
+            .start(nonblocking.unwrap_or(false))
+            .map_err(|(_, err)| nng_error(&env, &err, format!("Connection failed: {:?}", err)))?;
+        self.dialers.insert(id, (url.clone(), dialer));
+        self.url = Some(url);
+        Ok(())
+    }
+
+    // connect 失败时丢弃刚打开的 socket
+    fn discard(&mut self) {
+        if let Some(outbox) = self.outbox.take() {
+            outbox.close();
+        }
+        self.listeners.clear();
+        self.pending_listeners.clear();
+        self.pending_dialers.clear();
+        self.dialers.clear();
+        self.adaptive = None;
+        self.recv_into = None;
+        self.scheduler = None;
+        self.protocol = None;
+        if let Some(socket) = self.socket.take() {
+            if let Some(registry) = self.registry.take() {
+                registry.untrack(&socket);
+            }
+            socket.close();
+        }
+    }
+
+    // 发送后等待回复，只适用于既能发送又能接收的协议。收发在后台线程里进行，不阻塞事件循环；
+    // 同一个 socket 上的多个 send 按调用顺序一个接一个地往返（setAdaptiveTimeout 开启后各用各的 context，可以同时进行）
+    #[napi(ts_args_type = "message: Buffer | Uint8Array | string | ArrayBuffer", ts_return_type = "Promise<Buffer>")]
+    pub fn send(&self, env: Env, message: Payload) -> Result<JsObject> {
+        let (request, msg) = self.request(&env, "send", &message)?;
+        let (deferred, promise) = env.create_deferred::<Buffer, Resolver<Buffer>>()?;
+        guard::spawn(self.events.clone(), "Request", move || match request.round_trip(msg) {
+            Ok(response) => deferred.resolve(Box::new(move |_| Ok(response))),
+            Err(NngError::Closed) => reject_closed(deferred, "Socket closed"),
+            Err(NngError::TimedOut) => reject_nng(deferred, NngError::TimedOut, "Receive timeout".to_string()),
+            Err(err) => reject_nng(deferred, err, format!("Request error: {:?}", err)),
+        });
+        Ok(promise)
+    }
+
+    // 和 send 一样往返一次，但超时、socket 关闭和其它 nng 错误都 resolve 成 ok 为 false 的结果，不抛异常，
+    // 适合把超时当作常态处理的热路径。调用方式用错（协议不对、socket 没打开）仍然直接抛出
+    #[napi(ts_args_type = "message: Buffer | Uint8Array | string | ArrayBuffer", ts_return_type = "Promise<RequestOutcome>")]
+    pub fn try_request(&self, env: Env, message: Payload) -> Result<JsObject> {
+        let (request, msg) = self.request(&env, "tryRequest", &message)?;
+        let (deferred, promise) = env.create_deferred::<RequestOutcome, Resolver<RequestOutcome>>()?;
+        guard::spawn(self.events.clone(), "Request", move || {
+            let outcome = RequestOutcome::from(request.round_trip(msg));
+            deferred.resolve(Box::new(move |_| Ok(outcome)));
+        });
+        Ok(promise)
+    }
+
+    // 向所有 Respondent 发出调查，收集截止时间 deadlineMs 之前到达的回复，resolve 为按到达顺序排列的全部回复。
+    // 给了 callback 时每个回复到达时先交给它。每次调查用自己的 context，可以同时进行多个，
+    // 也不影响 recv 的接收循环和 SURVEYOR_DEADLINE 的设置；这样的调查不计入 surveyComplete 事件
+    #[napi(
+        ts_args_type = "message: Buffer | Uint8Array | string | ArrayBuffer, deadlineMs: number, callback?: ((err: Error | null, reply: Buffer) => any) | undefined | null",
+        ts_return_type = "Promise<Buffer[]>"
+    )]
+    pub fn survey(&self, env: Env, message: Payload, deadline_ms: u32, callback: Option<ThreadsafeFunction<Buffer>>) -> Result<JsObject> {
+        if self.protocol != Some(Protocol::Surveyor0) || self.raw {
+            return Err(protocol_misuse(&env, "survey is only supported on Surveyor0 sockets that are not raw".to_string()));
+        }
+        if deadline_ms == 0 {
+            return Err(napi::Error::new(napi::Status::InvalidArg, "deadlineMs must be greater than 0".to_string()));
+        }
+        let socket = self.socket.clone().ok_or_else(|| {
+            napi::Error::new(napi::Status::GenericFailure, "Socket not connected".to_string())
+        })?;
+        let msg = nng::Message::from(&message[..]);
+        let (deferred, promise) = env.create_deferred::<Vec<Buffer>, Resolver<Vec<Buffer>>>()?;
+        let capture = self.capture.clone();
+        guard::spawn(self.events.clone(), "Survey", move || {
+            let snapshot = capture.snapshot(&msg);
+            let surveyed = socket_context::survey(&socket, msg, Duration::from_millis(deadline_ms as u64), || capture.sent(snapshot), |reply| {
+                capture.received(reply);
+                if let Some(callback) = &callback {
+                    callback.call(Ok(reply.as_slice().to_vec().into()), ThreadsafeFunctionCallMode::NonBlocking);
                 }
-            })?;
+            });
+            match surveyed {
+                Ok(replies) => deferred.resolve(Box::new(move |_| Ok(replies.iter().map(|reply| reply.as_slice().to_vec().into()).collect()))),
+                Err(NngError::Canceled | NngError::Closed) => reject_closed(deferred, "Socket closed"),
+                Err(err) => reject_nng(deferred, err, format!("Survey error: {:?}", err)),
+            }
+        });
+        Ok(promise)
+    }
 
-            Ok(response.as_slice().into())
+    // 打开一个 context，只适用于非 raw 的 Req0、Rep0、Surveyor0 和 Respondent0。
+    // 并发的请求各用一个 context 时各自等自己的回复，不会像 send 那样排队
+    #[napi]
+    pub fn open_context(&self) -> Result<Context> {
+        let socket = self.socket.clone().ok_or_else(|| {
+            napi::Error::new(napi::Status::GenericFailure, "Socket not open".to_string())
+        })?;
+        Context::open(socket, self.outbox.clone(), self.capture.clone(), self.events.clone())
+    }
+
+    // 排队发送，交给 nng 后 resolve，不等待回复
+    #[napi(ts_args_type = "message: Buffer | Uint8Array | string | ArrayBuffer", ts_return_type = "Promise<void>")]
+    pub fn send_async(&self, env: Env, message: Payload) -> Result<JsObject> {
+        self.check_send(&env, "sendAsync")?;
+        self.outbox()?.send(env, self.message(&message)?)
+    }
+
+    // 和 sendAsync 一样排队发送，消息头由调用方给出，只适用于 raw socket
+    #[napi(ts_return_type = "Promise<void>")]
+    pub fn send_message(&self, env: Env, message: HeaderedMessage) -> Result<JsObject> {
+        self.check_send(&env, "sendMessage")?;
+        if !self.raw {
+            return Err(protocol_misuse(&env, "sendMessage requires a raw socket; cooked sockets manage headers themselves".to_string()));
+        }
+        let mut msg = nng::Message::from(&message.body[..]);
+        msg.as_mut_header().push_back(&message.header);
+        self.outbox()?.send(env, msg)
+    }
+
+    // 和 sendAsync 一样排队发送，可以附带一段消息头，不用自己拼 Message：
+    // raw socket 上是协议的路由信息；cooked 的 Push/Pub/Pair0 不处理消息头，原样写在正文前面。
+    // Req/Rep/Surveyor/Respondent/Bus/Pair1 的 cooked socket 会改写消息头，不能带 header
+    #[napi(ts_return_type = "Promise<void>")]
+    pub fn send_msg(&self, env: Env, message: OutgoingMessage) -> Result<JsObject> {
+        self.check_send(&env, "sendMsg")?;
+        let mut msg = nng::Message::from(&message.body[..]);
+        if let Some(header) = message.header.filter(|header| !header.is_empty()) {
+            if let Some(protocol) = self.protocol.filter(|protocol| !self.raw && manages_header(*protocol)) {
+                return Err(protocol_misuse(
+                    &env,
+                    format!("sendMsg cannot carry a header on cooked {:?} sockets, which manage headers themselves", protocol),
+                ));
+            }
+            msg.as_mut_header().push_back(&header);
+        }
+        self.outbox()?.send(env, msg)
+    }
+
+    // 不阻塞地发送，nng 发送队列满时返回 false
+    #[napi(ts_args_type = "message: Buffer | Uint8Array | string | ArrayBuffer")]
+    pub fn try_send(&self, env: Env, message: Payload) -> Result<bool> {
+        self.check_send(&env, "trySend")?;
+        let outbox = self.outbox()?;
+        let socket = self.socket.as_ref().ok_or_else(|| {
+            napi::Error::new(napi::Status::GenericFailure, "Socket not connected".to_string())
+        })?;
+        let message = self.message(&message)?;
+        let snapshot = self.capture.snapshot(&message);
+        match socket.try_send(message) {
+            Ok(()) => {
+                outbox.record_handed();
+                self.capture.sent(snapshot);
+                Ok(true)
+            }
+            Err((_, NngError::TryAgain)) => Ok(false),
+            Err((_, e)) => Err(nng_error(&env, &e, format!("Send error: {:?}", e))),
+        }
+    }
+
+    // 尽力发送：从不阻塞，队列满、超过内存上限或发送失败时直接丢弃并计数（见 postDropped）
+    #[napi(ts_args_type = "message: Buffer | Uint8Array | string | ArrayBuffer")]
+    pub fn post(&self, env: Env, message: Payload) -> Result<()> {
+        self.check_send(&env, "post")?;
+        let outbox = self.outbox()?;
+        let mut snapshot = None;
+        let sent = match &self.socket {
+            Some(socket) if !self.memory.over_limit() => {
+                let message = self.message(&message)?;
+                snapshot = self.capture.snapshot(&message);
+                socket.try_send(message).is_ok()
+            }
+            _ => false,
+        };
+        if sent {
+            outbox.record_handed();
+            self.capture.sent(snapshot);
         } else {
-            eprintln!("Socket not connected");
-            Err(napi::Error::new(napi::Status::GenericFailure, "Socket not connected".to_string()))
+            outbox.record_dropped();
         }
+        Ok(())
+    }
+
+    #[napi]
+    pub fn post_dropped(&self) -> i64 {
+        self.outbox.as_ref().map(|outbox| outbox.dropped() as i64).unwrap_or(0)
+    }
+
+    // post 的确认版本，就是 sendAsync：交给 nng 后 resolve，失败时 reject（死信照常）
+    #[napi(ts_args_type = "message: Buffer | Uint8Array | string | ArrayBuffer", ts_return_type = "Promise<void>")]
+    pub fn send_reliable(&self, env: Env, message: Payload) -> Result<JsObject> {
+        self.send_async(env, message)
+    }
+
+    // delayMs 毫秒后放进 sendAsync 的发送队列，交给 nng 后 resolve。
+    // 由 Rust 侧的时间轮计时，精度 10ms；socket 关闭时还没到期的以 SocketClosed reject
+    #[napi(ts_args_type = "message: Buffer | Uint8Array | string | ArrayBuffer, delayMs: number", ts_return_type = "Promise<void>")]
+    pub fn send_after(&mut self, env: Env, message: Payload, delay_ms: u32) -> Result<JsObject> {
+        self.check_send(&env, "sendAfter")?;
+        let message = self.message(&message)?;
+        let outbox = self.outbox()?;
+        let (deferred, promise) = env.create_deferred()?;
+        if let Some(outgoing) = outbox.admit(message, deferred) {
+            if let Some(scheduler) = &mut self.scheduler {
+                scheduler.schedule(outgoing, Duration::from_millis(delay_ms as u64));
+            }
+        }
+        Ok(promise)
     }
 
+    // 等到 sendAsync/trySend 的消息都已交给 nng 并从发送队列写出；timeoutMs 为 0 或不传时一直等待
+    #[napi(ts_return_type = "Promise<void>")]
+    pub fn drain(&self, env: Env, timeout_ms: Option<u32>) -> Result<JsObject> {
+        self.check_send(&env, "drain")?;
+        let timeout = timeout_ms.filter(|ms| *ms > 0).map(|ms| Duration::from_millis(ms as u64));
+        self.outbox()?.drain(env, timeout)
+    }
+
+    // 连接断开、等待重连期间，sendAsync/sendReliable/sendMessage/sendAfter 的消息最多保留 limit 条，
+    // 重新连上后按顺序发出；超出的立即 reject（死信 reason 为 queueFull）。
+    // 只在连上过之后生效，第一次连接之前照常交给 nng。不传或 0 关闭，已保留的消息立即交给 nng
     #[napi]
-    pub fn recv(&self, callback: ThreadsafeFunction<Buffer>) -> Result<()> {
+    pub fn set_disconnected_queue(&self, limit: Option<u32>) -> Result<()> {
+        self.outbox()?.set_disconnected_queue(limit.unwrap_or(0) as usize);
+        Ok(())
+    }
+
+    // 大量 pipe 同时断开（如 broker 重启）时不再逐条调用 onPipeRemoved，断开平息 windowMs 后
+    // 发一个 connectionLost 事件（value 为断开的 pipe 数）；断光之后第一条 pipe 回来时发 connectionRestored。
+    // pauseSenders 默认开启：一条 pipe 都不剩时 sendAsync 等的消息先留着，回来后按顺序发出，
+    // 和 setDisconnectedQueue 一样只在连上过之后生效。不传或 null 关闭；设置跨 open/close 保留
+    #[napi]
+    pub fn set_disconnect_coalescing(&self, options: Option<DisconnectCoalescing>) -> Result<()> {
+        self.storm.configure(options)?;
+        if let Some(outbox) = &self.outbox {
+            outbox.set_pause_senders(self.storm.pauses_senders());
+        }
+        Ok(())
+    }
+
+    // 设置死信出口：sendAsync/sendReliable/sendMessage 因发送失败、超过内存上限或 socket 关闭
+    // 没能送出的消息交给回调，或者转发到另一个已打开的 socket；传 null 取消
+    #[napi(ts_args_type = "sink: ((err: Error | null, letter: DeadLetter) => any) | SocketWrapper | undefined | null")]
+    pub fn set_dead_letter(
+        &self,
+        env: Env,
+        sink: Option<Either<JsFunction, ClassInstance<SocketWrapper>>>,
+    ) -> Result<()> {
+        match sink {
+            None => self.dead_letters.clear(),
+            Some(Either::A(callback)) => {
+                let mut callback = env.create_threadsafe_function(&callback, 0, |ctx: ThreadSafeCallContext<DeadLetter>| {
+                    Ok(vec![ctx.value])
+                })?;
+                // 死信回调本身不让进程保持运行；消息对应的 Promise 还没 settle 时进程不会退出
+                callback.unref(&env)?;
+                self.dead_letters.set_callback(callback);
+            }
+            Some(Either::B(target)) => {
+                if std::ptr::eq(&*target, self) {
+                    return Err(napi::Error::new(
+                        napi::Status::InvalidArg,
+                        "A socket cannot be its own dead-letter sink".to_string(),
+                    ));
+                }
+                if let Some(protocol) = target.protocol.filter(|protocol| !can_send(*protocol)) {
+                    return Err(protocol_misuse(
+                        &env,
+                        format!("The dead-letter socket must be able to send, but {:?} sockets can only receive", protocol),
+                    ));
+                }
+                let socket = target.socket.clone().ok_or_else(|| {
+                    napi::Error::new(napi::Status::GenericFailure, "Dead-letter socket is not open".to_string())
+                })?;
+                self.dead_letters.set_socket(socket);
+            }
+        }
+        Ok(())
+    }
+
+    // 发一个探测帧测量往返时间（毫秒），只支持 Pair 和非 raw 的 Req。
+    // 对端需要在接收（recv），探测帧不会交给双方的回调；Pair 的 pong 也由本端的接收循环处理
+    #[napi(ts_return_type = "Promise<number>")]
+    pub fn ping(&self, env: Env, timeout_ms: u32) -> Result<JsObject> {
+        let socket = self.socket.as_ref().ok_or_else(|| {
+            napi::Error::new(napi::Status::GenericFailure, "Socket not connected".to_string())
+        })?;
+        let protocol = self.protocol.unwrap_or(Protocol::Pair0);
+        let pair = matches!(protocol, Protocol::Pair0 | Protocol::Pair1);
+        let req = protocol == Protocol::Req0 && !self.raw;
+        if !pair && !req {
+            return Err(protocol_misuse(
+                &env,
+                format!("ping is only supported on Pair and non-raw Req sockets, not {:?}", protocol),
+            ));
+        }
+        let outbox = self.outbox()?;
+        let (deferred, promise) = env.create_deferred()?;
+        let timeout = Duration::from_millis(timeout_ms as u64);
+        if pair {
+            self.probes.ping_pair(socket, outbox, deferred, timeout);
+        } else {
+            self.probes.ping_req(socket, outbox, deferred, timeout);
+        }
+        Ok(promise)
+    }
+
+    // 在业务流量之前把连接建好：等每个 dial 过的地址都有一条 pipe（只 listen 的 socket 等到有对端连上），
+    // 非 raw 的 Req 再对每条 pipe 发一次 ping 确认对端在处理请求。timeoutMs 默认 10000
+    #[napi(ts_return_type = "Promise<void>")]
+    pub fn warm_up(&self, env: Env, timeout_ms: Option<u32>) -> Result<JsObject> {
+        let socket = self.socket.as_ref().ok_or_else(|| {
+            napi::Error::new(napi::Status::GenericFailure, "Socket not connected".to_string())
+        })?;
+        let (deferred, promise) = env.create_deferred()?;
+        let warm_up = WarmUp {
+            socket: socket.clone(),
+            dialers: self.dialers.iter().map(|(id, (url, _))| (*id, url.clone())).collect(),
+            pipes: self.pipes.clone(),
+            probes: self.probes.clone(),
+            outbox: self.outbox()?.clone(),
+            verify: self.protocol == Some(Protocol::Req0) && !self.raw,
+            timeout: Duration::from_millis(timeout_ms.unwrap_or(DEFAULT_WARM_UP_MS) as u64),
+        };
+        warm_up.start(self.events.clone(), deferred);
+        Ok(promise)
+    }
+
+    fn resolve_url(&self, url: String) -> String {
+        match &self.namespace {
+            Some(namespace) => transport::namespaced(namespace, &url),
+            None => url,
+        }
+    }
+
+    fn sub_socket(&self, env: &Env, operation: &str) -> Result<&Socket> {
+        let socket = self.socket.as_ref().ok_or_else(|| {
+            napi::Error::new(napi::Status::GenericFailure, "Socket not open".to_string())
+        })?;
+        match self.protocol {
+            Some(protocol) if protocol != Protocol::Sub0 => Err(protocol_misuse(env, format!("{} is only supported on Sub0 sockets, not {:?}", operation, protocol))),
+            _ => Ok(socket),
+        }
+    }
+
+    fn check_send(&self, env: &Env, operation: &str) -> Result<()> {
+        match self.protocol {
+            Some(protocol) if !can_send(protocol) => Err(protocol_misuse(
+                env,
+                format!("{} is not supported on {:?} sockets, which can only receive", operation, protocol),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_recv(&self, env: &Env, operation: &str) -> Result<()> {
+        match self.protocol {
+            Some(protocol) if !can_recv(protocol) => Err(protocol_misuse(
+                env,
+                format!("{} is not supported on {:?} sockets, which can only send", operation, protocol),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    // send 和 tryRequest 共用的检查，准备好后台线程里的一次往返
+    fn request(&self, env: &Env, operation: &str, message: &[u8]) -> Result<(Request, nng::Message)> {
+        self.check_send(env, operation)?;
+        self.check_recv(env, operation)?;
+        let Some(socket) = self.socket.clone() else {
+            self.events.warn("notConnected", "Socket not connected");
+            return Err(napi::Error::new(napi::Status::GenericFailure, "Socket not connected".to_string()));
+        };
+        let msg = self.message(message)?;
+        let request = Request {
+            socket,
+            adaptive: self.adaptive.clone(),
+            outbox: self.outbox.clone(),
+            capture: self.capture.clone(),
+            events: self.events.clone(),
+            raw: self.raw,
+            turn: self.requests.clone(),
+        };
+        Ok((request, msg))
+    }
+
+    // 只有 REQ/REP 的 raw 消息带回溯头，pub/sub 的消息头总是空的
+    fn message(&self, data: &[u8]) -> Result<nng::Message> {
+        if self.raw && matches!(self.protocol, Some(Protocol::Req0 | Protocol::Rep0)) {
+            backtrace::decode(data)
+        } else {
+            Ok(nng::Message::from(data))
+        }
+    }
+
+    fn outbox(&self) -> Result<&Outbox> {
+        self.outbox.as_ref().ok_or_else(|| {
+            napi::Error::new(napi::Status::GenericFailure, "Socket not connected".to_string())
+        })
+    }
+
+    // 启动接收循环；传入 AbortSignal 时 abort 会停止循环。
+    // 返回的 Promise 在循环退出、回调被释放后 resolve
+    #[napi(ts_args_type = "callback: (err: Error | null, arg: Buffer) => any, signal?: AbortSignal | undefined | null", ts_return_type = "Promise<void>")]
+    pub fn recv(&self, env: Env, callback: JsFunction, signal: Option<JsObject>) -> Result<JsObject> {
+        self.check_recv(&env, "recv")?;
+        let hooks = RecvHooks::default();
+        let callback: ThreadsafeFunction<Tracked<Buffer>> = self.recv_callback(&env, callback, 0, &hooks, callback_error::single)?;
+        let memory = self.memory.clone();
+        self.start_recv(env, signal, hooks, move |message, raw| {
+            let buffer = to_buffer(message, raw);
+            let charge = memory.charge(Pool::InFlight, buffer.len());
+            let _ = callback.call(Ok(Tracked::new(buffer, charge)), ThreadsafeFunctionCallMode::NonBlocking);
+        })
+    }
+
+    // 和 recv 一样，但每条消息是独立的 ArrayBuffer，可以零拷贝地 postMessage 给 worker_threads：
+    // worker.postMessage(payload, [payload])
+    #[napi(ts_args_type = "callback: (err: Error | null, arg: ArrayBuffer) => any, signal?: AbortSignal | undefined | null", ts_return_type = "Promise<void>")]
+    pub fn recv_transferable(&self, env: Env, callback: JsFunction, signal: Option<JsObject>) -> Result<JsObject> {
+        self.check_recv(&env, "recvTransferable")?;
+        let hooks = RecvHooks::default();
+        let callback: ThreadsafeFunction<Tracked<TransferableBuffer>> = self.recv_callback(&env, callback, 0, &hooks, callback_error::single)?;
+        let memory = self.memory.clone();
+        self.start_recv(env, signal, hooks, move |message, raw| {
+            let payload = TransferableBuffer(to_bytes(message, raw));
+            let charge = memory.charge(Pool::InFlight, payload.0.len());
+            let _ = callback.call(Ok(Tracked::new(payload, charge)), ThreadsafeFunctionCallMode::NonBlocking);
+        })
+    }
+
+    // 超过 thresholdBytes 的消息拆成 begin/chunk/end 依次交给回调，不再拼成一整个 Buffer；
+    // 较小的消息仍作为一个 "message" 交付。chunkBytes 默认 1MiB。
+    // 回调队列有上限，JS 处理不过来时接收线程会等待，未交付的分块不会无限堆积
+    #[napi(ts_args_type = "callback: (err: Error | null, part: MessagePart) => any, thresholdBytes: number, chunkBytes?: number | undefined | null, signal?: AbortSignal | undefined | null", ts_return_type = "Promise<void>")]
+    pub fn recv_chunked(
+        &self,
+        env: Env,
+        callback: JsFunction,
+        threshold_bytes: u32,
+        chunk_bytes: Option<u32>,
+        signal: Option<JsObject>,
+    ) -> Result<JsObject> {
+        self.check_recv(&env, "recvChunked")?;
+        let chunk_bytes = chunk_bytes.filter(|bytes| *bytes > 0).unwrap_or(DEFAULT_CHUNK_BYTES) as usize;
+        let hooks = RecvHooks::default();
+        let callback: ThreadsafeFunction<Tracked<MessagePart>> = self.recv_callback(&env, callback, CHUNK_QUEUE, &hooks, callback_error::single)?;
+        let memory = self.memory.clone();
+        let send = move |part: MessagePart| {
+            let bytes = part.data.as_ref().map(|data| data.len()).unwrap_or(0);
+            let charge = memory.charge(Pool::InFlight, bytes);
+            let _ = callback.call(Ok(Tracked::new(part, charge)), ThreadsafeFunctionCallMode::Blocking);
+        };
+        self.start_recv(env, signal, hooks, move |message, raw| {
+            let header = if raw { message.as_header().as_slice() } else { &[] };
+            let size = header.len() + message.len();
+            if size <= threshold_bytes as usize {
+                send(MessagePart::new("message").data(to_bytes(message, raw)));
+                return;
+            }
+            send(MessagePart::new("begin").size(size));
+            for chunk in header.chunks(chunk_bytes).chain(message.as_slice().chunks(chunk_bytes)) {
+                send(MessagePart::new("chunk").data(chunk.to_vec()));
+            }
+            send(MessagePart::new("end").size(size));
+        })
+    }
+
+    // 和 recv 一样，但每次回调都拿到同一个 Buffer 和这条消息的字节数，热路径上没有分配。
+    // Buffer 只在下一次回调之前有效，之后会被下一条消息覆盖；要保留内容时用 Buffer.from(buffer.subarray(0, length)) 复制。
+    // initialBytes 是 Buffer 的初始大小（默认 64KiB），更大的消息到来时换成更大的 Buffer
+    #[napi(ts_args_type = "callback: (err: Error | null, buffer: Buffer, length: number) => any, initialBytes?: number | undefined | null, signal?: AbortSignal | undefined | null", ts_return_type = "Promise<void>")]
+    pub fn recv_borrowed(&self, env: Env, callback: JsFunction, initial_bytes: Option<u32>, signal: Option<JsObject>) -> Result<JsObject> {
+        self.check_recv(&env, "recvBorrowed")?;
+        let initial = initial_bytes.filter(|bytes| *bytes > 0).map_or(DEFAULT_SLAB_BYTES, |bytes| bytes as usize);
+        let slab = self.slab.clone();
+        slab.reset();
+        let hooks = RecvHooks::default();
+        let callback: ThreadsafeFunction<Tracked<Vec<u8>>> =
+            self.recv_callback(&env, callback, 0, &hooks, move |env: &Env, value: Tracked<Vec<u8>>| {
+                let data = value.get();
+                let buffer = slab.fill(env, data, initial)?;
+                let length = env.create_uint32(data.len() as u32)?;
+                Ok(vec![buffer.into_unknown(), length.into_unknown()])
+            })?;
+        let memory = self.memory.clone();
+        self.start_recv(env, signal, hooks, move |message, raw| {
+            let data = to_bytes(message, raw);
+            let charge = memory.charge(Pool::InFlight, data.len());
+            let _ = callback.call(Ok(Tracked::new(data, charge)), ThreadsafeFunctionCallMode::NonBlocking);
+        })
+    }
+
+    // 把下一条消息拷进调用方提供的 Buffer，resolve 为消息的字节数，整个过程不分配新的 Buffer。
+    // 消息比 Buffer 长时只拷贝放得下的部分，返回值仍是完整长度，可以据此判断是否被截断。
+    // Promise resolve 之前不要读写这个 Buffer；同一时间只能有一个 recvInto 在等待
+    #[napi(ts_return_type = "Promise<number>")]
+    pub fn recv_into(&self, env: Env, buffer: Buffer) -> Result<JsObject> {
+        self.check_recv(&env, "recvInto")?;
+        let recv_into = self.recv_into.as_ref().ok_or_else(|| {
+            napi::Error::new(napi::Status::GenericFailure, "Socket not connected".to_string())
+        })?;
+        let (deferred, promise) = env.create_deferred()?;
+        recv_into.start(buffer, deferred);
+        Ok(promise)
+    }
+
+    // 收一条消息，resolve 为消息内容。timeoutMs 不传或为 0 时使用 setTimeouts 设置的接收超时，
+    // 超时以 "Receive timeout" reject，socket 关闭时以 SocketClosed reject。
+    // 可以同时有多个 recvOnce 在等待，每条消息只交给其中一个；和 recv 一起用时两边分走同一个接收队列
+    #[napi(ts_return_type = "Promise<Buffer>")]
+    pub fn recv_once(&self, env: Env, timeout_ms: Option<u32>) -> Result<JsObject> {
+        self.check_recv(&env, "recvOnce")?;
+        let socket = self.socket.clone().ok_or_else(|| {
+            napi::Error::new(napi::Status::GenericFailure, "Socket not connected".to_string())
+        })?;
+        let timeout = match timeout_ms.filter(|ms| *ms > 0) {
+            Some(ms) => Some(Duration::from_millis(ms as u64)),
+            None => socket.get_opt::<nng::options::RecvTimeout>().ok().flatten(),
+        };
+        let (deferred, promise) = env.create_deferred::<Buffer, Resolver<Buffer>>()?;
+        let (probes, outbox, capture, raw) = (self.probes.clone(), self.outbox.clone(), self.capture.clone(), self.raw);
+        guard::spawn(self.events.clone(), "Receive once", move || {
+            let deadline = timeout.map(|timeout| Instant::now() + timeout);
+            let received = loop {
+                let message = match recv_within(&socket, deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()))) {
+                    Ok(message) => message,
+                    Err(err) => break Err(err),
+                };
+                if let Some(outbox) = &outbox {
+                    outbox.record_received();
+                }
+                // 探测帧不交给调用方，接着等下一条
+                if !probes.intercept(&socket, outbox.as_ref(), &message) {
+                    break Ok(message);
+                }
+            };
+            match received {
+                Ok(message) => {
+                    capture.received(&message);
+                    let data = to_buffer(&message, raw);
+                    deferred.resolve(Box::new(move |_| Ok(data)));
+                }
+                Err(NngError::Canceled | NngError::Closed) => reject_closed(deferred, "Socket closed"),
+                Err(NngError::TimedOut) => reject_nng(deferred, NngError::TimedOut, "Receive timeout".to_string()),
+                Err(err) => reject_nng(deferred, err, format!("Receive error: {:?}", err)),
+            }
+        });
+        Ok(promise)
+    }
+
+    // 和 recv 一样，但消息头和正文分开交付；非 raw socket 的消息头总是空的
+    #[napi(ts_args_type = "callback: (err: Error | null, arg: Message) => any, signal?: AbortSignal | undefined | null", ts_return_type = "Promise<void>")]
+    pub fn recv_messages(&self, env: Env, callback: JsFunction, signal: Option<JsObject>) -> Result<JsObject> {
+        self.check_recv(&env, "recvMessages")?;
+        let hooks = RecvHooks::default();
+        let callback: ThreadsafeFunction<Tracked<HeaderedMessage>> = self.recv_callback(&env, callback, 0, &hooks, callback_error::single)?;
+        let memory = self.memory.clone();
+        self.start_recv(env, signal, hooks, move |message, _| {
+            let charge = memory.charge(Pool::InFlight, message.as_header().len() + message.len());
+            let message = HeaderedMessage {
+                header: message.as_header().as_slice().to_vec().into(),
+                body: message.as_slice().to_vec().into(),
+                pipe_id: capture::received_from(message),
+            };
+            let _ = callback.call(Ok(Tracked::new(message, charge)), ThreadsafeFunctionCallMode::NonBlocking);
+        })
+    }
+
+    // 和 recvMessages 一样，但每条消息包在 Envelope 里交付，可以 ack/nack。
+    // setAcknowledgements 开启 AtLeastOnce 后，没有 ack 的消息超时后重新交给回调
+    #[napi(ts_args_type = "callback: (err: Error | null, envelope: Envelope) => any, signal?: AbortSignal | undefined | null", ts_return_type = "Promise<void>")]
+    pub fn recv_envelopes(&self, env: Env, callback: JsFunction, signal: Option<JsObject>) -> Result<JsObject> {
+        self.check_recv(&env, "recvEnvelopes")?;
+        let hooks = RecvHooks::default();
+        let failures = hooks.clone();
+        let callback: ThreadsafeFunction<Tracked<Envelope>> =
+            self.recv_callback(&env, callback, 0, &hooks, move |env: &Env, envelope: Tracked<Envelope>| {
+                failures.set_failed(envelope.get().on_failure());
+                callback_error::single(env, envelope)
+            })?;
+        let memory = self.memory.clone();
+        let attachment = self.acks.attach(move |envelope: Envelope| {
+            let charge = memory.charge(Pool::InFlight, envelope.size());
+            let _ = callback.call(Ok(Tracked::new(envelope, charge)), ThreadsafeFunctionCallMode::NonBlocking);
+        });
+        self.start_recv(env, signal, hooks, move |message, _| attachment.deliver(message))
+    }
+
+    // 按 setCallbackErrorPolicy 的策略包装接收回调，Stop 策略通过 hooks 停止之后启动的循环
+    fn recv_callback<T, F>(&self, env: &Env, callback: JsFunction, max_queue_size: usize, hooks: &RecvHooks, args: F) -> Result<ThreadsafeFunction<T>>
+    where
+        T: Send + 'static,
+        F: FnMut(&Env, T) -> Result<Vec<JsUnknown>> + Send + 'static,
+    {
+        let mut callback = callback_error::recv_callback(env, callback, max_queue_size, self.callback_errors, self.events.clone(), hooks.clone(), args)?;
+        // 进程是否保持运行由 keep_alive 决定
+        callback.unref(env)?;
+        Ok(callback)
+    }
+
+    // deliver 在接收线程里处理每条消息，循环退出时随线程一起释放
+    fn start_recv<F>(&self, env: Env, signal: Option<JsObject>, hooks: RecvHooks, deliver: F) -> Result<JsObject>
+    where
+        F: Fn(&nng::Message, bool) + Send + Sync + 'static,
+    {
+        let (hold, promise) = self.keep_alive.hold(&env)?;
         let socket = self.socket.clone(); // Clone socket to move into thread
         let receiving = self.receiving.clone(); // Clone receiving flag
+        let recv_paused = self.recv_paused.clone();
         let is_closing = self.is_closing.clone(); // Clone closing flag
+        let events = self.events.clone();
+        let memory = self.memory.clone();
+        let probes = self.probes.clone();
+        let outbox = self.outbox.clone();
+        let capture = self.capture.clone();
+        let faults = self.faults.clone();
+        let deliver = Arc::new(deliver);
+        let raw = self.raw;
+        let pinning = self.pinning;
+        let watchdog = self.watchdog.clone();
+        // 非 raw 的 Surveyor0 需要跟踪调查的截止时间
+        let mut survey = match (self.protocol, &self.outbox) {
+            (Some(Protocol::Surveyor0), Some(outbox)) if !raw => Some(SurveyTracker::new(outbox.clone(), self.events.clone())),
+            _ => None,
+        };
+
+        // 用 aio 接收，abort 时取消正在等待的接收，不会吞掉消息
+        // 回调拿到的 aio 句柄随结果送回接收线程释放，不在 nng 的回调里释放最后一个句柄
+        let (results, received) = mpsc::channel();
+        let aio = Aio::new(move |aio, result| {
+            if let AioResult::Recv(result) = result {
+                let _ = results.send((aio, result));
+            }
+        })
+        .map_err(|err| napi::Error::new(napi::Status::GenericFailure, format!("Failed to create aio: {:?}", err)))?;
+        if survey.is_some() {
+            // 不用 socket 的接收超时，TimedOut 只表示调查到了截止时间
+            aio.set_timeout(None)
+                .map_err(|err| napi::Error::new(napi::Status::GenericFailure, format!("Failed to set receive timeout: {:?}", err)))?;
+        }
+        let aborted = Arc::new(AtomicBool::new(false));
+        {
+            let aborted = aborted.clone();
+            let aio = aio.clone();
+            hooks.arm_stop(move || {
+                aborted.store(true, Ordering::SeqCst);
+                aio.cancel();
+            });
+        }
+        if let Some(signal) = signal {
+            let aborted = aborted.clone();
+            let aio = aio.clone();
+            on_abort(&env, &signal, move || {
+                aborted.store(true, Ordering::SeqCst);
+                aio.cancel();
+            })?;
+        }
 
         std::thread::spawn(move || {
-            if let Some(socket) = socket {
-                receiving.store(true, Ordering::SeqCst); // 设置接收状态
-                loop {
-                    if !receiving.load(Ordering::SeqCst) { // 检查是否停止接收
-                        break;
-                    }
-                    match socket.recv() {
-                        Ok(message) => {
-                            let buffer = message.as_slice().into();
-                            let _ = callback.call(Ok(buffer), ThreadsafeFunctionCallMode::NonBlocking);
-                        },
-                        Err(NngError::TimedOut) => {
-                            eprintln!("Receive timed out.");
+            // panic 时按正常退出处理：释放回调、resolve Promise
+            guard::contain(&events, "Receive loop", || {
+                pinning.apply(&events);
+                let pulse = watchdog.watch();
+                if let Some(socket) = socket {
+                    receiving.store(true, Ordering::SeqCst); // 设置接收状态
+                    // 注入了延迟的消息由它按到期时间交付，循环退出时剩下的立即交付
+                    let mut delayed: Option<DelayLine<(nng::Message, u32)>> = None;
+                    loop {
+                        // 超过内存上限时先等 JS 处理掉已经交付的消息
+                        while (memory.over_limit() || recv_paused.load(Ordering::SeqCst))
+                            && receiving.load(Ordering::SeqCst)
+                            && !aborted.load(Ordering::SeqCst)
+                        {
+                            if memory.over_limit() {
+                                pulse.enter(Stage::MemoryLimit);
+                            }
+                            std::thread::sleep(MEMORY_POLL);
                         }
-                        Err(e) => {
-                            if is_closing.load(Ordering::SeqCst) {
-                                // 主动关闭时不报错
-                                return;
+                        while memory.over_budget() && receiving.load(Ordering::SeqCst) && !aborted.load(Ordering::SeqCst) {
+                            pulse.enter(Stage::DeliveryBudget);
+                            std::thread::sleep(BUDGET_POLL);
+                        }
+                        pulse.idle();
+                        if !receiving.load(Ordering::SeqCst) || aborted.load(Ordering::SeqCst) { // 检查是否停止接收
+                            break;
+                        }
+                        if let Err(e) = socket.recv_async(&aio) {
+                            // aio 本身出错，接收循环没法继续
+                            if !is_closing.load(Ordering::SeqCst) && e != NngError::Closed {
+                                events.fatal(format!("Receive loop failed: {:?}", e));
                             }
-                            eprintln!("Error receiving message: {:?}", e);
+                            break;
+                        }
+                        match received.recv().map(|(_, result)| result) {
+                            Ok(Ok(message)) => {
+                                if let Some(outbox) = &outbox {
+                                    outbox.record_received();
+                                }
+                                if probes.intercept(&socket, outbox.as_ref(), &message) {
+                                    continue;
+                                }
+                                capture.received(&message);
+                                if let Some(survey) = survey.as_mut() {
+                                    survey.response();
+                                }
+                                pulse.enter(Stage::Deliver);
+                                match faults.decide(false) {
+                                    None => deliver(&message, raw),
+                                    Some(Fate { copies: 0, .. }) => {}
+                                    Some(Fate { copies, delay }) => {
+                                        let line = delayed.get_or_insert_with(|| {
+                                            let deliver = deliver.clone();
+                                            DelayLine::start(events.clone(), move |(message, copies): (nng::Message, u32)| {
+                                                for _ in 0..copies {
+                                                    deliver(&message, raw);
+                                                }
+                                            })
+                                        });
+                                        line.push((message, copies), delay);
+                                    }
+                                }
+                            },
+                            Ok(Err(NngError::TimedOut)) if survey.is_some() => {
+                                if let Some(survey) = survey.as_mut() {
+                                    survey.complete();
+                                }
+                            }
+                            Ok(Err(NngError::TimedOut)) => {
+                                events.warn("recvTimedOut", "Receive timed out.");
+                            }
+                            Ok(Err(NngError::Canceled)) if aborted.load(Ordering::SeqCst) => break,
+                            // 新的调查取消了上一个调查的接收
+                            Ok(Err(NngError::Canceled)) if survey.is_some() => {}
+                            // 当前没有进行中的调查
+                            Ok(Err(NngError::IncorrectState)) if survey.is_some() => {
+                                if let Some(survey) = survey.as_mut() {
+                                    survey.idle();
+                                }
+                            }
+                            Ok(Err(e)) => {
+                                if is_closing.load(Ordering::SeqCst) {
+                                    // 主动关闭时不报错
+                                    break;
+                                }
+                                events.warn("recvFailed", format!("Error receiving message: {:?}", e));
+                            }
+                            Err(_) => break,
                         }
                     }
+                } else {
+                    events.warn("notConnected", "Socket is not connected.");
                 }
-            } else {
-                eprintln!("Socket is not connected.");
-            }
+            });
+            drop(deliver); // 先释放回调，Promise resolve 时 JS 侧可以安全地重新 recv
+            hold.finish();
         });
-        Ok(())
+        Ok(promise)
+    }
+
+    // 接收循环收到消息后超过 stallMs 还没回去等下一条（回调队列堵住、超过内存上限等 JS 处理等）时
+    // 发一个 stalled 事件：code 是卡住的阶段，value 是已经卡了多少毫秒，message 里有线程名、线程 id 和已经完成的轮数。
+    // 不传或 null 关闭；对正在运行的循环立即生效，设置跨 open/close 保留
+    #[napi]
+    pub fn set_recv_watchdog(&self, options: Option<WatchdogOptions>) -> Result<()> {
+        self.watchdog.configure(options)
+    }
+
+    // 和 net.Server 一样，默认有接收循环在运行时进程不会退出；unref 后这个 socket 的接收循环不再让进程保持运行，
+    // 适合脚本里顺带收消息、收完就该退出的场景。对正在运行和之后启动的 recv 系列循环都生效，跨 open/close 保留
+    #[napi]
+    pub fn unref(&self, env: Env) -> Result<()> {
+        self.keep_alive.set(&env, false)
+    }
+
+    #[napi(js_name = "ref")]
+    pub fn keep_ref(&self, env: Env) -> Result<()> {
+        self.keep_alive.set(&env, true)
+    }
+
+    #[napi]
+    pub fn has_ref(&self) -> bool {
+        self.keep_alive.has_ref()
     }
 
     #[napi]
-    pub fn close(&mut self) {
+    pub fn close(&mut self, env: Env) {
+        if let Some(outbox) = self.outbox.take() {
+            outbox.close(); // 断线期间留着的消息不再等重连；发送线程在队列处理完后退出
+        }
+        self.slab.release(&env);
+        self.pipes.clear();
+        self.listeners.clear(); // 随 socket 一起关闭
+        self.pending_listeners.clear();
+        self.pending_dialers.clear();
+        self.dialers.clear();
+        self.adaptive = None;
+        self.recv_into = None; // 等待中的 recvInto 被取消
+        self.acks.clear(); // 还没 ack 的消息不再重新投递
+        self.probes.close();
+        self.scheduler = None; // 还没到期的 sendAfter 被取消
+        self.capture.stop_recording();
+        self.events.crash_dump().detach();
         if let Some(socket) = self.socket.take() {
+            if let Some(registry) = self.registry.take() {
+                registry.untrack(&socket);
+            }
             self.receiving.store(false, Ordering::SeqCst); // 信号接收线程停止
             self.is_closing.store(true, Ordering::SeqCst); // 设置为主动关闭状态
-            let _ = socket.close(); // 关闭 socket
-            if let Some(url) = self.url.take() { // 记录关闭的 URL
-                println!("Socket closed, URL: {}", url);
-            }
+            socket.close(); // 关闭 socket
+            let message = match self.url.take() {
+                Some(url) => format!("Socket closed, URL: {}", url), // 记录关闭的 URL
+                None => "Socket closed".to_string(),
+            };
+            self.events.emit(SocketEvent::new("info").code("closed").message(message));
         } else {
-            println!("Socket was already closed or not connected");
+            self.events.emit(SocketEvent::new("info").code("alreadyClosed").message("Socket was already closed or not connected"));
+        }
+        self.events.clear(); // 关闭的 info 事件发出之后再断开 onEvent
+    }
+
+    #[napi]
+    pub fn on_event(&self, callback: ThreadsafeFunction<SocketEvent>) {
+        self.events.set(callback);
+    }
+
+    // 当前连着的某个 pipe 的信息，已经断开或不存在时为空。
+    // 配合 recvMessages 的 pipeId，Rep0 服务端可以按 ipc 对端的 peerPid/peerUid/peerGid 决定是否处理请求
+    #[napi]
+    pub fn pipe(&self, pipe_id: u32) -> Option<PipeInfo> {
+        self.pipes.pipe(pipe_id)
+    }
+
+    // 连接建立后调用，带对端地址、传输方式和 TLS 校验结果，可以用来维护自己的对端列表或审计日志
+    #[napi]
+    pub fn on_pipe_added(&self, callback: ThreadsafeFunction<PipeInfo>) {
+        self.pipes.set_added(callback);
+    }
+
+    // 连接断开后调用，内容和 onPipeAdded 收到的相同
+    #[napi]
+    pub fn on_pipe_removed(&self, callback: ThreadsafeFunction<PipeInfo>) {
+        self.pipes.set_removed(callback);
+    }
+
+    // 一个回调收到 pipe 的整个生命周期：AddPre、AddPost 和 RemovePost，带 pipe id 和对端地址。
+    // 和 onPipeAdded/onPipeRemoved 互不影响，可以同时设置
+    #[napi]
+    pub fn on_pipe_event(&self, callback: ThreadsafeFunction<PipeEventInfo>) {
+        self.pipes.set_lifecycle(callback);
+    }
+
+    #[napi]
+    pub fn set_labels(&self, labels: HashMap<String, String>) {
+        let mut labels = labels;
+        if let Some(namespace) = &self.namespace {
+            labels.insert("namespace".to_string(), namespace.clone());
+        }
+        self.events.labels().set(labels);
+    }
+
+    // 把 socket 归到一个租户下：之后 dial/listen/createDialer/createListener 的 inproc:// 和 ipc:// 地址
+    // 按 namespacedUrl 的规则改写，不同租户用同样的名字也不会连到一起；事件、统计和日志带上 namespace 标签。
+    // 已经建立的端点不受影响。传 null 取消
+    #[napi]
+    pub fn set_namespace(&mut self, namespace: Option<String>) -> Result<()> {
+        if let Some(namespace) = &namespace {
+            transport::check_namespace(namespace)?;
+        }
+        let mut labels = self.events.labels().get();
+        match &namespace {
+            Some(namespace) => labels.insert("namespace".to_string(), namespace.clone()),
+            None => labels.remove("namespace"),
+        };
+        self.events.labels().set(labels);
+        self.namespace = namespace;
+        Ok(())
+    }
+
+    #[napi]
+    pub fn namespace(&self) -> Option<String> {
+        self.namespace.clone()
+    }
+
+    #[napi]
+    pub fn labels(&self) -> HashMap<String, String> {
+        self.events.labels().get()
+    }
+
+    // 各级队列的当前深度，用来在开始丢消息或超时之前发现饱和
+    #[napi]
+    pub fn stats(&self) -> Result<SocketStats> {
+        let socket = self.socket.as_ref().ok_or_else(|| {
+            napi::Error::new(napi::Status::GenericFailure, "Socket not open".to_string())
+        })?;
+        let depths = self.outbox()?.depths();
+        let send_buffer = socket.get_opt::<nng::options::SendBufferSize>()
+            .map_err(|err| napi::Error::new(napi::Status::GenericFailure, format!("Failed to read send buffer size: {:?}", err)))?;
+        let recv_buffer = socket.get_opt::<nng::options::RecvBufferSize>()
+            .map_err(|err| napi::Error::new(napi::Status::GenericFailure, format!("Failed to read receive buffer size: {:?}", err)))?;
+        Ok(SocketStats {
+            outbox_depth: depths.outbox as i64,
+            send_queue_depth: depths.send_pending.map(|depth| depth as i64),
+            recv_queue_depth: depths.recv_pending.map(|depth| depth as i64),
+            send_buffer: send_buffer as i64,
+            recv_buffer: recv_buffer as i64,
+            labels: self.events.labels().get(),
+            endpoints: self.pipes.endpoints(),
+        })
+    }
+
+    // 暂停所有接收循环的交付，新消息留在 nng 的接收队列里，队列满后对端按协议等待或丢弃；resumeRecv 继续。
+    // 每个循环已经在等的那一条接收不会取消，到达后仍会交付
+    #[napi]
+    pub fn pause_recv(&self) {
+        self.recv_paused.store(true, Ordering::SeqCst);
+    }
+
+    #[napi]
+    pub fn resume_recv(&self) {
+        self.recv_paused.store(false, Ordering::SeqCst);
+    }
+
+    // SocketGroup.drain 用：能发送、已打开的 socket 的发送队列
+    pub(crate) fn send_outbox(&self) -> Option<Outbox> {
+        self.outbox.clone().filter(|_| self.protocol.is_some_and(can_send))
+    }
+
+    pub(crate) fn is_open(&self) -> bool {
+        self.socket.is_some()
+    }
+
+    // sendAsync 队列和已交付、JS 还没处理的消息占用的字节数
+    #[napi]
+    pub fn memory_usage(&self) -> MemoryUsage {
+        self.memory.usage()
+    }
+
+    // 超过上限后 sendAsync 被拒绝、post 丢弃，接收循环暂停直到回落，并上报 overLimit 事件；不传或 0 表示不限制
+    #[napi]
+    pub fn set_memory_limit(&self, limit_bytes: Option<i64>) {
+        self.memory.set_limit(limit_bytes);
+    }
+
+    // 接收循环最多让 messages 条消息同时排在 JS 事件循环里，JS 处理掉之后再交付下一批。
+    // 一个 socket 涌入大量消息时，其他 socket 的回调和定时器不会被排在它后面饿死；不传或 0 表示不限制
+    #[napi]
+    pub fn set_delivery_budget(&self, messages: Option<u32>) {
+        self.memory.set_budget(messages);
+    }
+
+    // 开启抓包：保留最近 limit 条收发的消息（复制一份，不影响发送和接收），供 capture() 事后排查。
+    // 不传或 0 关闭并清空。设置跨 open/close 保留，socket 关闭后仍然可以读取
+    #[napi]
+    pub fn set_capture(&self, limit: Option<u32>) {
+        self.capture.set_limit(limit.unwrap_or(0) as usize);
+    }
+
+    // 接收循环的 aio 失败、后台线程 panic 之类意外的致命错误发生时，往 dir 里写一份文本快照再发 error 事件：
+    // socket 选项、当前的 pipe、各端点的连接历史、队列和内存计数，以及 setCapture 抓到的最近消息。
+    // 写出后发 crashDump 事件，message 是文件路径。不传关闭；设置跨 open/close 保留
+    #[napi]
+    pub fn set_crash_dump(&self, dir: Option<String>) {
+        self.events.crash_dump().set_dir(dir);
+    }
+
+    // 抓到的消息，最旧的在前；没有开启时为空
+    #[napi]
+    pub fn capture(&self) -> Vec<CapturedMessage> {
+        self.capture.messages()
+    }
+
+    // 只用于测试：模拟不可靠的网络，对 sendAsync/sendReliable/sendMessage/sendAfter 交给 nng 之前、
+    // 以及 recv/recvMessages 等接收循环交付之前的消息按概率丢弃、重复、压后，或加上延迟。
+    // 同步 send、trySend、post 和 recvInto 不受影响。不传或 null 关闭；设置跨 open/close 保留
+    #[napi]
+    pub fn set_faults(&self, options: Option<FaultOptions>) -> Result<()> {
+        self.faults.configure(options)
+    }
+
+    // 之后启动的 recv/recvMessages 等接收循环的线程绑到 cpu 核上、按 niceness 调整优先级，
+    // 延迟敏感的 socket 不用和批量流量的接收线程抢 CPU。不传或 null 恢复默认；已经在运行的循环不受影响
+    #[napi]
+    pub fn set_receive_thread(&mut self, options: Option<ThreadPinning>) -> Result<()> {
+        self.pinning = Pinning::parse(options)?;
+        Ok(())
+    }
+
+    // recvEnvelopes 的确认方式：AtMostOnce（默认）交付即完成；AtLeastOnce 下交付的消息要 ack，
+    // timeoutMs 内没有 ack 或 nack(true) 的重新投递，超过 maxRedeliveries 次或 nack(false) 的交给死信出口。
+    // 只在本进程内重新投递。不传或 null 恢复 AtMostOnce，还没 ack 的消息算作已完成；设置跨 open/close 保留
+    #[napi]
+    pub fn set_acknowledgements(&self, options: Option<AckOptions>) -> Result<()> {
+        self.acks.configure(options)
+    }
+
+    // Pull worker 处理失败的消息不丢：recvEnvelopes 的消息被 nack()，或者回调抛出异常、返回的 Promise reject
+    // （CallbackErrorPolicy 不是 Throw）时，带着失败次数推给 target，而不是在本地重新投递。
+    // target 可以是连回分发端的回程 Push，也可以是专门的重试 socket；失败 maxAttempts 次后交给死信出口。
+    // 分发端用 recv/sendAsync 原样转发即可，次数由 worker 的 recvEnvelopes 读出（Envelope.attempts）。传 null 取消
+    #[napi(ts_args_type = "target: SocketWrapper | undefined | null, options?: RequeueOptions | undefined | null")]
+    pub fn set_requeue(&self, env: Env, target: Option<ClassInstance<SocketWrapper>>, options: Option<RequeueOptions>) -> Result<()> {
+        let Some(target) = target else {
+            self.requeue.clear();
+            return Ok(());
+        };
+        if let Some(protocol) = target.protocol.filter(|protocol| !can_send(*protocol)) {
+            return Err(protocol_misuse(
+                &env,
+                format!("The requeue socket must be able to send, but {:?} sockets can only receive", protocol),
+            ));
         }
+        let socket = target.socket.clone().ok_or_else(|| {
+            napi::Error::new(napi::Status::GenericFailure, "Requeue socket is not open".to_string())
+        })?;
+        self.requeue.set(socket, options)
+    }
+
+    // 之后启动的 recv/recvMessages 等接收循环的回调抛出异常时怎么处理，见 CallbackErrorPolicy。
+    // 默认 Throw，异常照旧成为未捕获异常；已经在运行的循环不受影响
+    #[napi]
+    pub fn set_callback_error_policy(&mut self, policy: CallbackErrorPolicy) {
+        self.callback_errors = policy;
+    }
+
+    // 把收发的消息连同时间戳写进 path（覆盖已有文件），直到 stopRecording 或 close；再次调用时换一个文件。
+    // 和 setCapture 一样复制一份，不影响发送和接收，探测帧不记录
+    #[napi]
+    pub fn record(&self, path: String) -> Result<()> {
+        self.capture.record(Recorder::create(&path, self.events.clone())?);
+        Ok(())
+    }
+
+    // 返回前文件已经写完，可以直接拿去 replay
+    #[napi]
+    pub fn stop_recording(&self) {
+        self.capture.stop_recording();
+    }
+
+    // 按录制时的间隔把 path 里的消息从这个 socket 发出去，resolve 为发出的条数。
+    // raw socket 连同录下的消息头一起发出，否则只发消息体
+    #[napi(ts_return_type = "Promise<number>")]
+    pub fn replay(&self, env: Env, path: String, options: Option<ReplayOptions>) -> Result<JsObject> {
+        self.check_send(&env, "replay")?;
+        let (speed, direction) = ReplayOptions::parse(options)?;
+        let socket = self.socket.clone().ok_or_else(|| {
+            napi::Error::new(napi::Status::GenericFailure, "Socket not connected".to_string())
+        })?;
+        let reader = Replay::open(&path)?;
+        let (deferred, promise) = env.create_deferred()?;
+        let replay = Replay {
+            socket,
+            outbox: self.outbox()?.clone(),
+            capture: self.capture.clone(),
+            raw: self.raw,
+            speed,
+            direction,
+        };
+        replay.start(reader, self.events.clone(), deferred);
+        Ok(promise)
+    }
+
+    // 未连接时返回 null
+    #[napi]
+    pub fn capabilities(&self) -> Option<SocketCapabilities> {
+        let protocol = self.protocol.filter(|_| self.socket.is_some())?;
+        Some(SocketCapabilities {
+            can_send: can_send(protocol),
+            can_recv: can_recv(protocol),
+            raw: self.raw,
+            alternating: !self.raw && matches!(protocol, Protocol::Req0 | Protocol::Rep0),
+        })
     }
 
     #[napi]
@@ -161,6 +1685,173 @@ impl SocketWrapper {
     }
 }
 
+// Surveyor0 的每次发送都开始一次新的调查，用 outbox 交给 nng 的消息数区分调查
+struct SurveyTracker {
+    outbox: Outbox,
+    events: EventEmitter,
+    survey: u64,
+    completed: u64, // 最近一次已经上报 surveyComplete 的调查
+    responses: i64,
+}
+
+impl SurveyTracker {
+    fn new(outbox: Outbox, events: EventEmitter) -> Self {
+        let survey = outbox.handed();
+        SurveyTracker { outbox, events, survey, completed: survey, responses: 0 }
+    }
+
+    fn sync(&mut self) {
+        let survey = self.outbox.handed();
+        if survey != self.survey {
+            self.survey = survey;
+            self.responses = 0;
+        }
+    }
+
+    fn response(&mut self) {
+        self.sync();
+        self.responses += 1;
+    }
+
+    // 截止时间到了，value 是本次调查收到的回复数
+    fn complete(&mut self) {
+        self.sync();
+        self.finish();
+    }
+
+    fn finish(&mut self) {
+        if self.completed == self.survey {
+            return;
+        }
+        self.completed = self.survey;
+        self.events.emit(SocketEvent::new("surveyComplete").value(self.responses));
+        self.responses = 0;
+    }
+
+    // 截止时没有挂起的接收时 nng 只会在下一次接收返回 IncorrectState。
+    // 这个接收可能是在新的调查发出之前发起的，只结束已经在跟踪的调查，不把新调查算作没有回复就结束了
+    fn idle(&mut self) {
+        self.finish();
+        std::thread::sleep(SURVEY_IDLE_POLL);
+    }
+}
+
+// Sub0/Pull0 只能接收，Pub0/Push0 只能发送
+fn can_send(protocol: Protocol) -> bool {
+    !matches!(protocol, Protocol::Sub0 | Protocol::Pull0)
+}
+
+// cooked 模式下发送时会清掉或改写消息头的协议
+fn manages_header(protocol: Protocol) -> bool {
+    matches!(
+        protocol,
+        Protocol::Req0 | Protocol::Rep0 | Protocol::Surveyor0 | Protocol::Respondent0 | Protocol::Bus0 | Protocol::Pair1
+    )
+}
+
+fn can_recv(protocol: Protocol) -> bool {
+    !matches!(protocol, Protocol::Pub0 | Protocol::Push0)
+}
+
+// 协议不支持的操作直接抛出 code 为 "ProtocolMisuse" 的错误，而不是等 nng 返回 NotSupported
+fn protocol_misuse(env: &Env, reason: String) -> napi::Error {
+    unsafe { JsError::from(napi::Error::new("ProtocolMisuse", reason)).throw_into(env.raw()) };
+    napi::Error::from_status(napi::Status::PendingException)
+}
+
+// socket 关闭或连接断开时 reject 等待中的 Promise，错误的 code 为 "SocketClosed"，
+// 调用方可以和超时等其他失败区分开。错误对象要在 JS 线程上创建，所以借 resolve 的回调返回
+pub fn reject_closed<T: ToNapiValue + 'static>(deferred: JsDeferred<T, Box<dyn FnOnce(Env) -> Result<T> + Send>>, reason: &str) {
+    reject_coded(deferred, "SocketClosed", reason);
+}
+
+// 和 reject_closed 一样，但 code 由调用方指定，如 "CallTimedOut"
+pub fn reject_coded<T: ToNapiValue + 'static>(deferred: JsDeferred<T, Box<dyn FnOnce(Env) -> Result<T> + Send>>, code: &'static str, reason: &str) {
+    let reason = reason.to_string();
+    deferred.resolve(Box::new(move |env| {
+        let error = unsafe { JsError::from(napi::Error::new(code, reason)).into_value(env.raw()) };
+        Err(napi::Error::from(unsafe { JsUnknown::from_raw_unchecked(env.raw(), error) }))
+    }));
+}
+
+// 带超时地收一条消息，超时只作用于这一次接收，不改 socket 的设置
+fn recv_within(socket: &Socket, timeout: Option<Duration>) -> std::result::Result<nng::Message, NngError> {
+    let (results, received) = mpsc::channel();
+    // 回调拿到的 aio 句柄一起送回来，在这个线程释放：最后一个句柄要是在回调里释放，
+    // nng_aio_stop 会在回调里等回调自己结束，nng 的任务线程就此卡死，之后关闭 socket 也会一直等下去
+    let aio = Aio::new(move |aio, result| {
+        let _ = results.send((aio, result));
+    })?;
+    aio.set_timeout(timeout)?;
+    socket.recv_async(&aio)?;
+    match received.recv() {
+        Ok((_, AioResult::Recv(result))) => result,
+        _ => Err(NngError::Closed),
+    }
+}
+
+// send 在后台线程里的一次往返
+struct Request {
+    socket: Socket,
+    adaptive: Option<AdaptiveTimeout>,
+    outbox: Option<Outbox>,
+    capture: Capture,
+    events: EventEmitter,
+    raw: bool,
+    turn: Arc<Mutex<()>>,
+}
+
+impl Request {
+    fn round_trip(&self, msg: nng::Message) -> std::result::Result<Buffer, NngError> {
+        let snapshot = self.capture.snapshot(&msg);
+        let sent = || {
+            if let Some(outbox) = &self.outbox {
+                outbox.record_handed();
+            }
+            self.capture.sent(snapshot);
+        };
+        let response = match &self.adaptive {
+            Some(adaptive) => {
+                let fallback = self.socket.get_opt::<nng::options::RecvTimeout>().ok().flatten();
+                adaptive.request(&self.socket, msg, fallback, sent)?
+            }
+            None => {
+                // 不用 context 时 socket 同一时间只能有一个请求在等回复
+                let _turn = self.turn.lock().unwrap();
+                self.socket.send(msg).map_err(|(_, e)| {
+                    if e != NngError::Closed {
+                        self.events.warn("sendFailed", format!("Failed to send message: {:?}", e));
+                    }
+                    e
+                })?;
+                sent();
+                self.socket.recv()?
+            }
+        };
+        if let Some(outbox) = &self.outbox {
+            outbox.record_received();
+        }
+        self.capture.received(&response);
+        Ok(to_buffer(&response, self.raw))
+    }
+}
+
+fn to_buffer(message: &nng::Message, raw: bool) -> Buffer {
+    to_bytes(message, raw).into()
+}
+
+fn to_bytes(message: &nng::Message, raw: bool) -> Vec<u8> {
+    if raw {
+        backtrace::encode(message)
+    } else {
+        message.as_slice().to_vec()
+    }
+}
+
+pub fn pipe_id(pipe: nng::Pipe) -> u32 {
+    unsafe { nng::ffi::nng_pipe_id(pipe.nng_pipe()) as u32 }
+}
+
 pub struct NngErrorWrapper(NngError);
 
 impl From<NngErrorWrapper> for napi::Error {
@@ -181,6 +1872,7 @@ pub enum ProtocolType {
     Push0,
     Pull0,
     Bus0,
+    Respondent0, // 回答 Surveyor0 的调查
 }
 
 impl From<ProtocolType> for Protocol {
@@ -196,6 +1888,7 @@ impl From<ProtocolType> for Protocol {
             ProtocolType::Push0 => Protocol::Push0,
             ProtocolType::Pull0 => Protocol::Pull0,
             ProtocolType::Bus0 => Protocol::Bus0,
+            ProtocolType::Respondent0 => Protocol::Respondent0,
         }
     }
 }
\ No newline at end of file
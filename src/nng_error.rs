@@ -0,0 +1,106 @@
+use napi::bindgen_prelude::*;
+use napi::{JsDeferred, JsError, JsObject, NapiValue};
+use napi_derive::napi;
+use nng::Error as NngError;
+
+// nng 返回的错误类别。SocketWrapper 和 Context 上由 nng 调用失败引起的错误带着 nngCode，
+// 可以直接和它比较，不用匹配错误信息；code 不变。以 SocketClosed reject 的错误不带 nngCode
+#[napi]
+pub enum NngErrorCode {
+    Interrupted,
+    OutOfMemory,
+    InvalidInput,
+    Busy,
+    TimedOut,
+    ConnectionRefused,
+    Closed,
+    TryAgain,
+    NotSupported,
+    AddressInUse,
+    IncorrectState,
+    EntryNotFound,
+    Protocol,
+    DestUnreachable,
+    AddressInvalid,
+    PermissionDenied,
+    MessageTooLarge,
+    ConnectionAborted,
+    ConnectionReset,
+    Canceled,
+    OutOfFiles,
+    OutOfSpace,
+    ResourceExists,
+    ReadOnly,
+    WriteOnly,
+    Crypto,
+    PeerAuth,
+    NoArgument,
+    Ambiguous,
+    BadType,
+    ConnectionShutdown,
+    Internal,
+    SystemError,    // 操作系统的错误
+    TransportError, // 传输层特有的错误
+    Unknown,
+}
+
+impl From<&NngError> for NngErrorCode {
+    fn from(err: &NngError) -> Self {
+        match err {
+            NngError::Interrupted => NngErrorCode::Interrupted,
+            NngError::OutOfMemory => NngErrorCode::OutOfMemory,
+            NngError::InvalidInput => NngErrorCode::InvalidInput,
+            NngError::Busy => NngErrorCode::Busy,
+            NngError::TimedOut => NngErrorCode::TimedOut,
+            NngError::ConnectionRefused => NngErrorCode::ConnectionRefused,
+            NngError::Closed => NngErrorCode::Closed,
+            NngError::TryAgain => NngErrorCode::TryAgain,
+            NngError::NotSupported => NngErrorCode::NotSupported,
+            NngError::AddressInUse => NngErrorCode::AddressInUse,
+            NngError::IncorrectState => NngErrorCode::IncorrectState,
+            NngError::EntryNotFound => NngErrorCode::EntryNotFound,
+            NngError::Protocol => NngErrorCode::Protocol,
+            NngError::DestUnreachable => NngErrorCode::DestUnreachable,
+            NngError::AddressInvalid => NngErrorCode::AddressInvalid,
+            NngError::PermissionDenied => NngErrorCode::PermissionDenied,
+            NngError::MessageTooLarge => NngErrorCode::MessageTooLarge,
+            NngError::ConnectionAborted => NngErrorCode::ConnectionAborted,
+            NngError::ConnectionReset => NngErrorCode::ConnectionReset,
+            NngError::Canceled => NngErrorCode::Canceled,
+            NngError::OutOfFiles => NngErrorCode::OutOfFiles,
+            NngError::OutOfSpace => NngErrorCode::OutOfSpace,
+            NngError::ResourceExists => NngErrorCode::ResourceExists,
+            NngError::ReadOnly => NngErrorCode::ReadOnly,
+            NngError::WriteOnly => NngErrorCode::WriteOnly,
+            NngError::Crypto => NngErrorCode::Crypto,
+            NngError::PeerAuth => NngErrorCode::PeerAuth,
+            NngError::NoArgument => NngErrorCode::NoArgument,
+            NngError::Ambiguous => NngErrorCode::Ambiguous,
+            NngError::BadType => NngErrorCode::BadType,
+            NngError::ConnectionShutdown => NngErrorCode::ConnectionShutdown,
+            NngError::Internal => NngErrorCode::Internal,
+            NngError::SystemErr(_) => NngErrorCode::SystemError,
+            NngError::TransportErr(_) => NngErrorCode::TransportError,
+            _ => NngErrorCode::Unknown,
+        }
+    }
+}
+
+type Resolver<T> = Box<dyn FnOnce(Env) -> Result<T> + Send>;
+
+// 带 nngCode 的错误，只能在 JS 线程上创建。同步方法直接返回它
+pub fn nng_error(env: &Env, err: &NngError, reason: String) -> napi::Error {
+    let code = NngErrorCode::from(err) as u32;
+    let error = unsafe { JsError::from(napi::Error::new(napi::Status::GenericFailure, reason)).into_value(env.raw()) };
+    let mut error = unsafe { JsObject::from_raw_unchecked(env.raw(), error) };
+    match env.create_uint32(code).and_then(|code| error.set_named_property("nngCode", code)) {
+        Ok(()) => napi::Error::from(error.into_unknown()),
+        Err(err) => err,
+    }
+}
+
+// 后台线程上的失败和 reject_closed 一样借 resolve 的回调回到 JS 线程创建错误
+pub fn reject_nng<T: ToNapiValue + 'static>(deferred: JsDeferred<T, Resolver<T>>, err: NngError, reason: String) {
+    deferred.resolve(Box::new(move |env| Err(nng_error(&env, &err, reason))));
+}
+
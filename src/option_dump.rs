@@ -0,0 +1,231 @@
+use napi::bindgen_prelude::Either3;
+use napi_derive::napi;
+use nng::ffi;
+use nng::{DialerBuilder, ListenerBuilder, Protocol, RawSocket, Socket};
+use std::collections::HashMap;
+use std::ffi::{c_char, c_int, CStr};
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Bool,
+    Int,
+    Size,
+    Ms, // 毫秒，-1 表示不超时
+    Text,
+}
+
+// 逐个尝试读取的 nng 选项，协议或传输不支持的读不到，不会列出
+const OPTIONS: &[(&[u8], Kind)] = &[
+    (ffi::NNG_OPT_SOCKNAME, Kind::Text),
+    (ffi::NNG_OPT_RAW, Kind::Bool),
+    (ffi::NNG_OPT_PROTONAME, Kind::Text),
+    (ffi::NNG_OPT_PEERNAME, Kind::Text),
+    (ffi::NNG_OPT_URL, Kind::Text),
+    (ffi::NNG_OPT_RECVBUF, Kind::Int),
+    (ffi::NNG_OPT_SENDBUF, Kind::Int),
+    (ffi::NNG_OPT_RECVTIMEO, Kind::Ms),
+    (ffi::NNG_OPT_SENDTIMEO, Kind::Ms),
+    (ffi::NNG_OPT_RECVMAXSZ, Kind::Size),
+    (ffi::NNG_OPT_RECONNMINT, Kind::Ms),
+    (ffi::NNG_OPT_RECONNMAXT, Kind::Ms),
+    (ffi::NNG_OPT_MAXTTL, Kind::Int),
+    (ffi::NNG_OPT_TCP_NODELAY, Kind::Bool),
+    (ffi::NNG_OPT_TCP_KEEPALIVE, Kind::Bool),
+    (ffi::NNG_OPT_TCP_BOUND_PORT, Kind::Int),
+    (ffi::NNG_OPT_IPC_PERMISSIONS, Kind::Int),
+    (ffi::NNG_OPT_TLS_SERVER_NAME, Kind::Text),
+    (ffi::NNG_OPT_WS_PROTOCOL, Kind::Text),
+    (ffi::NNG_OPT_WS_SENDMAXFRAME, Kind::Size),
+    (ffi::NNG_OPT_WS_RECVMAXFRAME, Kind::Size),
+    (ffi::NNG_OPT_PAIR1_POLY, Kind::Bool),
+    (ffi::NNG_OPT_SUB_PREFNEW, Kind::Bool),
+    (ffi::NNG_OPT_REQ_RESENDTIME, Kind::Ms),
+    (ffi::NNG_OPT_SURVEYOR_SURVEYTIME, Kind::Ms),
+];
+
+// 每个 socket/端点各不相同的选项，不给默认值
+const PER_INSTANCE: &[&str] = &["socket-name", "url"];
+
+// 一个选项的当前值；defaultValue 是同协议新建的 socket/端点上读到的值，确定不了时为空
+#[napi(object)]
+pub struct OptionEntry {
+    pub name: String,
+    pub value: Either3<f64, bool, String>,
+    pub default_value: Option<Either3<f64, bool, String>>,
+}
+
+#[napi(object)]
+pub struct EndpointOptionDump {
+    pub kind: String, // "listener" 或 "dialer"
+    pub id: u32,
+    pub url: String,
+    pub started: bool, // createListener/createDialer 创建、还没启动的为 false
+    pub options: Vec<OptionEntry>,
+}
+
+#[napi(object)]
+pub struct OptionDump {
+    pub socket: Vec<OptionEntry>,
+    pub endpoints: Vec<EndpointOptionDump>,
+    pub labels: HashMap<String, String>, // setLabels 设置的标签，由调用方填上
+}
+
+enum Value {
+    Number(f64),
+    Flag(bool),
+    Text(String),
+}
+
+impl Value {
+    fn to_js(&self) -> Either3<f64, bool, String> {
+        match self {
+            Value::Number(number) => Either3::A(*number),
+            Value::Flag(flag) => Either3::B(*flag),
+            Value::Text(text) => Either3::C(text.clone()),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Target {
+    Socket(ffi::nng_socket),
+    Dialer(ffi::nng_dialer),
+    Listener(ffi::nng_listener),
+}
+
+macro_rules! getter {
+    ($method:ident, $socket:ident, $dialer:ident, $listener:ident, $out:ty) => {
+        fn $method(self, name: *const c_char, out: *mut $out) -> c_int {
+            unsafe {
+                match self {
+                    Target::Socket(handle) => ffi::$socket(handle, name, out),
+                    Target::Dialer(handle) => ffi::$dialer(handle, name, out),
+                    Target::Listener(handle) => ffi::$listener(handle, name, out),
+                }
+            }
+        }
+    };
+}
+
+impl Target {
+    getter!(get_bool, nng_socket_get_bool, nng_dialer_get_bool, nng_listener_get_bool, bool);
+    getter!(get_int, nng_socket_get_int, nng_dialer_get_int, nng_listener_get_int, c_int);
+    getter!(get_size, nng_socket_get_size, nng_dialer_get_size, nng_listener_get_size, usize);
+    getter!(get_ms, nng_socket_get_ms, nng_dialer_get_ms, nng_listener_get_ms, ffi::nng_duration);
+    getter!(get_string, nng_socket_get_string, nng_dialer_get_string, nng_listener_get_string, *mut c_char);
+
+    fn read(self, name: &[u8], kind: Kind) -> Option<Value> {
+        let name = name.as_ptr() as *const c_char;
+        match kind {
+            Kind::Bool => {
+                let mut value = false;
+                (self.get_bool(name, &mut value) == 0).then_some(Value::Flag(value))
+            }
+            Kind::Int => {
+                let mut value: c_int = 0;
+                (self.get_int(name, &mut value) == 0).then_some(Value::Number(value as f64))
+            }
+            Kind::Size => {
+                let mut value: usize = 0;
+                (self.get_size(name, &mut value) == 0).then_some(Value::Number(value as f64))
+            }
+            Kind::Ms => {
+                let mut value: ffi::nng_duration = 0;
+                // nng 用负数表示不超时或沿用默认
+                (self.get_ms(name, &mut value) == 0).then_some(Value::Number(value.max(-1) as f64))
+            }
+            Kind::Text => {
+                let mut value: *mut c_char = std::ptr::null_mut();
+                if self.get_string(name, &mut value) != 0 || value.is_null() {
+                    return None;
+                }
+                let text = unsafe { CStr::from_ptr(value) }.to_string_lossy().into_owned();
+                unsafe { ffi::nng_strfree(value) };
+                Some(Value::Text(text))
+            }
+        }
+    }
+
+    fn read_all(self) -> Vec<(&'static str, Value)> {
+        OPTIONS
+            .iter()
+            .filter_map(|(name, kind)| {
+                let value = self.read(name, *kind)?;
+                Some((option_name(name), value))
+            })
+            .collect()
+    }
+}
+
+fn option_name(name: &'static [u8]) -> &'static str {
+    std::str::from_utf8(&name[..name.len() - 1]).unwrap_or_default()
+}
+
+fn entries(current: Vec<(&'static str, Value)>, defaults: Option<Vec<(&'static str, Value)>>) -> Vec<OptionEntry> {
+    current
+        .into_iter()
+        .map(|(name, value)| {
+            let default_value = defaults
+                .as_ref()
+                .filter(|_| !PER_INSTANCE.contains(&name))
+                .and_then(|defaults| defaults.iter().find(|(default_name, _)| *default_name == name))
+                .map(|(_, default)| default.to_js());
+            OptionEntry { name: name.to_string(), value: value.to_js(), default_value }
+        })
+        .collect()
+}
+
+// 要读取的一个端点
+pub struct EndpointRef {
+    target: Target,
+    id: u32,
+    url: String,
+    started: bool,
+}
+
+impl EndpointRef {
+    pub fn listener(id: u32, url: &str, handle: ffi::nng_listener, started: bool) -> Self {
+        EndpointRef { target: Target::Listener(handle), id, url: url.to_string(), started }
+    }
+
+    pub fn dialer(id: u32, url: &str, handle: ffi::nng_dialer, started: bool) -> Self {
+        EndpointRef { target: Target::Dialer(handle), id, url: url.to_string(), started }
+    }
+}
+
+// 默认值从同协议的临时 socket 上读：socket 选项直接读，端点选项在它上面按同样的 URL 创建一个不启动的端点再读
+pub fn dump(socket: &Socket, protocol: Protocol, raw: bool, endpoints: Vec<EndpointRef>) -> OptionDump {
+    let scratch = if raw { RawSocket::new(protocol).map(|raw| raw.socket) } else { Socket::new(protocol) }.ok();
+    let socket_defaults = scratch.as_ref().map(|scratch| Target::Socket(scratch.nng_socket()).read_all());
+    let endpoints = endpoints
+        .into_iter()
+        .map(|endpoint| {
+            let listener = matches!(endpoint.target, Target::Listener(_));
+            let defaults = scratch.as_ref().and_then(|scratch| {
+                if listener {
+                    let builder = ListenerBuilder::new(scratch, &endpoint.url).ok()?;
+                    Some(Target::Listener(builder.nng_listener()).read_all())
+                } else {
+                    let builder = DialerBuilder::new(scratch, &endpoint.url).ok()?;
+                    Some(Target::Dialer(builder.nng_dialer()).read_all())
+                }
+            });
+            EndpointOptionDump {
+                kind: if listener { "listener" } else { "dialer" }.to_string(),
+                id: endpoint.id,
+                url: endpoint.url,
+                started: endpoint.started,
+                options: entries(endpoint.target.read_all(), defaults),
+            }
+        })
+        .collect();
+    let dump = OptionDump {
+        socket: entries(Target::Socket(socket.nng_socket()).read_all(), socket_defaults),
+        endpoints,
+        labels: HashMap::new(),
+    };
+    if let Some(scratch) = scratch {
+        scratch.close();
+    }
+    dump
+}
@@ -0,0 +1,440 @@
+use napi::{bindgen_prelude::*, Env, JsDeferred, JsObject};
+use nng::{Error as NngError, Message, Pipe, Protocol, Socket};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::capture::Capture;
+use crate::dead_letter::DeadLetters;
+use crate::events::EventEmitter;
+use crate::faults::{DelayLine, Fate, Faults};
+use crate::guard;
+use crate::memory::{Charge, MemoryAccount, Pool};
+use crate::nanomsg::{pipe_id, reject_closed};
+use crate::nng_error::reject_nng;
+use crate::slow_consumer::is_inproc;
+use crate::stats::StatsSnapshot;
+
+pub type Resolver = Box<dyn FnOnce(Env) -> Result<()> + Send>;
+
+// drain 轮询的间隔
+const DRAIN_POLL: Duration = Duration::from_millis(10);
+
+pub struct Outgoing {
+    message: Message,
+    deferred: JsDeferred<(), Resolver>,
+    charge: Charge, // 交给 nng 后释放
+}
+
+struct TrackedPipe {
+    attached_at: u64, // pipe 建立时已交给 nng 的消息数
+    tx: u64,          // 最近一次读到的 tx_msgs
+    rx: u64,          // 最近一次读到的 rx_msgs
+}
+
+#[derive(Default)]
+struct FlushState {
+    handed: u64, // 已交给 nng 的消息数
+    pipes: HashMap<u32, TrackedPipe>,
+    retired_tx: u64, // 已断开的 pipe 发出的消息数
+    retired_rx: u64,
+    saw_inproc: bool,
+    connected: usize, // 当前的 pipe 数，包括 inproc
+    was_connected: bool,
+}
+
+// setDisconnectedQueue：连接断开、等待重连期间的消息先留在这里，重新连上后按顺序交给发送线程
+#[derive(Default)]
+struct Holding {
+    limit: usize, // 0 表示不限条数；pause 也没开时不留
+    pause: bool,  // setDisconnectCoalescing 开启的自动暂停，不限条数，只受内存上限约束
+    messages: VecDeque<Outgoing>,
+}
+
+impl Holding {
+    fn holds(&self) -> bool {
+        self.limit > 0 || self.pause
+    }
+}
+
+// 异步发送队列：sendAsync 的消息由单独线程按顺序交给 nng，
+// drain 等到队列清空并且 nng 的 pipe 统计显示消息都已经写出
+//
+// inproc 传输不更新 tx_msgs，连过 inproc 的 socket 只保证已交给 nng。
+// stats() 里的队列深度（消息数）；走过 inproc 时读不到 pipe 统计，nng 队列的两项为 None
+pub struct QueueDepths {
+    pub outbox: u64,
+    pub send_pending: Option<u64>, // 已交给 nng、还没从 pipe 写出；广播协议按最慢的 pipe 算
+    pub recv_pending: Option<u64>, // pipe 已经收到、接收循环还没取走
+}
+
+#[derive(Clone)]
+pub struct Outbox {
+    sender: Sender<Outgoing>,
+    queued: Arc<AtomicU64>,
+    received: Arc<AtomicU64>, // 从 socket 取走的消息数
+    dropped: Arc<AtomicU64>, // post 因队列满丢弃的消息数
+    state: Arc<Mutex<FlushState>>,
+    holding: Arc<Mutex<Holding>>,
+    broadcast: bool, // Pub/Bus/Surveyor 每条消息发给所有 pipe
+    memory: MemoryAccount,
+    events: EventEmitter,
+    dead_letters: DeadLetters, // 没能送出的消息交给这里
+    capture: Capture,
+}
+
+// 发送线程把消息交给 nng 的那一步
+#[derive(Clone)]
+struct HandOff {
+    socket: Socket,
+    queued: Arc<AtomicU64>,
+    state: Arc<Mutex<FlushState>>,
+    events: EventEmitter,
+    dead_letters: DeadLetters,
+    capture: Capture,
+}
+
+impl HandOff {
+    // copies 大于 1 是注入的重复，多出来的几份先发，结果不影响 Promise
+    fn send(&self, outgoing: Outgoing, copies: u32) {
+        let Outgoing { message, deferred, charge } = outgoing;
+        for _ in 1..copies {
+            let mut copy = Message::from(message.as_slice());
+            copy.as_mut_header().push_back(message.as_header().as_slice());
+            if self.socket.send(copy).is_ok() {
+                self.state.lock().unwrap().handed += 1;
+            }
+        }
+        let snapshot = self.capture.snapshot(&message);
+        let result = self.socket.send(message);
+        drop(charge);
+        if result.is_ok() {
+            self.state.lock().unwrap().handed += 1;
+            self.capture.sent(snapshot);
+        }
+        self.queued.fetch_sub(1, Ordering::SeqCst);
+        match result {
+            Ok(()) => deferred.resolve(Box::new(|_| Ok(()))),
+            Err((message, NngError::Closed)) => {
+                self.dead_letters.deliver(&self.events, message, "closed", Some(format!("{:?}", NngError::Closed)));
+                reject_closed(deferred, "Socket closed");
+            }
+            Err((message, e)) => {
+                self.dead_letters.deliver(&self.events, message, "sendFailed", Some(format!("{:?}", e)));
+                reject_nng(deferred, e, format!("Send error: {:?}", e));
+            }
+        }
+    }
+
+    // 注入的丢弃：当作已经发出，不交给 nng
+    fn lose(&self, outgoing: Outgoing) {
+        let Outgoing { deferred, charge, .. } = outgoing;
+        drop(charge);
+        self.queued.fetch_sub(1, Ordering::SeqCst);
+        deferred.resolve(Box::new(|_| Ok(())));
+    }
+}
+
+impl Outbox {
+    pub fn start(
+        socket: Socket,
+        protocol: Protocol,
+        memory: MemoryAccount,
+        events: EventEmitter,
+        dead_letters: DeadLetters,
+        capture: Capture,
+        faults: Faults,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel::<Outgoing>();
+        let outbox = Outbox {
+            sender,
+            queued: Arc::new(AtomicU64::new(0)),
+            received: Arc::new(AtomicU64::new(0)),
+            dropped: Arc::new(AtomicU64::new(0)),
+            state: Arc::new(Mutex::new(FlushState::default())),
+            holding: Arc::default(),
+            broadcast: matches!(protocol, Protocol::Pub0 | Protocol::Bus0 | Protocol::Surveyor0),
+            memory,
+            events,
+            dead_letters,
+            capture,
+        };
+
+        let hand_off = HandOff {
+            socket,
+            queued: outbox.queued.clone(),
+            state: outbox.state.clone(),
+            events: outbox.events.clone(),
+            dead_letters: outbox.dead_letters.clone(),
+            capture: outbox.capture.clone(),
+        };
+        let events = outbox.events.clone();
+        // 所有 Outbox 都释放后 recv 返回错误，线程退出；panic 后队列关闭，之后的 sendAsync 会被拒绝
+        guard::spawn(outbox.events.clone(), "Send queue", move || {
+            // 注入了延迟的消息由它按到期时间交给 nng，线程退出时剩下的立即发出
+            let mut delayed: Option<DelayLine<(Outgoing, u32)>> = None;
+            while let Ok(outgoing) = receiver.recv() {
+                match faults.decide(true) {
+                    None => hand_off.send(outgoing, 1),
+                    Some(Fate { copies: 0, .. }) => hand_off.lose(outgoing),
+                    Some(Fate { copies, delay }) => {
+                        let line = delayed.get_or_insert_with(|| {
+                            let hand_off = hand_off.clone();
+                            DelayLine::start(events.clone(), move |(outgoing, copies)| hand_off.send(outgoing, copies))
+                        });
+                        line.push((outgoing, copies), delay);
+                    }
+                }
+            }
+        });
+        outbox
+    }
+
+    pub fn send(&self, env: Env, message: Message) -> Result<JsObject> {
+        let (deferred, promise) = env.create_deferred::<(), Resolver>()?;
+        if let Some(outgoing) = self.admit(message, deferred) {
+            self.enqueue(outgoing);
+        }
+        Ok(promise)
+    }
+
+    // 按内存上限接收一条待发消息；超过上限时 reject 并交给死信出口
+    pub fn admit(&self, message: Message, deferred: JsDeferred<(), Resolver>) -> Option<Outgoing> {
+        match self.memory.try_charge(Pool::Queued, message.len()) {
+            Some(charge) => Some(Outgoing { message, deferred, charge }),
+            None => {
+                self.dead_letters.deliver(&self.events, message, "memoryLimit", None);
+                deferred.reject(napi::Error::new(napi::Status::GenericFailure, "Memory limit exceeded".to_string()));
+                None
+            }
+        }
+    }
+
+    pub fn enqueue(&self, outgoing: Outgoing) {
+        {
+            let mut holding = self.holding.lock().unwrap();
+            // 已经有消息在等时后来的也排在后面，重连时的顺序不会乱
+            if holding.holds() && (self.is_reconnecting() || !holding.messages.is_empty()) {
+                if holding.limit > 0 && holding.messages.len() >= holding.limit {
+                    drop(holding);
+                    let Outgoing { message, deferred, charge } = outgoing;
+                    drop(charge);
+                    self.dead_letters.deliver(&self.events, message, "queueFull", None);
+                    deferred.reject(napi::Error::new(
+                        napi::Status::GenericFailure,
+                        "Disconnected queue is full".to_string(),
+                    ));
+                    return;
+                }
+                self.queued.fetch_add(1, Ordering::SeqCst);
+                holding.messages.push_back(outgoing);
+                return;
+            }
+        }
+        self.queued.fetch_add(1, Ordering::SeqCst);
+        self.forward(outgoing);
+    }
+
+    // 交给发送线程，queued 已经计入
+    fn forward(&self, outgoing: Outgoing) {
+        if let Err(mpsc::SendError(outgoing)) = self.sender.send(outgoing) {
+            self.queued.fetch_sub(1, Ordering::SeqCst);
+            self.abandon(outgoing);
+        }
+    }
+
+    // 连上过、现在一条 pipe 都没有
+    fn is_reconnecting(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.was_connected && state.connected == 0
+    }
+
+    // 最多保留 limit 条，0 关闭；关闭时已经留着的消息立即交给发送线程
+    pub fn set_disconnected_queue(&self, limit: usize) {
+        let mut holding = self.holding.lock().unwrap();
+        holding.limit = limit;
+        if !holding.holds() || !self.is_reconnecting() {
+            self.flush_held(&mut holding);
+        }
+    }
+
+    pub fn set_pause_senders(&self, pause: bool) {
+        let mut holding = self.holding.lock().unwrap();
+        holding.pause = pause;
+        if !holding.holds() || !self.is_reconnecting() {
+            self.flush_held(&mut holding);
+        }
+    }
+
+    // 当前的 pipe 数，包括 inproc
+    pub fn connected(&self) -> usize {
+        self.state.lock().unwrap().connected
+    }
+
+    fn flush_held(&self, holding: &mut Holding) {
+        for outgoing in holding.messages.drain(..) {
+            self.forward(outgoing);
+        }
+    }
+
+    // socket 关闭时还留着的消息以 SocketClosed reject
+    pub fn close(&self) {
+        let held: Vec<Outgoing> = self.holding.lock().unwrap().messages.drain(..).collect();
+        for outgoing in held {
+            self.queued.fetch_sub(1, Ordering::SeqCst);
+            self.abandon(outgoing);
+        }
+    }
+
+    // socket 已关闭，消息交给死信出口，Promise 以 SocketClosed reject
+    pub fn abandon(&self, outgoing: Outgoing) {
+        let Outgoing { message, deferred, charge } = outgoing;
+        drop(charge);
+        self.dead_letters.deliver(&self.events, message, "closed", None);
+        reject_closed(deferred, "Socket closed");
+    }
+
+    // 绕过队列直接交给 nng 的消息（trySend、同步 send）也要计入
+    pub fn record_handed(&self) {
+        self.state.lock().unwrap().handed += 1;
+    }
+
+    pub fn handed(&self) -> u64 {
+        self.state.lock().unwrap().handed
+    }
+
+    pub fn record_dropped(&self) {
+        self.dropped.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::SeqCst)
+    }
+
+    pub fn record_received(&self) {
+        self.received.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn capture(&self) -> &Capture {
+        &self.capture
+    }
+
+    // 接收端的计数包含 Sub 过滤掉、Req/Surveyor 丢弃的过期消息，这些协议的 recv_pending 偏大
+    pub fn depths(&self) -> QueueDepths {
+        let outbox = self.queued.load(Ordering::SeqCst);
+        let mut state = self.state.lock().unwrap();
+        if state.saw_inproc {
+            return QueueDepths { outbox, send_pending: None, recv_pending: None };
+        }
+        refresh(&mut state);
+        let rx = state.retired_rx + state.pipes.values().map(|tracked| tracked.rx).sum::<u64>();
+        QueueDepths {
+            outbox,
+            send_pending: Some(self.unsent(&state)),
+            recv_pending: Some(rx.saturating_sub(self.received.load(Ordering::SeqCst))),
+        }
+    }
+
+    pub fn pipe_added(&self, pipe: Pipe) {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.connected += 1;
+            state.was_connected = true;
+            if is_inproc(pipe) {
+                state.saw_inproc = true;
+            } else {
+                let attached_at = state.handed;
+                state.pipes.insert(pipe_id(pipe), TrackedPipe { attached_at, tx: 0, rx: 0 });
+            }
+        }
+        self.flush_held(&mut self.holding.lock().unwrap());
+    }
+
+    pub fn pipe_removed(&self, pipe: Pipe) {
+        let mut state = self.state.lock().unwrap();
+        state.connected = state.connected.saturating_sub(1);
+        if let Some(tracked) = state.pipes.remove(&pipe_id(pipe)) {
+            state.retired_tx += tracked.tx;
+            state.retired_rx += tracked.rx;
+        }
+    }
+
+    pub fn drain(&self, env: Env, timeout: Option<Duration>) -> Result<JsObject> {
+        drain_all(env, vec![self.clone()], self.events.clone(), timeout)
+    }
+
+    fn is_flushed(&self) -> bool {
+        if self.queued.load(Ordering::SeqCst) > 0 {
+            return false;
+        }
+        let mut state = self.state.lock().unwrap();
+        if state.saw_inproc {
+            return true;
+        }
+        refresh(&mut state);
+        self.unsent(&state) == 0
+    }
+
+    // 已交给 nng 但还没写出的消息数
+    fn unsent(&self, state: &FlushState) -> u64 {
+        let handed = state.handed;
+        if self.broadcast {
+            // 没有 pipe 时 nng 直接丢弃广播消息
+            state
+                .pipes
+                .values()
+                .map(|tracked| (handed - tracked.attached_at).saturating_sub(tracked.tx))
+                .max()
+                .unwrap_or(0)
+        } else {
+            handed.saturating_sub(state.retired_tx + state.pipes.values().map(|tracked| tracked.tx).sum::<u64>())
+        }
+    }
+}
+
+// 等几个发送队列都清空，SocketGroup.drain 一次等整组
+pub fn drain_all(env: Env, outboxes: Vec<Outbox>, events: EventEmitter, timeout: Option<Duration>) -> Result<JsObject> {
+    let (deferred, promise) = env.create_deferred::<(), Resolver>()?;
+    let started = Instant::now();
+    guard::spawn(events, "Drain", move || loop {
+        if outboxes.iter().all(Outbox::is_flushed) {
+            deferred.resolve(Box::new(|_| Ok(())));
+            return;
+        }
+        if timeout.map(|timeout| started.elapsed() >= timeout).unwrap_or(false) {
+            deferred.reject(napi::Error::new(napi::Status::GenericFailure, "Drain timed out".to_string()));
+            return;
+        }
+        std::thread::sleep(DRAIN_POLL);
+    });
+    Ok(promise)
+}
+
+// drain 的同步版本，给不在 JS 线程上的调用方；超时返回 false
+pub fn wait_flushed(outboxes: &[Outbox], timeout: Duration) -> bool {
+    let started = Instant::now();
+    loop {
+        if outboxes.iter().all(Outbox::is_flushed) {
+            return true;
+        }
+        if started.elapsed() >= timeout {
+            return false;
+        }
+        std::thread::sleep(DRAIN_POLL);
+    }
+}
+
+// 从 nng 统计里更新各 pipe 的收发计数
+fn refresh(state: &mut FlushState) {
+    if let Some(snapshot) = StatsSnapshot::take() {
+        let counters = snapshot.pipe_counters();
+        for (id, tracked) in state.pipes.iter_mut() {
+            if let Some(counters) = counters.get(id) {
+                tracked.tx = counters.tx_msgs;
+                tracked.rx = counters.rx_msgs;
+            }
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use napi::{
+    bindgen_prelude::*,
+    threadsafe_function::{ThreadsafeFunction, ThreadsafeFunctionCallMode},
+};
+use napi_derive::napi;
+use nng::{options::{protocol::pubsub::Subscribe, Options}, Protocol, Socket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::events::EventEmitter;
+use crate::frames::{FrameErrors, MalformedFrames};
+use crate::guard;
+use crate::hashing::fnv1a;
+use crate::payload::Payload;
+use crate::sampling::TelemetrySampling;
+use crate::topic::{self, TopicMessage};
+use crate::topic_metrics::{TopicMetrics, TopicTraffic};
+
+// 按主题哈希选择分区，发布端和订阅端必须用同样的分区数
+#[napi]
+pub fn partition_for(topic: String, partitions: u32) -> Result<u32> {
+    if partitions == 0 {
+        return Err(napi::Error::new(napi::Status::InvalidArg, "partitions must be greater than 0".to_string()));
+    }
+    Ok((fnv1a(topic.as_bytes()) % partitions as u64) as u32)
+}
+
+// 发布端：每个分区一个 Pub socket，各自监听一个地址
+#[napi]
+pub struct PartitionedPublisher {
+    sockets: Vec<Socket>,
+    topic_metrics: TopicMetrics,
+}
+
+#[napi]
+impl PartitionedPublisher {
+    #[napi(constructor)]
+    pub fn new(urls: Vec<String>) -> Result<Self> {
+        if urls.is_empty() {
+            return Err(napi::Error::new(napi::Status::InvalidArg, "At least one partition url is required".to_string()));
+        }
+        let mut sockets = Vec::with_capacity(urls.len());
+        for url in &urls {
+            let socket = Socket::new(Protocol::Pub0).map_err(|err| {
+                napi::Error::new(napi::Status::GenericFailure, format!("Socket creation failed: {:?}", err))
+            })?;
+            socket.listen(url).map_err(|err| {
+                napi::Error::new(napi::Status::GenericFailure, format!("Listen failed on {}: {:?}", url, err))
+            })?;
+            sockets.push(socket);
+        }
+        Ok(PartitionedPublisher {
+            sockets,
+            topic_metrics: TopicMetrics::default(),
+        })
+    }
+
+    #[napi]
+    pub fn partitions(&self) -> u32 {
+        self.sockets.len() as u32
+    }
+
+    // 返回消息被发往的分区
+    #[napi(ts_args_type = "topic: string, message: Buffer | Uint8Array | string | ArrayBuffer")]
+    pub fn publish(&self, topic: String, message: Payload) -> Result<u32> {
+        topic::check_topic(&topic)?;
+        let partition = partition_for(topic.clone(), self.sockets.len() as u32)?;
+        let socket = self.sockets.get(partition as usize).ok_or_else(|| {
+            napi::Error::new(napi::Status::GenericFailure, "Publisher is closed".to_string())
+        })?;
+        let frame = topic::encode(&topic, 0, None, &message);
+        socket.send(&frame[..]).map_err(|(_, e)| {
+            napi::Error::new(napi::Status::GenericFailure, format!("Send error: {:?}", e))
+        })?;
+        self.topic_metrics.record(&topic, message.len());
+        Ok(partition)
+    }
+
+    // 按主题的消息数和字节数
+    #[napi]
+    pub fn topic_stats(&self) -> Vec<TopicTraffic> {
+        self.topic_metrics.snapshot()
+    }
+
+    // 最多跟踪的主题数（默认 1000），超出时淘汰最久没有流量的主题；0 表示关闭
+    #[napi]
+    pub fn set_topic_stats_limit(&self, limit: u32) {
+        self.topic_metrics.set_limit(limit);
+    }
+
+    // 主题统计的采样方式，高吞吐时降低统计开销；不传恢复为每条都记录
+    #[napi]
+    pub fn set_topic_stats_sampling(&self, sampling: Option<TelemetrySampling>) -> Result<()> {
+        self.topic_metrics.set_sampling(sampling)
+    }
+
+    #[napi]
+    pub fn close(&mut self) {
+        for socket in self.sockets.drain(..) {
+            socket.close();
+        }
+    }
+}
+
+// 订阅端聚合器：只连接订阅主题所在的分区，所有分区的消息汇总到一个回调
+#[napi]
+pub struct PartitionedSubscriber {
+    urls: Vec<String>,
+    sockets: Vec<Option<Socket>>, // 按分区下标，未用到的分区不建立连接
+    receiving: Arc<AtomicBool>,
+    is_closing: Arc<AtomicBool>,
+    topic_metrics: TopicMetrics,
+    malformed: FrameErrors,
+}
+
+#[napi]
+impl PartitionedSubscriber {
+    #[napi(constructor)]
+    pub fn new(urls: Vec<String>) -> Result<Self> {
+        if urls.is_empty() {
+            return Err(napi::Error::new(napi::Status::InvalidArg, "At least one partition url is required".to_string()));
+        }
+        let sockets = urls.iter().map(|_| None).collect();
+        Ok(PartitionedSubscriber {
+            urls,
+            sockets,
+            receiving: Arc::new(AtomicBool::new(false)),
+            is_closing: Arc::new(AtomicBool::new(false)),
+            topic_metrics: TopicMetrics::default(),
+            malformed: FrameErrors::default(),
+        })
+    }
+
+    // 返回主题所在的分区；需要在 recv 之前调用
+    #[napi]
+    pub fn subscribe(&mut self, topic: String) -> Result<u32> {
+        topic::check_topic(&topic)?;
+        if self.receiving.load(Ordering::SeqCst) {
+            return Err(napi::Error::new(napi::Status::GenericFailure, "Subscribe before calling recv".to_string()));
+        }
+        let partition = partition_for(topic.clone(), self.urls.len() as u32)?;
+        let index = partition as usize;
+
+        if self.sockets[index].is_none() {
+            let socket = Socket::new(Protocol::Sub0).map_err(|err| {
+                napi::Error::new(napi::Status::GenericFailure, format!("Socket creation failed: {:?}", err))
+            })?;
+            socket.dial_async(&self.urls[index]).map_err(|err| {
+                napi::Error::new(napi::Status::GenericFailure, format!("Connection failed: {:?}", err))
+            })?;
+            self.sockets[index] = Some(socket);
+        }
+
+        if let Some(socket) = &self.sockets[index] {
+            socket.set_opt::<Subscribe>(topic::subscription(&topic)).map_err(|err| {
+                napi::Error::new(napi::Status::GenericFailure, format!("Failed to subscribe: {:?}", err))
+            })?;
+        }
+        Ok(partition)
+    }
+
+    // 按主题的消息数和字节数
+    #[napi]
+    pub fn topic_stats(&self) -> Vec<TopicTraffic> {
+        self.topic_metrics.snapshot()
+    }
+
+    // 最多跟踪的主题数（默认 1000），超出时淘汰最久没有流量的主题；0 表示关闭
+    #[napi]
+    pub fn set_topic_stats_limit(&self, limit: u32) {
+        self.topic_metrics.set_limit(limit);
+    }
+
+    // 主题统计的采样方式，高吞吐时降低统计开销；不传恢复为每条都记录
+    #[napi]
+    pub fn set_topic_stats_sampling(&self, sampling: Option<TelemetrySampling>) -> Result<()> {
+        self.topic_metrics.set_sampling(sampling)
+    }
+
+    // 收到后丢弃的畸形主题帧，所有分区合计
+    #[napi]
+    pub fn malformed_frames(&self) -> MalformedFrames {
+        self.malformed.snapshot()
+    }
+
+    #[napi]
+    pub fn recv(&self, callback: ThreadsafeFunction<TopicMessage>) -> Result<()> {
+        self.receiving.store(true, Ordering::SeqCst);
+        for socket in self.sockets.iter().flatten() {
+            let socket = socket.clone();
+            let callback = callback.clone();
+            let receiving = self.receiving.clone();
+            let is_closing = self.is_closing.clone();
+            let topic_metrics = self.topic_metrics.clone();
+            let malformed = self.malformed.clone();
+            let events = EventEmitter::default();
+
+            guard::spawn(events.clone(), "Receive loop", move || {
+                while receiving.load(Ordering::SeqCst) {
+                    match socket.recv() {
+                        Ok(message) => {
+                            match topic::decode(message.as_slice()) {
+                                Ok(frame) => {
+                                    let message = frame.to_message();
+                                    topic_metrics.record(&message.topic, message.data.len());
+                                    let _ = callback.call(Ok(message), ThreadsafeFunctionCallMode::NonBlocking);
+                                }
+                                Err(err) => malformed.record(err, &events),
+                            }
+                        }
+                        Err(e) => {
+                            if is_closing.load(Ordering::SeqCst) {
+                                return; // 主动关闭时不报错
+                            }
+                            events.warn("recvFailed", format!("Error receiving message: {:?}", e));
+                        }
+                    }
+                }
+            });
+        }
+        Ok(())
+    }
+
+    #[napi]
+    pub fn close(&mut self) {
+        self.receiving.store(false, Ordering::SeqCst);
+        self.is_closing.store(true, Ordering::SeqCst);
+        for socket in self.sockets.iter_mut() {
+            if let Some(socket) = socket.take() {
+                socket.close();
+            }
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use napi::bindgen_prelude::*;
+use napi::sys;
+use std::ops::Deref;
+use std::ptr;
+
+// 发送类接口接受的消息：Buffer | Uint8Array | string | ArrayBuffer。
+// Buffer 和 Uint8Array 直接引用 JS 的内存；字符串按 UTF-8 编码，ArrayBuffer 复制一份
+pub enum Payload {
+    Buffer(Buffer),
+    Bytes(Vec<u8>),
+}
+
+impl Deref for Payload {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Payload::Buffer(buffer) => buffer,
+            Payload::Bytes(bytes) => bytes,
+        }
+    }
+}
+
+fn check(status: sys::napi_status) -> Result<()> {
+    if status == sys::Status::napi_ok {
+        Ok(())
+    } else {
+        Err(Error::new(Status::InvalidArg, "Failed to read payload".to_string()))
+    }
+}
+
+unsafe fn copy(data: *const std::ffi::c_void, len: usize) -> Vec<u8> {
+    if data.is_null() || len == 0 {
+        return Vec::new();
+    }
+    std::slice::from_raw_parts(data as *const u8, len).to_vec()
+}
+
+impl FromNapiValue for Payload {
+    unsafe fn from_napi_value(env: sys::napi_env, value: sys::napi_value) -> Result<Self> {
+        let mut value_type = 0;
+        check(sys::napi_typeof(env, value, &mut value_type))?;
+        if value_type == sys::ValueType::napi_string {
+            return Ok(Payload::Bytes(String::from_napi_value(env, value)?.into_bytes()));
+        }
+
+        // Node 的 napi_is_buffer 对所有 ArrayBufferView 都成立，Uint8Array 也走这里，不用复制
+        let mut is_buffer = false;
+        check(sys::napi_is_buffer(env, value, &mut is_buffer))?;
+        if is_buffer {
+            return Ok(Payload::Buffer(Buffer::from_napi_value(env, value)?));
+        }
+
+        let mut is_arraybuffer = false;
+        check(sys::napi_is_arraybuffer(env, value, &mut is_arraybuffer))?;
+        if is_arraybuffer {
+            let mut len = 0;
+            let mut data = ptr::null_mut();
+            check(sys::napi_get_arraybuffer_info(env, value, &mut data, &mut len))?;
+            return Ok(Payload::Bytes(copy(data, len)));
+        }
+
+        Err(Error::new(
+            Status::InvalidArg,
+            "Expected a Buffer, Uint8Array, string or ArrayBuffer".to_string(),
+        ))
+    }
+}
@@ -0,0 +1,112 @@
+use napi::Result;
+use napi_derive::napi;
+
+use crate::events::EventEmitter;
+
+// 接收循环本来就各占一个线程；延迟敏感的 socket 可以把这个线程绑到单独的 CPU 核、调高优先级，
+// 不和批量流量的接收线程抢同一个核。只在 Linux 上支持
+#[napi(object)]
+pub struct ThreadPinning {
+    pub cpu: Option<u32>,      // 绑定到这个 CPU 核（从 0 开始）
+    pub niceness: Option<i32>, // 线程的 nice 值，-20..=19，越小优先级越高；负值需要 CAP_SYS_NICE
+}
+
+// 启动接收循环时在新线程里应用
+#[derive(Clone, Copy, Default)]
+pub struct Pinning {
+    cpu: Option<u32>,
+    niceness: Option<i32>,
+}
+
+// cpu_set_t 固定 1024 位
+const CPU_SETSIZE: u32 = 1024;
+
+fn invalid(reason: String) -> napi::Error {
+    napi::Error::new(napi::Status::InvalidArg, reason)
+}
+
+impl Pinning {
+    // None 恢复默认，不绑核也不改优先级
+    pub fn parse(options: Option<ThreadPinning>) -> Result<Self> {
+        let Some(options) = options else {
+            return Ok(Pinning::default());
+        };
+        if !cfg!(target_os = "linux") {
+            return Err(napi::Error::new(
+                napi::Status::GenericFailure,
+                "Thread pinning is only supported on Linux".to_string(),
+            ));
+        }
+        if let Some(cpu) = options.cpu {
+            let available = std::thread::available_parallelism().map_or(1, |count| count.get() as u32);
+            if cpu >= available.min(CPU_SETSIZE) {
+                return Err(invalid(format!("cpu must be less than {}, got {}", available.min(CPU_SETSIZE), cpu)));
+            }
+        }
+        if let Some(niceness) = options.niceness {
+            if !(-20..=19).contains(&niceness) {
+                return Err(invalid(format!("niceness must be between -20 and 19, got {}", niceness)));
+            }
+        }
+        Ok(Pinning { cpu: options.cpu, niceness: options.niceness })
+    }
+
+    // 在接收线程里调用，失败（如没有权限调高优先级）只发 warning，接收照常进行
+    pub fn apply(self, events: &EventEmitter) {
+        if let Some(cpu) = self.cpu {
+            if let Err(err) = sys::set_affinity(cpu) {
+                events.warn("pinFailed", format!("Failed to pin receive thread to cpu {}: {}", cpu, err));
+            }
+        }
+        if let Some(niceness) = self.niceness {
+            if let Err(err) = sys::set_niceness(niceness) {
+                events.warn("pinFailed", format!("Failed to set receive thread niceness to {}: {}", niceness, err));
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use std::ffi::c_int;
+    use std::io;
+
+    const PRIO_PROCESS: c_int = 0;
+
+    extern "C" {
+        fn gettid() -> c_int;
+        fn sched_setaffinity(pid: c_int, size: usize, mask: *const u64) -> c_int;
+        fn setpriority(which: c_int, who: u32, prio: c_int) -> c_int;
+    }
+
+    // pid 为 0 表示当前线程
+    pub fn set_affinity(cpu: u32) -> io::Result<()> {
+        let mut mask = [0u64; super::CPU_SETSIZE as usize / 64];
+        mask[cpu as usize / 64] |= 1 << (cpu % 64);
+        match unsafe { sched_setaffinity(0, std::mem::size_of_val(&mask), mask.as_ptr()) } {
+            0 => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
+    // Linux 上 nice 值是按线程的，用线程 id 只改这一个线程
+    pub fn set_niceness(niceness: i32) -> io::Result<()> {
+        match unsafe { setpriority(PRIO_PROCESS, gettid() as u32, niceness) } {
+            0 => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod sys {
+    use std::io;
+
+    pub fn set_affinity(_cpu: u32) -> io::Result<()> {
+        Err(io::Error::from(io::ErrorKind::Unsupported))
+    }
+
+    pub fn set_niceness(_niceness: i32) -> io::Result<()> {
+        Err(io::Error::from(io::ErrorKind::Unsupported))
+    }
+}
@@ -0,0 +1,327 @@
+use napi::threadsafe_function::{ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use napi_derive::napi;
+use nng::options::transport::tls::Verified;
+use nng::options::{LocalAddr, Options, RemAddr, Url};
+use nng::{Pipe, SocketAddr};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::nanomsg::pipe_id;
+
+// 一条连接（pipe）的信息，拿不到的字段为空
+#[napi(object)]
+#[derive(Clone)]
+pub struct PipeInfo {
+    pub pipe_id: u32,
+    pub transport: String, // 端点 URL 的 scheme：tcp、ipc、inproc、tls+tcp、ws 等
+    pub url: Option<String>, // 建立这条连接的 dialer/listener 的 URL
+    pub remote_address: Option<String>,
+    pub local_address: Option<String>,
+    pub dialer_id: Option<u32>,
+    pub listener_id: Option<u32>,
+    // 只有 TLS 连接才有。这是 nng 1.4 在 pipe 上提供的唯一 TLS 信息：协商的协议版本、密码套件、
+    // 对端证书的主题和指纹都留在 TLS 引擎内部，没有对应的选项可读
+    pub tls_verified: Option<bool>,
+    // 只有 ipc 连接才有：对端进程的 pid、uid、gid，由内核在连接时提供，对端无法伪造，
+    // 可以用来对本机进程做授权。nng 在不支持的平台上拿不到时为空
+    pub peer_pid: Option<u32>,
+    pub peer_uid: Option<u32>,
+    pub peer_gid: Option<u32>,
+}
+
+impl PipeInfo {
+    fn read(pipe: Pipe) -> Self {
+        let dialer = pipe.dialer();
+        let listener = pipe.listener();
+        let url = match (dialer, listener) {
+            (Some(dialer), _) => dialer.get_opt::<Url>().ok(),
+            (_, Some(listener)) => listener.get_opt::<Url>().ok(),
+            _ => None,
+        };
+        let transport = url
+            .as_deref()
+            .and_then(|url| url.split("://").next())
+            .unwrap_or("unknown")
+            .to_string();
+        let ipc = transport == "ipc";
+        PipeInfo {
+            pipe_id: pipe_id(pipe),
+            peer_pid: ipc.then(|| peer_credential(pipe, nng::ffi::NNG_OPT_IPC_PEER_PID)).flatten(),
+            peer_uid: ipc.then(|| peer_credential(pipe, nng::ffi::NNG_OPT_IPC_PEER_UID)).flatten(),
+            peer_gid: ipc.then(|| peer_credential(pipe, nng::ffi::NNG_OPT_IPC_PEER_GID)).flatten(),
+            tls_verified: if transport.starts_with("tls") || transport == "wss" {
+                pipe.get_opt::<Verified>().ok()
+            } else {
+                None
+            },
+            transport,
+            url,
+            remote_address: pipe.get_opt::<RemAddr>().ok().map(format_addr),
+            local_address: pipe.get_opt::<LocalAddr>().ok().map(format_addr),
+            dialer_id: dialer.map(|dialer| unsafe { nng::ffi::nng_dialer_id(dialer.nng_dialer()) } as u32),
+            listener_id: listener.map(|listener| unsafe { nng::ffi::nng_listener_id(listener.nng_listener()) } as u32),
+        }
+    }
+}
+
+// nng-rs 只封装了 PeerPid，uid 和 gid 直接调 nng_pipe_get_uint64
+fn peer_credential(pipe: Pipe, option: &[u8]) -> Option<u32> {
+    let mut value = 0u64;
+    let rv = unsafe { nng::ffi::nng_pipe_get_uint64(pipe.nng_pipe(), option.as_ptr() as *const _, &mut value) };
+    (rv == 0).then_some(value as u32)
+}
+
+// nng-rs 直接用了网络字节序的端口号，这里转回来
+fn format_addr(addr: SocketAddr) -> String {
+    match addr {
+        SocketAddr::Inet(mut inet) => {
+            inet.set_port(u16::from_be(inet.port()));
+            format!("tcp://{}", inet)
+        }
+        SocketAddr::Inet6(mut inet) => {
+            inet.set_port(u16::from_be(inet.port()));
+            format!("tcp://{}", inet)
+        }
+        other => other.to_string(),
+    }
+}
+
+// 每个 dialer/listener 的连接历史，用来排查反复断线重连。时间戳是 Unix 毫秒
+#[napi(object)]
+pub struct EndpointStats {
+    pub kind: String, // "dialer" 或 "listener"
+    pub id: u32,
+    pub url: Option<String>,
+    pub connected: u32, // 当前的 pipe 数
+    pub connects: i64,
+    pub disconnects: i64,
+    pub reconnects: i64, // dialer 第一次连上之后的重连次数；listener 总是 0
+    pub last_connected_at: Option<f64>,
+    pub last_disconnected_at: Option<f64>,
+    pub uptime_ms: f64, // 至少有一条 pipe 的累计时长，包括当前这一段
+}
+
+#[derive(Default)]
+struct EndpointHistory {
+    url: Option<String>,
+    connected: u32,
+    connects: i64,
+    disconnects: i64,
+    last_connected_at: Option<f64>,
+    last_disconnected_at: Option<f64>,
+    uptime: Duration,          // 已经结束的在线时段
+    up_since: Option<Instant>, // 当前在线时段的开始
+}
+
+// (是否为 dialer, id)：nng 的 dialer 和 listener 各自编号，id 可能重复
+type EndpointKey = (bool, u32);
+
+pub fn now_ms() -> f64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs_f64() * 1000.0)
+        .unwrap_or(0.0)
+}
+
+fn endpoint_key(info: &PipeInfo) -> Option<EndpointKey> {
+    match (info.dialer_id, info.listener_id) {
+        (Some(id), _) => Some((true, id)),
+        (_, Some(id)) => Some((false, id)),
+        _ => None,
+    }
+}
+
+// 对应 nng 的三个 pipe 通知
+#[napi]
+pub enum PipeEventType {
+    AddPre,     // 连接刚建立，还没交给 socket 收发
+    AddPost,    // 已经加入 socket，可以收发
+    RemovePost, // 已经断开
+}
+
+// onPipeEvent 收到的事件
+#[napi(object)]
+pub struct PipeEventInfo {
+    pub event: PipeEventType,
+    pub pipe: PipeInfo,
+}
+
+type Hook<T = PipeInfo> = Arc<Mutex<Option<ThreadsafeFunction<T>>>>;
+
+// onPipeAdded/onPipeRemoved/onPipeEvent 回调。
+// 连接断开后 nng 已经读不到地址，所以在建立时把信息记下来，断开时原样交给 onPipeRemoved。
+#[derive(Clone, Default)]
+pub struct PipeHooks {
+    added: Hook,
+    removed: Hook,
+    lifecycle: Hook<PipeEventInfo>,
+    known: Arc<Mutex<HashMap<u32, PipeInfo>>>,
+    endpoints: Arc<Mutex<BTreeMap<EndpointKey, EndpointHistory>>>,
+}
+
+impl PipeHooks {
+    pub fn set_added(&self, callback: ThreadsafeFunction<PipeInfo>) {
+        *self.added.lock().unwrap() = Some(callback);
+    }
+
+    pub fn set_removed(&self, callback: ThreadsafeFunction<PipeInfo>) {
+        *self.removed.lock().unwrap() = Some(callback);
+    }
+
+    pub fn set_lifecycle(&self, callback: ThreadsafeFunction<PipeEventInfo>) {
+        *self.lifecycle.lock().unwrap() = Some(callback);
+    }
+
+    // AddPre 时 pipe 还没加入 socket，只通知 onPipeEvent
+    pub fn pipe_adding(&self, pipe: Pipe) {
+        if let Some(callback) = self.lifecycle.lock().unwrap().as_ref() {
+            let event = PipeEventInfo { event: PipeEventType::AddPre, pipe: PipeInfo::read(pipe) };
+            let _ = callback.call(Ok(event), ThreadsafeFunctionCallMode::NonBlocking);
+        }
+    }
+
+    pub fn pipe_added(&self, pipe: Pipe) {
+        let info = PipeInfo::read(pipe);
+        self.known.lock().unwrap().insert(info.pipe_id, info.clone());
+        if let Some(key) = endpoint_key(&info) {
+            let mut endpoints = self.endpoints.lock().unwrap();
+            let history = endpoints.entry(key).or_default();
+            history.url = history.url.take().or_else(|| info.url.clone());
+            history.connected += 1;
+            history.connects += 1;
+            history.last_connected_at = Some(now_ms());
+            history.up_since.get_or_insert_with(Instant::now);
+        }
+        self.notify(PipeEventType::AddPost, &info);
+        if let Some(callback) = self.added.lock().unwrap().as_ref() {
+            let _ = callback.call(Ok(info), ThreadsafeFunctionCallMode::NonBlocking);
+        }
+    }
+
+    // notify 为 false 时只更新记录，不调用 onPipeRemoved 和 onPipeEvent（断开被汇总成 connectionLost）
+    pub fn pipe_removed(&self, pipe: Pipe, notify: bool) {
+        let info = self.known.lock().unwrap().remove(&pipe_id(pipe));
+        if let Some(key) = info.as_ref().and_then(endpoint_key) {
+            let mut endpoints = self.endpoints.lock().unwrap();
+            if let Some(history) = endpoints.get_mut(&key) {
+                history.connected = history.connected.saturating_sub(1);
+                history.disconnects += 1;
+                history.last_disconnected_at = Some(now_ms());
+                if history.connected == 0 {
+                    if let Some(since) = history.up_since.take() {
+                        history.uptime += since.elapsed();
+                    }
+                }
+            }
+        }
+        let Some(info) = info.filter(|_| notify) else {
+            return;
+        };
+        self.notify(PipeEventType::RemovePost, &info);
+        if let Some(callback) = self.removed.lock().unwrap().as_ref() {
+            let _ = callback.call(Ok(info), ThreadsafeFunctionCallMode::NonBlocking);
+        }
+    }
+
+    fn notify(&self, event: PipeEventType, info: &PipeInfo) {
+        if let Some(callback) = self.lifecycle.lock().unwrap().as_ref() {
+            let event = PipeEventInfo { event, pipe: info.clone() };
+            let _ = callback.call(Ok(event), ThreadsafeFunctionCallMode::NonBlocking);
+        }
+    }
+
+    // 当前有活动 pipe 的 dialer，以及 pipe 总数
+    pub fn connected(&self) -> (HashSet<u32>, usize) {
+        let known = self.known.lock().unwrap();
+        let dialers = known.values().filter_map(|info| info.dialer_id).collect();
+        (dialers, known.len())
+    }
+
+    pub fn pipe(&self, pipe_id: u32) -> Option<PipeInfo> {
+        self.known.lock().unwrap().get(&pipe_id).cloned()
+    }
+
+    // 当前连着的 pipe，按 id 排序
+    pub fn pipes(&self) -> Vec<PipeInfo> {
+        let mut pipes: Vec<PipeInfo> = self.known.lock().unwrap().values().cloned().collect();
+        pipes.sort_by_key(|info| info.pipe_id);
+        pipes
+    }
+
+    pub fn endpoints(&self) -> Vec<EndpointStats> {
+        let endpoints = self.endpoints.lock().unwrap();
+        endpoints
+            .iter()
+            .map(|((dialer, id), history)| {
+                let current = history.up_since.map(|since| since.elapsed()).unwrap_or_default();
+                EndpointStats {
+                    kind: if *dialer { "dialer" } else { "listener" }.to_string(),
+                    id: *id,
+                    url: history.url.clone(),
+                    connected: history.connected,
+                    connects: history.connects,
+                    disconnects: history.disconnects,
+                    reconnects: if *dialer { (history.connects - 1).max(0) } else { 0 },
+                    last_connected_at: history.last_connected_at,
+                    last_disconnected_at: history.last_disconnected_at,
+                    uptime_ms: (history.uptime + current).as_secs_f64() * 1000.0,
+                }
+            })
+            .collect()
+    }
+
+    pub fn clear(&self) {
+        self.added.lock().unwrap().take();
+        self.removed.lock().unwrap().take();
+        self.lifecycle.lock().unwrap().take();
+        self.known.lock().unwrap().clear();
+        self.endpoints.lock().unwrap().clear();
+    }
+}
+
+// nng 内部的 pipe 接口，公开 API 里没有按 pipe 发送的入口
+extern "C" {
+    fn nni_pipe_find(pipe: *mut *mut std::ffi::c_void, id: u32) -> std::os::raw::c_int;
+    fn nni_pipe_rele(pipe: *mut std::ffi::c_void);
+    fn nni_pipe_send(pipe: *mut std::ffi::c_void, aio: *mut nng::ffi::nng_aio);
+}
+
+// 绕过协议层直接在一条 pipe 上发一条消息，阻塞到发完或超时。
+// Pub0 只会广播，给单个订阅者补发保留消息和快照时用；和协议层自己的发送在传输层排队，不会交错
+pub fn send_to(pipe: Pipe, data: &[u8], timeout: Duration) -> Result<(), nng::Error> {
+    let to_result = |rv: std::os::raw::c_int| match std::num::NonZeroU32::new(rv as u32) {
+        None => Ok(()),
+        Some(err) => Err(nng::Error::from(err)),
+    };
+    unsafe {
+        let mut raw = std::ptr::null_mut();
+        to_result(nni_pipe_find(&mut raw, pipe_id(pipe)))?;
+        let mut aio = std::ptr::null_mut();
+        let mut msg = std::ptr::null_mut();
+        let mut rv = nng::ffi::nng_aio_alloc(&mut aio, None, std::ptr::null_mut());
+        if rv == 0 {
+            rv = nng::ffi::nng_msg_alloc(&mut msg, 0);
+        }
+        if rv == 0 {
+            rv = nng::ffi::nng_msg_append(msg, data.as_ptr() as *const _, data.len());
+        }
+        if rv == 0 {
+            nng::ffi::nng_aio_set_timeout(aio, timeout.as_millis().min(i32::MAX as u128) as i32);
+            nng::ffi::nng_aio_set_msg(aio, msg);
+            nni_pipe_send(raw, aio);
+            nng::ffi::nng_aio_wait(aio);
+            rv = nng::ffi::nng_aio_result(aio);
+            // 发送成功时消息归传输层，失败时还在 aio 上
+            msg = if rv == 0 { std::ptr::null_mut() } else { nng::ffi::nng_aio_get_msg(aio) };
+        }
+        if !msg.is_null() {
+            nng::ffi::nng_msg_free(msg);
+        }
+        if !aio.is_null() {
+            nng::ffi::nng_aio_free(aio);
+        }
+        nni_pipe_rele(raw);
+        to_result(rv)
+    }
+}
@@ -0,0 +1,155 @@
+use nng::{ffi, Error as NngError};
+use std::ffi::{c_void, CString};
+use std::num::NonZeroU32;
+use std::sync::{Arc, Mutex, Weak};
+
+// nng 1.4 去掉了 Pair1 的 polyamorous 选项，改成单独的 nng_pair1_open_poly，
+// 而 nng crate 没有提供对应的入口，这里直接基于 ffi 封装一个最小的 socket。
+// 只有 polyamorous 模式可以按 pipe 定向发送。
+
+pub enum PolyPipeEvent {
+    Added,
+    Removed,
+}
+
+type NotifyFn = Box<dyn Fn(u32, PolyPipeEvent) + Send + Sync>;
+
+struct Inner {
+    handle: ffi::nng_socket,
+    notify: Mutex<Option<NotifyFn>>,
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        unsafe { ffi::nng_close(self.handle) };
+    }
+}
+
+#[derive(Clone)]
+pub struct PolySocket {
+    inner: Arc<Inner>,
+}
+
+// pipe 回调里需要用到 socket 时持有弱引用，避免回调和 socket 互相引用
+#[derive(Clone)]
+pub struct WeakPolySocket {
+    inner: Weak<Inner>,
+}
+
+impl WeakPolySocket {
+    pub fn upgrade(&self) -> Option<PolySocket> {
+        self.inner.upgrade().map(|inner| PolySocket { inner })
+    }
+}
+
+fn check(rv: i32) -> Result<(), NngError> {
+    match NonZeroU32::new(rv as u32) {
+        None => Ok(()),
+        Some(code) => Err(NngError::from(code)),
+    }
+}
+
+fn c_url(url: &str) -> Result<CString, NngError> {
+    CString::new(url).map_err(|_| NngError::AddressInvalid)
+}
+
+impl PolySocket {
+    pub fn open() -> Result<Self, NngError> {
+        let mut handle = ffi::nng_socket::default();
+        check(unsafe { ffi::nng_pair1_open_poly(&mut handle) })?;
+        Ok(PolySocket {
+            inner: Arc::new(Inner {
+                handle,
+                notify: Mutex::new(None),
+            }),
+        })
+    }
+
+    pub fn listen(&self, url: &str) -> Result<(), NngError> {
+        let url = c_url(url)?;
+        check(unsafe { ffi::nng_listen(self.inner.handle, url.as_ptr(), std::ptr::null_mut(), 0) })
+    }
+
+    pub fn dial(&self, url: &str) -> Result<(), NngError> {
+        let url = c_url(url)?;
+        check(unsafe { ffi::nng_dial(self.inner.handle, url.as_ptr(), std::ptr::null_mut(), 0) })
+    }
+
+    // pipe 为 0 时由 nng 自行选择
+    pub fn send_to(&self, pipe: u32, data: &[u8]) -> Result<(), NngError> {
+        unsafe {
+            let mut msg = std::ptr::null_mut();
+            check(ffi::nng_msg_alloc(&mut msg, 0))?;
+            if let Err(e) = check(ffi::nng_msg_append(msg, data.as_ptr() as *const c_void, data.len())) {
+                ffi::nng_msg_free(msg);
+                return Err(e);
+            }
+            if pipe != 0 {
+                ffi::nng_msg_set_pipe(msg, ffi::nng_pipe { _bindgen_opaque_blob: pipe });
+            }
+            // 发送成功后消息归 nng 所有，失败时需要自己释放
+            if let Err(e) = check(ffi::nng_sendmsg(self.inner.handle, msg, 0)) {
+                ffi::nng_msg_free(msg);
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    // 返回消息内容和来源 pipe id
+    pub fn recv(&self) -> Result<(Vec<u8>, u32), NngError> {
+        unsafe {
+            let mut msg = std::ptr::null_mut();
+            check(ffi::nng_recvmsg(self.inner.handle, &mut msg, 0))?;
+            let len = ffi::nng_msg_len(msg);
+            let body = ffi::nng_msg_body(msg) as *const u8;
+            let data = if len == 0 { Vec::new() } else { std::slice::from_raw_parts(body, len).to_vec() };
+            let pipe = ffi::nng_pipe_id(ffi::nng_msg_get_pipe(msg)) as u32;
+            ffi::nng_msg_free(msg);
+            Ok((data, pipe))
+        }
+    }
+
+    pub fn pipe_notify<F>(&self, callback: F) -> Result<(), NngError>
+    where
+        F: Fn(u32, PolyPipeEvent) + Send + Sync + 'static,
+    {
+        *self.inner.notify.lock().unwrap() = Some(Box::new(callback));
+        let arg = &*self.inner as *const Inner as *mut c_void;
+        for event in [ffi::NNG_PIPE_EV_ADD_POST, ffi::NNG_PIPE_EV_REM_POST] {
+            check(unsafe { ffi::nng_pipe_notify(self.inner.handle, event, Some(Self::trampoline), arg) })?;
+        }
+        Ok(())
+    }
+
+    pub fn handle(&self) -> ffi::nng_socket {
+        self.inner.handle
+    }
+
+    pub fn downgrade(&self) -> WeakPolySocket {
+        WeakPolySocket {
+            inner: Arc::downgrade(&self.inner),
+        }
+    }
+
+    pub fn close_pipe(&self, pipe: u32) {
+        unsafe { ffi::nng_pipe_close(ffi::nng_pipe { _bindgen_opaque_blob: pipe }) };
+    }
+
+    pub fn close(&self) {
+        unsafe { ffi::nng_close(self.inner.handle) };
+    }
+
+    unsafe extern "C" fn trampoline(pipe: ffi::nng_pipe, event: ffi::nng_pipe_ev, arg: *mut c_void) {
+        // Inner 在 socket 关闭前一直存活，关闭后 nng 不会再回调
+        let inner = &*(arg as *const Inner);
+        let event = match event {
+            ffi::NNG_PIPE_EV_ADD_POST => PolyPipeEvent::Added,
+            ffi::NNG_PIPE_EV_REM_POST => PolyPipeEvent::Removed,
+            _ => return,
+        };
+        if let Some(callback) = inner.notify.lock().unwrap().as_ref() {
+            callback(ffi::nng_pipe_id(pipe) as u32, event);
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use napi::{Env, JsDeferred, Result};
+use nng::{Aio, AioResult, Context, Message, Socket};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::events::EventEmitter;
+use crate::guard;
+use crate::nanomsg::reject_closed;
+use crate::outbox::Outbox;
+
+// ping 用的探测帧：MAGIC + kind(1) + id(4, 大端)。接收循环会拦下它们，不会交给用户回调
+const MAGIC: &[u8] = b"\0nng-probe\0";
+const KIND_PING: u8 = 1;
+const KIND_PONG: u8 = 2;
+
+type Resolver = Box<dyn FnOnce(Env) -> Result<f64> + Send>;
+type Waiter = (Instant, JsDeferred<f64, Resolver>);
+
+fn encode(kind: u8, id: u32) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(MAGIC.len() + 5);
+    frame.extend_from_slice(MAGIC);
+    frame.push(kind);
+    frame.extend_from_slice(&id.to_be_bytes());
+    frame
+}
+
+fn decode(data: &[u8]) -> Option<(u8, u32)> {
+    let rest = data.strip_prefix(MAGIC)?;
+    if rest.len() != 5 {
+        return None;
+    }
+    Some((rest[0], u32::from_be_bytes([rest[1], rest[2], rest[3], rest[4]])))
+}
+
+fn elapsed_ms(started: Instant) -> f64 {
+    started.elapsed().as_secs_f64() * 1000.0
+}
+
+fn timed_out() -> napi::Error {
+    napi::Error::new(napi::Status::GenericFailure, "Ping timed out".to_string())
+}
+
+fn ping_failed(err: nng::Error) -> napi::Error {
+    match err {
+        nng::Error::TimedOut => timed_out(),
+        err => napi::Error::new(napi::Status::GenericFailure, format!("Ping failed: {:?}", err)),
+    }
+}
+
+// 等待 pong 的 ping，Pair 的 pong 由接收循环交回来
+#[derive(Clone)]
+pub struct Probes {
+    waiting: Arc<Mutex<HashMap<u32, Waiter>>>,
+    next_id: Arc<AtomicU32>,
+    events: EventEmitter,
+}
+
+impl Probes {
+    pub fn new(events: EventEmitter) -> Self {
+        Probes {
+            waiting: Arc::default(),
+            next_id: Arc::default(),
+            events,
+        }
+    }
+
+    // Pair：直接发出 ping，对端的接收循环回 pong，本端的接收循环收到后 resolve
+    // 探测帧也经过 pipe，要计入 outbox 交给 nng 的消息数，drain 和 stats 才对得上
+    pub fn ping_pair(&self, socket: &Socket, outbox: &Outbox, deferred: JsDeferred<f64, Resolver>, timeout: Duration) {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        self.waiting.lock().unwrap().insert(id, (Instant::now(), deferred));
+        let socket = socket.clone();
+        let outbox = outbox.clone();
+        let waiting = self.waiting.clone();
+        guard::spawn(self.events.clone(), "Ping", move || {
+            if let Err((_, err)) = socket.send(Message::from(&encode(KIND_PING, id)[..])) {
+                if let Some((_, deferred)) = waiting.lock().unwrap().remove(&id) {
+                    deferred.reject(ping_failed(err));
+                }
+                return;
+            }
+            outbox.record_handed();
+            std::thread::sleep(timeout);
+            if let Some((_, deferred)) = waiting.lock().unwrap().remove(&id) {
+                deferred.reject(timed_out());
+            }
+        });
+    }
+
+    // Req：在单独的 context 里收发，不影响 socket 上正在进行的请求
+    pub fn ping_req(&self, socket: &Socket, outbox: &Outbox, deferred: JsDeferred<f64, Resolver>, timeout: Duration) {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let socket = socket.clone();
+        let outbox = outbox.clone();
+        guard::spawn(self.events.clone(), "Ping", move || {
+            let started = Instant::now();
+            match round_trip(&socket, &outbox, id, timeout) {
+                Ok(()) => deferred.resolve(Box::new(move |_| Ok(elapsed_ms(started)))),
+                Err(err) => deferred.reject(ping_failed(err)),
+            }
+        });
+    }
+
+    // socket 关闭时还在等 pong 的 ping 以 SocketClosed reject，不用等到超时
+    pub fn close(&self) {
+        for (_, (_, deferred)) in self.waiting.lock().unwrap().drain() {
+            reject_closed(deferred, "Socket closed");
+        }
+    }
+
+    // 同步的 Req ping，调用方自己管理线程；warmUp 用它并发地确认各条 pipe
+    pub fn ping_req_blocking(&self, socket: &Socket, outbox: &Outbox, timeout: Duration) -> std::result::Result<(), nng::Error> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        round_trip(socket, outbox, id, timeout)
+    }
+
+    // 接收循环调用：探测帧在这里处理掉，返回 true 表示不要交给用户
+    pub fn intercept(&self, socket: &Socket, outbox: Option<&Outbox>, message: &Message) -> bool {
+        let (kind, id) = match decode(message.as_slice()) {
+            Some(probe) => probe,
+            None => return false,
+        };
+        match kind {
+            KIND_PING => {
+                // 带上原消息头，raw REP 也能把 pong 送回去
+                let mut pong = Message::from(&encode(KIND_PONG, id)[..]);
+                pong.as_mut_header().push_back(message.as_header().as_slice());
+                if socket.send(pong).is_ok() {
+                    if let Some(outbox) = outbox {
+                        outbox.record_handed();
+                    }
+                }
+            }
+            KIND_PONG => {
+                if let Some((started, deferred)) = self.waiting.lock().unwrap().remove(&id) {
+                    let rtt = elapsed_ms(started);
+                    deferred.resolve(Box::new(move |_| Ok(rtt)));
+                }
+            }
+            _ => {}
+        }
+        true
+    }
+}
+
+fn round_trip(socket: &Socket, outbox: &Outbox, id: u32, timeout: Duration) -> std::result::Result<(), nng::Error> {
+    let context = Context::new(socket)?;
+    let (results, received) = mpsc::channel();
+    // 回调拿到的 aio 句柄也送回来，保证最后一个句柄在这个线程而不是在 nng 的回调里释放
+    let aio = Aio::new(move |aio, result| {
+        let _ = results.send((aio, result));
+    })?;
+    aio.set_timeout(Some(timeout))?;
+    context.send(&aio, Message::from(&encode(KIND_PING, id)[..])).map_err(|(_, err)| err)?;
+    match received.recv() {
+        Ok((_, AioResult::Send(Ok(())))) => outbox.record_handed(),
+        Ok((_, AioResult::Send(Err((_, err))))) => return Err(err),
+        _ => return Err(nng::Error::Closed),
+    }
+    // context 只会收到这个请求的回复；对端不认识探测帧时回复的是别的内容
+    context.recv(&aio)?;
+    match received.recv() {
+        Ok((_, AioResult::Recv(Ok(reply)))) if decode(reply.as_slice()) == Some((KIND_PONG, id)) => Ok(()),
+        Ok((_, AioResult::Recv(Ok(_)))) => Err(nng::Error::Protocol),
+        Ok((_, AioResult::Recv(Err(err)))) => Err(err),
+        _ => Err(nng::Error::Closed),
+    }
+}
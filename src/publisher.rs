@@ -0,0 +1,510 @@
+use napi::{
+    bindgen_prelude::*,
+    threadsafe_function::{ThreadsafeFunction, ThreadsafeFunctionCallMode},
+    Env, JsFunction, JsUnknown, NapiRaw,
+};
+use napi_derive::napi;
+use nng::{options::{Options, SendBufferSize}, Pipe, PipeEvent, Protocol, Socket};
+use std::collections::{HashMap, VecDeque};
+use std::sync::mpsc::{self, Sender};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::events::{EventEmitter, SocketEvent};
+use crate::guard;
+use crate::interval::{self, Interval};
+use crate::memory::{Charge, MemoryAccount, MemoryUsage, Pool};
+use crate::payload::Payload;
+use crate::pipes;
+use crate::sampling::TelemetrySampling;
+use crate::slow_consumer::{SlowConsumerMonitor, SlowConsumerOptions, SlowConsumerPolicy};
+use crate::tls::{TlsListener, TlsOptions};
+use crate::topic::{self, FLAG_REPLAYED, FLAG_SNAPSHOT};
+use crate::topic_metrics::{TopicMetrics, TopicTraffic};
+
+#[napi(object)]
+pub struct PauseState {
+    pub paused: bool,
+    pub buffered: u32,
+    pub overflowed: i64, // 暂停期间因缓冲区已满被丢弃的消息总数
+}
+
+// 暂停期间缓存的消息，恢复时按顺序发出
+struct PauseBuffer {
+    limit: usize,
+    messages: VecDeque<(String, Vec<u8>, Charge)>,
+}
+
+enum ReplayCommand {
+    PipeAdded(Pipe),
+    Stop,
+}
+
+// 每个主题保留最近 K 条消息，同时受 socket 的内存上限约束
+struct Retained {
+    limit: usize,
+    topics: HashMap<String, VecDeque<(Vec<u8>, Charge)>>,
+    memory: MemoryAccount,
+}
+
+impl Retained {
+    fn push(&mut self, topic: &str, payload: &[u8]) {
+        if self.limit == 0 {
+            return;
+        }
+        let queue = self.topics.entry(topic.to_string()).or_default();
+        if queue.len() >= self.limit {
+            queue.pop_front();
+        }
+        // 超过内存上限时先淘汰该主题更早的消息，仍然放不下才放弃
+        loop {
+            if let Some(charge) = self.memory.try_charge(Pool::Retained, payload.len()) {
+                queue.push_back((payload.to_vec(), charge));
+                return;
+            }
+            if queue.pop_front().is_none() {
+                return;
+            }
+        }
+    }
+
+    fn frames(&self) -> Vec<Vec<u8>> {
+        let mut frames = Vec::new();
+        for (topic, queue) in &self.topics {
+            for (payload, _) in queue {
+                frames.push(topic::encode(topic, FLAG_REPLAYED, None, payload));
+            }
+        }
+        frames
+    }
+}
+
+// 基于主题帧的发布端
+//
+// 新订阅者连上时保留消息和快照只发给这条 pipe，带 replayed 标记。
+// nng 的 Pub0 只会广播，所以这两类消息绕过协议层直接在 pipe 上发送。
+#[napi]
+pub struct Publisher {
+    socket: Option<Socket>,
+    outlet: Outlet,
+    replay: Option<Sender<ReplayCommand>>,
+    tls: Mutex<Vec<TlsListener>>,
+    intervals: Mutex<HashMap<u32, Interval>>, // publishInterval 启动的定时发布
+    next_interval: AtomicU32,
+    snapshots: Snapshots,
+}
+
+// setSnapshotProvider 登记的快照函数，按主题
+type Snapshots = Arc<Mutex<HashMap<String, ThreadsafeFunction<Pipe>>>>;
+
+// 给单个订阅者补发时每条消息最多等多久，超时的订阅者跳过剩下的重放
+const REPLAY_TIMEOUT: Duration = Duration::from_secs(1);
+
+// publish 的发送路径：暂停缓冲、序号、统计和保留消息。
+// 可以复制给 publishInterval 的定时线程，不经过 JS 线程直接发布
+#[derive(Clone)]
+struct Outlet {
+    socket: Socket,
+    sequences: Arc<Mutex<HashMap<String, u64>>>, // 每个主题的发送序号，订阅端据此统计丢包
+    retained: Arc<Mutex<Retained>>,
+    monitor: SlowConsumerMonitor,
+    events: EventEmitter,
+    paused: Arc<Mutex<Option<PauseBuffer>>>,
+    overflowed: Arc<Mutex<i64>>,
+    topic_metrics: TopicMetrics,
+    memory: MemoryAccount,
+}
+
+#[napi]
+impl Publisher {
+    #[napi(constructor)]
+    pub fn new() -> Result<Self> {
+        let socket = Socket::new(Protocol::Pub0).map_err(|err| {
+            napi::Error::new(napi::Status::GenericFailure, format!("Socket creation failed: {:?}", err))
+        })?;
+        let events = EventEmitter::default();
+        let memory = MemoryAccount::new(events.clone());
+        let retained = Arc::new(Mutex::new(Retained {
+            limit: 0,
+            topics: HashMap::new(),
+            memory: memory.clone(),
+        }));
+
+        let monitor = SlowConsumerMonitor::default();
+
+        // 重放在单独线程里做，不在 nng 的 pipe 回调里直接发送
+        let (tx, rx) = mpsc::channel();
+        let notify_tx = Mutex::new(tx.clone());
+        let notify_monitor = monitor.clone();
+        socket
+            .pipe_notify(move |pipe, event| match event {
+                PipeEvent::AddPost => {
+                    notify_monitor.pipe_added(pipe);
+                    let _ = notify_tx.lock().unwrap().send(ReplayCommand::PipeAdded(pipe));
+                }
+                PipeEvent::RemovePost => notify_monitor.pipe_removed(pipe),
+                _ => {}
+            })
+            .map_err(|err| {
+                napi::Error::new(napi::Status::GenericFailure, format!("Failed to watch pipes: {:?}", err))
+            })?;
+
+        let replay_retained = retained.clone();
+        let replay_events = events.clone();
+        let snapshots: Snapshots = Arc::default();
+        let replay_snapshots = snapshots.clone();
+        guard::spawn(events.clone(), "Retained replay", move || {
+            while let Ok(ReplayCommand::PipeAdded(pipe)) = rx.recv() {
+                let frames = replay_retained.lock().unwrap().frames();
+                for frame in frames {
+                    if let Err(e) = pipes::send_to(pipe, &frame, REPLAY_TIMEOUT) {
+                        replay_events.warn("sendFailed", format!("Failed to replay retained message: {:?}", e));
+                        break;
+                    }
+                }
+                // 快照在 JS 线程上生成后发出，排在保留消息后面
+                for provider in replay_snapshots.lock().unwrap().values() {
+                    provider.call(Ok(pipe), ThreadsafeFunctionCallMode::NonBlocking);
+                }
+            }
+        });
+
+        Ok(Publisher {
+            outlet: Outlet {
+                socket: socket.clone(),
+                sequences: Arc::default(),
+                retained,
+                monitor,
+                events,
+                paused: Arc::default(),
+                overflowed: Arc::default(),
+                topic_metrics: TopicMetrics::default(),
+                memory,
+            },
+            socket: Some(socket),
+            replay: Some(tx),
+            tls: Mutex::new(Vec::new()),
+            intervals: Mutex::new(HashMap::new()),
+            next_interval: AtomicU32::new(1),
+            snapshots,
+        })
+    }
+
+    #[napi]
+    pub fn listen(&self, url: String) -> Result<()> {
+        let socket = self.socket()?;
+        socket.listen(&url).map_err(|err| {
+            napi::Error::new(napi::Status::GenericFailure, format!("Listen failed: {:?}", err))
+        })
+    }
+
+    // TLS 监听，证书可以通过 reloadTls 或 watchIntervalMs 在运行中替换，不影响已建立的连接
+    #[napi]
+    pub fn listen_tls(&self, url: String, options: TlsOptions) -> Result<()> {
+        let listener = TlsListener::start(self.socket()?.nng_socket(), &url, options, self.outlet.events.clone())?;
+        self.tls.lock().unwrap().push(listener);
+        Ok(())
+    }
+
+    #[napi]
+    pub fn reload_tls(&self, options: Option<TlsOptions>) -> Result<()> {
+        for listener in self.tls.lock().unwrap().iter() {
+            listener.reload(options.clone())?;
+        }
+        Ok(())
+    }
+
+    #[napi]
+    pub fn dial(&self, url: String) -> Result<()> {
+        let socket = self.socket()?;
+        socket.dial_async(&url).map_err(|err| {
+            napi::Error::new(napi::Status::GenericFailure, format!("Connection failed: {:?}", err))
+        })
+    }
+
+    // 每个主题保留的消息条数，0 表示关闭并清空
+    #[napi]
+    pub fn set_retain(&self, count: u32) {
+        let mut retained = self.outlet.retained.lock().unwrap();
+        retained.limit = count as usize;
+        if count == 0 {
+            retained.topics.clear();
+        } else {
+            for queue in retained.topics.values_mut() {
+                while queue.len() > count as usize {
+                    queue.pop_front();
+                }
+            }
+        }
+    }
+
+    #[napi]
+    pub fn clear_retained(&self, topic: Option<String>) {
+        let mut retained = self.outlet.retained.lock().unwrap();
+        match topic {
+            Some(topic) => {
+                retained.topics.remove(&topic);
+            }
+            None => retained.topics.clear(),
+        }
+    }
+
+    #[napi(ts_args_type = "topic: string, message: Buffer | Uint8Array | string | ArrayBuffer")]
+    pub fn publish(&self, topic: String, message: Payload) -> Result<()> {
+        topic::check_topic(&topic)?;
+        self.socket()?;
+        self.outlet.publish(topic, &message)
+    }
+
+    // 每 intervalMs 毫秒发布一次，由原生定时线程计时，不受事件循环卡顿影响。
+    // producer 是函数时每次在 JS 线程上调用它取消息，返回 undefined 或 null 跳过这一次；
+    // JS 线程忙不过来时错过的调用直接跳过。传入固定消息时定时线程直接发布，不经过 JS 线程。
+    // 返回的 id 交给 clearPublishInterval 停止
+    #[napi(ts_args_type = "topic: string, producer: (() => Buffer | Uint8Array | string | ArrayBuffer | undefined | null) | Buffer | Uint8Array | string | ArrayBuffer, intervalMs: number")]
+    pub fn publish_interval(&self, env: Env, topic: String, producer: JsUnknown, interval_ms: u32) -> Result<u32> {
+        topic::check_topic(&topic)?;
+        self.socket()?;
+        if interval_ms == 0 {
+            return Err(napi::Error::new(napi::Status::InvalidArg, "intervalMs must be greater than 0".to_string()));
+        }
+        let outlet = self.outlet.clone();
+        let events = self.outlet.events.clone();
+        let tick: Box<dyn FnMut() + Send> = if producer.get_type()? == ValueType::Function {
+            let producer = unsafe { producer.cast::<JsFunction>() };
+            let publish = move |_: (), payload: Payload| {
+                if let Err(err) = outlet.publish(topic.clone(), &payload) {
+                    outlet.events.warn("sendFailed", format!("Interval publish failed: {}", err.reason));
+                }
+            };
+            // 回调队列只有一格，JS 线程忙不过来时多出的节拍直接跳过
+            let call = interval::producer(&env, "Interval producer", producer, 1, events.clone(), publish)?;
+            Box::new(move || {
+                call.call(Ok(()), ThreadsafeFunctionCallMode::NonBlocking);
+            })
+        } else {
+            let payload = unsafe { Payload::from_napi_value(env.raw(), producer.raw()) }?.to_vec();
+            Box::new(move || {
+                if let Err(err) = outlet.publish(topic.clone(), &payload) {
+                    outlet.events.warn("sendFailed", format!("Interval publish failed: {}", err.reason));
+                }
+            })
+        };
+        let id = self.next_interval.fetch_add(1, Ordering::SeqCst);
+        let interval = Interval::start(events, Duration::from_millis(interval_ms as u64), tick);
+        self.intervals.lock().unwrap().insert(id, interval);
+        Ok(id)
+    }
+
+    // 停止 publishInterval，id 不存在时返回 false
+    #[napi]
+    pub fn clear_publish_interval(&self, id: u32) -> bool {
+        self.intervals.lock().unwrap().remove(&id).is_some()
+    }
+
+    // 新订阅者连上时在 JS 线程上调用 provider 取 topic 的全量快照，作为 snapshot 消息只发给这个订阅者，之后的 publish 就是增量。
+    // 快照发出前新订阅者可能已经收到几条增量，快照里已经包含它们，订阅端收到快照时整体替换状态即可。
+    // provider 返回 undefined 或 null 时这次不发；传 null 取消
+    #[napi(ts_args_type = "topic: string, provider: (() => Buffer | Uint8Array | string | ArrayBuffer | undefined | null) | null")]
+    pub fn set_snapshot_provider(&self, env: Env, topic: String, provider: Option<JsFunction>) -> Result<()> {
+        topic::check_topic(&topic)?;
+        self.socket()?;
+        let Some(provider) = provider else {
+            self.snapshots.lock().unwrap().remove(&topic);
+            return Ok(());
+        };
+        let events = self.outlet.events.clone();
+        let snapshot_topic = topic.clone();
+        let send = move |pipe: Pipe, payload: Payload| {
+            let frame = topic::encode(&snapshot_topic, FLAG_REPLAYED | FLAG_SNAPSHOT, None, &payload);
+            if let Err(e) = pipes::send_to(pipe, &frame, REPLAY_TIMEOUT) {
+                events.warn("sendFailed", format!("Snapshot publish failed: {:?}", e));
+            }
+        };
+        // 每个新订阅者都要一份快照，队列不限长
+        let call = interval::producer(&env, "Snapshot provider", provider, 0, self.outlet.events.clone(), send)?;
+        self.snapshots.lock().unwrap().insert(topic, call);
+        Ok(())
+    }
+
+    // 暂停发布，期间最多缓存 limit 条消息（默认 1024），超出的丢弃并计数
+    #[napi]
+    pub fn pause(&self, limit: Option<u32>) {
+        let mut paused = self.outlet.paused.lock().unwrap();
+        if paused.is_none() {
+            *self.outlet.overflowed.lock().unwrap() = 0;
+            *paused = Some(PauseBuffer {
+                limit: limit.unwrap_or(1024) as usize,
+                messages: VecDeque::new(),
+            });
+        }
+    }
+
+    // 恢复发布并按顺序发出缓存的消息，返回发出的条数。
+    // 中途发送失败时保持暂停，失败的那条和之后的消息留在缓存里，可以再次 resume
+    #[napi]
+    pub fn resume(&self) -> Result<u32> {
+        let mut paused = self.outlet.paused.lock().unwrap();
+        let Some(buffer) = paused.as_mut() else {
+            return Ok(0);
+        };
+        let mut flushed = 0;
+        while let Some((topic, message, _)) = buffer.messages.front() {
+            self.outlet.send_topic(topic, message).map_err(|err| {
+                let reason = format!("{} after flushing {} messages, {} still buffered", err.reason, flushed, buffer.messages.len());
+                napi::Error::new(err.status, reason)
+            })?;
+            buffer.messages.pop_front();
+            flushed += 1;
+        }
+        paused.take();
+        Ok(flushed)
+    }
+
+    #[napi]
+    pub fn pause_state(&self) -> PauseState {
+        let paused = self.outlet.paused.lock().unwrap();
+        PauseState {
+            paused: paused.is_some(),
+            buffered: paused.as_ref().map(|buffer| buffer.messages.len() as u32).unwrap_or(0),
+            overflowed: *self.outlet.overflowed.lock().unwrap(),
+        }
+    }
+
+    // 按主题的消息数和字节数
+    #[napi]
+    pub fn topic_stats(&self) -> Vec<TopicTraffic> {
+        self.outlet.topic_metrics.snapshot()
+    }
+
+    // 最多跟踪的主题数（默认 1000），超出时淘汰最久没有流量的主题；0 表示关闭
+    #[napi]
+    pub fn set_topic_stats_limit(&self, limit: u32) {
+        self.outlet.topic_metrics.set_limit(limit);
+    }
+
+    // 主题统计的采样方式，高吞吐时降低统计开销；不传恢复为每条都记录
+    #[napi]
+    pub fn set_topic_stats_sampling(&self, sampling: Option<TelemetrySampling>) -> Result<()> {
+        self.outlet.topic_metrics.set_sampling(sampling)
+    }
+
+    // 暂停缓冲和保留消息占用的字节数
+    #[napi]
+    pub fn memory_usage(&self) -> MemoryUsage {
+        self.outlet.memory.usage()
+    }
+
+    // 超过上限后暂停期间的消息按溢出处理、不再保留新消息，并上报 overLimit 事件；不传或 0 表示不限制
+    #[napi]
+    pub fn set_memory_limit(&self, limit_bytes: Option<i64>) {
+        self.outlet.memory.set_limit(limit_bytes);
+    }
+
+    #[napi]
+    pub fn on_event(&self, callback: ThreadsafeFunction<SocketEvent>) {
+        self.outlet.events.set(callback);
+    }
+
+    // 附带到事件和日志里的标签
+    #[napi]
+    pub fn set_labels(&self, labels: HashMap<String, String>) {
+        self.outlet.events.labels().set(labels);
+    }
+
+    #[napi]
+    pub fn labels(&self) -> HashMap<String, String> {
+        self.outlet.events.labels().get()
+    }
+
+    // 订阅者的发送队列持续满时的处理策略，结果通过 onEvent 上报。
+    // SocketBuffer 改的是整个 socket 的发送队列，不是单个订阅者的；inproc 的订阅者不参与检测
+    #[napi]
+    pub fn set_slow_consumer_policy(&self, options: SlowConsumerOptions) -> Result<()> {
+        let socket = self.socket()?;
+        if let SlowConsumerPolicy::SocketBuffer = options.policy {
+            let limit = options.buffer_limit.unwrap_or(1024);
+            socket.set_opt::<SendBufferSize>(limit as i32).map_err(|err| {
+                napi::Error::new(napi::Status::GenericFailure, format!("Failed to set send buffer: {:?}", err))
+            })?;
+        }
+        let queue_len = socket.get_opt::<SendBufferSize>().map_err(|err| {
+            napi::Error::new(napi::Status::GenericFailure, format!("Failed to read send buffer: {:?}", err))
+        })?;
+        self.outlet.monitor.configure(&options, queue_len.max(0) as u64, self.outlet.events.clone());
+        Ok(())
+    }
+
+    #[napi]
+    pub fn close(&mut self) {
+        self.intervals.lock().unwrap().clear();
+        self.snapshots.lock().unwrap().clear();
+        self.outlet.monitor.stop();
+        self.outlet.events.clear();
+        if let Some(replay) = self.replay.take() {
+            let _ = replay.send(ReplayCommand::Stop); // 让重放线程退出并释放 socket
+        }
+        for listener in self.tls.lock().unwrap().drain(..) {
+            listener.close();
+        }
+        if let Some(socket) = self.socket.take() {
+            socket.close();
+        }
+    }
+
+    fn socket(&self) -> Result<&Socket> {
+        self.socket.as_ref().ok_or_else(|| {
+            napi::Error::new(napi::Status::GenericFailure, "Publisher is closed".to_string())
+        })
+    }
+}
+
+impl Outlet {
+    fn publish(&self, topic: String, message: &[u8]) -> Result<()> {
+        {
+            let mut paused = self.paused.lock().unwrap();
+            if let Some(buffer) = paused.as_mut() {
+                let charge = if buffer.messages.len() < buffer.limit {
+                    self.memory.try_charge(Pool::Queued, message.len())
+                } else {
+                    None
+                };
+                if let Some(charge) = charge {
+                    buffer.messages.push_back((topic, message.to_vec(), charge));
+                } else {
+                    let mut overflowed = self.overflowed.lock().unwrap();
+                    *overflowed += 1;
+                    if *overflowed == 1 {
+                        // 每次暂停只在第一次溢出时上报
+                        self.events.emit(SocketEvent::new("pauseOverflow").value(buffer.limit as i64));
+                    }
+                }
+                return Ok(());
+            }
+        }
+        self.send_topic(&topic, message)
+    }
+
+    // 发出去之后才占用序号，发送失败（比如 resume 中途）不会在订阅端留下空洞；
+    // 发送期间一直拿着锁，序号和实际发出的顺序一致
+    fn send_topic(&self, topic: &str, message: &[u8]) -> Result<()> {
+        let mut sequences = self.sequences.lock().unwrap();
+        let sequence = sequences.get(topic).copied().unwrap_or(0) + 1;
+        let frame = topic::encode(topic, 0, Some(sequence), message);
+        self.socket.send(&frame[..]).map_err(|(_, e)| {
+            napi::Error::new(napi::Status::GenericFailure, format!("Send error: {:?}", e))
+        })?;
+        sequences.insert(topic.to_string(), sequence);
+        drop(sequences);
+        self.monitor.record_published(1);
+        self.topic_metrics.record(topic, message.len());
+        self.retained.lock().unwrap().push(topic, message);
+        Ok(())
+    }
+}
+
+impl Drop for Publisher {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
@@ -0,0 +1,200 @@
+use napi::{Env, JsDeferred, Result};
+use napi_derive::napi;
+use nng::{Message, Socket};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::sync::mpsc::{self, Sender};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::capture::{Capture, Entry};
+use crate::events::EventEmitter;
+use crate::guard;
+use crate::nanomsg::reject_closed;
+use crate::outbox::Outbox;
+
+// 录制文件格式：8 字节文件头，之后每条消息为
+// 方向(1，0 发送 1 接收) + 时间(8，Unix 毫秒的 f64 大端) + 消息头长度(4, 大端) + 消息长度(4, 大端) + 消息头 + 消息
+const MAGIC: &[u8; 8] = b"NNGREC\x00\x01";
+const RECORD_HEADER_LEN: usize = 17;
+
+type Resolver = Box<dyn FnOnce(Env) -> Result<u32> + Send>;
+
+fn failed(reason: String) -> napi::Error {
+    napi::Error::new(napi::Status::GenericFailure, reason)
+}
+
+fn encode(entry: &Entry) -> Vec<u8> {
+    let mut record = Vec::with_capacity(RECORD_HEADER_LEN + entry.header.len() + entry.data.len());
+    record.push(if entry.sent { 0 } else { 1 });
+    record.extend_from_slice(&entry.at.to_be_bytes());
+    record.extend_from_slice(&(entry.header.len() as u32).to_be_bytes());
+    record.extend_from_slice(&(entry.data.len() as u32).to_be_bytes());
+    record.extend_from_slice(&entry.header);
+    record.extend_from_slice(&entry.data);
+    record
+}
+
+// 读下一条记录，文件正好结束时返回 None
+fn decode(reader: &mut impl Read) -> std::io::Result<Option<Entry>> {
+    let mut fixed = [0u8; RECORD_HEADER_LEN];
+    match reader.read_exact(&mut fixed[..1]) {
+        Ok(()) => {}
+        Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    }
+    reader.read_exact(&mut fixed[1..])?;
+    let at = f64::from_be_bytes(fixed[1..9].try_into().unwrap());
+    let header_len = u32::from_be_bytes(fixed[9..13].try_into().unwrap()) as usize;
+    let data_len = u32::from_be_bytes(fixed[13..17].try_into().unwrap()) as usize;
+    let mut header = vec![0u8; header_len];
+    reader.read_exact(&mut header)?;
+    let mut data = vec![0u8; data_len];
+    reader.read_exact(&mut data)?;
+    Ok(Some(Entry { sent: fixed[0] == 0, data, header, pipe: None, at }))
+}
+
+// 正在写的录制文件。收发路径只把编码好的记录交给写线程，不在这里做文件 IO
+pub struct Recorder {
+    records: Sender<Vec<u8>>,
+    writer: JoinHandle<()>,
+}
+
+impl Recorder {
+    pub fn create(path: &str, events: EventEmitter) -> Result<Self> {
+        let mut file = File::create(path).map(BufWriter::new).map_err(|err| failed(format!("Failed to create {}: {}", path, err)))?;
+        file.write_all(MAGIC).map_err(|err| failed(format!("Failed to write {}: {}", path, err)))?;
+        let (records, received) = mpsc::channel::<Vec<u8>>();
+        let path = path.to_string();
+        let task_events = events.clone();
+        let writer = std::thread::spawn(move || {
+            guard::contain(&task_events, "Recorder", move || {
+                // 写失败后不再写入，丢掉之后的记录直到停止录制
+                let mut failed = false;
+                for record in received {
+                    if failed {
+                        continue;
+                    }
+                    if let Err(err) = file.write_all(&record) {
+                        events.warn("recordFailed", format!("Failed to write {}: {}", path, err));
+                        failed = true;
+                    }
+                }
+                if let Err(err) = file.flush() {
+                    events.warn("recordFailed", format!("Failed to write {}: {}", path, err));
+                }
+            })
+        });
+        Ok(Recorder { records, writer })
+    }
+
+    pub fn write(&self, entry: &Entry) {
+        let _ = self.records.send(encode(entry));
+    }
+
+    // 等写线程把剩下的记录写完并 flush
+    pub fn finish(self) {
+        drop(self.records);
+        let _ = self.writer.join();
+    }
+}
+
+#[napi(object)]
+pub struct ReplayOptions {
+    pub speed: Option<f64>,        // 倍速，默认 1；Infinity 表示不等待
+    pub direction: Option<String>, // "sent" 或 "received" 时只重放这一类
+}
+
+impl ReplayOptions {
+    // (speed, direction)
+    pub fn parse(options: Option<ReplayOptions>) -> Result<(f64, Option<bool>)> {
+        let (speed, direction) = options.map(|options| (options.speed, options.direction)).unwrap_or_default();
+        let speed = speed.unwrap_or(1.0);
+        if speed.is_nan() || speed <= 0.0 {
+            return Err(napi::Error::new(napi::Status::InvalidArg, "speed must be greater than 0".to_string()));
+        }
+        let direction = match direction.as_deref() {
+            None => None,
+            Some("sent") => Some(true),
+            Some("received") => Some(false),
+            Some(other) => {
+                return Err(napi::Error::new(
+                    napi::Status::InvalidArg,
+                    format!("direction must be \"sent\" or \"received\", got {:?}", other),
+                ))
+            }
+        };
+        Ok((speed, direction))
+    }
+}
+
+// 按录制时的间隔把消息重新发出去，speed 为倍速
+pub struct Replay {
+    pub socket: Socket,
+    pub outbox: Outbox,
+    pub capture: Capture,
+    pub raw: bool,
+    pub speed: f64,
+    pub direction: Option<bool>, // 只重放发送（true）或接收（false）的消息，None 为全部
+}
+
+impl Replay {
+    // 读文件头，打不开或格式不对时直接返回错误
+    pub fn open(path: &str) -> Result<BufReader<File>> {
+        let mut reader = File::open(path).map(BufReader::new).map_err(|err| failed(format!("Failed to open {}: {}", path, err)))?;
+        let mut magic = [0u8; 8];
+        if reader.read_exact(&mut magic).is_err() || &magic != MAGIC {
+            return Err(failed(format!("Not a recording: {}", path)));
+        }
+        Ok(reader)
+    }
+
+    pub fn start(self, reader: BufReader<File>, events: EventEmitter, deferred: JsDeferred<u32, Resolver>) {
+        guard::spawn(events, "Replay", move || {
+            let mut reader = reader;
+            let mut replayed = 0u32;
+            let mut origin: Option<(f64, Instant)> = None; // 第一条消息的录制时间和重放开始的时刻
+            loop {
+                let entry = match decode(&mut reader) {
+                    Ok(Some(entry)) => entry,
+                    Ok(None) => break,
+                    Err(err) => {
+                        deferred.reject(failed(format!("Replay failed after {} messages: {}", replayed, err)));
+                        return;
+                    }
+                };
+                if self.direction.is_some_and(|sent| sent != entry.sent) {
+                    continue;
+                }
+                let (first_at, started) = *origin.get_or_insert((entry.at, Instant::now()));
+                if self.speed.is_finite() {
+                    let offset = ((entry.at - first_at) / self.speed).max(0.0);
+                    let due = started + Duration::from_secs_f64(offset / 1000.0);
+                    std::thread::sleep(due.saturating_duration_since(Instant::now()));
+                }
+
+                let mut message = Message::from(&entry.data[..]);
+                if self.raw {
+                    message.as_mut_header().push_back(&entry.header);
+                }
+                let snapshot = self.capture.snapshot(&message);
+                match self.socket.send(message) {
+                    Ok(()) => {
+                        self.outbox.record_handed();
+                        self.capture.sent(snapshot);
+                        replayed += 1;
+                    }
+                    Err((_, nng::Error::Closed)) => {
+                        reject_closed(deferred, "Socket closed");
+                        return;
+                    }
+                    Err((_, err)) => {
+                        deferred.reject(failed(format!("Replay failed after {} messages: Send error: {:?}", replayed, err)));
+                        return;
+                    }
+                }
+            }
+            deferred.resolve(Box::new(move |_| Ok(replayed)));
+        });
+    }
+}
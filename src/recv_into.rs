@@ -0,0 +1,112 @@
+use napi::bindgen_prelude::Buffer;
+use napi::{Env, JsDeferred, Result};
+use nng::options::{Options, RecvTimeout};
+use nng::{Aio, AioResult, Error as NngError, Socket};
+use std::sync::{Arc, Mutex};
+
+use crate::nanomsg::reject_closed;
+use crate::nng_error::reject_nng;
+use crate::outbox::Outbox;
+use crate::probe::Probes;
+
+type Resolver = Box<dyn FnOnce(Env) -> Result<u32> + Send>;
+
+struct Pending {
+    buffer: Buffer,
+    deferred: JsDeferred<u32, Resolver>,
+}
+
+// recvInto 用的接收：socket 打开时创建一个 aio 反复使用，每次在 JS 线程上把收到的消息拷进调用方的 Buffer。
+// 同一时间只能有一个 recvInto 在等待
+pub struct RecvInto {
+    aio: Aio,
+    socket: Socket,
+    pending: Arc<Mutex<Option<Pending>>>,
+}
+
+fn reject(deferred: JsDeferred<u32, Resolver>, err: NngError) {
+    match err {
+        NngError::Canceled | NngError::Closed => reject_closed(deferred, "Socket closed"),
+        NngError::TimedOut => reject_nng(deferred, NngError::TimedOut, "Receive timeout".to_string()),
+        err => reject_nng(deferred, err, format!("Receive error: {:?}", err)),
+    }
+}
+
+// 放不下时截断，返回的仍是整条消息的长度
+fn copy_into(buffer: &mut Buffer, message: &nng::Message, raw: bool) -> u32 {
+    let header = if raw { message.as_header().as_slice() } else { &[] };
+    let size = header.len() + message.len();
+    let mut offset = 0;
+    for part in [header, message.as_slice()] {
+        let len = part.len().min(buffer.len() - offset);
+        buffer[offset..offset + len].copy_from_slice(&part[..len]);
+        offset += len;
+    }
+    size as u32
+}
+
+impl RecvInto {
+    pub fn new(socket: Socket, raw: bool, probes: Probes, outbox: Outbox) -> std::result::Result<Self, NngError> {
+        let pending: Arc<Mutex<Option<Pending>>> = Arc::default();
+        let slot = pending.clone();
+        let probe_socket = socket.clone();
+        let aio = Aio::new(move |aio, result| {
+            let message = match result {
+                AioResult::Recv(Ok(message)) => message,
+                AioResult::Recv(Err(err)) => {
+                    if let Some(pending) = slot.lock().unwrap().take() {
+                        reject(pending.deferred, err);
+                    }
+                    return;
+                }
+                _ => return,
+            };
+            outbox.record_received();
+            // 探测帧不交给调用方，接着等下一条
+            if probes.intercept(&probe_socket, Some(&outbox), &message) {
+                if let Err(err) = probe_socket.recv_async(&aio) {
+                    if let Some(pending) = slot.lock().unwrap().take() {
+                        reject(pending.deferred, err);
+                    }
+                }
+                return;
+            }
+            outbox.capture().received(&message);
+            if let Some(Pending { mut buffer, deferred }) = slot.lock().unwrap().take() {
+                // Buffer 归 JS 所有，拷贝放到 JS 线程的 resolve 里做，worker 线程只交出消息
+                deferred.resolve(Box::new(move |_| Ok(copy_into(&mut buffer, &message, raw))));
+            }
+        })?;
+        Ok(RecvInto { aio, socket, pending })
+    }
+
+    pub fn start(&self, buffer: Buffer, deferred: JsDeferred<u32, Resolver>) {
+        let mut pending = self.pending.lock().unwrap();
+        if pending.is_some() {
+            deferred.reject(napi::Error::new(
+                napi::Status::GenericFailure,
+                "Another recvInto is already waiting".to_string(),
+            ));
+            return;
+        }
+        // 和同步接收一样使用 setTimeouts 设置的接收超时
+        let timeout = self.socket.get_opt::<RecvTimeout>().ok().flatten();
+        let started = self.aio.set_timeout(timeout).and_then(|_| self.socket.recv_async(&self.aio));
+        match started {
+            Ok(()) => *pending = Some(Pending { buffer, deferred }),
+            Err(err) => reject(deferred, err),
+        }
+    }
+}
+
+// socket 关闭时等待中的 recvInto 以 SocketClosed reject
+impl Drop for RecvInto {
+    fn drop(&mut self) {
+        self.aio.cancel();
+        // 等正在跑的回调结束再释放 aio，否则最后一个句柄可能在回调里释放，卡住 nng 的任务线程
+        unsafe { nng::ffi::nng_aio_stop(self.aio.nng_aio()) };
+        if let Some(pending) = self.pending.lock().unwrap().take() {
+            reject_closed(pending.deferred, "Socket closed");
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use napi::Result;
+use napi_derive::napi;
+use nng::{Message, Socket};
+use std::sync::{Arc, Mutex};
+
+use crate::dead_letter::DeadLetters;
+use crate::events::EventEmitter;
+
+// 重新排队的消息带上的帧头：MAGIC + 已经失败的次数(4, 大端) + 原消息。
+// 只有 recvEnvelopes 会拆掉它，分发端用 recv/sendAsync 转发时原样带着
+const MAGIC: &[u8] = b"\0nng-requeue\0";
+const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+
+#[napi(object)]
+pub struct RequeueOptions {
+    pub max_attempts: Option<u32>, // 处理失败这么多次后交给死信出口（reason 为 retryLimit），默认 5
+}
+
+struct Target {
+    socket: Socket,
+    max_attempts: u32,
+}
+
+// 把出错的消息推回分发端（经由回程的 Push）或者专门的重试 socket，
+// 次数跟着消息走，换了 worker 也能接着数
+#[derive(Clone, Default)]
+pub struct Requeue {
+    target: Arc<Mutex<Option<Target>>>,
+}
+
+// 拆出帧头里的失败次数，不带帧头的消息为 0 次
+pub fn strip(data: &[u8]) -> (u32, &[u8]) {
+    match data.strip_prefix(MAGIC).filter(|rest| rest.len() >= 4) {
+        Some(rest) => (u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]), &rest[4..]),
+        None => (0, data),
+    }
+}
+
+fn encode(attempts: u32, body: &[u8]) -> Message {
+    let mut message = Message::with_capacity(MAGIC.len() + 4 + body.len());
+    message.push_back(MAGIC);
+    message.push_back(&attempts.to_be_bytes());
+    message.push_back(body);
+    message
+}
+
+impl Requeue {
+    pub fn set(&self, socket: Socket, options: Option<RequeueOptions>) -> Result<()> {
+        let max_attempts = options.and_then(|options| options.max_attempts).unwrap_or(DEFAULT_MAX_ATTEMPTS);
+        if max_attempts == 0 {
+            return Err(napi::Error::new(napi::Status::InvalidArg, "maxAttempts must be greater than 0".to_string()));
+        }
+        *self.target.lock().unwrap() = Some(Target { socket, max_attempts });
+        Ok(())
+    }
+
+    pub fn clear(&self) {
+        *self.target.lock().unwrap() = None;
+    }
+
+    // 这条消息又失败了一次（之前失败过 attempts 次）。没有设置时返回 false，由调用方按原来的方式处理
+    pub fn forward(&self, events: &EventEmitter, dead_letters: &DeadLetters, body: &[u8], attempts: u32) -> bool {
+        let target = self.target.lock().unwrap();
+        let Some(target) = target.as_ref() else {
+            return false;
+        };
+        let attempts = attempts + 1;
+        if attempts >= target.max_attempts {
+            let error = format!("Failed {} times", attempts);
+            dead_letters.deliver(events, Message::from(body), "retryLimit", Some(error));
+            return true;
+        }
+        // 重试 socket 满了或已关闭时不等待，交给死信出口
+        if let Err((_, err)) = target.socket.try_send(encode(attempts, body)) {
+            dead_letters.deliver(events, Message::from(body), "requeueFailed", Some(format!("{:?}", err)));
+        }
+        true
+    }
+}
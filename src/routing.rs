@@ -0,0 +1,82 @@
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+
+use crate::topic;
+
+// 一条路由规则，给出的条件都满足才算匹配，什么都不给时匹配所有消息
+#[napi(object)]
+pub struct RoutingRule {
+    pub prefix: Option<Buffer>, // 消息开头的字节
+    pub topic: Option<String>,  // 主题帧的主题，* 匹配任意一串字符，比如 "sensors.*"；不是主题帧的消息不匹配
+    pub min_size: Option<u32>,  // 消息字节数的范围，两头都含
+    pub max_size: Option<u32>,
+}
+
+// 编译好的规则，在转发线程里对每条消息求值，不经过 JS 线程
+pub struct Rule {
+    prefix: Option<Vec<u8>>,
+    topic: Option<Vec<String>>, // 按 * 切开的片段
+    min_size: usize,
+    max_size: usize,
+}
+
+impl Rule {
+    pub fn compile(rule: &RoutingRule) -> Result<Rule> {
+        if let Some(topic) = &rule.topic {
+            topic::check_topic(topic)?;
+        }
+        let (min_size, max_size) = (rule.min_size.unwrap_or(0), rule.max_size.unwrap_or(u32::MAX));
+        if min_size > max_size {
+            return Err(napi::Error::new(napi::Status::InvalidArg, "minSize must not exceed maxSize".to_string()));
+        }
+        Ok(Rule {
+            prefix: rule.prefix.as_ref().map(|prefix| prefix.to_vec()),
+            topic: rule.topic.as_ref().map(|pattern| pattern.split('*').map(str::to_string).collect()),
+            min_size: min_size as usize,
+            max_size: max_size as usize,
+        })
+    }
+
+    pub fn matches(&self, message: &[u8]) -> bool {
+        if message.len() < self.min_size || message.len() > self.max_size {
+            return false;
+        }
+        if let Some(prefix) = &self.prefix {
+            if !message.starts_with(prefix) {
+                return false;
+            }
+        }
+        match &self.topic {
+            Some(pattern) => topic::decode(message).is_ok_and(|frame| glob(pattern, frame.topic)),
+            None => true,
+        }
+    }
+}
+
+// 没有规则时匹配所有消息，否则满足其中一条即可
+pub fn any_matches(rules: &[Rule], message: &[u8]) -> bool {
+    rules.is_empty() || rules.iter().any(|rule| rule.matches(message))
+}
+
+// pieces 是模式按 * 切开的片段：第一段是前缀、最后一段是后缀，中间的按顺序出现即可
+fn glob(pieces: &[String], text: &str) -> bool {
+    let (first, rest) = pieces.split_first().expect("split yields at least one piece");
+    let Some(mut text) = text.strip_prefix(first.as_str()) else {
+        return false;
+    };
+    let Some((last, middle)) = rest.split_last() else {
+        return text.is_empty();
+    };
+    // 先去掉后缀，中间的片段不能占用它
+    let Some(stripped) = text.strip_suffix(last.as_str()) else {
+        return false;
+    };
+    text = stripped;
+    for piece in middle {
+        match text.find(piece.as_str()) {
+            Some(at) => text = &text[at + piece.len()..],
+            None => return false,
+        }
+    }
+    true
+}
@@ -0,0 +1,1358 @@
+use napi::{
+    bindgen_prelude::*,
+    threadsafe_function::{ThreadsafeFunction, ThreadsafeFunctionCallMode},
+    Env, JsDeferred, JsFunction, JsObject,
+};
+use napi_derive::napi;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::events::{EventEmitter, SocketEvent};
+use crate::frames::{FrameError, FrameErrors, MalformedFrames};
+use crate::guard;
+use crate::handshake::{HandshakeOptions, Hello, PeerInfo};
+use crate::nanomsg::reject_coded;
+use crate::payload::Payload;
+use crate::poly::{PolyPipeEvent, PolySocket};
+use crate::psk::{self, NONCE_LEN, PROOF_LEN, ROLE_CLIENT, ROLE_SERVER};
+use crate::tls::{TlsListener, TlsOptions};
+use crate::topic_rpc::ReplyChannel;
+
+// RPC 帧格式：kind(1) + id(4, 大端) + method 长度(2, 大端) + method + payload
+pub const KIND_REQUEST: u8 = 1;
+pub const KIND_CHUNK: u8 = 2; // 流式响应的一段
+pub const KIND_END: u8 = 3; // 响应结束，可以带最后一段数据
+pub const KIND_ERROR: u8 = 4; // payload 为错误信息
+pub const KIND_HELLO: u8 = 5; // 握手，payload 见 handshake::Hello
+pub const KIND_AUTH: u8 = 6; // 客户端出示的 token
+pub const KIND_AUTH_RESULT: u8 = 7; // 认证结果：1 为通过，0 后面跟拒绝原因
+pub const KIND_CHALLENGE: u8 = 8; // 预共享密钥的 nonce
+pub const KIND_PROOF: u8 = 9; // 应答方的 nonce + HMAC，见 psk::proof
+
+// 握手完成前最多缓存的来自该 pipe 的帧数
+const HANDSHAKE_BACKLOG: usize = 256;
+const DEFAULT_AUTH_TIMEOUT_MS: u32 = 5000;
+const DEFAULT_PSK_TIMEOUT_MS: u32 = 5000;
+const DEFAULT_CLOSE_WAIT_MS: u32 = 5000;
+// CloseMode.Wait 检查调用是否都已结束的间隔
+pub const CLOSE_POLL: Duration = Duration::from_millis(10);
+
+const HEADER_LEN: usize = 7;
+
+pub struct RpcFrame<'a> {
+    pub kind: u8,
+    pub id: u32,
+    pub method: &'a str,
+    pub payload: &'a [u8],
+}
+
+pub fn encode_frame(kind: u8, id: u32, method: &str, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(HEADER_LEN + method.len() + payload.len());
+    frame.push(kind);
+    frame.extend_from_slice(&id.to_be_bytes());
+    frame.extend_from_slice(&(method.len() as u16).to_be_bytes());
+    frame.extend_from_slice(method.as_bytes());
+    frame.extend_from_slice(payload);
+    frame
+}
+
+pub fn decode_frame(data: &[u8]) -> std::result::Result<RpcFrame<'_>, FrameError> {
+    if data.len() < HEADER_LEN {
+        return Err(FrameError::Truncated);
+    }
+    let kind = data[0];
+    if !(KIND_REQUEST..=KIND_PROOF).contains(&kind) {
+        return Err(FrameError::UnknownKind);
+    }
+    let id = u32::from_be_bytes(data[1..5].try_into().unwrap());
+    let method_len = u16::from_be_bytes(data[5..7].try_into().unwrap()) as usize;
+    let method = data.get(HEADER_LEN..HEADER_LEN + method_len).ok_or(FrameError::Truncated)?;
+    Ok(RpcFrame {
+        kind,
+        id,
+        method: std::str::from_utf8(method).map_err(|_| FrameError::InvalidUtf8)?,
+        payload: &data[HEADER_LEN + method_len..],
+    })
+}
+
+// 两端都用 polyamorous socket，服务端同时服务多个客户端时按 pipe 定向发送
+fn send_frame(socket: &PolySocket, pipe: u32, frame: Vec<u8>) -> Result<()> {
+    socket.send_to(pipe, &frame).map_err(|e| {
+        napi::Error::new(napi::Status::GenericFailure, format!("Send error: {:?}", e))
+    })
+}
+
+// 回复的去向：点对点调用按 pipe 发回，主题调用经回复通道发给调用方
+pub enum ReplyTo {
+    Pipe { socket: PolySocket, pipe: u32 },
+    Topic { topic: String, channel: Option<ReplyChannel> }, // 通知没有回复通道
+}
+
+// 收到的一次调用，handler 通过 write/end/fail 回复；id 为 0 的是通知，不需要回复
+#[napi]
+pub struct RpcCall {
+    id: u32,
+    method: String,
+    data: Vec<u8>,
+    reply_to: ReplyTo,
+    ended: bool,
+}
+
+impl RpcCall {
+    pub fn new(id: u32, method: &str, data: &[u8], reply_to: ReplyTo) -> Self {
+        RpcCall {
+            id,
+            method: method.to_string(),
+            data: data.to_vec(),
+            reply_to,
+            ended: false,
+        }
+    }
+}
+
+#[napi]
+impl RpcCall {
+    #[napi(getter)]
+    pub fn method(&self) -> String {
+        self.method.clone()
+    }
+
+    #[napi(getter)]
+    pub fn data(&self) -> Buffer {
+        self.data.clone().into()
+    }
+
+    // 发起调用的对端，服务端可以用它反向调用客户端；主题调用没有对端 id，为 0
+    #[napi(getter)]
+    pub fn peer_id(&self) -> u32 {
+        match &self.reply_to {
+            ReplyTo::Pipe { pipe, .. } => *pipe,
+            ReplyTo::Topic { .. } => 0,
+        }
+    }
+
+    // 主题调用的主题，点对点调用为 null
+    #[napi(getter)]
+    pub fn topic(&self) -> Option<String> {
+        match &self.reply_to {
+            ReplyTo::Pipe { .. } => None,
+            ReplyTo::Topic { topic, .. } => Some(topic.clone()),
+        }
+    }
+
+    // 发送一段流式响应
+    #[napi(ts_args_type = "chunk: Buffer | Uint8Array | string | ArrayBuffer")]
+    pub fn write(&self, chunk: Payload) -> Result<()> {
+        self.check_open()?;
+        self.reply(encode_frame(KIND_CHUNK, self.id, "", &chunk))
+    }
+
+    #[napi(ts_args_type = "chunk?: Buffer | Uint8Array | string | ArrayBuffer | undefined | null")]
+    pub fn end(&mut self, chunk: Option<Payload>) -> Result<()> {
+        self.check_open()?;
+        let payload = chunk.map(|chunk| chunk.to_vec()).unwrap_or_default();
+        self.ended = true;
+        self.reply(encode_frame(KIND_END, self.id, "", &payload))
+    }
+
+    #[napi]
+    pub fn fail(&mut self, message: String) -> Result<()> {
+        self.check_open()?;
+        self.ended = true;
+        self.reply(encode_frame(KIND_ERROR, self.id, "", message.as_bytes()))
+    }
+
+    fn reply(&self, frame: Vec<u8>) -> Result<()> {
+        if self.id == 0 {
+            return Ok(());
+        }
+        match &self.reply_to {
+            ReplyTo::Pipe { socket, pipe } => send_frame(socket, *pipe, frame),
+            ReplyTo::Topic { channel, .. } => channel.as_ref().map_or(Ok(()), |channel| channel.send(&frame)),
+        }
+    }
+
+    fn check_open(&self) -> Result<()> {
+        if self.ended {
+            return Err(napi::Error::new(napi::Status::GenericFailure, "Reply already ended".to_string()));
+        }
+        Ok(())
+    }
+}
+
+type Resolver<T> = Box<dyn FnOnce(Env) -> Result<T> + Send>;
+
+#[napi(object)]
+pub struct RpcChunk {
+    pub value: Option<Buffer>,
+    pub done: bool,
+}
+
+#[derive(Default)]
+struct StreamState {
+    chunks: VecDeque<Vec<u8>>,
+    done: bool,
+    error: Option<String>,
+    code: Option<&'static str>, // 因关闭、对端断开或超时而结束时错误带的 code，如 SocketClosed
+    waiting: Option<JsDeferred<RpcChunk, Resolver<RpcChunk>>>,
+}
+
+impl StreamState {
+    fn push(&mut self, chunk: Vec<u8>) {
+        match self.waiting.take() {
+            Some(deferred) => deferred.resolve(Box::new(move |_| Ok(chunk_result(Some(chunk))))),
+            None => self.chunks.push_back(chunk),
+        }
+    }
+
+    fn finish(&mut self, error: Option<String>) {
+        self.done = true;
+        if let Some(deferred) = self.waiting.take() {
+            match &error {
+                Some(reason) => self.reject(deferred, reason),
+                None => deferred.resolve(Box::new(|_| Ok(chunk_result(None)))),
+            }
+        }
+        self.error = error;
+    }
+
+    fn end_with(&mut self, code: &'static str, reason: String) {
+        self.code = Some(code);
+        self.finish(Some(reason));
+    }
+
+    fn reject(&self, deferred: JsDeferred<RpcChunk, Resolver<RpcChunk>>, reason: &str) {
+        match self.code {
+            Some(code) => reject_coded(deferred, code, reason),
+            None => deferred.reject(napi::Error::new(napi::Status::GenericFailure, reason.to_string())),
+        }
+    }
+}
+
+fn chunk_result(chunk: Option<Vec<u8>>) -> RpcChunk {
+    RpcChunk {
+        done: chunk.is_none(),
+        value: chunk.map(|chunk| chunk.into()),
+    }
+}
+
+// 客户端的流式响应，实现了异步迭代协议，可以直接 for await
+#[napi]
+pub struct RpcStream {
+    state: Arc<Mutex<StreamState>>,
+}
+
+#[napi]
+impl RpcStream {
+    #[napi(ts_return_type = "Promise<RpcChunk>")]
+    pub fn next(&self, env: Env) -> Result<JsObject> {
+        let (deferred, promise) = env.create_deferred::<RpcChunk, Resolver<RpcChunk>>()?;
+        let mut state = self.state.lock().unwrap();
+        if let Some(chunk) = state.chunks.pop_front() {
+            deferred.resolve(Box::new(move |_| Ok(chunk_result(Some(chunk)))));
+        } else if let Some(reason) = &state.error {
+            state.reject(deferred, reason);
+        } else if state.done {
+            deferred.resolve(Box::new(|_| Ok(chunk_result(None))));
+        } else {
+            state.waiting = Some(deferred);
+        }
+        Ok(promise)
+    }
+}
+
+// close 时怎么处理还在等回复的调用
+#[napi]
+pub enum CloseMode {
+    Reject, // 立即以 SocketClosed reject（默认）
+    Wait,   // 不再发起新的调用，最多等 waitMs 让已发出的调用收到回复，剩下的以 SocketClosed reject
+    Detach, // 立即关闭 socket，已发出的调用收不到回复，waitMs 后以 SocketClosed reject；调用自己的 timeoutMs 先到时按超时结束
+}
+
+#[napi(object)]
+pub struct CloseOptions {
+    pub mode: Option<CloseMode>,
+    pub wait_ms: Option<u32>, // 默认 5000
+}
+
+// call 和 stream 的选项
+#[napi(object)]
+pub struct CallOptions {
+    // 不传不限时；到时间还没结束的调用以 code 为 CallTimedOut 的错误 reject，流式调用以同样的错误结束
+    pub timeout_ms: Option<u32>,
+}
+
+enum Pending {
+    Call {
+        buffer: Vec<u8>, // 一次性调用时把所有分段拼起来
+        deferred: JsDeferred<Buffer, Resolver<Buffer>>,
+    },
+    Stream(Arc<Mutex<StreamState>>),
+}
+
+impl Pending {
+    fn fail(self, reason: String) {
+        match self {
+            Pending::Call { deferred, .. } => deferred.reject(napi::Error::new(napi::Status::GenericFailure, reason)),
+            Pending::Stream(state) => state.lock().unwrap().finish(Some(reason)),
+        }
+    }
+
+    // socket 关闭或对端断开：以 SocketClosed reject，不等调用超时
+    fn close(self, reason: &str) {
+        self.end_with("SocketClosed", reason);
+    }
+
+    fn time_out(self) {
+        self.end_with("CallTimedOut", "Call timed out");
+    }
+
+    fn end_with(self, code: &'static str, reason: &str) {
+        match self {
+            Pending::Call { deferred, .. } => reject_coded(deferred, code, reason),
+            Pending::Stream(state) => state.lock().unwrap().end_with(code, reason.to_string()),
+        }
+    }
+}
+
+// 还在等回复的调用，回复要来自发往的那个 pipe 才算数
+struct Outstanding {
+    pipe: u32, // 0 表示由 nng 选择，收到第一帧回复时绑定到回复的 pipe
+    deadline: Option<Instant>,
+    call: Pending,
+}
+
+// 对端在握手和认证都完成前不会收到任何应用消息
+#[derive(Default)]
+struct Peer {
+    awaiting_hello: bool,
+    awaiting_auth: bool,
+    awaiting_psk: bool,
+    psk_nonce: [u8; NONCE_LEN], // 本端发给这个对端的 challenge
+    negotiated: Option<(u32, Hello)>, // 协商出的版本和对端参数，未开启握手时为 None
+    buffered: Vec<Vec<u8>>, // 就绪前收到的帧，就绪后按顺序处理
+}
+
+impl Peer {
+    fn is_ready(&self) -> bool {
+        !self.awaiting_hello && !self.awaiting_auth && !self.awaiting_psk
+    }
+}
+
+// 预共享密钥配置，两端要设置相同的 secret
+#[derive(Clone)]
+struct PresharedKey {
+    secret: Vec<u8>,
+    role: u8, // 本端的角色，应答时写进 HMAC
+    timeout: Duration,
+}
+
+// 服务端的认证配置
+struct Verifier {
+    callback: ThreadsafeFunction<AuthRequest>,
+    timeout: Duration,
+}
+
+// 一端的配置，close 后保留，客户端重新 connect 时沿用
+#[derive(Default)]
+struct Config {
+    handlers: Mutex<HashMap<String, ThreadsafeFunction<RpcCall>>>,
+    handshake: Mutex<Option<Hello>>,
+    token: Mutex<Option<String>>, // 客户端连接时出示的认证 token
+    verifier: Mutex<Option<Verifier>>,
+    psk: Mutex<Option<PresharedKey>>,
+    malformed: FrameErrors,
+    events: EventEmitter,
+}
+
+// 一次连接里接收线程和 pipe 回调共享的状态，每次 attach 新建一份。
+// close 之后还在收尾的线程只碰它自己那份，不会影响重新 connect 后的调用
+struct Shared {
+    config: Arc<Config>,
+    pending: Mutex<HashMap<u32, Outstanding>>, // 调用 id -> 等待中的调用
+    peers: Mutex<HashMap<u32, Peer>>,
+}
+
+impl Config {
+    // 关闭后回调还留着，但不再让进程保持运行；重新连上时恢复
+    fn hold(&self, env: &Env, referenced: bool) -> Result<()> {
+        fn hold<T: 'static>(env: &Env, callback: &mut ThreadsafeFunction<T>, referenced: bool) -> Result<()> {
+            if referenced {
+                callback.refer(env)
+            } else {
+                callback.unref(env)
+            }
+        }
+        for callback in self.handlers.lock().unwrap().values_mut() {
+            hold(env, callback, referenced)?;
+        }
+        if let Some(verifier) = self.verifier.lock().unwrap().as_mut() {
+            hold(env, &mut verifier.callback, referenced)?;
+        }
+        self.events.hold(env, referenced)
+    }
+}
+
+impl Shared {
+    fn new(config: Arc<Config>) -> Self {
+        Shared { config, pending: Mutex::default(), peers: Mutex::default() }
+    }
+
+    fn new_peer(&self) -> Peer {
+        Peer {
+            awaiting_hello: self.config.handshake.lock().unwrap().is_some(),
+            awaiting_auth: self.config.verifier.lock().unwrap().is_some(),
+            awaiting_psk: self.config.psk.lock().unwrap().is_some(),
+            psk_nonce: psk::nonce(),
+            ..Peer::default()
+        }
+    }
+
+    fn pipe_added(self: &Arc<Self>, socket: &PolySocket, pipe: u32) {
+        let peer = self.new_peer();
+        let nonce = self.peers.lock().unwrap().entry(pipe).or_insert(peer).psk_nonce;
+
+        let psk = self.config.psk.lock().unwrap().clone();
+        if let Some(psk) = &psk {
+            if let Err(e) = socket.send_to(pipe, &encode_frame(KIND_CHALLENGE, 0, "", &nonce)) {
+                self.config.events.warn("sendFailed", format!("Failed to send challenge: {:?}", e));
+            }
+            self.expire(socket, pipe, psk.timeout, |peer| peer.awaiting_psk, "pskRejected", "Shared secret check timed out");
+        }
+        if let Some(hello) = self.config.handshake.lock().unwrap().clone() {
+            if let Err(e) = socket.send_to(pipe, &encode_frame(KIND_HELLO, 0, "", &hello.encode())) {
+                self.config.events.warn("sendFailed", format!("Failed to send handshake: {:?}", e));
+            }
+        }
+        // 开启预共享密钥时，确认对端持有同一个密钥后才出示 token
+        if psk.is_none() {
+            self.send_token(socket, pipe);
+        }
+
+        // 超时还没通过认证的 pipe 直接断开
+        let timeout = self.config.verifier.lock().unwrap().as_ref().map(|verifier| verifier.timeout);
+        if let Some(timeout) = timeout {
+            self.expire(socket, pipe, timeout, |peer| peer.awaiting_auth, "authRejected", "Authentication timed out");
+        }
+    }
+
+    fn send_token(&self, socket: &PolySocket, pipe: u32) {
+        if let Some(token) = self.config.token.lock().unwrap().clone() {
+            if let Err(e) = socket.send_to(pipe, &encode_frame(KIND_AUTH, 0, "", token.as_bytes())) {
+                self.config.events.warn("sendFailed", format!("Failed to send auth token: {:?}", e));
+            }
+        }
+    }
+
+    // timeout 后 pending 仍为 true 的 pipe 直接断开
+    fn expire(
+        self: &Arc<Self>,
+        socket: &PolySocket,
+        pipe: u32,
+        timeout: Duration,
+        pending: fn(&Peer) -> bool,
+        event: &'static str,
+        reason: &'static str,
+    ) {
+        let shared = Arc::downgrade(self);
+        let weak = socket.downgrade();
+        guard::spawn(self.config.events.clone(), "Peer timeout", move || {
+            std::thread::sleep(timeout);
+            if let (Some(shared), Some(socket)) = (shared.upgrade(), weak.upgrade()) {
+                let expired = matches!(shared.peers.lock().unwrap().get(&pipe), Some(peer) if pending(peer));
+                if expired {
+                    shared.reject(&socket, pipe, event, reason.to_string());
+                }
+            }
+        });
+    }
+
+    // 对端发来 challenge，用本端的角色和 nonce 应答
+    fn answer_challenge(&self, socket: &PolySocket, pipe: u32, challenge: &[u8]) {
+        let psk = match self.config.psk.lock().unwrap().clone() {
+            Some(psk) => psk,
+            None => return, // 本端没有开启，对端会超时断开
+        };
+        let own = self.peers.lock().unwrap().entry(pipe).or_insert_with(|| self.new_peer()).psk_nonce;
+        if challenge.len() != NONCE_LEN || challenge == own {
+            self.reject(socket, pipe, "pskRejected", "Invalid challenge".to_string());
+            return;
+        }
+        let mut payload = own.to_vec();
+        payload.extend_from_slice(&psk::proof(&psk.secret, psk.role, challenge, &own));
+        if let Err(e) = socket.send_to(pipe, &encode_frame(KIND_PROOF, 0, "", &payload)) {
+            self.config.events.warn("sendFailed", format!("Failed to send proof: {:?}", e));
+        }
+    }
+
+    // 对端的应答必须是用另一种角色、针对本端 nonce 算出来的
+    fn check_proof(&self, socket: &PolySocket, pipe: u32, payload: &[u8]) {
+        let psk = match self.config.psk.lock().unwrap().clone() {
+            Some(psk) => psk,
+            None => return,
+        };
+        let own = match self.peers.lock().unwrap().get(&pipe) {
+            Some(peer) if peer.awaiting_psk => peer.psk_nonce,
+            _ => return,
+        };
+        let remote_role = if psk.role == ROLE_SERVER { ROLE_CLIENT } else { ROLE_SERVER };
+        let valid = payload.len() == NONCE_LEN + PROOF_LEN && payload[..NONCE_LEN] != own && {
+            let (nonce, proof) = payload.split_at(NONCE_LEN);
+            psk::verify(&psk::proof(&psk.secret, remote_role, &own, nonce), proof)
+        };
+        if !valid {
+            self.reject(socket, pipe, "pskRejected", "Shared secret mismatch".to_string());
+            return;
+        }
+        self.config.events.emit(SocketEvent::new("pskVerified").pipe(pipe));
+        self.send_token(socket, pipe);
+        self.update_peer(socket, pipe, |peer| peer.awaiting_psk = false);
+    }
+
+    // 对端断开时，发往它的调用不会再有响应
+    fn pipe_removed(&self, pipe: u32) {
+        let no_peers = {
+            let mut peers = self.peers.lock().unwrap();
+            peers.remove(&pipe);
+            peers.is_empty()
+        };
+        let mut pending = self.pending.lock().unwrap();
+        let ids: Vec<u32> = pending
+            .iter()
+            .filter(|(_, call)| call.pipe == pipe || (call.pipe == 0 && no_peers))
+            .map(|(id, _)| *id)
+            .collect();
+        for id in ids {
+            if let Some(outstanding) = pending.remove(&id) {
+                outstanding.call.close("Peer disconnected");
+            }
+        }
+    }
+
+    // 到 timeoutMs 还没结束的调用以 CallTimedOut 结束，之后到的回复丢弃
+    fn expire_call(self: &Arc<Self>, id: u32, timeout: Duration) {
+        let shared = Arc::downgrade(self);
+        guard::spawn(self.config.events.clone(), "Call timeout", move || {
+            std::thread::sleep(timeout);
+            if let Some(shared) = shared.upgrade() {
+                let expired = shared.pending.lock().unwrap().remove(&id);
+                if let Some(outstanding) = expired {
+                    outstanding.call.time_out();
+                }
+            }
+        });
+    }
+
+    fn accept_hello(&self, socket: &PolySocket, pipe: u32, payload: &[u8]) {
+        let local = match self.config.handshake.lock().unwrap().clone() {
+            Some(local) => local,
+            None => return, // 本端没有开启握手
+        };
+        let result = match Hello::decode(payload) {
+            Ok(remote) => local.negotiate(&remote).map(|version| (version, remote)),
+            Err(err) => {
+                self.config.malformed.record(err, &self.config.events);
+                Err(format!("Malformed handshake: {}", err))
+            }
+        };
+        match result {
+            Ok((version, remote)) => {
+                self.config.events.emit(SocketEvent::new("handshake").pipe(pipe).value(version as i64));
+                self.update_peer(socket, pipe, |peer| {
+                    peer.awaiting_hello = false;
+                    peer.negotiated = Some((version, remote));
+                });
+            }
+            Err(reason) => self.reject(socket, pipe, "handshakeRejected", reason),
+        }
+    }
+
+    // 服务端收到 token，交给 JS 的 verifier 决定
+    fn verify(self: &Arc<Self>, socket: &PolySocket, pipe: u32, payload: &[u8]) {
+        let callback = match self.config.verifier.lock().unwrap().as_ref() {
+            Some(verifier) => verifier.callback.clone(),
+            None => return,
+        };
+        let request = AuthRequest {
+            peer_id: pipe,
+            token: String::from_utf8_lossy(payload).into_owned(),
+            shared: self.clone(),
+            socket: socket.clone(),
+            decided: false,
+        };
+        callback.call(Ok(request), ThreadsafeFunctionCallMode::NonBlocking);
+    }
+
+    // 客户端收到服务端的认证结果
+    fn auth_result(&self, pipe: u32, payload: &[u8]) {
+        match payload.split_first() {
+            Some((1, _)) => self.config.events.emit(SocketEvent::new("authenticated").pipe(pipe)),
+            Some((_, reason)) => self
+                .config
+                .events
+                .emit(SocketEvent::new("authRejected").pipe(pipe).message(String::from_utf8_lossy(reason))),
+            None => {}
+        }
+    }
+
+    fn reject(&self, socket: &PolySocket, pipe: u32, event: &str, reason: String) {
+        self.peers.lock().unwrap().remove(&pipe);
+        self.config.events.emit(SocketEvent::new(event).pipe(pipe).message(reason));
+        socket.close_pipe(pipe);
+    }
+
+    // 修改对端状态，就绪后处理之前缓存的帧
+    fn update_peer<F: FnOnce(&mut Peer)>(&self, socket: &PolySocket, pipe: u32, update: F) {
+        let buffered = {
+            let mut peers = self.peers.lock().unwrap();
+            let peer = peers.entry(pipe).or_insert_with(|| self.new_peer());
+            update(peer);
+            if !peer.is_ready() {
+                return;
+            }
+            std::mem::take(&mut peer.buffered)
+        };
+        for message in buffered {
+            self.dispatch(socket, pipe, &message);
+        }
+    }
+
+    // 对端就绪前的帧先缓存，返回 false 表示已缓存或丢弃
+    fn ready(&self, pipe: u32, message: &[u8]) -> bool {
+        let mut peers = self.peers.lock().unwrap();
+        // pipe 回调可能晚于第一条消息
+        let peer = peers.entry(pipe).or_insert_with(|| self.new_peer());
+        if peer.is_ready() {
+            return true;
+        }
+        if peer.buffered.len() < HANDSHAKE_BACKLOG {
+            peer.buffered.push(message.to_vec());
+        }
+        false
+    }
+
+    fn dispatch(&self, socket: &PolySocket, pipe: u32, message: &[u8]) {
+        // 缓存的帧在接收时已经解析过一次
+        let frame = match decode_frame(message) {
+            Ok(frame) => frame,
+            Err(_) => return,
+        };
+        if frame.kind != KIND_REQUEST {
+            dispatch_response(&self.pending, pipe, frame);
+            return;
+        }
+
+        let handler = self.config.handlers.lock().unwrap().get(frame.method).cloned();
+        match handler {
+            Some(handler) => {
+                let reply_to = ReplyTo::Pipe { socket: socket.clone(), pipe };
+                let call = RpcCall::new(frame.id, frame.method, frame.payload, reply_to);
+                handler.call(Ok(call), ThreadsafeFunctionCallMode::NonBlocking);
+            }
+            None if frame.id != 0 => {
+                let reason = format!("Unknown method: {}", frame.method);
+                let _ = send_frame(socket, pipe, encode_frame(KIND_ERROR, frame.id, "", reason.as_bytes()));
+            }
+            None => {}
+        }
+    }
+
+    fn is_ready(&self, pipe: u32) -> bool {
+        matches!(self.peers.lock().unwrap().get(&pipe), Some(peer) if peer.is_ready())
+    }
+
+    fn ready_peers(&self) -> Vec<u32> {
+        let mut peers: Vec<u32> = self
+            .peers
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, peer)| peer.is_ready())
+            .map(|(pipe, _)| *pipe)
+            .collect();
+        peers.sort_unstable();
+        peers
+    }
+
+    fn peer_info(&self, pipe: u32) -> Option<PeerInfo> {
+        match self.peers.lock().unwrap().get(&pipe) {
+            Some(Peer {
+                negotiated: Some((version, remote)),
+                ..
+            }) => Some(PeerInfo {
+                peer_id: pipe,
+                version: *version,
+                metadata: remote.metadata.clone(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+// 服务端收到的认证请求，verifier 回调里调用 accept 或 reject
+#[napi]
+pub struct AuthRequest {
+    peer_id: u32,
+    token: String,
+    shared: Arc<Shared>,
+    socket: PolySocket,
+    decided: bool,
+}
+
+#[napi]
+impl AuthRequest {
+    #[napi(getter)]
+    pub fn peer_id(&self) -> u32 {
+        self.peer_id
+    }
+
+    #[napi(getter)]
+    pub fn token(&self) -> String {
+        self.token.clone()
+    }
+
+    #[napi]
+    pub fn accept(&mut self) -> Result<()> {
+        self.decide()?;
+        if !self.shared.peers.lock().unwrap().contains_key(&self.peer_id) {
+            return Ok(()); // 已经断开或超时
+        }
+        let _ = self.socket.send_to(self.peer_id, &encode_frame(KIND_AUTH_RESULT, 0, "", &[1]));
+        self.shared.config.events.emit(SocketEvent::new("authenticated").pipe(self.peer_id));
+        self.shared
+            .update_peer(&self.socket, self.peer_id, |peer| peer.awaiting_auth = false);
+        Ok(())
+    }
+
+    #[napi]
+    pub fn reject(&mut self, reason: Option<String>) -> Result<()> {
+        self.decide()?;
+        let reason = reason.unwrap_or_else(|| "Authentication failed".to_string());
+        let mut payload = vec![0];
+        payload.extend_from_slice(reason.as_bytes());
+        // 尽力通知客户端原因，随后立即断开
+        let _ = self.socket.send_to(self.peer_id, &encode_frame(KIND_AUTH_RESULT, 0, "", &payload));
+        self.shared.reject(&self.socket, self.peer_id, "authRejected", reason);
+        Ok(())
+    }
+
+    fn decide(&mut self) -> Result<()> {
+        if self.decided {
+            return Err(napi::Error::new(napi::Status::GenericFailure, "Auth request already decided".to_string()));
+        }
+        self.decided = true;
+        Ok(())
+    }
+}
+
+// 服务端和客户端共用的一端：既能注册方法处理对端的调用，也能向对端发起调用
+struct Endpoint {
+    socket: Option<PolySocket>,
+    config: Arc<Config>,
+    shared: Arc<Shared>,
+    next_id: AtomicU32,
+    receiving: Arc<AtomicBool>,
+    is_closing: Arc<AtomicBool>,
+}
+
+impl Endpoint {
+    fn new() -> Self {
+        let config = Arc::new(Config::default());
+        Endpoint {
+            socket: None,
+            shared: Arc::new(Shared::new(config.clone())),
+            config,
+            next_id: AtomicU32::new(1),
+            receiving: Arc::new(AtomicBool::new(false)),
+            is_closing: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    fn open() -> Result<PolySocket> {
+        PolySocket::open().map_err(|err| {
+            napi::Error::new(napi::Status::GenericFailure, format!("Socket creation failed: {:?}", err))
+        })
+    }
+
+    // 新的 socket 用新的连接状态和标志，之前关闭的 socket 的收尾线程按它自己的那份退出
+    fn attach(&mut self, socket: PolySocket) -> Result<()> {
+        let shared = Arc::new(Shared::new(self.config.clone()));
+        self.shared = shared.clone();
+        let weak = socket.downgrade();
+        socket
+            .pipe_notify(move |pipe, event| match event {
+                PolyPipeEvent::Added => {
+                    if let Some(socket) = weak.upgrade() {
+                        shared.pipe_added(&socket, pipe);
+                    }
+                }
+                PolyPipeEvent::Removed => shared.pipe_removed(pipe),
+            })
+            .map_err(|err| {
+                napi::Error::new(napi::Status::GenericFailure, format!("Failed to watch pipes: {:?}", err))
+            })?;
+        self.socket = Some(socket);
+        self.receiving = Arc::new(AtomicBool::new(false));
+        self.is_closing = Arc::new(AtomicBool::new(false));
+        Ok(())
+    }
+
+    fn socket(&self) -> Result<&PolySocket> {
+        self.socket.as_ref().ok_or_else(|| {
+            napi::Error::new(napi::Status::GenericFailure, "Socket not connected".to_string())
+        })
+    }
+
+    fn handle(&self, env: Env, method: String, mut callback: ThreadsafeFunction<RpcCall>) -> Result<()> {
+        self.release_if_closed(&env, &mut callback)?;
+        self.config.handlers.lock().unwrap().insert(method, callback);
+        Ok(())
+    }
+
+    fn on_event(&self, env: Env, mut callback: ThreadsafeFunction<SocketEvent>) -> Result<()> {
+        self.release_if_closed(&env, &mut callback)?;
+        self.config.events.set(callback);
+        Ok(())
+    }
+
+    // 没连上时注册的回调和 close 之后的一样，不让进程保持运行
+    fn release_if_closed<T: 'static>(&self, env: &Env, callback: &mut ThreadsafeFunction<T>) -> Result<()> {
+        if self.socket.is_none() {
+            callback.unref(env)?;
+        }
+        Ok(())
+    }
+
+    // 握手和认证要在建立连接前打开，否则已有的 pipe 不会交换
+    fn check_not_started(&self) -> Result<()> {
+        if self.receiving.load(Ordering::SeqCst) {
+            return Err(napi::Error::new(
+                napi::Status::GenericFailure,
+                "Handshake and auth must be configured before listen/connect".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn set_handshake(&self, options: HandshakeOptions) -> Result<()> {
+        self.check_not_started()?;
+        *self.config.handshake.lock().unwrap() = Some(Hello::from_options(options));
+        Ok(())
+    }
+
+    fn set_shared_secret(&self, secret: Buffer, role: u8, timeout_ms: Option<u32>) -> Result<()> {
+        self.check_not_started()?;
+        if secret.is_empty() {
+            return Err(napi::Error::new(napi::Status::InvalidArg, "Shared secret must not be empty".to_string()));
+        }
+        *self.config.psk.lock().unwrap() = Some(PresharedKey {
+            secret: secret.to_vec(),
+            role,
+            timeout: Duration::from_millis(timeout_ms.unwrap_or(DEFAULT_PSK_TIMEOUT_MS) as u64),
+        });
+        Ok(())
+    }
+
+    fn call(&self, env: Env, pipe: u32, method: &str, data: &[u8], options: Option<CallOptions>) -> Result<JsObject> {
+        let (deferred, promise) = env.create_deferred::<Buffer, Resolver<Buffer>>()?;
+        let call = Pending::Call {
+            buffer: Vec::new(),
+            deferred,
+        };
+        let id = self.track(pipe, call, options);
+        if let Err(err) = self.send_request(id, pipe, method, data) {
+            if let Some(outstanding) = self.shared.pending.lock().unwrap().remove(&id) {
+                outstanding.call.fail(err.reason);
+            }
+        }
+        Ok(promise)
+    }
+
+    fn stream(&self, env: Env, pipe: u32, method: &str, data: &[u8], options: Option<CallOptions>) -> Result<JsObject> {
+        let state = Arc::new(Mutex::new(StreamState::default()));
+        let id = self.track(pipe, Pending::Stream(state.clone()), options);
+        if let Err(err) = self.send_request(id, pipe, method, data) {
+            self.shared.pending.lock().unwrap().remove(&id);
+            return Err(err);
+        }
+
+        let stream = RpcStream { state }.into_instance(env)?.as_object(env);
+        let make_iterable = env.run_script::<_, JsFunction>(
+            "(function (s) { s[Symbol.asyncIterator] = function () { return this; }; return s; })",
+        )?;
+        make_iterable.call(None, &[stream])?.coerce_to_object()
+    }
+
+    // 单向通知，对端 handler 的回复会被丢弃
+    fn notify(&self, pipe: u32, method: &str, data: &[u8]) -> Result<()> {
+        self.send_request(0, pipe, method, data)
+    }
+
+    // 登记等待回复的调用，设置了 timeoutMs 时到时间按超时结束
+    fn track(&self, pipe: u32, call: Pending, options: Option<CallOptions>) -> u32 {
+        let id = self.next_id();
+        let timeout = options.and_then(|options| options.timeout_ms).map(|timeout_ms| Duration::from_millis(timeout_ms as u64));
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        self.shared.pending.lock().unwrap().insert(id, Outstanding { pipe, deadline, call });
+        if let Some(timeout) = timeout {
+            self.shared.expire_call(id, timeout);
+        }
+        id
+    }
+
+    fn next_id(&self) -> u32 {
+        // 0 留给通知
+        loop {
+            let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+            if id != 0 {
+                return id;
+            }
+        }
+    }
+
+    fn send_request(&self, id: u32, pipe: u32, method: &str, data: &[u8]) -> Result<()> {
+        send_frame(self.socket()?, pipe, encode_frame(KIND_REQUEST, id, method, data))
+    }
+
+    // 不管哪种方式，close 返回后都不能再发起调用。配置留给下一次 connect
+    fn close(&mut self, env: &Env, options: Option<CloseOptions>) -> Result<()> {
+        let (mode, wait) = close_options(options);
+        let socket = self.socket.take();
+        self.config.hold(env, false)?;
+        let (shared, receiving, is_closing) = (self.shared.clone(), self.receiving.clone(), self.is_closing.clone());
+        match mode {
+            CloseMode::Reject => {
+                // 先于关闭 socket：否则 pipe 断开时它们会以 "Peer disconnected" 结束
+                for (_, outstanding) in shared.pending.lock().unwrap().drain() {
+                    outstanding.call.close("Socket closed");
+                }
+                shut_down(&shared, socket, &receiving, &is_closing);
+            }
+            CloseMode::Wait => {
+                // 接收线程照常把回复交给等待中的调用，等完或到时间后再关闭
+                guard::spawn(shared.config.events.clone(), "Close wait", move || {
+                    let deadline = Instant::now() + wait;
+                    while !shared.pending.lock().unwrap().is_empty() && Instant::now() < deadline {
+                        std::thread::sleep(CLOSE_POLL);
+                    }
+                    for (_, outstanding) in shared.pending.lock().unwrap().drain() {
+                        outstanding.call.close("Socket closed");
+                    }
+                    shut_down(&shared, socket, &receiving, &is_closing);
+                });
+            }
+            CloseMode::Detach => {
+                let detached: Vec<Outstanding> = shared.pending.lock().unwrap().drain().map(|(_, outstanding)| outstanding).collect();
+                shut_down(&shared, socket, &receiving, &is_closing);
+                if !detached.is_empty() {
+                    guard::spawn(EventEmitter::default(), "Close detach", move || settle_detached(detached, Instant::now() + wait));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn start(&self) {
+        if self.receiving.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let socket = match &self.socket {
+            Some(socket) => socket.clone(),
+            None => return,
+        };
+        let shared = self.shared.clone();
+        let receiving = self.receiving.clone();
+        let is_closing = self.is_closing.clone();
+
+        guard::spawn(self.config.events.clone(), "Receive loop", move || {
+            while receiving.load(Ordering::SeqCst) {
+                let (message, pipe) = match socket.recv() {
+                    Ok(received) => received,
+                    Err(e) => {
+                        if is_closing.load(Ordering::SeqCst) {
+                            return; // 主动关闭时不报错
+                        }
+                        shared.config.events.warn("recvFailed", format!("Error receiving message: {:?}", e));
+                        continue;
+                    }
+                };
+                match decode_frame(&message) {
+                    Ok(frame) if frame.kind == KIND_HELLO => shared.accept_hello(&socket, pipe, frame.payload),
+                    Ok(frame) if frame.kind == KIND_AUTH => shared.verify(&socket, pipe, frame.payload),
+                    Ok(frame) if frame.kind == KIND_AUTH_RESULT => shared.auth_result(pipe, frame.payload),
+                    Ok(frame) if frame.kind == KIND_CHALLENGE => shared.answer_challenge(&socket, pipe, frame.payload),
+                    Ok(frame) if frame.kind == KIND_PROOF => shared.check_proof(&socket, pipe, frame.payload),
+                    Ok(_) if shared.ready(pipe, &message) => shared.dispatch(&socket, pipe, &message),
+                    Ok(_) => {}
+                    Err(err) => shared.config.malformed.record(err, &shared.config.events),
+                }
+            }
+        });
+    }
+}
+
+#[napi]
+pub struct RpcServer {
+    endpoint: Endpoint,
+    tls: Mutex<Vec<TlsListener>>,
+}
+
+#[napi]
+impl RpcServer {
+    #[napi(constructor)]
+    pub fn new() -> Result<Self> {
+        let mut endpoint = Endpoint::new();
+        endpoint.attach(Endpoint::open()?)?;
+        Ok(RpcServer {
+            endpoint,
+            tls: Mutex::new(Vec::new()),
+        })
+    }
+
+    // 开启版本协商握手，需要在 listen 之前调用，客户端也要开启
+    #[napi]
+    pub fn set_handshake(&self, options: HandshakeOptions) -> Result<()> {
+        self.endpoint.set_handshake(options)
+    }
+
+    #[napi]
+    pub fn listen(&self, url: String) -> Result<()> {
+        let socket = self.endpoint.socket()?;
+        socket.listen(&url).map_err(|err| {
+            napi::Error::new(napi::Status::GenericFailure, format!("Listen failed: {:?}", err))
+        })?;
+        self.endpoint.start();
+        Ok(())
+    }
+
+    // TLS 监听，证书可以通过 reloadTls 或 watchIntervalMs 在运行中替换，不影响已建立的连接
+    #[napi]
+    pub fn listen_tls(&self, url: String, options: TlsOptions) -> Result<()> {
+        let listener = TlsListener::start(self.endpoint.socket()?.handle(), &url, options, self.endpoint.config.events.clone())?;
+        self.tls.lock().unwrap().push(listener);
+        self.endpoint.start();
+        Ok(())
+    }
+
+    #[napi]
+    pub fn reload_tls(&self, options: Option<TlsOptions>) -> Result<()> {
+        for listener in self.tls.lock().unwrap().iter() {
+            listener.reload(options.clone())?;
+        }
+        Ok(())
+    }
+
+    #[napi]
+    pub fn handle(&self, env: Env, method: String, callback: ThreadsafeFunction<RpcCall>) -> Result<()> {
+        self.endpoint.handle(env, method, callback)
+    }
+
+    // 要求客户端出示 token，verifier 回调收到 AuthRequest 后 accept 或 reject，
+    // 超时（默认 5 秒）未通过的连接会被断开；通过前不会处理该连接的任何消息
+    #[napi]
+    pub fn set_auth_verifier(&self, callback: ThreadsafeFunction<AuthRequest>, timeout_ms: Option<u32>) -> Result<()> {
+        self.endpoint.check_not_started()?;
+        *self.endpoint.config.verifier.lock().unwrap() = Some(Verifier {
+            callback,
+            timeout: Duration::from_millis(timeout_ms.unwrap_or(DEFAULT_AUTH_TIMEOUT_MS) as u64),
+        });
+        Ok(())
+    }
+
+    // 没有 PKI 时的轻量认证：连接建立后双方用预共享密钥做挑战-应答，
+    // 客户端要设置相同的 secret；超时（默认 5 秒）或应答不对的连接会被断开。需要在 listen 之前调用
+    #[napi]
+    pub fn set_shared_secret(&self, secret: Buffer, timeout_ms: Option<u32>) -> Result<()> {
+        self.endpoint.set_shared_secret(secret, ROLE_SERVER, timeout_ms)
+    }
+
+    // 握手和认证结果等事件：handshake / handshakeRejected / authenticated / authRejected / pskVerified / pskRejected
+    #[napi]
+    pub fn on_event(&self, env: Env, callback: ThreadsafeFunction<SocketEvent>) -> Result<()> {
+        self.endpoint.on_event(env, callback)
+    }
+
+    // 附带到事件和日志里的标签
+    #[napi]
+    pub fn set_labels(&self, labels: HashMap<String, String>) {
+        self.endpoint.config.events.labels().set(labels);
+    }
+
+    #[napi]
+    pub fn labels(&self) -> HashMap<String, String> {
+        self.endpoint.config.events.labels().get()
+    }
+
+    // 当前可用的客户端，id 与 RpcCall.peerId 一致；开启握手时只包含握手成功的
+    #[napi]
+    pub fn peers(&self) -> Vec<u32> {
+        self.endpoint.shared.ready_peers()
+    }
+
+    #[napi]
+    pub fn peer_info(&self, peer_id: u32) -> Option<PeerInfo> {
+        self.endpoint.shared.peer_info(peer_id)
+    }
+
+    // 收到后丢弃的畸形帧，按原因计数
+    #[napi]
+    pub fn malformed_frames(&self) -> MalformedFrames {
+        self.endpoint.config.malformed.snapshot()
+    }
+
+    // 反向调用某个客户端注册的方法
+    #[napi(
+        ts_args_type = "peerId: number, method: string, data: Buffer | Uint8Array | string | ArrayBuffer, options?: CallOptions | undefined | null",
+        ts_return_type = "Promise<Buffer>"
+    )]
+    pub fn call(&self, env: Env, peer_id: u32, method: String, data: Payload, options: Option<CallOptions>) -> Result<JsObject> {
+        self.check_peer(peer_id)?;
+        self.endpoint.call(env, peer_id, &method, &data, options)
+    }
+
+    #[napi(
+        ts_args_type = "peerId: number, method: string, data: Buffer | Uint8Array | string | ArrayBuffer, options?: CallOptions | undefined | null",
+        ts_return_type = "RpcStream & AsyncIterable<Buffer>"
+    )]
+    pub fn stream(&self, env: Env, peer_id: u32, method: String, data: Payload, options: Option<CallOptions>) -> Result<JsObject> {
+        self.check_peer(peer_id)?;
+        self.endpoint.stream(env, peer_id, &method, &data, options)
+    }
+
+    // 向某个客户端推送通知，不指定 peerId 时发给所有客户端
+    #[napi(ts_args_type = "method: string, data: Buffer | Uint8Array | string | ArrayBuffer, peerId?: number | undefined | null")]
+    pub fn notify(&self, method: String, data: Payload, peer_id: Option<u32>) -> Result<()> {
+        match peer_id {
+            Some(peer_id) => {
+                self.check_peer(peer_id)?;
+                self.endpoint.notify(peer_id, &method, &data)
+            }
+            None => {
+                for peer_id in self.peers() {
+                    self.endpoint.notify(peer_id, &method, &data)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    #[napi]
+    pub fn close(&mut self, env: Env, options: Option<CloseOptions>) -> Result<()> {
+        for listener in self.tls.lock().unwrap().drain(..) {
+            listener.close();
+        }
+        self.endpoint.close(&env, options)
+    }
+
+    fn check_peer(&self, peer_id: u32) -> Result<()> {
+        self.endpoint.socket()?;
+        if !self.endpoint.shared.is_ready(peer_id) {
+            return Err(napi::Error::new(napi::Status::InvalidArg, format!("Unknown peer: {}", peer_id)));
+        }
+        Ok(())
+    }
+}
+
+#[napi]
+pub struct RpcClient {
+    endpoint: Endpoint,
+}
+
+#[napi]
+impl RpcClient {
+    #[napi(constructor)]
+    pub fn new() -> Self {
+        RpcClient {
+            endpoint: Endpoint::new(),
+        }
+    }
+
+    // 开启版本协商握手，需要在 connect 之前调用
+    #[napi]
+    pub fn set_handshake(&self, options: HandshakeOptions) -> Result<()> {
+        self.endpoint.set_handshake(options)
+    }
+
+    // 连接时出示给服务端的认证 token，需要在 connect 之前调用
+    #[napi]
+    pub fn set_auth_token(&self, token: String) -> Result<()> {
+        self.endpoint.check_not_started()?;
+        *self.endpoint.config.token.lock().unwrap() = Some(token);
+        Ok(())
+    }
+
+    // 和服务端相同的预共享密钥，需要在 connect 之前调用；设置了 token 时验证通过后才发送 token
+    #[napi]
+    pub fn set_shared_secret(&self, secret: Buffer, timeout_ms: Option<u32>) -> Result<()> {
+        self.endpoint.set_shared_secret(secret, ROLE_CLIENT, timeout_ms)
+    }
+
+    // 同时只连一个服务端；要换地址先 close 再 connect，之前设置的握手、token、密钥、handler 和 onEvent 照常生效
+    #[napi]
+    pub fn connect(&mut self, env: Env, url: String) -> Result<()> {
+        if self.endpoint.socket.is_some() {
+            return Err(napi::Error::new(
+                napi::Status::GenericFailure,
+                "Already connected; close the client before connecting again".to_string(),
+            ));
+        }
+        let socket = Endpoint::open()?;
+        self.endpoint.attach(socket.clone())?;
+        if let Err(err) = socket.dial(&url) {
+            self.endpoint.socket = None; // 连接失败的 socket 不算连上，可以重新 connect
+            socket.close();
+            return Err(napi::Error::new(napi::Status::GenericFailure, format!("Connection failed: {:?}", err)));
+        }
+        self.endpoint.config.hold(&env, true)?;
+        self.endpoint.start();
+        Ok(())
+    }
+
+    // 注册供服务端反向调用的方法
+    #[napi]
+    pub fn handle(&self, env: Env, method: String, callback: ThreadsafeFunction<RpcCall>) -> Result<()> {
+        self.endpoint.handle(env, method, callback)
+    }
+
+    #[napi]
+    pub fn on_event(&self, env: Env, callback: ThreadsafeFunction<SocketEvent>) -> Result<()> {
+        self.endpoint.on_event(env, callback)
+    }
+
+    // 附带到事件和日志里的标签
+    #[napi]
+    pub fn set_labels(&self, labels: HashMap<String, String>) {
+        self.endpoint.config.events.labels().set(labels);
+    }
+
+    #[napi]
+    pub fn labels(&self) -> HashMap<String, String> {
+        self.endpoint.config.events.labels().get()
+    }
+
+    // 握手成功后服务端的版本和 metadata
+    #[napi]
+    pub fn server_info(&self) -> Option<PeerInfo> {
+        let peers = self.endpoint.shared.ready_peers();
+        peers.first().and_then(|pipe| self.endpoint.shared.peer_info(*pipe))
+    }
+
+    #[napi]
+    pub fn malformed_frames(&self) -> MalformedFrames {
+        self.endpoint.config.malformed.snapshot()
+    }
+
+    // 一次性调用，所有响应分段拼接后返回
+    #[napi(
+        ts_args_type = "method: string, data: Buffer | Uint8Array | string | ArrayBuffer, options?: CallOptions | undefined | null",
+        ts_return_type = "Promise<Buffer>"
+    )]
+    pub fn call(&self, env: Env, method: String, data: Payload, options: Option<CallOptions>) -> Result<JsObject> {
+        self.endpoint.call(env, 0, &method, &data, options)
+    }
+
+    // 流式调用，返回可 for await 的 RpcStream
+    #[napi(
+        ts_args_type = "method: string, data: Buffer | Uint8Array | string | ArrayBuffer, options?: CallOptions | undefined | null",
+        ts_return_type = "RpcStream & AsyncIterable<Buffer>"
+    )]
+    pub fn stream(&self, env: Env, method: String, data: Payload, options: Option<CallOptions>) -> Result<JsObject> {
+        self.endpoint.stream(env, 0, &method, &data, options)
+    }
+
+    #[napi(ts_args_type = "method: string, data: Buffer | Uint8Array | string | ArrayBuffer")]
+    pub fn notify(&self, method: String, data: Payload) -> Result<()> {
+        self.endpoint.notify(0, &method, &data)
+    }
+
+    #[napi]
+    pub fn close(&mut self, env: Env, options: Option<CloseOptions>) -> Result<()> {
+        self.endpoint.close(&env, options)
+    }
+}
+
+// 只清掉这次连接的状态；handler、握手、token、密钥和 onEvent 都在 Config 里，重新 connect 后继续用
+fn shut_down(shared: &Shared, socket: Option<PolySocket>, receiving: &AtomicBool, is_closing: &AtomicBool) {
+    if let Some(socket) = socket {
+        receiving.store(false, Ordering::SeqCst);
+        is_closing.store(true, Ordering::SeqCst);
+        socket.close();
+    }
+    shared.peers.lock().unwrap().clear();
+}
+
+// Detach 后的调用不会再有回复：自己的 timeoutMs 先到的照常按超时结束，其余的到 end 时以 SocketClosed 结束
+fn settle_detached(mut detached: Vec<Outstanding>, end: Instant) {
+    loop {
+        let now = Instant::now();
+        let (expired, rest): (Vec<Outstanding>, Vec<Outstanding>) =
+            detached.into_iter().partition(|outstanding| outstanding.deadline.is_some_and(|deadline| deadline <= now));
+        for outstanding in expired {
+            outstanding.call.time_out();
+        }
+        detached = rest;
+        if detached.is_empty() || now >= end {
+            break;
+        }
+        let next = detached.iter().filter_map(|outstanding| outstanding.deadline).fold(end, Instant::min);
+        std::thread::sleep(next - now);
+    }
+    for outstanding in detached {
+        outstanding.call.close("Socket closed before the reply arrived");
+    }
+}
+
+pub fn close_options(options: Option<CloseOptions>) -> (CloseMode, Duration) {
+    let (mode, wait_ms) = options.map(|options| (options.mode, options.wait_ms)).unwrap_or_default();
+    (mode.unwrap_or(CloseMode::Reject), Duration::from_millis(wait_ms.unwrap_or(DEFAULT_CLOSE_WAIT_MS) as u64))
+}
+
+// 回复按 (pipe, id) 对应到调用，其他对端发来的同 id 回复丢弃
+fn dispatch_response(pending: &Mutex<HashMap<u32, Outstanding>>, pipe: u32, frame: RpcFrame) {
+    let mut pending = pending.lock().unwrap();
+    let outstanding = match pending.get_mut(&frame.id) {
+        Some(outstanding) if outstanding.pipe == 0 || outstanding.pipe == pipe => outstanding,
+        _ => return, // 已经结束、未知的调用，或者不是发往的那个对端
+    };
+    outstanding.pipe = pipe;
+    let finished = match (&mut outstanding.call, frame.kind) {
+        (Pending::Call { buffer, .. }, KIND_CHUNK) => {
+            buffer.extend_from_slice(frame.payload);
+            false
+        }
+        (Pending::Stream(state), KIND_CHUNK) => {
+            state.lock().unwrap().push(frame.payload.to_vec());
+            false
+        }
+        (_, KIND_END) | (_, KIND_ERROR) => true,
+        _ => false,
+    };
+    if !finished {
+        return;
+    }
+
+    let error = if frame.kind == KIND_ERROR {
+        Some(String::from_utf8_lossy(frame.payload).into_owned())
+    } else {
+        None
+    };
+    match pending.remove(&frame.id).map(|outstanding| outstanding.call) {
+        Some(Pending::Call { mut buffer, deferred }) => match error {
+            Some(reason) => deferred.reject(napi::Error::new(napi::Status::GenericFailure, reason)),
+            None => {
+                buffer.extend_from_slice(frame.payload);
+                deferred.resolve(Box::new(move |_| Ok(buffer.into())));
+            }
+        },
+        Some(Pending::Stream(state)) => {
+            let mut state = state.lock().unwrap();
+            if error.is_none() && !frame.payload.is_empty() {
+                state.push(frame.payload.to_vec());
+            }
+            state.finish(error);
+        }
+        None => {}
+    }
+}
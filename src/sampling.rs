@@ -0,0 +1,125 @@
+use napi::Result;
+use napi_derive::napi;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+// 统计的采样方式，几种条件可以同时设置，消息要全部满足才会被记录；都不设置表示每条都记录
+#[napi(object)]
+pub struct TelemetrySampling {
+    pub every_nth: Option<u32>, // 每 N 条记录一条
+    pub probability: Option<f64>, // 每条按这个概率记录，(0, 1]
+    pub max_per_second: Option<u32>, // 每秒最多记录的条数
+}
+
+// 采样判断不加锁：高吞吐下统计本身不该成为瓶颈
+struct Inner {
+    every_nth: AtomicU32, // 0 表示不限
+    threshold: AtomicU64, // 随机数小于它才记录，u64::MAX 表示不限
+    max_per_second: AtomicU32, // 0 表示不限
+    seen: AtomicU64,
+    pending: AtomicU64, // 上次记录以来经过的消息数
+    window: AtomicU64, // 当前限速窗口（从 started 起的秒数）
+    window_count: AtomicU32,
+    started: Instant,
+    seed: u64,
+}
+
+#[derive(Clone)]
+pub struct Sampler {
+    inner: Arc<Inner>,
+}
+
+impl Default for Sampler {
+    fn default() -> Self {
+        Sampler {
+            inner: Arc::new(Inner {
+                every_nth: AtomicU32::new(0),
+                threshold: AtomicU64::new(u64::MAX),
+                max_per_second: AtomicU32::new(0),
+                seen: AtomicU64::new(0),
+                pending: AtomicU64::new(0),
+                window: AtomicU64::new(0),
+                window_count: AtomicU32::new(0),
+                started: Instant::now(),
+                seed: RandomState::new().hash_one(0u64),
+            }),
+        }
+    }
+}
+
+fn invalid(reason: String) -> napi::Error {
+    napi::Error::new(napi::Status::InvalidArg, reason)
+}
+
+// splitmix64，只用来做采样和故障注入的决定
+pub fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e3779b97f4a7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+impl Sampler {
+    // None 恢复为每条都记录
+    pub fn configure(&self, sampling: Option<TelemetrySampling>) -> Result<()> {
+        let sampling = sampling.unwrap_or(TelemetrySampling {
+            every_nth: None,
+            probability: None,
+            max_per_second: None,
+        });
+        if sampling.every_nth == Some(0) {
+            return Err(invalid("everyNth must be at least 1".to_string()));
+        }
+        if sampling.max_per_second == Some(0) {
+            return Err(invalid("maxPerSecond must be at least 1".to_string()));
+        }
+        let threshold = match sampling.probability {
+            None => u64::MAX,
+            Some(p) if p > 0.0 && p <= 1.0 => {
+                if p == 1.0 { u64::MAX } else { (p * u64::MAX as f64) as u64 }
+            }
+            Some(p) => return Err(invalid(format!("probability must be in (0, 1], got {}", p))),
+        };
+        let inner = &self.inner;
+        inner.every_nth.store(sampling.every_nth.unwrap_or(0), Ordering::Relaxed);
+        inner.threshold.store(threshold, Ordering::Relaxed);
+        inner.max_per_second.store(sampling.max_per_second.unwrap_or(0), Ordering::Relaxed);
+        inner.pending.store(0, Ordering::Relaxed);
+        Ok(())
+    }
+
+    // 这条消息要记录时返回它代表的消息数（上次记录以来经过的条数），
+    // 计数乘上它就是总量的估计；不记录时返回 None
+    pub fn sample(&self) -> Option<u64> {
+        let inner = &self.inner;
+        let every_nth = inner.every_nth.load(Ordering::Relaxed);
+        let threshold = inner.threshold.load(Ordering::Relaxed);
+        let max_per_second = inner.max_per_second.load(Ordering::Relaxed);
+        if every_nth <= 1 && threshold == u64::MAX && max_per_second == 0 {
+            return Some(1);
+        }
+
+        let seen = inner.seen.fetch_add(1, Ordering::Relaxed);
+        inner.pending.fetch_add(1, Ordering::Relaxed);
+        if every_nth > 1 && !seen.is_multiple_of(every_nth as u64) {
+            return None;
+        }
+        if threshold != u64::MAX && mix(inner.seed ^ seen) >= threshold {
+            return None;
+        }
+        if max_per_second > 0 {
+            let now = inner.started.elapsed().as_secs();
+            let window = inner.window.load(Ordering::Relaxed);
+            if window != now && inner.window.compare_exchange(window, now, Ordering::Relaxed, Ordering::Relaxed).is_ok() {
+                inner.window_count.store(0, Ordering::Relaxed);
+            }
+            if inner.window_count.fetch_add(1, Ordering::Relaxed) >= max_per_second {
+                return None;
+            }
+        }
+        Some(inner.pending.swap(0, Ordering::Relaxed).max(1))
+    }
+}
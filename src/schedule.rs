@@ -0,0 +1,142 @@
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::events::EventEmitter;
+use crate::guard;
+use crate::outbox::{Outbox, Outgoing};
+
+// 时间轮的精度和槽数：延迟按 10ms 向上取整，一圈 2.56 秒，更长的延迟记下还要转几圈
+const TICK: Duration = Duration::from_millis(10);
+const SLOTS: u64 = 256;
+
+struct Entry {
+    rounds: u64, // 还要经过几圈才到期
+    outgoing: Outgoing,
+}
+
+struct Wheel {
+    slots: Vec<Vec<Entry>>,
+    tick: u64, // 已经处理到的刻度，当前槽为 tick % SLOTS
+    pending: usize,
+    stopped: bool,
+}
+
+struct Shared {
+    wheel: Mutex<Wheel>,
+    wake: Condvar,
+    started: Instant,
+}
+
+// sendAfter 用的定时器：每个 socket 一个时间轮，由一个后台线程推进，到期的消息进入 sendAsync 的发送队列。
+// 线程在第一次 sendAfter 时启动，没有待发消息时不会空转
+pub struct Scheduler {
+    shared: Arc<Shared>,
+    outbox: Outbox,
+    events: EventEmitter,
+    running: bool,
+}
+
+impl Scheduler {
+    pub fn new(outbox: Outbox, events: EventEmitter) -> Self {
+        let wheel = Wheel {
+            slots: (0..SLOTS).map(|_| Vec::new()).collect(),
+            tick: 0,
+            pending: 0,
+            stopped: false,
+        };
+        Scheduler {
+            shared: Arc::new(Shared { wheel: Mutex::new(wheel), wake: Condvar::new(), started: Instant::now() }),
+            outbox,
+            events,
+            running: false,
+        }
+    }
+
+    pub fn schedule(&mut self, outgoing: Outgoing, delay: Duration) {
+        if !self.running {
+            self.running = true;
+            let shared = self.shared.clone();
+            let outbox = self.outbox.clone();
+            guard::spawn(self.events.clone(), "Scheduler", move || run(shared, outbox));
+        }
+        let shared = &self.shared;
+        let mut wheel = shared.wheel.lock().unwrap();
+        let now = elapsed_ticks(shared);
+        if wheel.pending == 0 {
+            // 空闲时线程不推进刻度，直接跳到当前时间
+            wheel.tick = wheel.tick.max(now);
+        }
+        let due = ticks(shared.started.elapsed() + delay).max(wheel.tick + 1);
+        let offset = due - wheel.tick;
+        let slot = ((wheel.tick + offset) % SLOTS) as usize;
+        wheel.slots[slot].push(Entry { rounds: (offset - 1) / SLOTS, outgoing });
+        wheel.pending += 1;
+        shared.wake.notify_one();
+    }
+}
+
+// socket 关闭时还没到期的消息交给死信出口，Promise 以 SocketClosed reject
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        let mut wheel = self.shared.wheel.lock().unwrap();
+        wheel.stopped = true;
+        wheel.pending = 0;
+        let entries: Vec<Entry> = wheel.slots.iter_mut().flat_map(std::mem::take).collect();
+        drop(wheel);
+        self.shared.wake.notify_one();
+        for entry in entries {
+            self.outbox.abandon(entry.outgoing);
+        }
+    }
+}
+
+// 向上取整的刻度数
+fn ticks(duration: Duration) -> u64 {
+    let tick = TICK.as_nanos();
+    duration.as_nanos().div_ceil(tick) as u64
+}
+
+fn elapsed_ticks(shared: &Shared) -> u64 {
+    (shared.started.elapsed().as_nanos() / TICK.as_nanos()) as u64
+}
+
+fn run(shared: Arc<Shared>, outbox: Outbox) {
+    let mut wheel = shared.wheel.lock().unwrap();
+    loop {
+        if wheel.stopped {
+            return;
+        }
+        if wheel.pending == 0 {
+            wheel = shared.wake.wait(wheel).unwrap();
+            continue;
+        }
+        let now = elapsed_ticks(&shared);
+        if wheel.tick >= now {
+            let next = shared.started + Duration::from_nanos(TICK.as_nanos() as u64 * (wheel.tick + 1));
+            let wait = next.saturating_duration_since(Instant::now());
+            wheel = shared.wake.wait_timeout(wheel, wait).unwrap().0;
+            continue;
+        }
+        // 落后的刻度逐个补上，每个刻度只看一个槽
+        let mut due = Vec::new();
+        while wheel.tick < now && wheel.pending > 0 {
+            wheel.tick += 1;
+            let slot = (wheel.tick % SLOTS) as usize;
+            let entries = std::mem::take(&mut wheel.slots[slot]);
+            for mut entry in entries {
+                if entry.rounds == 0 {
+                    wheel.pending -= 1;
+                    due.push(entry.outgoing);
+                } else {
+                    entry.rounds -= 1;
+                    wheel.slots[slot].push(entry);
+                }
+            }
+        }
+        drop(wheel);
+        for outgoing in due {
+            outbox.enqueue(outgoing);
+        }
+        wheel = shared.wheel.lock().unwrap();
+    }
+}
@@ -0,0 +1,158 @@
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::context::{Closing, Registry};
+use crate::outbox::{self, Outbox};
+
+const DEFAULT_DRAIN_MS: u32 = 5000;
+
+#[napi(object)]
+pub struct SignalOptions {
+    pub drain_ms: Option<u32>, // 最多等这么久让 sendAsync 的队列发完，默认 5000
+}
+
+// 信号属于整个进程，不分 env
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+// 调用过 installSignalHandlers 的 env 的登记表，关闭流程处理这些 env 里打开着的 socket。
+// env 销毁时它的登记表已经清空，留在这里也不会再碰到已经关闭的 socket
+static REGISTRIES: Mutex<Vec<Registry>> = Mutex::new(Vec::new());
+
+fn for_each<F: FnMut(&Closing)>(mut f: F) {
+    for registry in REGISTRIES.lock().unwrap().iter() {
+        registry.for_each(&mut f);
+    }
+}
+
+// 关闭流程：调用过 installSignalHandlers 的 env 里的 SocketWrapper 停止交付收到的消息，等发送队列发完（最多 drain），
+// 然后关闭进程里所有的 nng socket，包括 Publisher、JobQueue 等内部的，接收线程随之退出
+fn shut_down(drain: Duration) {
+    let mut outboxes: Vec<Outbox> = Vec::new();
+    for_each(|entry| {
+        entry.recv_paused.store(true, Ordering::SeqCst);
+        outboxes.push(entry.outbox.clone());
+    });
+    outbox::wait_flushed(&outboxes, drain);
+    for_each(|entry| {
+        entry.is_closing.store(true, Ordering::SeqCst); // 接收线程不再把关闭报成错误
+    });
+    unsafe { nng::ffi::nng_closeall() };
+}
+
+// 在原生代码里处理退出信号：收到时执行上面的关闭流程，再按信号的默认行为结束进程，
+// JS 线程卡住、JS 的信号处理函数没有机会运行时也能清理。关闭流程中再收到一次信号时立即结束。
+// 支持 SIGINT、SIGTERM、SIGHUP 和 SIGQUIT，只在 Unix 上可用。
+// 之后再用 process.on 监听同一个信号会取代这里的处理
+#[napi]
+pub fn install_signal_handlers(env: Env, signals: Vec<String>, options: Option<SignalOptions>) -> Result<()> {
+    let drain = Duration::from_millis(options.and_then(|options| options.drain_ms).unwrap_or(DEFAULT_DRAIN_MS) as u64);
+    let numbers = signals
+        .iter()
+        .map(|name| {
+            sys::number(name).ok_or_else(|| {
+                napi::Error::new(napi::Status::InvalidArg, format!("Unsupported signal: {}", name))
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let registry = Registry::of(&env)?;
+    let mut registries = REGISTRIES.lock().unwrap();
+    if !registries.iter().any(|known| known.same(&registry)) {
+        registries.push(registry);
+    }
+    drop(registries);
+    sys::install(&numbers, drain)
+}
+
+#[cfg(unix)]
+mod sys {
+    use napi::Result;
+    use std::os::raw::c_int;
+    use std::sync::atomic::{AtomicI32, Ordering};
+    use std::time::Duration;
+
+    use super::SHUTTING_DOWN;
+    use crate::events::EventEmitter;
+    use crate::guard;
+
+    extern "C" {
+        fn signal(signum: c_int, handler: usize) -> usize;
+        fn raise(signum: c_int) -> c_int;
+        fn pipe(fds: *mut c_int) -> c_int;
+        fn read(fd: c_int, buf: *mut u8, count: usize) -> isize;
+        fn write(fd: c_int, buf: *const u8, count: usize) -> isize;
+    }
+
+    const SIG_DFL: usize = 0;
+
+    // 信号处理函数写、关闭线程读的管道，-1 表示还没创建
+    static WAKE: AtomicI32 = AtomicI32::new(-1);
+
+    // 这几个信号在 Linux 和 macOS 上编号相同
+    pub fn number(name: &str) -> Option<c_int> {
+        match name {
+            "SIGHUP" => Some(1),
+            "SIGINT" => Some(2),
+            "SIGQUIT" => Some(3),
+            "SIGTERM" => Some(15),
+            _ => None,
+        }
+    }
+
+    // 信号处理函数里只做异步信号安全的事：第一次把信号编号写进管道，之后直接按默认行为结束
+    extern "C" fn on_signal(signum: c_int) {
+        if SHUTTING_DOWN.swap(true, Ordering::SeqCst) {
+            unsafe {
+                signal(signum, SIG_DFL);
+                raise(signum);
+            }
+            return;
+        }
+        let byte = signum as u8;
+        unsafe { write(WAKE.load(Ordering::SeqCst), &byte, 1) };
+    }
+
+    // 重复调用时沿用第一次的管道和关闭线程，drain 以第一次为准
+    pub fn install(signals: &[c_int], drain: Duration) -> Result<()> {
+        if WAKE.load(Ordering::SeqCst) < 0 {
+            let mut fds: [c_int; 2] = [-1; 2];
+            if unsafe { pipe(fds.as_mut_ptr()) } != 0 {
+                return Err(napi::Error::new(
+                    napi::Status::GenericFailure,
+                    format!("Failed to create signal pipe: {}", std::io::Error::last_os_error()),
+                ));
+            }
+            WAKE.store(fds[1], Ordering::SeqCst);
+            let reader = fds[0];
+            guard::spawn(EventEmitter::default(), "Signal shutdown", move || {
+                let mut byte = 0u8;
+                while unsafe { read(reader, &mut byte, 1) } != 1 {}
+                super::shut_down(drain);
+                let signum = byte as c_int;
+                unsafe {
+                    signal(signum, SIG_DFL);
+                    raise(signum);
+                }
+            });
+        }
+        for signum in signals {
+            unsafe { signal(*signum, on_signal as extern "C" fn(c_int) as usize) };
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(unix))]
+mod sys {
+    use napi::Result;
+    use std::time::Duration;
+
+    pub fn number(_name: &str) -> Option<i32> {
+        None
+    }
+
+    pub fn install(_signals: &[i32], _drain: Duration) -> Result<()> {
+        Err(napi::Error::new(napi::Status::GenericFailure, "Signal handlers are only supported on Unix".to_string()))
+    }
+}
@@ -0,0 +1,79 @@
+use napi::{sys, Env, JsBuffer, NapiRaw, NapiValue, Result};
+use std::ptr;
+use std::sync::{Arc, Mutex};
+
+// 默认的初始容量，放不下的消息到来时翻倍扩容
+pub const DEFAULT_SLAB_BYTES: usize = 64 * 1024;
+
+struct Slab {
+    reference: sys::napi_ref, // 持有 Buffer 的强引用，不会被 GC
+    capacity: usize,
+}
+
+// 只在 JS 线程上读写
+unsafe impl Send for Slab {}
+
+#[derive(Default)]
+struct State {
+    slab: Option<Slab>,
+    released: bool, // socket 已关闭：之后才到的消息各自复制一份，不再重新创建 slab
+}
+
+fn check(status: sys::napi_status, what: &str) -> Result<()> {
+    if status == sys::Status::napi_ok {
+        Ok(())
+    } else {
+        Err(napi::Error::new(napi::Status::GenericFailure, format!("Failed to {} receive slab", what)))
+    }
+}
+
+// recvBorrowed 反复使用的同一个 Buffer：每条消息拷进去后把它交给回调，
+// 热路径上不再为每条消息分配 Buffer，GC 就没有东西可回收。
+// 内容只在下一次回调之前有效，需要保留时由调用方自己复制
+#[derive(Clone, Default)]
+pub struct BorrowedSlab {
+    state: Arc<Mutex<State>>,
+}
+
+impl BorrowedSlab {
+    // JS 线程调用：把 data 拷进 slab，返回 slab 对应的 Buffer（长度是容量，不是 data 的长度）
+    pub fn fill(&self, env: &Env, data: &[u8], initial: usize) -> Result<JsBuffer> {
+        let mut state = self.state.lock().unwrap();
+        if state.released {
+            return Ok(env.create_buffer_with_data(data.to_vec())?.into_raw());
+        }
+        let slab = &mut state.slab;
+        let capacity = slab.as_ref().map(|slab| slab.capacity).unwrap_or(0);
+        if capacity < data.len() {
+            let capacity = data.len().max(initial).max(capacity * 2);
+            if let Some(old) = slab.take() {
+                // 旧 Buffer 交给 GC；JS 里还拿着它的地方读到的是上一条消息
+                unsafe { sys::napi_delete_reference(env.raw(), old.reference) };
+            }
+            let buffer = env.create_buffer(capacity)?.into_raw();
+            let mut reference = ptr::null_mut();
+            check(unsafe { sys::napi_create_reference(env.raw(), buffer.raw(), 1, &mut reference) }, "retain")?;
+            *slab = Some(Slab { reference, capacity });
+        }
+        let reference = slab.as_ref().map(|slab| slab.reference).unwrap_or(ptr::null_mut());
+        let mut value = ptr::null_mut();
+        check(unsafe { sys::napi_get_reference_value(env.raw(), reference, &mut value) }, "read")?;
+        let mut buffer = unsafe { JsBuffer::from_raw_unchecked(env.raw(), value) }.into_value()?;
+        buffer[..data.len()].copy_from_slice(data);
+        Ok(buffer.into_raw())
+    }
+
+    // 开始新的 recvBorrowed 时调用
+    pub fn reset(&self) {
+        self.state.lock().unwrap().released = false;
+    }
+
+    // JS 线程调用，socket 关闭时释放 slab
+    pub fn release(&self, env: &Env) {
+        let mut state = self.state.lock().unwrap();
+        state.released = true;
+        if let Some(slab) = state.slab.take() {
+            unsafe { sys::napi_delete_reference(env.raw(), slab.reference) };
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use napi_derive::napi;
+use nng::{options::{Options, Url}, Pipe};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::events::{EventEmitter, SocketEvent};
+use crate::guard;
+use crate::nanomsg::pipe_id;
+use crate::stats::StatsSnapshot;
+
+#[napi]
+pub enum SlowConsumerPolicy {
+    Skip,       // 只上报事件，nng 继续丢弃该订阅者放不下的消息
+    Disconnect, // 关闭该订阅者的 pipe
+    // 把整个 socket 的 SendBufferSize 加大到 bufferLimit，溢出后再上报。
+    // nng 的 pub0 没有单个 pipe 的队列选项，所有订阅者（包括之后连上的）都用这个长度
+    SocketBuffer,
+}
+
+#[napi(object)]
+pub struct SlowConsumerOptions {
+    pub policy: SlowConsumerPolicy,
+    pub grace_ms: Option<u32>,          // 队列持续满多久才算慢消费者，默认 1000
+    pub buffer_limit: Option<u32>,      // SocketBuffer 策略下整个 socket 的发送队列长度，默认 1024
+    pub check_interval_ms: Option<u32>, // 检查间隔，默认 100
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Action {
+    Skip,
+    Disconnect,
+    Buffer,
+}
+
+#[derive(Clone, Copy)]
+struct Config {
+    action: Action,
+    grace: Duration,
+    interval: Duration,
+    queue_len: u64,
+}
+
+struct TrackedPipe {
+    pipe: Pipe,
+    attached_at: u64, // pipe 建立时已发布的消息数
+    prev_published: u64,
+    prev_tx: u64,
+    lagging_since: Option<Instant>,
+    flagged: bool,
+}
+
+// 通过 nng 统计里每个 pipe 的 tx_msgs 与已发布数量对比，判断发送队列是否一直是满的
+// inproc 传输不更新 tx_msgs，所以不参与检测
+#[derive(Clone, Default)]
+pub struct SlowConsumerMonitor {
+    pipes: Arc<Mutex<HashMap<u32, TrackedPipe>>>,
+    published: Arc<AtomicU64>,
+    config: Arc<Mutex<Option<Config>>>,
+    running: Arc<AtomicBool>,
+}
+
+impl SlowConsumerMonitor {
+    pub fn record_published(&self, count: u64) {
+        self.published.fetch_add(count, Ordering::SeqCst);
+    }
+
+    pub fn pipe_added(&self, pipe: Pipe) {
+        if is_inproc(pipe) {
+            return;
+        }
+        let published = self.published.load(Ordering::SeqCst);
+        self.pipes.lock().unwrap().insert(
+            pipe_id(pipe),
+            TrackedPipe {
+                pipe,
+                attached_at: published,
+                prev_published: published,
+                prev_tx: 0,
+                lagging_since: None,
+                flagged: false,
+            },
+        );
+    }
+
+    pub fn pipe_removed(&self, pipe: Pipe) {
+        self.pipes.lock().unwrap().remove(&pipe_id(pipe));
+    }
+
+    // queue_len 是当前 socket 每个 pipe 的发送队列长度
+    pub fn configure(&self, options: &SlowConsumerOptions, queue_len: u64, events: EventEmitter) {
+        let action = match options.policy {
+            SlowConsumerPolicy::Skip => Action::Skip,
+            SlowConsumerPolicy::Disconnect => Action::Disconnect,
+            SlowConsumerPolicy::SocketBuffer => Action::Buffer,
+        };
+        *self.config.lock().unwrap() = Some(Config {
+            action,
+            grace: Duration::from_millis(options.grace_ms.unwrap_or(1000) as u64),
+            interval: Duration::from_millis(options.check_interval_ms.unwrap_or(100).max(1) as u64),
+            queue_len,
+        });
+
+        if !self.running.swap(true, Ordering::SeqCst) {
+            let monitor = self.clone();
+            guard::spawn(events.clone(), "Slow consumer monitor", move || monitor.run(events));
+        }
+    }
+
+    pub fn stop(&self) {
+        self.running.store(false, Ordering::SeqCst);
+    }
+
+    fn run(self, events: EventEmitter) {
+        while self.running.load(Ordering::SeqCst) {
+            let config = match *self.config.lock().unwrap() {
+                Some(config) => config,
+                None => return,
+            };
+            std::thread::sleep(config.interval);
+            if let Some(snapshot) = StatsSnapshot::take() {
+                self.check(&snapshot, config, &events);
+            }
+        }
+    }
+
+    fn check(&self, snapshot: &StatsSnapshot, config: Config, events: &EventEmitter) {
+        let counters = snapshot.pipe_counters();
+        let published = self.published.load(Ordering::SeqCst);
+        let now = Instant::now();
+        let mut pipes = self.pipes.lock().unwrap();
+        let mut disconnected = Vec::new();
+
+        for (id, tracked) in pipes.iter_mut() {
+            let tx = match counters.get(id) {
+                Some(counters) => counters.tx_msgs,
+                None => continue,
+            };
+            let published_delta = published.saturating_sub(tracked.prev_published);
+            let tx_delta = tx.saturating_sub(tracked.prev_tx);
+            let backlog = published.saturating_sub(tracked.attached_at).saturating_sub(tx);
+            tracked.prev_published = published;
+            tracked.prev_tx = tx;
+
+            let lagging = tx_delta < published_delta && backlog >= config.queue_len;
+            if !lagging {
+                tracked.lagging_since = None;
+                if tracked.flagged {
+                    tracked.flagged = false;
+                    events.emit(SocketEvent::new("slowConsumerRecovered").pipe(*id));
+                }
+                continue;
+            }
+
+            let since = *tracked.lagging_since.get_or_insert(now);
+            if tracked.flagged || now.duration_since(since) < config.grace {
+                continue;
+            }
+            tracked.flagged = true;
+            match config.action {
+                Action::Disconnect => {
+                    tracked.pipe.close();
+                    disconnected.push(*id);
+                    events.emit(
+                        SocketEvent::new("slowConsumerDisconnected")
+                            .pipe(*id)
+                            .value(backlog as i64),
+                    );
+                }
+                Action::Skip => events.emit(
+                    SocketEvent::new("slowConsumer")
+                        .pipe(*id)
+                        .message("send queue full, messages are being skipped")
+                        .value(backlog as i64),
+                ),
+                Action::Buffer => events.emit(
+                    SocketEvent::new("slowConsumer")
+                        .pipe(*id)
+                        .message(format!("send buffer of {} messages exhausted", config.queue_len))
+                        .value(backlog as i64),
+                ),
+            }
+        }
+
+        for id in disconnected {
+            pipes.remove(&id);
+        }
+    }
+}
+
+pub fn is_inproc(pipe: Pipe) -> bool {
+    let url = match (pipe.listener(), pipe.dialer()) {
+        (Some(listener), _) => listener.get_opt::<Url>(),
+        (None, Some(dialer)) => dialer.get_opt::<Url>(),
+        _ => return false,
+    };
+    url.map(|url| url.starts_with("inproc://")).unwrap_or(false)
+}
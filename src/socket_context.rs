@@ -0,0 +1,214 @@
+use napi::bindgen_prelude::*;
+use napi::{JsDeferred, JsObject};
+use napi_derive::napi;
+use nng::options::protocol::survey::SurveyTime;
+use nng::options::{Options, RecvTimeout, SendTimeout};
+use nng::{Aio, AioResult, Error as NngError, Message, Socket};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::capture::Capture;
+use crate::events::EventEmitter;
+use crate::guard;
+use crate::nanomsg::reject_closed;
+use crate::nng_error::reject_nng;
+use crate::outbox::Outbox;
+use crate::payload::Payload;
+
+type Resolver<T = ()> = Box<dyn FnOnce(Env) -> Result<T> + Send>;
+
+// socket 上的一个 context：Req0、Rep0、Surveyor0 和 Respondent0 的每个 context 各自维护请求状态，
+// 同一个 socket 上可以同时进行多个请求，回复只交给发出请求的那个 context。
+// 一个 context 同一时间只做一个操作，在它上面的调用按顺序一个接一个地进行
+#[napi]
+pub struct Context {
+    socket: Socket,
+    context: Option<nng::Context>,
+    outbox: Option<Outbox>,
+    capture: Capture,
+    events: EventEmitter,
+    turn: Arc<Mutex<()>>,
+}
+
+impl Context {
+    pub fn open(socket: Socket, outbox: Option<Outbox>, capture: Capture, events: EventEmitter) -> Result<Self> {
+        let context = nng::Context::new(&socket).map_err(|err| match err {
+            NngError::NotSupported => napi::Error::new(
+                napi::Status::InvalidArg,
+                "Contexts are only supported on Req0, Rep0, Surveyor0 and Respondent0 sockets that are not raw".to_string(),
+            ),
+            err => napi::Error::new(napi::Status::GenericFailure, format!("Failed to open context: {:?}", err)),
+        })?;
+        Ok(Context { socket, context: Some(context), outbox, capture, events, turn: Arc::default() })
+    }
+
+    fn operation(&self) -> Result<Operation> {
+        let context = self.context.clone().ok_or_else(|| {
+            napi::Error::new(napi::Status::GenericFailure, "Context closed".to_string())
+        })?;
+        Ok(Operation {
+            socket: self.socket.clone(),
+            context,
+            outbox: self.outbox.clone(),
+            capture: self.capture.clone(),
+            turn: self.turn.clone(),
+        })
+    }
+}
+
+#[napi]
+impl Context {
+    #[napi(getter)]
+    pub fn id(&self) -> u32 {
+        self.context.as_ref().map_or(0, |context| unsafe { nng::ffi::nng_ctx_id(context.nng_ctx()) } as u32)
+    }
+
+    // 交给 nng 后 resolve。Rep0 和 Respondent0 上是对这个 context 上一次 recv 收到的请求的回复
+    #[napi(ts_args_type = "message: Buffer | Uint8Array | string | ArrayBuffer", ts_return_type = "Promise<void>")]
+    pub fn send(&self, env: Env, message: Payload) -> Result<JsObject> {
+        let operation = self.operation()?;
+        let message = Message::from(&message[..]);
+        let (deferred, promise) = env.create_deferred::<(), Resolver>()?;
+        guard::spawn(self.events.clone(), "Context", move || {
+            let _turn = operation.turn.lock().unwrap();
+            settle(deferred, operation.send(message), |_| ());
+        });
+        Ok(promise)
+    }
+
+    // 在这个 context 上收一条消息。timeoutMs 不传或为 0 时使用 socket 的接收超时
+    #[napi(ts_return_type = "Promise<Buffer>")]
+    pub fn recv(&self, env: Env, timeout_ms: Option<u32>) -> Result<JsObject> {
+        let operation = self.operation()?;
+        let (deferred, promise) = env.create_deferred::<Buffer, Resolver<Buffer>>()?;
+        guard::spawn(self.events.clone(), "Context", move || {
+            let _turn = operation.turn.lock().unwrap();
+            settle(deferred, operation.recv(timeout_ms), |message| message.as_slice().to_vec().into());
+        });
+        Ok(promise)
+    }
+
+    // 发送后在同一个 context 上等回复，用于 Req0 和 Surveyor0
+    #[napi(ts_args_type = "message: Buffer | Uint8Array | string | ArrayBuffer, timeoutMs?: number | undefined | null", ts_return_type = "Promise<Buffer>")]
+    pub fn request(&self, env: Env, message: Payload, timeout_ms: Option<u32>) -> Result<JsObject> {
+        let operation = self.operation()?;
+        let message = Message::from(&message[..]);
+        let (deferred, promise) = env.create_deferred::<Buffer, Resolver<Buffer>>()?;
+        guard::spawn(self.events.clone(), "Context", move || {
+            let _turn = operation.turn.lock().unwrap();
+            let response = operation.send(message).and_then(|_| operation.recv(timeout_ms));
+            settle(deferred, response, |message| message.as_slice().to_vec().into());
+        });
+        Ok(promise)
+    }
+
+    // 进行中的操作以 SocketClosed reject；socket 关闭时它上面的 context 随之关闭
+    #[napi]
+    pub fn close(&mut self) {
+        if let Some(context) = self.context.take() {
+            context.close();
+        }
+    }
+}
+
+// 在后台线程里进行的一次操作
+struct Operation {
+    socket: Socket,
+    context: nng::Context,
+    outbox: Option<Outbox>,
+    capture: Capture,
+    turn: Arc<Mutex<()>>,
+}
+
+impl Operation {
+    fn send(&self, message: Message) -> std::result::Result<(), NngError> {
+        let snapshot = self.capture.snapshot(&message);
+        let (aio, results) = aio(self.socket.get_opt::<SendTimeout>().ok().flatten())?;
+        hand_over(&self.context, &aio, &results, message)?;
+        if let Some(outbox) = &self.outbox {
+            outbox.record_handed();
+        }
+        self.capture.sent(snapshot);
+        Ok(())
+    }
+
+    fn recv(&self, timeout_ms: Option<u32>) -> std::result::Result<Message, NngError> {
+        let timeout = match timeout_ms.filter(|ms| *ms > 0) {
+            Some(ms) => Some(Duration::from_millis(ms as u64)),
+            None => self.socket.get_opt::<RecvTimeout>().ok().flatten(),
+        };
+        let (aio, results) = aio(timeout)?;
+        self.context.recv(&aio)?;
+        let message = match results.recv() {
+            Ok((_, AioResult::Recv(result))) => result?,
+            _ => return Err(NngError::Closed),
+        };
+        if let Some(outbox) = &self.outbox {
+            outbox.record_received();
+        }
+        self.capture.received(&message);
+        Ok(message)
+    }
+}
+
+// 回调拿到的 aio 句柄跟结果一起送回调用线程，由调用线程释放，不会在 nng 的回调里释放最后一个句柄
+fn aio(timeout: Option<Duration>) -> std::result::Result<(Aio, mpsc::Receiver<(Aio, AioResult)>), NngError> {
+    let (results, received) = mpsc::channel();
+    let aio = Aio::new(move |aio, result| {
+        let _ = results.send((aio, result));
+    })?;
+    aio.set_timeout(timeout)?;
+    Ok((aio, received))
+}
+
+// 在一个新的 context 上发起一次调查，截止时间只设在这个 context 上，不影响 socket 的设置和同时进行的其它调查。
+// 每个回复到达时交给 each，截止时间到了返回全部回复
+pub fn survey<S, F>(socket: &Socket, message: Message, deadline: Duration, sent: S, mut each: F) -> std::result::Result<Vec<Message>, NngError>
+where
+    S: FnOnce(),
+    F: FnMut(&Message),
+{
+    let context = nng::Context::new(socket)?;
+    context.set_opt::<SurveyTime>(Some(deadline))?;
+    let (aio, results) = aio(socket.get_opt::<SendTimeout>().ok().flatten())?;
+    hand_over(&context, &aio, &results, message)?;
+    sent();
+    aio.set_timeout(None)?; // 接收只等到调查的截止时间
+    let mut replies = Vec::new();
+    loop {
+        context.recv(&aio)?;
+        match results.recv() {
+            Ok((_, AioResult::Recv(Ok(reply)))) => {
+                each(&reply);
+                replies.push(reply);
+            }
+            Ok((_, AioResult::Recv(Err(NngError::TimedOut)))) => return Ok(replies),
+            Ok((_, AioResult::Recv(Err(err)))) => return Err(err),
+            _ => return Err(NngError::Closed),
+        }
+    }
+}
+
+fn hand_over(context: &nng::Context, aio: &Aio, results: &mpsc::Receiver<(Aio, AioResult)>, message: Message) -> std::result::Result<(), NngError> {
+    context.send(aio, message).map_err(|(_, err)| err)?;
+    match results.recv() {
+        Ok((_, AioResult::Send(Ok(())))) => Ok(()),
+        Ok((_, AioResult::Send(Err((_, err))))) => Err(err),
+        _ => Err(NngError::Closed),
+    }
+}
+
+fn settle<T, R, F>(deferred: JsDeferred<R, Resolver<R>>, result: std::result::Result<T, NngError>, convert: F)
+where
+    T: Send + 'static,
+    R: ToNapiValue + 'static,
+    F: FnOnce(T) -> R + Send + 'static,
+{
+    match result {
+        Ok(value) => deferred.resolve(Box::new(move |_| Ok(convert(value)))),
+        Err(NngError::Canceled | NngError::Closed) => reject_closed(deferred, "Context closed"),
+        Err(NngError::TimedOut) => reject_nng(deferred, NngError::TimedOut, "Operation timed out".to_string()),
+        Err(err) => reject_nng(deferred, err, format!("Context error: {:?}", err)),
+    }
+}
@@ -0,0 +1,83 @@
+use nng::ffi;
+use std::collections::HashMap;
+use std::ffi::CStr;
+
+// nng 内部统计树里单个 pipe 的计数
+#[derive(Default, Clone, Copy)]
+pub struct PipeCounters {
+    pub rx_msgs: u64,
+    pub tx_msgs: u64,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+}
+
+// nng_stats_get 的快照，析构时释放
+pub struct StatsSnapshot {
+    root: *mut ffi::nng_stat,
+}
+
+impl StatsSnapshot {
+    pub fn take() -> Option<Self> {
+        let mut root = std::ptr::null_mut();
+        let rv = unsafe { ffi::nng_stats_get(&mut root) };
+        if rv != 0 || root.is_null() {
+            return None;
+        }
+        Some(StatsSnapshot { root })
+    }
+
+    // 按 pipe id 汇总所有 pipe 的收发计数
+    pub fn pipe_counters(&self) -> HashMap<u32, PipeCounters> {
+        let mut pipes = HashMap::new();
+        visit(self.root, &mut |stat| {
+            if stat_name(stat) != "pipe" {
+                return;
+            }
+            let mut id = None;
+            let mut counters = PipeCounters::default();
+            let mut child = unsafe { ffi::nng_stat_child(stat) };
+            while !child.is_null() {
+                let value = unsafe { ffi::nng_stat_value(child) };
+                match stat_name(child).as_str() {
+                    "id" => id = Some(value as u32),
+                    "rx_msgs" => counters.rx_msgs = value,
+                    "tx_msgs" => counters.tx_msgs = value,
+                    "rx_bytes" => counters.rx_bytes = value,
+                    "tx_bytes" => counters.tx_bytes = value,
+                    _ => {}
+                }
+                child = unsafe { ffi::nng_stat_next(child) };
+            }
+            if let Some(id) = id {
+                pipes.insert(id, counters);
+            }
+        });
+        pipes
+    }
+}
+
+impl Drop for StatsSnapshot {
+    fn drop(&mut self) {
+        unsafe { ffi::nng_stats_free(self.root) };
+    }
+}
+
+fn visit(stat: *mut ffi::nng_stat, f: &mut dyn FnMut(*mut ffi::nng_stat)) {
+    if stat.is_null() {
+        return;
+    }
+    f(stat);
+    let mut child = unsafe { ffi::nng_stat_child(stat) };
+    while !child.is_null() {
+        visit(child, f);
+        child = unsafe { ffi::nng_stat_next(child) };
+    }
+}
+
+fn stat_name(stat: *mut ffi::nng_stat) -> String {
+    let name = unsafe { ffi::nng_stat_name(stat) };
+    if name.is_null() {
+        return String::new();
+    }
+    unsafe { CStr::from_ptr(name) }.to_string_lossy().into_owned()
+}
@@ -0,0 +1,148 @@
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+use nng::{Protocol, Socket};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::hashing::HashRing;
+use crate::nanomsg::ProtocolType;
+use crate::payload::Payload;
+use crate::poly::{PolyPipeEvent, PolySocket};
+
+// 按 key 做一致性哈希的分发器，同一个 key 的消息总是落到同一个 worker
+//
+// Pair1 模式使用一个 polyamorous socket，按 pipe 路由；
+// Push0 模式下 nng 无法指定 pipe，所以每个 worker 单独一个 Push socket。
+#[napi]
+pub struct StickyRouter {
+    protocol: Protocol,
+    socket: Option<PolySocket>, // Pair1 共享 socket
+    workers: HashMap<u32, Socket>, // Push0 每个 worker 的 socket
+    ring: Arc<Mutex<HashRing>>,
+    next_worker: u32,
+}
+
+#[napi]
+impl StickyRouter {
+    #[napi(constructor)]
+    pub fn new(protocol: ProtocolType) -> Result<Self> {
+        let protocol: Protocol = protocol.into();
+        let ring = Arc::new(Mutex::new(HashRing::new()));
+
+        let socket = match protocol {
+            Protocol::Push0 => None,
+            Protocol::Pair1 => {
+                let socket = PolySocket::open().map_err(|err| {
+                    napi::Error::new(napi::Status::GenericFailure, format!("Socket creation failed: {:?}", err))
+                })?;
+                let notify_ring = ring.clone();
+                socket
+                    .pipe_notify(move |id, event| match event {
+                        PolyPipeEvent::Added => notify_ring.lock().unwrap().add(id),
+                        PolyPipeEvent::Removed => notify_ring.lock().unwrap().remove(id),
+                    })
+                    .map_err(|err| {
+                        napi::Error::new(napi::Status::GenericFailure, format!("Failed to watch pipes: {:?}", err))
+                    })?;
+                Some(socket)
+            }
+            _ => {
+                return Err(napi::Error::new(
+                    napi::Status::InvalidArg,
+                    "Sticky routing only supports Push0 and Pair1".to_string(),
+                ))
+            }
+        };
+
+        Ok(StickyRouter {
+            protocol,
+            socket,
+            workers: HashMap::new(),
+            ring,
+            next_worker: 1,
+        })
+    }
+
+    // Pair1 模式下监听地址，worker 主动连接进来
+    #[napi]
+    pub fn listen(&self, url: String) -> Result<()> {
+        let socket = self.socket.as_ref().ok_or_else(|| {
+            napi::Error::new(napi::Status::GenericFailure, "listen is only available in Pair1 mode".to_string())
+        })?;
+        socket.listen(&url).map_err(|err| {
+            napi::Error::new(napi::Status::GenericFailure, format!("Listen failed: {:?}", err))
+        })
+    }
+
+    // 连接一个 worker，返回它在哈希环上的 id（Pair1 模式下返回 0，id 在 pipe 建立后才分配）
+    #[napi]
+    pub fn add_worker(&mut self, url: String) -> Result<u32> {
+        if let Some(socket) = &self.socket {
+            socket.dial(&url).map_err(|err| {
+                napi::Error::new(napi::Status::GenericFailure, format!("Connection failed: {:?}", err))
+            })?;
+            return Ok(0);
+        }
+
+        let socket = Socket::new(self.protocol).map_err(|err| {
+            napi::Error::new(napi::Status::GenericFailure, format!("Socket creation failed: {:?}", err))
+        })?;
+        socket.dial(&url).map_err(|err| {
+            napi::Error::new(napi::Status::GenericFailure, format!("Connection failed: {:?}", err))
+        })?;
+
+        let id = self.next_worker;
+        self.next_worker += 1;
+        self.workers.insert(id, socket);
+        self.ring.lock().unwrap().add(id);
+        Ok(id)
+    }
+
+    // 移除一个 Push0 worker，只有落在它上面的 key 会被重新分配
+    #[napi]
+    pub fn remove_worker(&mut self, id: u32) -> bool {
+        match self.workers.remove(&id) {
+            Some(socket) => {
+                self.ring.lock().unwrap().remove(id);
+                socket.close();
+                true
+            }
+            None => false,
+        }
+    }
+
+    #[napi]
+    pub fn worker_count(&self) -> u32 {
+        self.ring.lock().unwrap().len() as u32
+    }
+
+    // 按 key 选择 worker 发送，返回选中的 worker id
+    #[napi(ts_args_type = "key: string, message: Buffer | Uint8Array | string | ArrayBuffer")]
+    pub fn send(&self, key: String, message: Payload) -> Result<u32> {
+        let target = self.ring.lock().unwrap().get(key.as_bytes()).ok_or_else(|| {
+            napi::Error::new(napi::Status::GenericFailure, "No worker available".to_string())
+        })?;
+        let result = match &self.socket {
+            Some(socket) => socket.send_to(target, &message),
+            None => {
+                let socket = self.workers.get(&target).ok_or_else(|| {
+                    napi::Error::new(napi::Status::GenericFailure, "Worker went away".to_string())
+                })?;
+                socket.send(&message[..]).map_err(|(_, e)| e)
+            }
+        };
+        result.map_err(|e| napi::Error::new(napi::Status::GenericFailure, format!("Send error: {:?}", e)))?;
+        Ok(target)
+    }
+
+    #[napi]
+    pub fn close(&mut self) {
+        if let Some(socket) = self.socket.take() {
+            socket.close();
+        }
+        for (_, socket) in self.workers.drain() {
+            socket.close();
+        }
+        *self.ring.lock().unwrap() = HashRing::new();
+    }
+}
@@ -0,0 +1,114 @@
+use napi::Result;
+use napi_derive::napi;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::events::{EventEmitter, SocketEvent};
+use crate::guard;
+
+const DEFAULT_WINDOW_MS: u32 = 100;
+
+// broker 重启之类的情况下几千条 pipe 同时断开，逐条回调会把 JS 线程堵住。
+// 开启后断开不再逐条调用 onPipeRemoved，一阵断开平息 windowMs 后汇总成一个 connectionLost 事件
+#[napi(object)]
+pub struct DisconnectCoalescing {
+    pub window_ms: Option<u32>,      // 多久没有新的断开算平息，默认 100
+    pub pause_senders: Option<bool>, // 一条 pipe 都不剩时 sendAsync 的消息先留着，第一条 pipe 回来后再发，默认 true
+}
+
+#[derive(Default)]
+struct Burst {
+    window: Option<Duration>, // None 表示没有开启
+    pause: bool,
+    lost: u64,
+    started: Option<Instant>, // 这一阵断开的第一条
+    last: Option<Instant>,
+    lost_all: bool, // 上一次汇总时一条 pipe 都不剩，第一条 pipe 回来时发 connectionRestored
+}
+
+#[derive(Clone, Default)]
+pub struct DisconnectStorm {
+    burst: Arc<Mutex<Burst>>,
+}
+
+impl DisconnectStorm {
+    // None 关闭
+    pub fn configure(&self, options: Option<DisconnectCoalescing>) -> Result<()> {
+        let mut burst = self.burst.lock().unwrap();
+        let Some(options) = options else {
+            burst.window = None;
+            burst.pause = false;
+            return Ok(());
+        };
+        let window_ms = options.window_ms.unwrap_or(DEFAULT_WINDOW_MS);
+        if window_ms == 0 {
+            return Err(napi::Error::new(napi::Status::InvalidArg, "windowMs must be greater than 0".to_string()));
+        }
+        burst.window = Some(Duration::from_millis(window_ms as u64));
+        burst.pause = options.pause_senders.unwrap_or(true);
+        Ok(())
+    }
+
+    pub fn pauses_senders(&self) -> bool {
+        self.burst.lock().unwrap().pause
+    }
+
+    // 记下一次断开，开启时返回 true，调用方不再逐条回调。
+    // remaining 读取当前剩下的 pipe 数，汇总时调用
+    pub fn pipe_removed<F>(&self, events: &EventEmitter, remaining: F) -> bool
+    where
+        F: Fn() -> usize + Send + 'static,
+    {
+        let mut burst = self.burst.lock().unwrap();
+        let Some(window) = burst.window else {
+            return false;
+        };
+        let now = Instant::now();
+        burst.lost += 1;
+        burst.last = Some(now);
+        if burst.started.is_some() {
+            return true;
+        }
+        burst.started = Some(now);
+        drop(burst);
+
+        let shared = self.burst.clone();
+        let task_events = events.clone();
+        guard::spawn(events.clone(), "Disconnect storm", move || loop {
+            std::thread::sleep(window);
+            let mut burst = shared.lock().unwrap();
+            let (Some(started), Some(last)) = (burst.started, burst.last) else {
+                return;
+            };
+            if last.elapsed() < window {
+                continue;
+            }
+            let lost = std::mem::take(&mut burst.lost);
+            burst.started = None;
+            let remaining = remaining();
+            burst.lost_all = remaining == 0;
+            drop(burst);
+            task_events.emit(
+                SocketEvent::new("connectionLost")
+                    .message(format!(
+                        "Lost {} pipes in {}ms, {} still connected",
+                        lost,
+                        last.duration_since(started).as_millis(),
+                        remaining
+                    ))
+                    .value(lost as i64),
+            );
+            return;
+        });
+        true
+    }
+
+    // 汇总时已经断光，这是回来的第一条 pipe
+    pub fn pipe_added(&self, events: &EventEmitter) {
+        let mut burst = self.burst.lock().unwrap();
+        if burst.window.is_some() && std::mem::take(&mut burst.lost_all) {
+            drop(burst);
+            events.emit(SocketEvent::new("connectionRestored"));
+        }
+    }
+}
@@ -0,0 +1,322 @@
+use napi::{
+    bindgen_prelude::*,
+    sys,
+    threadsafe_function::{ThreadsafeFunction, ThreadsafeFunctionCallMode},
+};
+use napi_derive::napi;
+use nng::{
+    options::{
+        protocol::pubsub::{Subscribe, Unsubscribe},
+        Options,
+    },
+    PipeEvent, Protocol, Socket,
+};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::events::{EventEmitter, SocketEvent};
+use crate::frames::{FrameErrors, MalformedFrames};
+use crate::guard;
+use crate::nanomsg::pipe_id;
+use crate::sampling::TelemetrySampling;
+use crate::topic::{self, TopicMessage};
+use crate::topic_metrics::{TopicMetrics, TopicTraffic};
+
+#[napi(object)]
+pub struct SubscriptionStats {
+    pub topic: String,
+    pub received: i64,
+    pub dropped: i64, // 按发布端序号的缺口统计，通常是 nng 接收缓冲区溢出
+    pub lag: i64,     // 已收到但还没交给 JS 回调的消息数
+    pub labels: HashMap<String, String>,
+}
+
+#[derive(Default)]
+struct TopicCounters {
+    received: i64,
+    dropped: i64,
+    dispatched: i64,
+    last_sequence: Option<u64>,
+}
+
+type Counters = Arc<Mutex<HashMap<String, TopicCounters>>>;
+
+// to_napi_value 在 JS 线程上、调用回调之前执行，用它记录已分发的消息
+pub struct Delivery {
+    message: TopicMessage,
+    counters: Counters,
+}
+
+impl ToNapiValue for Delivery {
+    unsafe fn to_napi_value(env: sys::napi_env, val: Self) -> Result<sys::napi_value> {
+        if let Some(counters) = val.counters.lock().unwrap().get_mut(&val.message.topic) {
+            counters.dispatched += 1;
+        }
+        TopicMessage::to_napi_value(env, val.message)
+    }
+}
+
+// 基于主题帧的订阅端，和 Publisher 配套使用
+#[napi]
+pub struct Subscriber {
+    socket: Option<Socket>,
+    receiving: Arc<AtomicBool>,
+    is_closing: Arc<AtomicBool>,
+    live_topics: Arc<Mutex<HashSet<String>>>, // 已经收到实时消息的主题，之后的重放直接丢弃
+    snapshot_topics: Arc<Mutex<HashSet<String>>>, // 已经收到快照的主题，之后给其他新订阅者的快照直接丢弃
+    counters: Counters, // 键就是当前的订阅列表，重连后按它重新订阅
+    events: EventEmitter,
+    topic_metrics: TopicMetrics,
+    malformed: FrameErrors,
+}
+
+#[napi]
+impl Subscriber {
+    #[napi(constructor)]
+    pub fn new() -> Self {
+        Subscriber {
+            socket: None,
+            receiving: Arc::new(AtomicBool::new(false)),
+            is_closing: Arc::new(AtomicBool::new(false)),
+            live_topics: Arc::new(Mutex::new(HashSet::new())),
+            snapshot_topics: Arc::new(Mutex::new(HashSet::new())),
+            counters: Arc::new(Mutex::new(HashMap::new())),
+            events: EventEmitter::default(),
+            topic_metrics: TopicMetrics::default(),
+            malformed: FrameErrors::default(),
+        }
+    }
+
+    // 异步拨号，发布端晚于订阅端启动时会自动重连
+    #[napi]
+    pub fn connect(&mut self, url: String) -> Result<()> {
+        let socket = match &self.socket {
+            Some(socket) => socket.clone(),
+            None => self.open()?,
+        };
+        socket.dial_async(&url).map_err(|err| {
+            napi::Error::new(napi::Status::GenericFailure, format!("Connection failed: {:?}", err))
+        })?;
+        self.socket = Some(socket);
+        Ok(())
+    }
+
+    fn open(&self) -> Result<Socket> {
+        let socket = Socket::new(Protocol::Sub0).map_err(|err| {
+            napi::Error::new(napi::Status::GenericFailure, format!("Socket creation failed: {:?}", err))
+        })?;
+        // 同一个 dialer 再次建立 pipe 就是重连：发布端可能已经重启，按订阅列表重新订阅，
+        // 清掉实时主题、快照和序号，重启后的保留消息、快照和从 1 开始的序号按新订阅处理。
+        // 在单独的线程里做，不在 nng 的 pipe 回调里设置选项
+        let seen: Mutex<HashSet<u32>> = Mutex::new(HashSet::new());
+        let resubscribe_socket = socket.clone();
+        let live_topics = self.live_topics.clone();
+        let snapshot_topics = self.snapshot_topics.clone();
+        let counters = self.counters.clone();
+        let events = self.events.clone();
+        socket
+            .pipe_notify(move |pipe, event| {
+                let dialer = match (event, pipe.dialer()) {
+                    (PipeEvent::AddPost, Some(dialer)) => dialer,
+                    _ => return,
+                };
+                let id = unsafe { nng::ffi::nng_dialer_id(dialer.nng_dialer()) } as u32;
+                if seen.lock().unwrap().insert(id) {
+                    return;
+                }
+                let socket = resubscribe_socket.clone();
+                let live_topics = live_topics.clone();
+                let snapshot_topics = snapshot_topics.clone();
+                let counters = counters.clone();
+                let events = events.clone();
+                guard::spawn(events.clone(), "Resubscribe", move || {
+                    live_topics.lock().unwrap().clear();
+                    snapshot_topics.lock().unwrap().clear();
+                    let topics: Vec<String> = {
+                        let mut counters = counters.lock().unwrap();
+                        counters.values_mut().for_each(|counters| counters.last_sequence = None);
+                        counters.keys().cloned().collect()
+                    };
+                    for topic in &topics {
+                        if let Err(err) = socket.set_opt::<Subscribe>(topic::subscription(topic)) {
+                            events.warn("resubscribeFailed", format!("Failed to resubscribe to {}: {:?}", topic, err));
+                        }
+                    }
+                    events.emit(SocketEvent::new("resubscribed").pipe(pipe_id(pipe)).value(topics.len() as i64));
+                });
+            })
+            .map_err(|err| napi::Error::new(napi::Status::GenericFailure, format!("Failed to watch pipes: {:?}", err)))?;
+        Ok(socket)
+    }
+
+    // 可以在 connect 之前调用：连上时发布端马上重放保留消息，先订阅才不会被 nng 过滤掉
+    #[napi]
+    pub fn subscribe(&mut self, topic: String) -> Result<()> {
+        topic::check_topic(&topic)?;
+        if self.socket.is_none() {
+            self.socket = Some(self.open()?);
+        }
+        self.socket()?.set_opt::<Subscribe>(topic::subscription(&topic)).map_err(|err| {
+            napi::Error::new(napi::Status::GenericFailure, format!("Failed to subscribe: {:?}", err))
+        })?;
+        self.counters.lock().unwrap().entry(topic).or_default();
+        Ok(())
+    }
+
+    #[napi]
+    pub fn unsubscribe(&self, topic: String) -> Result<()> {
+        topic::check_topic(&topic)?;
+        self.socket()?.set_opt::<Unsubscribe>(topic::subscription(&topic)).map_err(|err| {
+            napi::Error::new(napi::Status::GenericFailure, format!("Failed to unsubscribe: {:?}", err))
+        })?;
+        self.live_topics.lock().unwrap().remove(&topic);
+        self.snapshot_topics.lock().unwrap().remove(&topic);
+        self.counters.lock().unwrap().remove(&topic);
+        Ok(())
+    }
+
+    // 附带到统计和日志里的标签
+    #[napi]
+    pub fn set_labels(&self, labels: HashMap<String, String>) {
+        self.events.labels().set(labels);
+    }
+
+    #[napi]
+    pub fn labels(&self) -> HashMap<String, String> {
+        self.events.labels().get()
+    }
+
+    // 重连后重新订阅时发出 resubscribed 事件，value 是重新订阅的主题数
+    #[napi]
+    pub fn on_event(&self, callback: ThreadsafeFunction<SocketEvent>) {
+        self.events.set(callback);
+    }
+
+    #[napi]
+    pub fn stats(&self) -> Vec<SubscriptionStats> {
+        let labels = self.events.labels().get();
+        self.counters
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(topic, counters)| SubscriptionStats {
+                topic: topic.clone(),
+                received: counters.received,
+                dropped: counters.dropped,
+                lag: counters.received - counters.dispatched,
+                labels: labels.clone(),
+            })
+            .collect()
+    }
+
+    // 收到后丢弃的畸形主题帧，按原因计数
+    #[napi]
+    pub fn malformed_frames(&self) -> MalformedFrames {
+        self.malformed.snapshot()
+    }
+
+    // 按主题的消息数和字节数
+    #[napi]
+    pub fn topic_stats(&self) -> Vec<TopicTraffic> {
+        self.topic_metrics.snapshot()
+    }
+
+    // 最多跟踪的主题数（默认 1000），超出时淘汰最久没有流量的主题；0 表示关闭
+    #[napi]
+    pub fn set_topic_stats_limit(&self, limit: u32) {
+        self.topic_metrics.set_limit(limit);
+    }
+
+    // 主题统计的采样方式，高吞吐时降低统计开销；不传恢复为每条都记录
+    #[napi]
+    pub fn set_topic_stats_sampling(&self, sampling: Option<TelemetrySampling>) -> Result<()> {
+        self.topic_metrics.set_sampling(sampling)
+    }
+
+    #[napi(ts_args_type = "callback: (err: Error | null, arg: TopicMessage) => any")]
+    pub fn recv(&self, callback: ThreadsafeFunction<Delivery>) -> Result<()> {
+        let socket = self.socket()?.clone();
+        let receiving = self.receiving.clone();
+        let is_closing = self.is_closing.clone();
+        let live_topics = self.live_topics.clone();
+        let snapshot_topics = self.snapshot_topics.clone();
+        let counters = self.counters.clone();
+        let events = self.events.clone();
+        let topic_metrics = self.topic_metrics.clone();
+        let malformed = self.malformed.clone();
+
+        guard::spawn(events.clone(), "Receive loop", move || {
+            receiving.store(true, Ordering::SeqCst);
+            while receiving.load(Ordering::SeqCst) {
+                match socket.recv() {
+                    Ok(message) => {
+                        let frame = match topic::decode(message.as_slice()) {
+                            Ok(frame) => frame,
+                            Err(err) => {
+                                malformed.record(err, &events);
+                                continue;
+                            }
+                        };
+                        let message = frame.to_message();
+                        if message.snapshot {
+                            // 快照不管之前有没有收到增量都交付，但每个主题只交付一次；
+                            // 先到的增量已经包含在快照里，应用快照时整体替换即可
+                            if !snapshot_topics.lock().unwrap().insert(message.topic.clone()) {
+                                continue;
+                            }
+                            live_topics.lock().unwrap().insert(message.topic.clone());
+                        } else {
+                            let mut live_topics = live_topics.lock().unwrap();
+                            if !message.replayed {
+                                live_topics.insert(message.topic.clone());
+                            } else if live_topics.contains(&message.topic) {
+                                continue; // 重连后发布端的重放，已经收到过实时数据
+                            }
+                        }
+                        topic_metrics.record(&message.topic, message.data.len());
+                        if let Some(counters) = counters.lock().unwrap().get_mut(&message.topic) {
+                            counters.received += 1;
+                            if let (Some(sequence), false) = (frame.sequence, message.replayed) {
+                                if let Some(last) = counters.last_sequence {
+                                    if sequence > last + 1 {
+                                        counters.dropped += (sequence - last - 1) as i64;
+                                    }
+                                }
+                                counters.last_sequence = Some(sequence);
+                            }
+                        }
+                        let delivery = Delivery {
+                            message,
+                            counters: counters.clone(),
+                        };
+                        let _ = callback.call(Ok(delivery), ThreadsafeFunctionCallMode::NonBlocking);
+                    }
+                    Err(e) => {
+                        if is_closing.load(Ordering::SeqCst) {
+                            return; // 主动关闭时不报错
+                        }
+                        events.warn("recvFailed", format!("Error receiving message: {:?}", e));
+                    }
+                }
+            }
+        });
+        Ok(())
+    }
+
+    #[napi]
+    pub fn close(&mut self) {
+        if let Some(socket) = self.socket.take() {
+            self.receiving.store(false, Ordering::SeqCst);
+            self.is_closing.store(true, Ordering::SeqCst);
+            self.events.clear();
+            socket.close();
+        }
+    }
+
+    fn socket(&self) -> Result<&Socket> {
+        self.socket.as_ref().ok_or_else(|| {
+            napi::Error::new(napi::Status::GenericFailure, "Socket not connected".to_string())
+        })
+    }
+}
@@ -0,0 +1,231 @@
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+use nng::{ffi, Error as NngError};
+use std::ffi::CString;
+use std::num::NonZeroU32;
+use std::os::raw::{c_char, c_int};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use crate::events::{EventEmitter, SocketEvent};
+use crate::guard;
+
+// nng-sys 自带的绑定里没有 TLS 配置相关的函数，这里自己声明。
+// 这些函数总会编译进 nng，没有启用 TLS 引擎（cargo feature "tls"）时返回 NotSupported。
+#[repr(C)]
+struct NngTlsConfig {
+    _private: [u8; 0],
+}
+
+const NNG_TLS_MODE_SERVER: c_int = 1;
+
+extern "C" {
+    fn nng_tls_config_alloc(cfg: *mut *mut NngTlsConfig, mode: c_int) -> c_int;
+    fn nng_tls_config_free(cfg: *mut NngTlsConfig);
+    fn nng_tls_config_cert_key_file(cfg: *mut NngTlsConfig, path: *const c_char, pass: *const c_char) -> c_int;
+    fn nng_tls_config_ca_file(cfg: *mut NngTlsConfig, path: *const c_char) -> c_int;
+    fn nng_tls_config_auth_mode(cfg: *mut NngTlsConfig, mode: c_int) -> c_int;
+}
+
+#[napi]
+pub enum TlsAuthMode {
+    None,
+    Optional,
+    Required,
+}
+
+#[napi(object)]
+#[derive(Clone)]
+pub struct TlsOptions {
+    pub cert_key_file: String, // PEM 格式，证书和私钥放在同一个文件里
+    pub ca_file: Option<String>,
+    pub auth_mode: Option<TlsAuthMode>, // 是否校验客户端证书，默认不校验
+    pub watch_interval_ms: Option<u32>, // 设置后定期检查文件修改时间，变化时自动重新加载
+}
+
+impl TlsAuthMode {
+    // nng 的 NNG_TLS_AUTH_MODE_*
+    pub fn nng_mode(self) -> c_int {
+        match self {
+            TlsAuthMode::None => 0,
+            TlsAuthMode::Optional => 1,
+            TlsAuthMode::Required => 2,
+        }
+    }
+}
+
+pub fn check(rv: c_int) -> std::result::Result<(), NngError> {
+    match NonZeroU32::new(rv as u32) {
+        None => Ok(()),
+        Some(code) => Err(NngError::from(code)),
+    }
+}
+
+fn c_string(value: &str) -> Result<CString> {
+    CString::new(value).map_err(|_| {
+        napi::Error::new(napi::Status::InvalidArg, format!("Invalid path: {:?}", value))
+    })
+}
+
+// 持有一份 nng TLS 配置的引用
+struct Config(*mut NngTlsConfig);
+
+impl Drop for Config {
+    fn drop(&mut self) {
+        unsafe { nng_tls_config_free(self.0) };
+    }
+}
+
+impl Config {
+    fn load(options: &TlsOptions) -> Result<Config> {
+        let fail = |what: &str, err: NngError| {
+            napi::Error::new(napi::Status::GenericFailure, format!("Failed to {}: {:?}", what, err))
+        };
+        let mut cfg = std::ptr::null_mut();
+        check(unsafe { nng_tls_config_alloc(&mut cfg, NNG_TLS_MODE_SERVER) })
+            .map_err(|err| fail("create TLS config", err))?;
+        let config = Config(cfg);
+
+        let cert_key_file = c_string(&options.cert_key_file)?;
+        check(unsafe { nng_tls_config_cert_key_file(config.0, cert_key_file.as_ptr(), std::ptr::null()) })
+            .map_err(|err| fail("load certificate", err))?;
+        if let Some(ca_file) = &options.ca_file {
+            let ca_file = c_string(ca_file)?;
+            check(unsafe { nng_tls_config_ca_file(config.0, ca_file.as_ptr()) })
+                .map_err(|err| fail("load CA bundle", err))?;
+        }
+        let auth_mode = options.auth_mode.map_or(0, TlsAuthMode::nng_mode);
+        check(unsafe { nng_tls_config_auth_mode(config.0, auth_mode) })
+            .map_err(|err| fail("set TLS auth mode", err))?;
+        Ok(config)
+    }
+}
+
+struct Inner {
+    handle: ffi::nng_listener,
+    options: Mutex<TlsOptions>,
+    watching: AtomicBool,
+    events: EventEmitter,
+}
+
+// 可以在运行中替换证书的 TLS 监听器
+//
+// nng 的 listener 只在接受新连接时读取配置，替换配置不影响已经建立的 pipe。
+#[derive(Clone)]
+pub struct TlsListener {
+    inner: Arc<Inner>,
+}
+
+unsafe impl Send for Inner {}
+unsafe impl Sync for Inner {}
+
+impl TlsListener {
+    pub fn start(socket: ffi::nng_socket, url: &str, options: TlsOptions, events: EventEmitter) -> Result<Self> {
+        let config = Config::load(&options)?;
+        let c_url = c_string(url)?;
+        let mut handle = ffi::nng_listener::default();
+        check(unsafe { ffi::nng_listener_create(&mut handle, socket, c_url.as_ptr()) }).map_err(|err| {
+            napi::Error::new(napi::Status::GenericFailure, format!("Listen failed: {:?}", err))
+        })?;
+        let listener = TlsListener {
+            inner: Arc::new(Inner {
+                handle,
+                options: Mutex::new(options),
+                watching: AtomicBool::new(false),
+                events,
+            }),
+        };
+        let started = listener.apply(&config).and_then(|_| {
+            check(unsafe { ffi::nng_listener_start(handle, 0) }).map_err(|err| {
+                napi::Error::new(napi::Status::GenericFailure, format!("Listen failed: {:?}", err))
+            })
+        });
+        if let Err(err) = started {
+            listener.close();
+            return Err(err);
+        }
+        listener.watch();
+        Ok(listener)
+    }
+
+    // 重新加载证书；传入新的选项时同时替换文件路径。加载失败时保留原来的配置
+    pub fn reload(&self, options: Option<TlsOptions>) -> Result<()> {
+        let options = match options {
+            Some(options) => options,
+            None => self.inner.options.lock().unwrap().clone(),
+        };
+        let config = Config::load(&options)?;
+        self.apply(&config)?;
+        let watch_changed = options.watch_interval_ms != self.inner.options.lock().unwrap().watch_interval_ms;
+        *self.inner.options.lock().unwrap() = options;
+        if watch_changed {
+            self.watch();
+        }
+        self.inner.events.emit(SocketEvent::new("tlsReloaded"));
+        Ok(())
+    }
+
+    pub fn close(&self) {
+        self.inner.watching.store(false, Ordering::SeqCst);
+        unsafe { ffi::nng_listener_close(self.inner.handle) };
+    }
+
+    fn apply(&self, config: &Config) -> Result<()> {
+        // listener 会持有自己的引用，Config 释放的只是我们这一份
+        let name = ffi::NNG_OPT_TLS_CONFIG.as_ptr() as *const c_char;
+        check(unsafe { ffi::nng_listener_set_ptr(self.inner.handle, name, config.0 as *mut _) }).map_err(|err| {
+            napi::Error::new(napi::Status::GenericFailure, format!("Failed to apply TLS config: {:?}", err))
+        })
+    }
+
+    // 轮询文件修改时间，避免为了监听文件引入额外依赖
+    fn watch(&self) {
+        let interval = match self.inner.options.lock().unwrap().watch_interval_ms {
+            Some(interval) if interval > 0 => Duration::from_millis(interval as u64),
+            _ => {
+                self.inner.watching.store(false, Ordering::SeqCst);
+                return;
+            }
+        };
+        if self.inner.watching.swap(true, Ordering::SeqCst) {
+            return; // 已有的线程会读到新的间隔
+        }
+        let weak = Arc::downgrade(&self.inner);
+        guard::spawn(self.inner.events.clone(), "TLS watcher", move || {
+            let mut last = None;
+            loop {
+                let inner = match weak.upgrade() {
+                    Some(inner) if inner.watching.load(Ordering::SeqCst) => inner,
+                    _ => return,
+                };
+                let stamp = Some(modified(&inner.options.lock().unwrap()));
+                if last.is_some() && stamp != last {
+                    let listener = TlsListener { inner: inner.clone() };
+                    if let Err(err) = listener.reload(None) {
+                        inner.events.emit(SocketEvent::new("tlsReloadFailed").message(err.reason));
+                    }
+                }
+                last = stamp;
+                let interval = inner
+                    .options
+                    .lock()
+                    .unwrap()
+                    .watch_interval_ms
+                    .map(|ms| Duration::from_millis(ms as u64))
+                    .unwrap_or(interval);
+                drop(inner);
+                std::thread::sleep(interval);
+            }
+        });
+    }
+}
+
+fn modified(options: &TlsOptions) -> Vec<Option<SystemTime>> {
+    let stamp = |path: &str| std::fs::metadata(path).and_then(|meta| meta.modified()).ok();
+    let mut stamps = vec![stamp(&options.cert_key_file)];
+    if let Some(ca_file) = &options.ca_file {
+        stamps.push(stamp(ca_file));
+    }
+    stamps
+}
@@ -0,0 +1,93 @@
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+
+use crate::frames::FrameError;
+
+// 主题帧格式：topic 字节 + 0x00 + flags(1 字节) + [序号(8 字节大端)] + payload
+// 订阅 "topic\0" 即可精确匹配
+pub const TOPIC_DELIMITER: u8 = 0;
+
+pub const FLAG_REPLAYED: u8 = 0x01; // 发布端重放的保留消息
+pub const FLAG_SEQUENCED: u8 = 0x02; // flags 后面带有 8 字节的主题内序号
+pub const FLAG_SNAPSHOT: u8 = 0x04; // 新订阅者连上时发布端提供的全量快照，总是和 FLAG_REPLAYED 一起出现
+const KNOWN_FLAGS: u8 = FLAG_REPLAYED | FLAG_SEQUENCED | FLAG_SNAPSHOT;
+
+#[napi(object)]
+pub struct TopicMessage {
+    pub topic: String,
+    pub data: Buffer,
+    pub replayed: bool,
+    pub snapshot: bool,
+}
+
+pub struct Frame<'a> {
+    pub topic: &'a str,
+    pub flags: u8,
+    pub sequence: Option<u64>,
+    pub payload: &'a [u8],
+}
+
+pub fn check_topic(topic: &str) -> Result<()> {
+    if topic.as_bytes().contains(&TOPIC_DELIMITER) {
+        return Err(napi::Error::new(
+            napi::Status::InvalidArg,
+            format!("Topic must not contain NUL bytes: {:?}", topic),
+        ));
+    }
+    Ok(())
+}
+
+pub fn encode(topic: &str, flags: u8, sequence: Option<u64>, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(topic.len() + 10 + payload.len());
+    frame.extend_from_slice(topic.as_bytes());
+    frame.push(TOPIC_DELIMITER);
+    match sequence {
+        Some(sequence) => {
+            frame.push(flags | FLAG_SEQUENCED);
+            frame.extend_from_slice(&sequence.to_be_bytes());
+        }
+        None => frame.push(flags & !FLAG_SEQUENCED),
+    }
+    frame.extend_from_slice(payload);
+    frame
+}
+
+// 订阅用的前缀，带上分隔符避免 "foo" 匹配到 "foobar"
+pub fn subscription(topic: &str) -> Vec<u8> {
+    let mut prefix = topic.as_bytes().to_vec();
+    prefix.push(TOPIC_DELIMITER);
+    prefix
+}
+
+pub fn decode(frame: &[u8]) -> std::result::Result<Frame<'_>, FrameError> {
+    let pos = frame.iter().position(|b| *b == TOPIC_DELIMITER).ok_or(FrameError::MissingDelimiter)?;
+    let topic = std::str::from_utf8(&frame[..pos]).map_err(|_| FrameError::InvalidUtf8)?;
+    let flags = *frame.get(pos + 1).ok_or(FrameError::Truncated)?;
+    if flags & !KNOWN_FLAGS != 0 {
+        return Err(FrameError::UnknownFlags);
+    }
+    let mut rest = &frame[pos + 2..];
+    let mut sequence = None;
+    if flags & FLAG_SEQUENCED != 0 {
+        let bytes: [u8; 8] = rest.get(..8).ok_or(FrameError::Truncated)?.try_into().unwrap();
+        sequence = Some(u64::from_be_bytes(bytes));
+        rest = &rest[8..];
+    }
+    Ok(Frame {
+        topic,
+        flags,
+        sequence,
+        payload: rest,
+    })
+}
+
+impl Frame<'_> {
+    pub fn to_message(&self) -> TopicMessage {
+        TopicMessage {
+            topic: self.topic.to_string(),
+            data: self.payload.into(),
+            replayed: self.flags & FLAG_REPLAYED != 0,
+            snapshot: self.flags & FLAG_SNAPSHOT != 0,
+        }
+    }
+}
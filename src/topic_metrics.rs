@@ -0,0 +1,128 @@
+use napi::Result;
+use napi_derive::napi;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::sampling::{Sampler, TelemetrySampling};
+
+// 默认最多跟踪的主题数
+const DEFAULT_TOPIC_LIMIT: u32 = 1000;
+
+#[napi(object)]
+pub struct TopicTraffic {
+    pub topic: String,
+    pub messages: i64,
+    pub bytes: i64, // payload 字节数，不含主题帧头
+    // 开启采样时两者都是按采样权重推算的估计值
+}
+
+struct Entry {
+    messages: i64,
+    bytes: i64,
+    last_seen: u64,
+}
+
+struct Inner {
+    limit: usize,
+    tick: u64,
+    topics: HashMap<String, Entry>,
+}
+
+// 按主题统计流量，主题数超过上限时淘汰最久没有流量的主题，避免主题基数失控
+#[derive(Clone)]
+pub struct TopicMetrics {
+    inner: Arc<Mutex<Inner>>,
+    sampler: Sampler,
+}
+
+impl Default for TopicMetrics {
+    fn default() -> Self {
+        TopicMetrics {
+            inner: Arc::new(Mutex::new(Inner {
+                limit: DEFAULT_TOPIC_LIMIT as usize,
+                tick: 0,
+                topics: HashMap::new(),
+            })),
+            sampler: Sampler::default(),
+        }
+    }
+}
+
+impl TopicMetrics {
+    pub fn record(&self, topic: &str, bytes: usize) {
+        // 没被采到的消息不碰锁
+        let weight = match self.sampler.sample() {
+            Some(weight) => weight as i64,
+            None => return,
+        };
+        let mut inner = self.inner.lock().unwrap();
+        if inner.limit == 0 {
+            return;
+        }
+        inner.tick += 1;
+        let tick = inner.tick;
+        if let Some(entry) = inner.topics.get_mut(topic) {
+            entry.messages += weight;
+            entry.bytes += bytes as i64 * weight;
+            entry.last_seen = tick;
+            return;
+        }
+        if inner.topics.len() >= inner.limit {
+            inner.evict(1);
+        }
+        inner.topics.insert(
+            topic.to_string(),
+            Entry {
+                messages: weight,
+                bytes: bytes as i64 * weight,
+                last_seen: tick,
+            },
+        );
+    }
+
+    // 0 表示关闭统计并清空
+    pub fn set_limit(&self, limit: u32) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.limit = limit as usize;
+        let excess = inner.topics.len().saturating_sub(inner.limit);
+        inner.evict(excess);
+    }
+
+    pub fn set_sampling(&self, sampling: Option<TelemetrySampling>) -> Result<()> {
+        self.sampler.configure(sampling)
+    }
+
+    // 按字节数从大到小排列
+    pub fn snapshot(&self) -> Vec<TopicTraffic> {
+        let inner = self.inner.lock().unwrap();
+        let mut traffic: Vec<TopicTraffic> = inner
+            .topics
+            .iter()
+            .map(|(topic, entry)| TopicTraffic {
+                topic: topic.clone(),
+                messages: entry.messages,
+                bytes: entry.bytes,
+            })
+            .collect();
+        traffic.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.topic.cmp(&b.topic)));
+        traffic
+    }
+}
+
+impl Inner {
+    fn evict(&mut self, count: usize) {
+        for _ in 0..count {
+            let oldest = self
+                .topics
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_seen)
+                .map(|(topic, _)| topic.clone());
+            match oldest {
+                Some(topic) => {
+                    self.topics.remove(&topic);
+                }
+                None => return,
+            }
+        }
+    }
+}
@@ -0,0 +1,533 @@
+use napi::{
+    bindgen_prelude::*,
+    threadsafe_function::{ThreadsafeFunction, ThreadsafeFunctionCallMode},
+    Env, JsDeferred, JsObject,
+};
+use napi_derive::napi;
+use nng::options::{protocol::pubsub::Subscribe, Options, SendTimeout};
+use nng::{Message, Protocol, Socket};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::events::{EventEmitter, SocketEvent};
+use crate::frames::{take_str, FrameError, FrameErrors, MalformedFrames};
+use crate::guard;
+use crate::nanomsg::reject_closed;
+use crate::payload::Payload;
+use crate::rpc::{close_options, decode_frame, encode_frame, CloseMode, CloseOptions, ReplyTo, RpcCall, RpcFrame, CLOSE_POLL, KIND_CHUNK, KIND_END, KIND_ERROR, KIND_REQUEST};
+use crate::topic;
+
+// 主题 RPC：调用方用 Pub 按主题广播请求，订阅了该主题的服务端各自处理，
+// 回复经调用方的回复通道（Pull）定向送回，调用方按调用 id 收集所有服务端的回复。
+// 请求为主题帧，payload 为：回复地址长度(2, 大端) + 回复地址 + RPC 帧；
+// 回复为：服务端名称长度(2, 大端) + 名称 + RPC 帧（CHUNK / END / ERROR）
+const DEFAULT_CALL_TIMEOUT_MS: u32 = 1000;
+
+// 回复通道的发送超时，调用方已经离开时不会一直阻塞 handler
+const REPLY_SEND_TIMEOUT: Duration = Duration::from_secs(1);
+
+type Resolver<T> = Box<dyn FnOnce(Env) -> Result<T> + Send>;
+
+fn failed(reason: String) -> napi::Error {
+    napi::Error::new(napi::Status::GenericFailure, reason)
+}
+
+fn put_str(buffer: &mut Vec<u8>, value: &str) {
+    buffer.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    buffer.extend_from_slice(value.as_bytes());
+}
+
+// 服务端到某个调用方的回复通道，多个调用共用同一个 Push socket
+#[derive(Clone)]
+pub struct ReplyChannel {
+    socket: Socket,
+    responder: Arc<str>,
+}
+
+impl ReplyChannel {
+    pub fn send(&self, frame: &[u8]) -> Result<()> {
+        let mut message = Vec::with_capacity(2 + self.responder.len() + frame.len());
+        put_str(&mut message, &self.responder);
+        message.extend_from_slice(frame);
+        self.socket
+            .send(Message::from(&message[..]))
+            .map_err(|(_, e)| failed(format!("Send error: {:?}", e)))
+    }
+}
+
+type Handlers = HashMap<String, ThreadsafeFunction<RpcCall>>;
+
+// 按主题接收调用的服务端，同一主题可以有多个服务端，每个都会收到请求并各自回复
+#[napi]
+pub struct TopicRpcServer {
+    name: Arc<str>,
+    socket: Option<Socket>,
+    handlers: Arc<Mutex<HashMap<String, Handlers>>>, // 主题 -> 该主题下的方法
+    channels: Arc<Mutex<HashMap<String, Socket>>>, // 回复地址 -> Push socket
+    malformed: FrameErrors,
+    events: EventEmitter,
+    receiving: Arc<AtomicBool>,
+    is_closing: Arc<AtomicBool>,
+}
+
+#[napi]
+impl TopicRpcServer {
+    // name 随回复带给调用方，用来区分是哪个服务端的回复
+    #[napi(constructor)]
+    pub fn new(name: String) -> Result<Self> {
+        let socket = Socket::new(Protocol::Sub0)
+            .map_err(|err| failed(format!("Socket creation failed: {:?}", err)))?;
+        Ok(TopicRpcServer {
+            name: name.into(),
+            socket: Some(socket),
+            handlers: Arc::default(),
+            channels: Arc::default(),
+            malformed: FrameErrors::default(),
+            events: EventEmitter::default(),
+            receiving: Arc::new(AtomicBool::new(false)),
+            is_closing: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    // 连接调用方的 Pub 地址，调用方还没启动时在后台重试
+    #[napi]
+    pub fn connect(&self, url: String) -> Result<()> {
+        self.socket()?
+            .dial_async(&url)
+            .map_err(|err| failed(format!("Connection failed: {:?}", err)))?;
+        self.start();
+        Ok(())
+    }
+
+    // 注册某个主题下的方法，第一次注册该主题时订阅它
+    #[napi]
+    pub fn handle(&self, topic: String, method: String, callback: ThreadsafeFunction<RpcCall>) -> Result<()> {
+        topic::check_topic(&topic)?;
+        let socket = self.socket()?;
+        let mut handlers = self.handlers.lock().unwrap();
+        if !handlers.contains_key(&topic) {
+            socket
+                .set_opt::<Subscribe>(topic::subscription(&topic))
+                .map_err(|err| failed(format!("Subscribe failed: {:?}", err)))?;
+        }
+        handlers.entry(topic).or_default().insert(method, callback);
+        Ok(())
+    }
+
+    // 回复通道连接失败等事件：replyFailed
+    #[napi]
+    pub fn on_event(&self, callback: ThreadsafeFunction<SocketEvent>) {
+        self.events.set(callback);
+    }
+
+    // 收到后丢弃的畸形请求，按原因计数
+    #[napi]
+    pub fn malformed_frames(&self) -> MalformedFrames {
+        self.malformed.snapshot()
+    }
+
+    #[napi]
+    pub fn close(&mut self) {
+        if let Some(socket) = self.socket.take() {
+            self.receiving.store(false, Ordering::SeqCst);
+            self.is_closing.store(true, Ordering::SeqCst);
+            socket.close();
+        }
+        for (_, channel) in self.channels.lock().unwrap().drain() {
+            channel.close();
+        }
+        self.handlers.lock().unwrap().clear();
+        self.events.clear();
+    }
+
+    fn socket(&self) -> Result<&Socket> {
+        self.socket.as_ref().ok_or_else(|| failed("Socket not connected".to_string()))
+    }
+
+    fn start(&self) {
+        if self.receiving.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let socket = match &self.socket {
+            Some(socket) => socket.clone(),
+            None => return,
+        };
+        let name = self.name.clone();
+        let handlers = self.handlers.clone();
+        let channels = self.channels.clone();
+        let malformed = self.malformed.clone();
+        let events = self.events.clone();
+        let receiving = self.receiving.clone();
+        let is_closing = self.is_closing.clone();
+
+        guard::spawn(self.events.clone(), "Receive loop", move || {
+            while receiving.load(Ordering::SeqCst) {
+                let message = match socket.recv() {
+                    Ok(message) => message,
+                    Err(e) => {
+                        if is_closing.load(Ordering::SeqCst) {
+                            return; // 主动关闭时不报错
+                        }
+                        events.warn("recvFailed", format!("Error receiving message: {:?}", e));
+                        continue;
+                    }
+                };
+                let (request, reply_url, frame) = match decode_request(&message) {
+                    Ok(Some(decoded)) => decoded,
+                    Ok(None) => continue,
+                    Err(err) => {
+                        malformed.record(err, &events);
+                        continue;
+                    }
+                };
+                let topic = request.topic.to_string();
+                let handler = handlers
+                    .lock()
+                    .unwrap()
+                    .get(&topic)
+                    .and_then(|methods| methods.get(frame.method))
+                    .cloned();
+
+                // 通知不需要回复，也就不用连接回复通道
+                let channel = if frame.id == 0 {
+                    None
+                } else {
+                    match channel_for(&channels, reply_url) {
+                        Ok(socket) => Some(ReplyChannel { socket, responder: name.clone() }),
+                        Err(err) => {
+                            events.emit(SocketEvent::new("replyFailed").message(err.reason));
+                            continue;
+                        }
+                    }
+                };
+                match (handler, channel) {
+                    (Some(handler), channel) => {
+                        let reply_to = ReplyTo::Topic { topic, channel };
+                        let call = RpcCall::new(frame.id, frame.method, frame.payload, reply_to);
+                        handler.call(Ok(call), ThreadsafeFunctionCallMode::NonBlocking);
+                    }
+                    (None, Some(channel)) => {
+                        let reason = format!("Unknown method: {}", frame.method);
+                        if let Err(err) = channel.send(&encode_frame(KIND_ERROR, frame.id, "", reason.as_bytes())) {
+                            events.emit(SocketEvent::new("replyFailed").message(err.reason));
+                        }
+                    }
+                    (None, None) => {}
+                }
+            }
+        });
+    }
+}
+
+// 主题帧 + 回复地址 + RPC 帧；不是请求的 RPC 帧返回 None
+fn decode_request(message: &[u8]) -> std::result::Result<Option<(topic::Frame<'_>, &str, RpcFrame<'_>)>, FrameError> {
+    let request = topic::decode(message)?;
+    let (reply_url, rest) = take_str(request.payload)?;
+    let frame = decode_frame(rest)?;
+    Ok((frame.kind == KIND_REQUEST).then_some((request, reply_url, frame)))
+}
+
+// 每个回复地址连接一次，之后的调用复用
+fn channel_for(channels: &Mutex<HashMap<String, Socket>>, url: &str) -> Result<Socket> {
+    let mut channels = channels.lock().unwrap();
+    if let Some(socket) = channels.get(url) {
+        return Ok(socket.clone());
+    }
+    let socket = Socket::new(Protocol::Push0)
+        .map_err(|err| failed(format!("Socket creation failed: {:?}", err)))?;
+    socket
+        .set_opt::<SendTimeout>(Some(REPLY_SEND_TIMEOUT))
+        .map_err(|err| failed(format!("Failed to set send timeout: {:?}", err)))?;
+    if let Err(err) = socket.dial(url) {
+        socket.close();
+        return Err(failed(format!("Failed to connect reply channel {}: {:?}", url, err)));
+    }
+    channels.insert(url.to_string(), socket.clone());
+    Ok(socket)
+}
+
+// 一个服务端的回复；服务端 fail 时 error 有值，data 为空
+#[napi(object)]
+pub struct TopicReply {
+    pub responder: String,
+    pub data: Option<Buffer>,
+    pub error: Option<String>,
+}
+
+#[napi(object)]
+pub struct TopicCallOptions {
+    pub timeout_ms: Option<u32>, // 等待回复的时间，默认 1000
+    pub expected: Option<u32>,   // 收到这么多个回复后不再等待
+}
+
+// 一次主题调用收集到的回复
+struct Gathering {
+    deferred: JsDeferred<Vec<TopicReply>, Resolver<Vec<TopicReply>>>,
+    replies: Vec<TopicReply>,
+    partial: HashMap<String, Vec<u8>>, // 还没结束的服务端已经发来的分段
+    expected: Option<usize>,
+}
+
+impl Gathering {
+    fn resolve(self) {
+        let replies = self.replies;
+        self.deferred.resolve(Box::new(move |_| Ok(replies)));
+    }
+}
+
+type Gatherings = Arc<Mutex<HashMap<u32, Gathering>>>;
+
+// 按主题发起调用的一端：Pub 广播请求，Pull 接收各服务端的回复
+#[napi]
+pub struct TopicRpcClient {
+    publisher: Option<Socket>,
+    replies: Option<Socket>,
+    reply_url: Option<String>,
+    pending: Gatherings,
+    next_id: AtomicU32,
+    malformed: FrameErrors,
+    events: EventEmitter,
+    receiving: Arc<AtomicBool>,
+    is_closing: Arc<AtomicBool>,
+}
+
+#[napi]
+impl TopicRpcClient {
+    #[napi(constructor)]
+    pub fn new() -> Self {
+        TopicRpcClient {
+            publisher: None,
+            replies: None,
+            reply_url: None,
+            pending: Arc::default(),
+            next_id: AtomicU32::new(1),
+            malformed: FrameErrors::default(),
+            events: EventEmitter::default(),
+            receiving: Arc::new(AtomicBool::new(false)),
+            is_closing: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    // 在 url 上发布请求，在 replyUrl 上接收回复。replyUrl 原样带给服务端，
+    // 要写成服务端能连上的地址，不能用 0.0.0.0 这类通配地址
+    #[napi]
+    pub fn listen(&mut self, url: String, reply_url: String) -> Result<()> {
+        if self.publisher.is_some() {
+            return Err(failed("Already listening".to_string()));
+        }
+        let publisher = Socket::new(Protocol::Pub0)
+            .map_err(|err| failed(format!("Socket creation failed: {:?}", err)))?;
+        let replies = Socket::new(Protocol::Pull0)
+            .map_err(|err| failed(format!("Socket creation failed: {:?}", err)))?;
+        let listened = publisher
+            .listen(&url)
+            .and_then(|_| replies.listen(&reply_url))
+            .map_err(|err| failed(format!("Listen failed: {:?}", err)));
+        if let Err(err) = listened {
+            publisher.close();
+            replies.close();
+            return Err(err);
+        }
+        self.publisher = Some(publisher);
+        self.replies = Some(replies);
+        self.reply_url = Some(reply_url);
+        self.start();
+        Ok(())
+    }
+
+    // 向订阅了 topic 的所有服务端调用 method，在 timeoutMs 内收集回复，
+    // 收到 expected 个回复后提前返回。没有服务端订阅时返回空数组；
+    // 服务端刚连上、还没收到订阅前发出的请求会被 Pub 丢弃
+    #[napi(
+        ts_args_type = "topic: string, method: string, data: Buffer | Uint8Array | string | ArrayBuffer, options?: TopicCallOptions | undefined | null",
+        ts_return_type = "Promise<Array<TopicReply>>"
+    )]
+    pub fn call(&self, env: Env, topic: String, method: String, data: Payload, options: Option<TopicCallOptions>) -> Result<JsObject> {
+        let (timeout_ms, expected) = options.map(|options| (options.timeout_ms, options.expected)).unwrap_or_default();
+        let (deferred, promise) = env.create_deferred::<Vec<TopicReply>, Resolver<Vec<TopicReply>>>()?;
+        let id = self.next_id();
+        let gathering = Gathering {
+            deferred,
+            replies: Vec::new(),
+            partial: HashMap::new(),
+            expected: expected.map(|expected| expected as usize),
+        };
+        if expected == Some(0) {
+            gathering.resolve();
+            return Ok(promise);
+        }
+        self.pending.lock().unwrap().insert(id, gathering);
+        if let Err(err) = self.send_request(id, &topic, &method, &data) {
+            if let Some(gathering) = self.pending.lock().unwrap().remove(&id) {
+                gathering.deferred.reject(err);
+            }
+            return Ok(promise);
+        }
+
+        // 到时间后把已经收到的回复交出去，晚到的回复丢弃
+        let pending = self.pending.clone();
+        let timeout = Duration::from_millis(timeout_ms.unwrap_or(DEFAULT_CALL_TIMEOUT_MS) as u64);
+        guard::spawn(self.events.clone(), "Call timeout", move || {
+            std::thread::sleep(timeout);
+            if let Some(gathering) = pending.lock().unwrap().remove(&id) {
+                gathering.resolve();
+            }
+        });
+        Ok(promise)
+    }
+
+    // 单向通知，服务端的回复会被丢弃
+    #[napi(ts_args_type = "topic: string, method: string, data: Buffer | Uint8Array | string | ArrayBuffer")]
+    pub fn notify(&self, topic: String, method: String, data: Payload) -> Result<()> {
+        self.send_request(0, &topic, &method, &data)
+    }
+
+    #[napi]
+    pub fn on_event(&self, callback: ThreadsafeFunction<SocketEvent>) {
+        self.events.set(callback);
+    }
+
+    // 收到后丢弃的畸形回复，按原因计数
+    #[napi]
+    pub fn malformed_frames(&self) -> MalformedFrames {
+        self.malformed.snapshot()
+    }
+
+    // Detach 时不用 waitMs：还没结束的调用到了各自的 timeoutMs 照常交出已经收到的回复
+    #[napi]
+    pub fn close(&mut self, options: Option<CloseOptions>) {
+        let (mode, wait) = close_options(options);
+        // 两个 socket 都取走后不能再发起调用；Wait 时刚发出的请求可能还在 Pub 的队列里，稍后再关
+        let sockets = (self.publisher.take(), self.replies.take());
+        self.reply_url = None;
+        let (pending, events, receiving, is_closing) =
+            (self.pending.clone(), self.events.clone(), self.receiving.clone(), self.is_closing.clone());
+        let shut_down = move || {
+            receiving.store(false, Ordering::SeqCst);
+            is_closing.store(true, Ordering::SeqCst);
+            for socket in [sockets.0, sockets.1].into_iter().flatten() {
+                socket.close();
+            }
+            events.clear();
+        };
+        match mode {
+            CloseMode::Reject => {
+                for (_, gathering) in self.pending.lock().unwrap().drain() {
+                    reject_closed(gathering.deferred, "Socket closed");
+                }
+                shut_down();
+            }
+            CloseMode::Wait => {
+                // 回复通道保持打开，等已发出的调用都结束或到时间
+                guard::spawn(self.events.clone(), "Close wait", move || {
+                    let deadline = Instant::now() + wait;
+                    while !pending.lock().unwrap().is_empty() && Instant::now() < deadline {
+                        std::thread::sleep(CLOSE_POLL);
+                    }
+                    for (_, gathering) in pending.lock().unwrap().drain() {
+                        reject_closed(gathering.deferred, "Socket closed");
+                    }
+                    shut_down();
+                });
+            }
+            CloseMode::Detach => shut_down(),
+        }
+    }
+
+    fn next_id(&self) -> u32 {
+        // 0 留给通知
+        loop {
+            let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+            if id != 0 {
+                return id;
+            }
+        }
+    }
+
+    fn send_request(&self, id: u32, topic_name: &str, method: &str, data: &[u8]) -> Result<()> {
+        topic::check_topic(topic_name)?;
+        let (publisher, reply_url) = match (&self.publisher, &self.reply_url) {
+            (Some(publisher), Some(reply_url)) => (publisher, reply_url),
+            _ => return Err(failed("Socket not connected".to_string())),
+        };
+        let mut payload = Vec::new();
+        put_str(&mut payload, reply_url);
+        payload.extend_from_slice(&encode_frame(KIND_REQUEST, id, method, data));
+        publisher
+            .send(Message::from(&topic::encode(topic_name, 0, None, &payload)[..]))
+            .map_err(|(_, e)| failed(format!("Send error: {:?}", e)))
+    }
+
+    fn start(&self) {
+        if self.receiving.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let socket = match &self.replies {
+            Some(socket) => socket.clone(),
+            None => return,
+        };
+        let pending = self.pending.clone();
+        let malformed = self.malformed.clone();
+        let events = self.events.clone();
+        let receiving = self.receiving.clone();
+        let is_closing = self.is_closing.clone();
+
+        guard::spawn(self.events.clone(), "Receive loop", move || {
+            while receiving.load(Ordering::SeqCst) {
+                let message = match socket.recv() {
+                    Ok(message) => message,
+                    Err(e) => {
+                        if is_closing.load(Ordering::SeqCst) {
+                            return;
+                        }
+                        events.warn("recvFailed", format!("Error receiving message: {:?}", e));
+                        continue;
+                    }
+                };
+                match take_str(&message).and_then(|(responder, rest)| Ok((responder, decode_frame(rest)?))) {
+                    Ok((responder, frame)) => gather(&pending, responder, frame.kind, frame.id, frame.payload),
+                    Err(err) => malformed.record(err, &events),
+                }
+            }
+        });
+    }
+}
+
+impl Default for TopicRpcClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn gather(pending: &Mutex<HashMap<u32, Gathering>>, responder: &str, kind: u8, id: u32, payload: &[u8]) {
+    let mut pending = pending.lock().unwrap();
+    let Some(gathering) = pending.get_mut(&id) else { return }; // 已经返回或未知的调用
+    let reply = match kind {
+        KIND_CHUNK => {
+            gathering.partial.entry(responder.to_string()).or_default().extend_from_slice(payload);
+            return;
+        }
+        KIND_END => {
+            let mut data = gathering.partial.remove(responder).unwrap_or_default();
+            data.extend_from_slice(payload);
+            TopicReply { responder: responder.to_string(), data: Some(data.into()), error: None }
+        }
+        KIND_ERROR => {
+            gathering.partial.remove(responder);
+            TopicReply {
+                responder: responder.to_string(),
+                data: None,
+                error: Some(String::from_utf8_lossy(payload).into_owned()),
+            }
+        }
+        _ => return,
+    };
+    gathering.replies.push(reply);
+    if gathering.expected.is_some_and(|expected| gathering.replies.len() >= expected) {
+        if let Some(gathering) = pending.remove(&id) {
+            gathering.resolve();
+        }
+    }
+}
@@ -0,0 +1,15 @@
+use napi::bindgen_prelude::*;
+use napi::{Env, NapiRaw};
+
+// 交给 JS 时复制到 V8 自己分配的 ArrayBuffer 里。
+// 和 Buffer（外部内存，postMessage 时会被复制）不同，它可以放进 transferList 直接转移给 worker_threads。
+pub struct TransferableBuffer(pub Vec<u8>);
+
+impl ToNapiValue for TransferableBuffer {
+    unsafe fn to_napi_value(env: napi::sys::napi_env, val: Self) -> Result<napi::sys::napi_value> {
+        let env = Env::from_raw(env);
+        let mut arraybuffer = env.create_arraybuffer(val.0.len())?;
+        arraybuffer.as_mut().copy_from_slice(&val.0);
+        Ok(arraybuffer.into_raw().raw())
+    }
+}
@@ -0,0 +1,142 @@
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[napi]
+pub enum Transport {
+    Tcp,
+    Ipc,
+    Inproc,
+    Ws,
+    TlsTcp,
+    Wss,
+    Zt,
+}
+
+impl Transport {
+    fn scheme(self) -> &'static str {
+        match self {
+            Transport::Tcp => "tcp",
+            Transport::Ipc => "ipc",
+            Transport::Inproc => "inproc",
+            Transport::Ws => "ws",
+            Transport::TlsTcp => "tls+tcp",
+            Transport::Wss => "wss",
+            Transport::Zt => "zt",
+        }
+    }
+
+    fn from_scheme(scheme: &str) -> Option<Self> {
+        match scheme {
+            "tcp" => Some(Transport::Tcp),
+            "ipc" => Some(Transport::Ipc),
+            "inproc" => Some(Transport::Inproc),
+            "ws" => Some(Transport::Ws),
+            "tls+tcp" => Some(Transport::TlsTcp),
+            "wss" => Some(Transport::Wss),
+            "zt" => Some(Transport::Zt),
+            _ => None,
+        }
+    }
+}
+
+fn invalid(reason: String) -> napi::Error {
+    napi::Error::new(napi::Status::InvalidArg, reason)
+}
+
+// 拼出 nng 能识别的 URL：
+// - Tcp/TlsTcp/Zt 需要 host 和 port，host 为空表示监听所有地址；IPv6 地址自动加方括号
+// - Ws/Wss 另外可以带 path
+// - Ipc 的 path 是文件路径，Inproc 的 path 是名字，都不能带 host/port
+#[napi]
+pub fn build_url(transport: Transport, host: Option<String>, port: Option<u32>, path: Option<String>) -> Result<String> {
+    let scheme = transport.scheme();
+    match transport {
+        Transport::Ipc | Transport::Inproc => {
+            if host.is_some() || port.is_some() {
+                return Err(invalid(format!("{}:// URLs take only a path", scheme)));
+            }
+            match path.filter(|path| !path.is_empty()) {
+                Some(path) => Ok(format!("{}://{}", scheme, path)),
+                None => Err(invalid(format!("{}:// URLs need a path", scheme))),
+            }
+        }
+        _ => {
+            let port = port.ok_or_else(|| invalid(format!("{}:// URLs need a port", scheme)))?;
+            if port > u16::MAX as u32 {
+                return Err(invalid(format!("Port out of range: {}", port)));
+            }
+            let host = host.unwrap_or_default();
+            let host = if host.contains(':') && !host.starts_with('[') { format!("[{}]", host) } else { host };
+            let path = match (transport, path) {
+                (Transport::Ws | Transport::Wss, Some(path)) if !path.starts_with('/') => format!("/{}", path),
+                (Transport::Ws | Transport::Wss, Some(path)) => path,
+                (_, Some(_)) => return Err(invalid(format!("{}:// URLs do not take a path", scheme))),
+                (_, None) => String::new(),
+            };
+            Ok(format!("{}://{}:{}{}", scheme, host, port, path))
+        }
+    }
+}
+
+// 按 scheme 判断 URL 用的传输方式，不认识的 scheme 报错；
+// 也接受 nng 的 tcp4/tcp6 等变体
+#[napi]
+pub fn parse_transport(url: String) -> Result<Transport> {
+    let (scheme, rest) = url
+        .split_once("://")
+        .ok_or_else(|| invalid(format!("Missing scheme in URL: {}", url)))?;
+    let transport = Transport::from_scheme(scheme.trim_end_matches(['4', '6']))
+        .ok_or_else(|| invalid(format!("Unknown transport scheme: {}", scheme)))?;
+    if rest.is_empty() {
+        return Err(invalid(format!("Missing address in URL: {}", url)));
+    }
+    Ok(transport)
+}
+
+// 生成进程内唯一的 inproc:// 地址。inproc 不经过网络栈，同一进程里的模块、
+// worker_threads 之间都能互连（nng 的状态在进程内共享），适合做进程内的流水线
+#[napi]
+pub fn inproc_url(name: Option<String>) -> Result<String> {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let name = name.unwrap_or_else(|| "napi-nng".to_string());
+    if name.is_empty() || name.contains("://") {
+        return Err(invalid(format!("Invalid inproc name: {:?}", name)));
+    }
+    let count = COUNTER.fetch_add(1, Ordering::SeqCst);
+    Ok(format!("inproc://{}-{}-{}", name, std::process::id(), count))
+}
+
+pub fn check_namespace(namespace: &str) -> Result<()> {
+    let valid = !namespace.is_empty()
+        && namespace.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid {
+        return Err(invalid(format!(
+            "Namespace must be non-empty and contain only letters, digits, '-', '_' and '.': {:?}",
+            namespace
+        )));
+    }
+    Ok(())
+}
+
+// 多租户进程里按命名空间隔开本地地址：inproc://name 变成 inproc://<namespace>/name，
+// ipc:// 的文件名前加 "<namespace>."，同一目录下不同租户的 socket 文件不会冲突。
+// 其他传输的地址原样返回，端口本来就是全机共享的
+pub fn namespaced(namespace: &str, url: &str) -> String {
+    if let Some(name) = url.strip_prefix("inproc://") {
+        return format!("inproc://{}/{}", namespace, name);
+    }
+    if let Some(path) = url.strip_prefix("ipc://") {
+        return match path.rsplit_once('/') {
+            Some((dir, file)) => format!("ipc://{}/{}.{}", dir, namespace, file),
+            None => format!("ipc://{}.{}", namespace, path),
+        };
+    }
+    url.to_string()
+}
+
+#[napi]
+pub fn namespaced_url(namespace: String, url: String) -> Result<String> {
+    check_namespace(&namespace)?;
+    Ok(namespaced(&namespace, &url))
+}
@@ -0,0 +1,95 @@
+use napi::{Env, JsDeferred, Result};
+use nng::Socket;
+use std::time::{Duration, Instant};
+
+use crate::events::EventEmitter;
+use crate::guard;
+use crate::outbox::Outbox;
+use crate::pipes::PipeHooks;
+use crate::probe::Probes;
+
+type Resolver = Box<dyn FnOnce(Env) -> Result<()> + Send>;
+
+// 等待 dialer 连上的轮询间隔
+const CONNECT_POLL: Duration = Duration::from_millis(10);
+
+pub struct WarmUp {
+    pub socket: Socket,
+    pub dialers: Vec<(u32, String)>, // 已启动的 dialer 和它们的 URL
+    pub pipes: PipeHooks,
+    pub probes: Probes,
+    pub outbox: Outbox,
+    pub verify: bool, // 非 raw 的 Req：连上后再对每条 pipe 发一次 ping
+    pub timeout: Duration,
+}
+
+fn failed(reason: String) -> napi::Error {
+    napi::Error::new(napi::Status::GenericFailure, reason)
+}
+
+impl WarmUp {
+    pub fn start(self, events: EventEmitter, deferred: JsDeferred<(), Resolver>) {
+        guard::spawn(events, "Warm-up", move || match self.run() {
+            Ok(()) => deferred.resolve(Box::new(|_| Ok(()))),
+            Err(err) => deferred.reject(err),
+        });
+    }
+
+    fn run(&self) -> Result<()> {
+        let started = Instant::now();
+        // 每个 dialer 至少有一条 pipe；只有 listener 的 socket 等到有对端连上
+        let pipes = loop {
+            let (connected, pipes) = self.pipes.connected();
+            let missing: Vec<&str> = self
+                .dialers
+                .iter()
+                .filter(|(id, _)| !connected.contains(id))
+                .map(|(_, url)| url.as_str())
+                .collect();
+            let ready = if self.dialers.is_empty() { pipes > 0 } else { missing.is_empty() };
+            if ready {
+                break pipes;
+            }
+            if started.elapsed() >= self.timeout {
+                return Err(failed(if self.dialers.is_empty() {
+                    "Warm-up timed out: no peer connected".to_string()
+                } else {
+                    format!(
+                        "Warm-up timed out: {} of {} endpoints not connected ({})",
+                        missing.len(),
+                        self.dialers.len(),
+                        missing.join(", ")
+                    )
+                }));
+            }
+            std::thread::sleep(CONNECT_POLL);
+        };
+        if !self.verify {
+            return Ok(());
+        }
+
+        // Req 把同时发出的请求分给空闲的 pipe，每条 pipe 一个 ping 就能把它们都走一遍
+        let remaining = self.timeout.saturating_sub(started.elapsed()).max(CONNECT_POLL);
+        let results: Vec<std::result::Result<(), nng::Error>> = std::thread::scope(|scope| {
+            let pings: Vec<_> = (0..pipes)
+                .map(|_| scope.spawn(|| self.probes.ping_req_blocking(&self.socket, &self.outbox, remaining)))
+                .collect();
+            pings.into_iter().map(|ping| ping.join().unwrap_or(Err(nng::Error::Internal))).collect()
+        });
+        let failures: Vec<String> = results
+            .into_iter()
+            .filter_map(|result| result.err())
+            .map(|err| format!("{:?}", err))
+            .collect();
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(failed(format!(
+                "Warm-up ping failed on {} of {} pipes: {}",
+                failures.len(),
+                pipes,
+                failures.join(", ")
+            )))
+        }
+    }
+}
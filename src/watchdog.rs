@@ -0,0 +1,175 @@
+use napi::Result;
+use napi_derive::napi;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::events::{EventEmitter, SocketEvent};
+use crate::guard;
+
+const DEFAULT_STALL_MS: u32 = 5000;
+const MIN_CHECK: Duration = Duration::from_millis(10);
+
+// 接收循环从收到一条消息到回去等下一条超过 stallMs 时发一个 stalled 事件，每次卡住只发一次。
+// 等 nng 收消息不算，空闲的 socket 不会触发；pauseRecv 暂停期间也不算
+#[napi(object)]
+pub struct WatchdogOptions {
+    pub stall_ms: Option<u32>, // 默认 5000
+}
+
+// 接收循环卡住时所在的阶段，作为 stalled 事件的 code
+#[derive(Clone, Copy)]
+pub enum Stage {
+    Deliver,        // 把消息交给回调；回调队列满（recvChunked）时会等 JS
+    MemoryLimit,    // 超过 setMemoryLimit，等 JS 处理掉已经交付的消息
+    DeliveryBudget, // 超过 setDeliveryBudget，排在 JS 事件循环里的消息太多
+}
+
+impl Stage {
+    fn code(self) -> &'static str {
+        match self {
+            Stage::Deliver => "deliver",
+            Stage::MemoryLimit => "memoryLimit",
+            Stage::DeliveryBudget => "deliveryBudget",
+        }
+    }
+}
+
+struct Beat {
+    thread: String,
+    busy: Option<(Stage, Instant)>, // 这一轮开始忙的时间，阶段是最近进入的那个
+    iterations: u64,
+    reported: bool,
+}
+
+#[derive(Default)]
+struct State {
+    stall: Option<Duration>, // None 表示没有开启
+    running: bool,           // 检查线程是否在运行，没有开启或没有接收循环时退出
+    next_id: u64,
+    loops: HashMap<u64, Beat>,
+}
+
+#[derive(Clone)]
+pub struct Watchdog {
+    state: Arc<Mutex<State>>,
+    events: EventEmitter,
+}
+
+// 一个接收循环在看门狗上的登记，循环退出时丢掉
+pub struct Pulse {
+    id: u64,
+    state: Arc<Mutex<State>>,
+}
+
+impl Watchdog {
+    pub fn new(events: EventEmitter) -> Self {
+        Watchdog { state: Arc::default(), events }
+    }
+
+    // None 关闭；对正在运行的循环立即生效
+    pub fn configure(&self, options: Option<WatchdogOptions>) -> Result<()> {
+        let stall = match options {
+            Some(options) => {
+                let stall_ms = options.stall_ms.unwrap_or(DEFAULT_STALL_MS);
+                if stall_ms == 0 {
+                    return Err(napi::Error::new(napi::Status::InvalidArg, "stallMs must be greater than 0".to_string()));
+                }
+                Some(Duration::from_millis(stall_ms as u64))
+            }
+            None => None,
+        };
+        self.state.lock().unwrap().stall = stall;
+        self.start();
+        Ok(())
+    }
+
+    // 在接收线程上调用，记下线程名和 id 用于诊断
+    pub fn watch(&self) -> Pulse {
+        let current = std::thread::current();
+        let thread = format!("{} ({:?})", current.name().unwrap_or("unnamed"), current.id());
+        let mut state = self.state.lock().unwrap();
+        state.next_id += 1;
+        let id = state.next_id;
+        state.loops.insert(id, Beat { thread, busy: None, iterations: 0, reported: false });
+        drop(state);
+        self.start();
+        Pulse { id, state: self.state.clone() }
+    }
+
+    fn start(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.running || state.stall.is_none() || state.loops.is_empty() {
+            return;
+        }
+        state.running = true;
+        drop(state);
+        let (shared, events) = (self.state.clone(), self.events.clone());
+        guard::spawn(self.events.clone(), "Receive watchdog", move || loop {
+            let mut state = shared.lock().unwrap();
+            let Some(stall) = state.stall.filter(|_| !state.loops.is_empty()) else {
+                state.running = false;
+                return;
+            };
+            let stalled = check(&mut state, stall);
+            drop(state);
+            for event in stalled {
+                events.emit(event);
+            }
+            std::thread::sleep((stall / 4).max(MIN_CHECK));
+        });
+    }
+}
+
+fn check(state: &mut State, stall: Duration) -> Vec<SocketEvent> {
+    let mut stalled = Vec::new();
+    for beat in state.loops.values_mut() {
+        let Some((stage, since)) = beat.busy else {
+            continue;
+        };
+        let elapsed = since.elapsed();
+        if beat.reported || elapsed < stall {
+            continue;
+        }
+        beat.reported = true;
+        stalled.push(
+            SocketEvent::new("stalled")
+                .code(stage.code())
+                .value(elapsed.as_millis() as i64)
+                .message(format!(
+                    "Receive loop on thread {} has been in {} for {}ms after {} iterations",
+                    beat.thread,
+                    stage.code(),
+                    elapsed.as_millis(),
+                    beat.iterations
+                )),
+        );
+    }
+    stalled
+}
+
+impl Pulse {
+    // 进入一个可能卡住的阶段；同一轮里从收到消息开始计时
+    pub fn enter(&self, stage: Stage) {
+        if let Some(beat) = self.state.lock().unwrap().loops.get_mut(&self.id) {
+            let since = beat.busy.map_or_else(Instant::now, |(_, since)| since);
+            beat.busy = Some((stage, since));
+        }
+    }
+
+    // 这一轮结束，回去等 nng 的下一条消息
+    pub fn idle(&self) {
+        if let Some(beat) = self.state.lock().unwrap().loops.get_mut(&self.id) {
+            if beat.busy.take().is_some() {
+                beat.iterations += 1;
+            }
+            beat.reported = false;
+        }
+    }
+}
+
+impl Drop for Pulse {
+    fn drop(&mut self) {
+        self.state.lock().unwrap().loops.remove(&self.id);
+    }
+}
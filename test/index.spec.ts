@@ -1,4 +1,10 @@
-import { SocketWrapper } from "../index";
+import { SocketWrapper, StickyRouter, ProtocolType, NngErrorCode, PipeEventType, Publisher, Subscriber, SlowConsumerPolicy, PartitionedPublisher, PartitionedSubscriber, partitionFor, TopicMessage, RpcServer, RpcClient, CloseMode, SocketEvent, TopicRpcServer, TopicRpcClient, inprocUrl, startEchoServer, runConformance, JobQueue, EventLog, LogSubscriber, requestId, setRequestId, backtrace, stripBacktrace, PipeInfo, DeadLetter, EndpointOptions, TlsAuthMode, SocketGroup, CallbackErrorPolicy, AckMode, Transport, buildUrl, parseTransport, namespacedUrl, ConsumerGroup, GroupConsumer, bridge, BridgeProtocol, FanOut, FanIn, SourcedMessage, RoutingRule, installSignalHandlers } from "../index";
+import { spawn } from "child_process";
+import { copyFileSync, existsSync, readFileSync, rmSync, writeFileSync } from "fs";
+import { AddressInfo, Socket, connect, createServer } from "net";
+import { tmpdir } from "os";
+import { join } from "path";
+import { Worker } from "worker_threads";
 
 describe("default", () => {
   let socket: SocketWrapper;
@@ -11,3 +17,2635 @@ describe("default", () => {
     expect(socket.isConnect()).toBe(false);
   });
 });
+
+describe("inproc", () => {
+  it("generates unique addresses that sockets can connect through", async () => {
+    const url = inprocUrl("spec");
+    expect(url).toMatch(/^inproc:\/\/spec-/);
+    expect(inprocUrl("spec")).not.toBe(url);
+
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    pull.listen(url);
+    const push = new SocketWrapper();
+    push.open(ProtocolType.Push0);
+    push.dial(url);
+
+    const received = new Promise<string>((resolve) => pull.recv((err, msg) => resolve(msg.toString())));
+    await push.sendAsync(Buffer.from("hello"));
+    expect(await received).toBe("hello");
+
+    push.close();
+    pull.close();
+  });
+
+  it("receives a single message with recvOnce", async () => {
+    const url = inprocUrl("spec-recv-once");
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    pull.listen(url);
+    const push = new SocketWrapper();
+    push.open(ProtocolType.Push0);
+    push.dial(url);
+
+    const received = pull.recvOnce(1000);
+    await push.sendAsync(Buffer.from("once"));
+    expect((await received).toString()).toBe("once");
+    await expect(pull.recvOnce(20)).rejects.toThrow("Receive timeout");
+    await expect(pull.recvOnce(20)).rejects.toMatchObject({ nngCode: NngErrorCode.TimedOut });
+
+    push.close();
+    pull.close();
+  });
+
+  it("reports each pipe's lifecycle through onPipeEvent", async () => {
+    const url = inprocUrl("spec-pipe-events");
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    const events: PipeEventType[] = [];
+    pull.onPipeEvent((err, info) => events.push(info.event));
+    pull.listen(url);
+    const push = new SocketWrapper();
+    push.open(ProtocolType.Push0);
+    push.dial(url);
+    await new Promise((resolve) => setTimeout(resolve, 20));
+    push.close();
+    await new Promise((resolve) => setTimeout(resolve, 20));
+
+    expect(events).toEqual([PipeEventType.AddPre, PipeEventType.AddPost, PipeEventType.RemovePost]);
+    pull.close();
+  });
+
+  it("describes added and removed pipes with their endpoint and addresses", async () => {
+    const url = `ipc://${join(tmpdir(), `spec-pipe-hooks-${process.pid}.ipc`)}`;
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    const added: PipeInfo[] = [];
+    const removed: PipeInfo[] = [];
+    pull.onPipeAdded((err, info) => added.push(info));
+    pull.onPipeRemoved((err, info) => removed.push(info));
+    const listener = pull.listen(url);
+    const push = new SocketWrapper();
+    push.open(ProtocolType.Push0);
+    const dialed: PipeInfo[] = [];
+    push.onPipeAdded((err, info) => dialed.push(info));
+    push.dial(url);
+    await new Promise((resolve) => setTimeout(resolve, 20));
+    push.close();
+    await new Promise((resolve) => setTimeout(resolve, 20));
+
+    expect(added).toEqual([expect.objectContaining({ transport: "ipc", url, remoteAddress: url, localAddress: url, listenerId: listener })]);
+    expect(dialed).toEqual([expect.objectContaining({ transport: "ipc", url, dialerId: expect.any(Number) })]);
+    expect(removed.map((info) => info.pipeId)).toEqual([added[0].pipeId]);
+    pull.close();
+  });
+
+  it("exposes the ipc peer's credentials on the pipe a message arrived on", async () => {
+    const url = `ipc://${join(tmpdir(), `spec-peer-${process.pid}.ipc`)}`;
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    pull.listen(url);
+    const push = new SocketWrapper();
+    push.open(ProtocolType.Push0);
+    push.dial(url);
+
+    const received = new Promise<number | undefined>((resolve) => pull.recvMessages((err, msg) => resolve(msg.pipeId)));
+    await push.sendAsync("who");
+    expect(pull.pipe((await received)!)).toMatchObject({ transport: "ipc", peerPid: process.pid });
+
+    push.close();
+    pull.close();
+  });
+
+  it("echoes requests from a Rust-side server", async () => {
+    const url = inprocUrl("spec-echo");
+    const echo = startEchoServer(ProtocolType.Rep0, url);
+    const req = new SocketWrapper();
+    req.open(ProtocolType.Req0);
+    req.dial(url);
+
+    expect((await req.send(Buffer.from("ping"))).toString()).toBe("ping");
+    expect(echo.received()).toBe(1);
+    expect(await req.tryRequest("pong")).toMatchObject({ ok: true, data: Buffer.from("pong") });
+
+    req.close();
+    echo.close();
+  });
+
+  it("derives the request timeout from recent round trips", async () => {
+    const url = inprocUrl("spec-adaptive");
+    const echo = startEchoServer(ProtocolType.Rep0, url);
+    const req = new SocketWrapper();
+    req.open(ProtocolType.Req0);
+    req.dial(url);
+
+    expect(() => req.setAdaptiveTimeout({ minMs: 500, maxMs: 50 })).toThrow("minMs (500) is greater than maxMs (50)");
+    req.setAdaptiveTimeout({ minSamples: 3, minMs: 50, maxMs: 500 });
+    expect(req.adaptiveTimeout()).toBeNull();
+    for (const body of ["a", "b", "c"]) {
+      expect((await req.send(body)).toString()).toBe(body);
+    }
+    expect(req.adaptiveTimeout()).toBe(50);
+    req.setAdaptiveTimeout(null);
+    expect(req.adaptiveTimeout()).toBeNull();
+
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    expect(() => pull.setAdaptiveTimeout({})).toThrow("Adaptive timeouts are only supported on non-raw Req sockets");
+    pull.close();
+    req.close();
+    echo.close();
+  });
+
+  it("matches concurrent requests to their replies through contexts", async () => {
+    const url = inprocUrl("spec-contexts");
+    const echo = startEchoServer(ProtocolType.Rep0, url);
+    const req = new SocketWrapper();
+    req.open(ProtocolType.Req0);
+    req.dial(url);
+
+    const bodies = ["a", "b", "c"];
+    const replies = await Promise.all(bodies.map((body) => req.openContext().request(Buffer.from(body), 1000)));
+    expect(replies.map(String)).toEqual(bodies);
+
+    req.close();
+    echo.close();
+  });
+
+  it("lets raw Req0/Rep0 sockets manage request ids and reply out of order", async () => {
+    const url = inprocUrl("spec-raw-req");
+    const rep = new SocketWrapper();
+    rep.open(ProtocolType.Rep0, true);
+    rep.listen(url);
+    const req = new SocketWrapper();
+    req.open(ProtocolType.Req0, true);
+    req.dial(url);
+
+    await req.sendAsync(setRequestId(Buffer.from("first"), 7));
+    await req.sendAsync(setRequestId(Buffer.from("second"), 8));
+    const requests = [await rep.recvOnce(1000), await rep.recvOnce(1000)];
+    expect(requests.map(requestId)).toEqual([7, 8]);
+    expect(requests.map((request) => stripBacktrace(request).toString())).toEqual(["first", "second"]);
+
+    for (const request of requests.reverse()) {
+      await rep.sendAsync(Buffer.concat([backtrace(request), Buffer.from(`re:${stripBacktrace(request)}`)]));
+    }
+    const replies = [await req.recvOnce(1000), await req.recvOnce(1000)];
+    expect(replies.map(requestId)).toEqual([8, 7]);
+    expect(replies.map((reply) => stripBacktrace(reply).toString())).toEqual(["re:second", "re:first"]);
+    expect(() => req.sendAsync("no backtrace")).toThrow("Raw message has no request id backtrace");
+
+    req.close();
+    rep.close();
+  });
+
+  it("routes raw replies through the header of the request", async () => {
+    const url = inprocUrl("spec-headers");
+    const rep = new SocketWrapper();
+    rep.open(ProtocolType.Rep0, true);
+    rep.listen(url);
+    const headers: number[] = [];
+    rep.recvMessages((err, msg) => {
+      headers.push(msg.header.length);
+      rep.sendMessage({ header: msg.header, body: Buffer.concat([Buffer.from("re:"), msg.body]) });
+    });
+    const req = new SocketWrapper();
+    req.open(ProtocolType.Req0);
+    req.dial(url);
+
+    expect((await req.send(Buffer.from("x"))).toString()).toBe("re:x");
+    expect(headers).toEqual([8]);
+    req.close();
+    rep.close();
+  });
+
+  it("sends a body with an optional header through sendMsg", async () => {
+    const url = inprocUrl("spec-send-msg");
+    const rep = new SocketWrapper();
+    rep.open(ProtocolType.Rep0, true);
+    rep.listen(url);
+    rep.recvMessages((err, msg) => rep.sendMsg({ header: msg.header, body: "reply" }));
+    const req = new SocketWrapper();
+    req.open(ProtocolType.Req0);
+    req.dial(url);
+    expect((await req.send("x")).toString()).toBe("reply");
+    expect(() => req.sendMsg({ header: "h", body: "x" })).toThrow("sendMsg cannot carry a header on cooked Req0 sockets");
+
+    const pushUrl = inprocUrl("spec-send-msg-push");
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    pull.listen(pushUrl);
+    const push = new SocketWrapper();
+    push.open(ProtocolType.Push0);
+    push.dial(pushUrl);
+    await push.sendMsg({ header: "route:", body: Buffer.from("body") });
+    await push.sendMsg({ body: new Uint8Array([0x21]) });
+    expect((await pull.recvOnce(1000)).toString()).toBe("route:body");
+    expect((await pull.recvOnce(1000)).toString()).toBe("!");
+    [req, rep, push, pull].forEach((socket) => socket.close());
+  });
+
+  it("collects respondent replies until the survey deadline", async () => {
+    const urls = [inprocUrl("spec-survey"), inprocUrl("spec-survey")];
+    const respondents = urls.map((url) => startEchoServer(ProtocolType.Respondent0, url));
+    const surveyor = new SocketWrapper();
+    surveyor.open(ProtocolType.Surveyor0);
+    urls.forEach((url) => surveyor.dial(url));
+    await new Promise((resolve) => setTimeout(resolve, 20));
+
+    const replies = await surveyor.survey("q", 100);
+    expect(replies.map(String)).toEqual(["q", "q"]);
+
+    surveyor.close();
+    respondents.forEach((respondent) => respondent.close());
+  });
+
+  it("reports how many respondents answered once the survey deadline passes", async () => {
+    const urls = [inprocUrl("spec-survey-complete"), inprocUrl("spec-survey-complete")];
+    const respondents = urls.map((url) => startEchoServer(ProtocolType.Respondent0, url));
+    const surveyor = new SocketWrapper();
+    surveyor.open(ProtocolType.Surveyor0);
+    surveyor.setNanomsgOption("SURVEYOR_DEADLINE", 50);
+    const completed: (number | undefined)[] = [];
+    surveyor.onEvent((err, event) => {
+      if (event.name === "surveyComplete") completed.push(event.value);
+    });
+    urls.forEach((url) => surveyor.dial(url));
+    await new Promise((resolve) => setTimeout(resolve, 20));
+
+    const replies: string[] = [];
+    surveyor.recv((err, msg) => replies.push(msg.toString()));
+    await surveyor.sendAsync("q");
+    await new Promise((resolve) => setTimeout(resolve, 150));
+
+    expect(replies).toEqual(["q", "q"]);
+    expect(completed).toEqual([2]);
+    surveyor.close();
+    respondents.forEach((respondent) => respondent.close());
+  });
+
+  it("measures round trips with ping without delivering the probes", async () => {
+    const pairUrl = inprocUrl("spec-ping-pair");
+    const a = new SocketWrapper();
+    a.open(ProtocolType.Pair0);
+    a.listen(pairUrl);
+    const b = new SocketWrapper();
+    b.open(ProtocolType.Pair0);
+    b.dial(pairUrl);
+    const received: string[] = [];
+    a.recv((err, msg) => received.push(msg.toString()));
+    b.recv((err, msg) => received.push(msg.toString()));
+    expect(await a.ping(1000)).toEqual(expect.any(Number));
+
+    const reqUrl = inprocUrl("spec-ping-req");
+    const rep = new SocketWrapper();
+    rep.open(ProtocolType.Rep0);
+    rep.listen(reqUrl);
+    rep.recv((err, msg) => received.push(msg.toString()));
+    const req = new SocketWrapper();
+    req.open(ProtocolType.Req0);
+    req.dial(reqUrl);
+    expect(await req.ping(1000)).toEqual(expect.any(Number));
+    await new Promise((resolve) => setTimeout(resolve, 20));
+
+    expect(received).toEqual([]);
+    expect(() => rep.ping(1000)).toThrow("ping is only supported on Pair and non-raw Req sockets");
+    [a, b, rep, req].forEach((socket) => socket.close());
+  });
+
+  it("warms up only once every dialed endpoint answers a ping", async () => {
+    const urls = [inprocUrl("spec-warm-up"), inprocUrl("spec-warm-up")];
+    const serve = (url: string) => {
+      const rep = new SocketWrapper();
+      rep.open(ProtocolType.Rep0);
+      rep.listen(url);
+      rep.recv(() => {});
+      return rep;
+    };
+    const reps = [serve(urls[0])];
+    const req = new SocketWrapper();
+    req.open(ProtocolType.Req0);
+    urls.forEach((url) => req.dial(url, true));
+
+    await expect(req.warmUp(100)).rejects.toThrow(`Warm-up timed out: 1 of 2 endpoints not connected (${urls[1]})`);
+    reps.push(serve(urls[1]));
+    await req.warmUp(2000);
+
+    [req, ...reps].forEach((socket) => socket.close());
+  });
+
+  it("pauses, drains and closes the sockets of a group together", async () => {
+    const url = inprocUrl("spec-group");
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    pull.listen(url);
+    const push = new SocketWrapper();
+    push.open(ProtocolType.Push0);
+    push.dial(url);
+    const group = new SocketGroup();
+    expect(group.add(pull)).toBe(true);
+    expect(group.add(push)).toBe(true);
+    expect(group.add(pull)).toBe(false);
+    expect(group.size()).toBe(2);
+
+    const received: string[] = [];
+    pull.recv((err, msg) => received.push(msg.toString()));
+    group.pauseRecv();
+    await push.sendAsync("held");
+    await group.drain(1000);
+    await new Promise((resolve) => setTimeout(resolve, 50));
+    expect(received).toEqual([]);
+    group.resumeRecv();
+    await new Promise((resolve) => setTimeout(resolve, 50));
+    expect(received).toEqual(["held"]);
+
+    expect(group.stats()).toMatchObject({ sockets: 2, open: 2, members: [expect.any(Object), expect.any(Object)] });
+    push.close();
+    expect(group.stats()).toMatchObject({ sockets: 2, open: 1 });
+    expect(group.remove(push)).toBe(true);
+    expect(group.remove(push)).toBe(false);
+    group.close();
+    expect(group.size()).toBe(0);
+    expect(pull.isConnect()).toBe(false);
+  });
+
+  it.skipIf(process.platform === "win32")("drains and closes natively on a shutdown signal while JS is blocked", async () => {
+    const url = `ipc://${join(tmpdir(), `spec-signals-${process.pid}.ipc`)}`;
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    pull.listen(url);
+    const received: string[] = [];
+    pull.recv((err, msg) => received.push(msg.toString()));
+    const script = `
+      const { SocketWrapper, ProtocolType, installSignalHandlers } = require(${JSON.stringify(join(__dirname, "..", "index.js"))});
+      installSignalHandlers(["SIGTERM"], { drainMs: 1000 });
+      process.on("exit", () => console.log("exit handler ran"));
+      const push = new SocketWrapper();
+      push.open(ProtocolType.Push0);
+      push.dial(${JSON.stringify(url)});
+      ["a", "b", "c"].forEach((v) => push.sendAsync(v));
+      console.log("ready");
+      for (;;) {}
+    `;
+    const child = spawn(process.execPath, ["-e", script]);
+    let output = "";
+    child.stdout.on("data", (chunk) => (output += chunk));
+    while (!output.includes("ready")) await new Promise((resolve) => setTimeout(resolve, 10));
+    await new Promise((resolve) => setTimeout(resolve, 50));
+    child.kill("SIGTERM");
+    const [code, signal] = await new Promise<[number | null, string | null]>((resolve) => child.on("exit", (...args) => resolve(args)));
+    await new Promise((resolve) => setTimeout(resolve, 20));
+
+    expect(signal).toBe("SIGTERM");
+    expect(code).toBeNull();
+    expect(output).not.toContain("exit handler ran");
+    expect(received).toEqual(["a", "b", "c"]);
+    expect(() => installSignalHandlers(["SIGUSR1"])).toThrow();
+    pull.close();
+  });
+
+  it("keeps the sockets of each worker thread independent of the others", async () => {
+    const url = inprocUrl("spec-envs");
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    pull.listen(url);
+    const received: string[] = [];
+    pull.recv((err, msg) => received.push(msg.toString()));
+    const start = (name: string) => {
+      const worker = new Worker(
+        `
+        const { parentPort } = require("worker_threads");
+        const { SocketWrapper, ProtocolType } = require(${JSON.stringify(join(__dirname, "..", "index.js"))});
+        const push = new SocketWrapper();
+        push.open(ProtocolType.Push0);
+        push.dial(${JSON.stringify(url)});
+        parentPort.on("message", (v) => push.sendAsync(v).then(() => parentPort.postMessage("sent")));
+        parentPort.postMessage("ready");
+        `,
+        { eval: true },
+      );
+      const next = () => new Promise((resolve) => worker.once("message", resolve));
+      return { worker, next, send: (v: string) => (worker.postMessage(`${name}:${v}`), next()) };
+    };
+    const [first, second] = [start("first"), start("second")];
+    await Promise.all([first.next(), second.next()]);
+
+    await first.send("1");
+    await second.send("1");
+    await first.worker.terminate();
+    await second.send("2");
+    await new Promise((resolve) => setTimeout(resolve, 20));
+
+    expect(received).toEqual(["first:1", "second:1", "second:2"]);
+    await second.worker.terminate();
+    pull.close();
+  });
+
+  it("closes the sockets a worker left open when its env is torn down", async () => {
+    const url = inprocUrl("spec-teardown");
+    for (let i = 0; i < 3; i++) {
+      const worker = new Worker(
+        `
+        const { parentPort } = require("worker_threads");
+        const { SocketWrapper, ProtocolType } = require(${JSON.stringify(join(__dirname, "..", "index.js"))});
+        const pull = new SocketWrapper();
+        pull.open(ProtocolType.Pull0);
+        pull.listen(${JSON.stringify(url)});
+        pull.recv(() => {});
+        parentPort.postMessage("listening");
+        `,
+        { eval: true },
+      );
+      await new Promise((resolve) => worker.once("message", resolve));
+      await worker.terminate();
+    }
+
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    pull.listen(url);
+    pull.close();
+  });
+
+  it("passes the protocol conformance checks", async () => {
+    const checks = await runConformance();
+    expect(checks.filter((check) => !check.passed)).toEqual([]);
+  });
+});
+
+describe("endpoints", () => {
+  it("opens a socket without connecting and applies timeouts before dialing", async () => {
+    const url = inprocUrl("spec-open");
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    expect(pull.isConnect()).toBe(true);
+    pull.setTimeouts(20, 20);
+    await expect(pull.recvOnce()).rejects.toThrow("Receive timeout");
+
+    pull.listen(url);
+    const push = new SocketWrapper();
+    push.open(ProtocolType.Push0);
+    push.dial(url);
+    const received = pull.recvOnce();
+    await push.sendAsync("late");
+    expect((await received).toString()).toBe("late");
+    push.close();
+    pull.close();
+  });
+
+  it("listens on several addresses and closes them one at a time", async () => {
+    const urls = [inprocUrl("spec-listeners"), `ipc://${join(tmpdir(), `spec-listeners-${process.pid}.ipc`)}`];
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    const ids = urls.map((url) => pull.listen(url));
+    expect(pull.listeners()).toEqual(urls.map((url, i) => ({ id: ids[i], url })));
+    const received: string[] = [];
+    pull.recv((err, msg) => received.push(msg.toString()));
+
+    const pushes = urls.map((url) => {
+      const push = new SocketWrapper();
+      push.open(ProtocolType.Push0);
+      push.dial(url);
+      return push;
+    });
+    await Promise.all(pushes.map((push, i) => push.sendAsync(`via ${i}`)));
+    await new Promise((resolve) => setTimeout(resolve, 20));
+    expect(received.sort()).toEqual(["via 0", "via 1"]);
+
+    expect(pull.closeListener(ids[0])).toBe(true);
+    expect(pull.closeListener(ids[0])).toBe(false);
+    expect(pull.listeners()).toEqual([{ id: ids[1], url: urls[1] }]);
+    const late = new SocketWrapper();
+    late.open(ProtocolType.Push0);
+    expect(() => late.dial(urls[0])).toThrow();
+    late.close();
+    pushes.forEach((push) => push.close());
+    pull.close();
+  });
+
+  it("only starts created endpoints once their start call runs", async () => {
+    const url = inprocUrl("spec-two-phase");
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    const listener = pull.createListener(url, { recvMaxSize: 1024 });
+    expect(pull.listeners()).toEqual([]);
+    const push = new SocketWrapper();
+    push.open(ProtocolType.Push0);
+    expect(() => push.dial(url)).toThrow("Connection failed");
+
+    pull.startListener(listener);
+    expect(pull.listeners()).toEqual([{ id: listener, url }]);
+    expect(() => pull.startListener(listener)).toThrow(`No pending listener with id ${listener}`);
+    const dialer = push.createDialer(url, { reconnectMinMs: 10, reconnectMaxMs: 100 });
+    push.startDialer(dialer);
+    const received = pull.recvOnce(1000);
+    await push.sendAsync("started");
+    expect((await received).toString()).toBe("started");
+    push.close();
+    pull.close();
+  });
+
+  it("records connect history for each endpoint across a reconnect", async () => {
+    const url = inprocUrl("spec-endpoint-history");
+    const listen = () => {
+      const pull = new SocketWrapper();
+      pull.open(ProtocolType.Pull0);
+      pull.listen(url);
+      return pull;
+    };
+    let pull = listen();
+    const push = new SocketWrapper();
+    push.open(ProtocolType.Push0);
+    push.dial(url);
+    const dialer = () => push.stats().endpoints.find((endpoint) => endpoint.kind === "dialer");
+    expect(dialer()).toMatchObject({ url, connected: 1, connects: 1, disconnects: 0, reconnects: 0 });
+
+    pull.close();
+    pull = listen();
+    for (let i = 0; i < 100 && dialer()?.connects !== 2; i++) {
+      await new Promise((resolve) => setTimeout(resolve, 20));
+    }
+    expect(dialer()).toMatchObject({
+      url,
+      connected: 1,
+      connects: 2,
+      disconnects: 1,
+      reconnects: 1,
+      lastConnectedAt: expect.any(Number),
+      lastDisconnectedAt: expect.any(Number),
+    });
+    expect(dialer()!.uptimeMs > 0).toBe(true);
+    push.close();
+    pull.close();
+  });
+
+  it("dumps the socket and endpoint options with their defaults", () => {
+    const url = inprocUrl("spec-dump-options");
+    const rep = new SocketWrapper();
+    rep.open(ProtocolType.Rep0);
+    rep.listen(url);
+    const req = new SocketWrapper();
+    req.open(ProtocolType.Req0);
+    req.setTimeouts(250, 500);
+    req.dial(url);
+
+    const dump = req.dumpOptions();
+    expect(dump.socket).toContainEqual({ name: "protocol-name", value: "req", defaultValue: "req" });
+    expect(dump.socket).toContainEqual({ name: "recv-timeout", value: 250, defaultValue: -1 });
+    expect(dump.socket).toContainEqual({ name: "send-timeout", value: 500, defaultValue: -1 });
+    expect(dump.socket.find((entry) => entry.name === "socket-name")).not.toHaveProperty("defaultValue");
+    expect(dump.endpoints).toEqual([
+      expect.objectContaining({ kind: "dialer", url, started: true, options: expect.arrayContaining([{ name: "url", value: url }]) }),
+    ]);
+    req.close();
+    rep.close();
+  });
+
+  it("coalesces a burst of disconnects into one connectionLost event", async () => {
+    const url = inprocUrl("spec-disconnect-storm");
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    pull.listen(url);
+    pull.setDisconnectCoalescing({ windowMs: 30 });
+    const removed: PipeInfo[] = [];
+    pull.onPipeRemoved((err, info) => removed.push(info));
+    const events: SocketEvent[] = [];
+    pull.onEvent((err, event) => ["connectionLost", "connectionRestored"].includes(event.name) && events.push(event));
+    const connect = () => {
+      const push = new SocketWrapper();
+      push.open(ProtocolType.Push0);
+      push.dial(url);
+      return push;
+    };
+
+    const pushes = [1, 2, 3, 4, 5].map(connect);
+    pushes.forEach((push) => push.close());
+    await new Promise((resolve) => setTimeout(resolve, 100));
+    expect(events).toEqual([expect.objectContaining({ name: "connectionLost", value: 5 })]);
+    expect(removed).toEqual([]);
+
+    const returned = connect();
+    await new Promise((resolve) => setTimeout(resolve, 20));
+    expect(events.map((event) => event.name)).toEqual(["connectionLost", "connectionRestored"]);
+    expect(() => pull.setDisconnectCoalescing({ windowMs: 0 })).toThrow("windowMs must be greater than 0");
+    returned.close();
+    pull.close();
+  });
+
+  it("keeps the inproc addresses of different namespaces apart", async () => {
+    const url = inprocUrl("spec-namespace");
+    const tenant = (namespace: string) => {
+      const pull = new SocketWrapper();
+      pull.open(ProtocolType.Pull0);
+      pull.setNamespace(namespace);
+      pull.listen(url);
+      const push = new SocketWrapper();
+      push.open(ProtocolType.Push0);
+      push.setNamespace(namespace);
+      push.dial(url);
+      return { pull, push };
+    };
+    const a = tenant("tenant-a");
+    const b = tenant("tenant-b");
+
+    expect(a.pull.namespace()).toBe("tenant-a");
+    expect(a.pull.labels()).toMatchObject({ namespace: "tenant-a" });
+    expect(a.pull.listeners()).toEqual([expect.objectContaining({ url: namespacedUrl("tenant-a", url) })]);
+    await a.push.sendAsync("for a");
+    await b.push.sendAsync("for b");
+    expect((await a.pull.recvOnce(1000)).toString()).toBe("for a");
+    expect((await b.pull.recvOnce(1000)).toString()).toBe("for b");
+
+    expect(namespacedUrl("tenant-a", "ipc:///tmp/jobs.ipc")).toBe("ipc:///tmp/tenant-a.jobs.ipc");
+    expect(namespacedUrl("tenant-a", "tcp://127.0.0.1:5555")).toBe("tcp://127.0.0.1:5555");
+    expect(() => a.pull.setNamespace("bad/name")).toThrow("Namespace must be non-empty");
+    a.pull.setNamespace(null);
+    expect(a.pull.labels()).not.toHaveProperty("namespace");
+    [a.push, a.pull, b.push, b.pull].forEach((socket) => socket.close());
+  });
+
+  it("redials on the reconnect backoff given to connect()", async () => {
+    const url = `ipc://${join(tmpdir(), `spec-backoff-${process.pid}.ipc`)}`;
+    const listen = () => {
+      const pull = new SocketWrapper();
+      pull.open(ProtocolType.Pull0);
+      pull.listen(url);
+      return pull;
+    };
+    let pull = listen();
+    const push = new SocketWrapper();
+    expect(push.connect(ProtocolType.Push0, url, 1000, 1000, false, { minMs: 20, maxMs: 40 })).toBe(true);
+    expect(() => push.setReconnectBackoff({ minMs: 50, maxMs: 10 })).toThrow();
+    await push.sendAsync("before");
+    expect((await pull.recvOnce(1000)).toString()).toBe("before");
+
+    pull.close();
+    await new Promise((resolve) => setTimeout(resolve, 50));
+    pull = listen();
+    const restarted = Date.now();
+    await push.sendAsync("after");
+    expect((await pull.recvOnce(1000)).toString()).toBe("after");
+    expect(Date.now() - restarted).toBeLessThan(250);
+
+    push.close();
+    pull.close();
+  });
+
+  it("builds and parses URLs for each transport", () => {
+    expect(buildUrl(Transport.Tcp, "127.0.0.1", 5555)).toBe("tcp://127.0.0.1:5555");
+    expect(buildUrl(Transport.Tcp, null, 5555)).toBe("tcp://:5555");
+    expect(buildUrl(Transport.TlsTcp, "::1", 443)).toBe("tls+tcp://[::1]:443");
+    expect(buildUrl(Transport.Ws, "localhost", 8080, "feed")).toBe("ws://localhost:8080/feed");
+    expect(buildUrl(Transport.Ipc, null, null, "/tmp/spec.ipc")).toBe("ipc:///tmp/spec.ipc");
+    expect(buildUrl(Transport.Inproc, null, null, "spec")).toBe("inproc://spec");
+    expect(() => buildUrl(Transport.Tcp, "localhost")).toThrow("tcp:// URLs need a port");
+    expect(() => buildUrl(Transport.Tcp, "localhost", 70000)).toThrow("Port out of range: 70000");
+    expect(() => buildUrl(Transport.Tcp, "localhost", 80, "feed")).toThrow("tcp:// URLs do not take a path");
+    expect(() => buildUrl(Transport.Ipc, "localhost", 80, "spec")).toThrow("ipc:// URLs take only a path");
+
+    expect(parseTransport("tls+tcp://example.com:443")).toBe(Transport.TlsTcp);
+    expect(parseTransport("tcp6://[::1]:5555")).toBe(Transport.Tcp);
+    expect(parseTransport(buildUrl(Transport.Wss, "example.com", 443, "/feed"))).toBe(Transport.Wss);
+    expect(() => parseTransport("udp://localhost:5555")).toThrow("Unknown transport scheme: udp");
+    expect(() => parseTransport("localhost:5555")).toThrow("Missing scheme in URL");
+  });
+});
+
+describe("protocols", () => {
+  it("throws ProtocolMisuse for operations the protocol cannot perform", () => {
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    const push = new SocketWrapper();
+    push.open(ProtocolType.Push0);
+    const thrown = (operation: () => unknown) => {
+      try {
+        operation();
+      } catch (err) {
+        return err;
+      }
+    };
+
+    expect(thrown(() => pull.sendAsync("x"))).toMatchObject({ code: "ProtocolMisuse", message: expect.stringContaining("Pull0") });
+    expect(thrown(() => push.recvOnce(10))).toMatchObject({ code: "ProtocolMisuse", message: expect.stringContaining("Push0") });
+    pull.close();
+    push.close();
+  });
+
+  it("reports what each open socket can do", () => {
+    const socket = new SocketWrapper();
+    expect(socket.capabilities()).toBeNull();
+    socket.open(ProtocolType.Pull0);
+    expect(socket.capabilities()).toEqual({ canSend: false, canRecv: true, raw: false, alternating: false });
+    socket.close();
+    socket.open(ProtocolType.Req0);
+    expect(socket.capabilities()).toEqual({ canSend: true, canRecv: true, raw: false, alternating: true });
+    socket.close();
+    socket.open(ProtocolType.Req0, true);
+    expect(socket.capabilities()).toEqual({ canSend: true, canRecv: true, raw: true, alternating: false });
+    socket.close();
+    expect(socket.capabilities()).toBeNull();
+  });
+
+  it("maps classic nanomsg option names onto nng options", async () => {
+    const url = inprocUrl("spec-nanomsg-options");
+    const pub = new SocketWrapper();
+    pub.open(ProtocolType.Pub0);
+    pub.listen(url);
+    const sub = new SocketWrapper();
+    sub.open(ProtocolType.Sub0);
+    sub.setNanomsgOption("NN_SNDTIMEO", 250);
+    sub.setNanomsgOption("rcvtimeo", -1);
+    sub.setNanomsgOption("chan", "a");
+    sub.dial(url);
+
+    expect(sub.getNanomsgOption("SNDTIMEO")).toBe(250);
+    expect(sub.getNanomsgOption("NN_RCVTIMEO")).toBe(-1);
+    expect(() => sub.setNanomsgOption("NN_SNDPRIO", 1)).toThrow("NN_SNDPRIO is not supported: nng has no endpoint priorities");
+    expect(() => sub.setNanomsgOption("NN_BOGUS", 1)).toThrow("Unknown nanomsg option: NN_BOGUS");
+
+    const received: string[] = [];
+    sub.recv((err, msg) => received.push(msg.toString()));
+    await new Promise((resolve) => setTimeout(resolve, 50));
+    pub.post("a1");
+    pub.post("b1");
+    await new Promise((resolve) => setTimeout(resolve, 50));
+    expect(received).toEqual(["a1"]);
+    sub.close();
+    pub.close();
+  });
+});
+
+describe("payloads", () => {
+  it("accepts strings, Uint8Arrays and ArrayBuffers wherever a Buffer is accepted", async () => {
+    const url = inprocUrl("spec-payloads");
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    pull.listen(url);
+    const push = new SocketWrapper();
+    push.open(ProtocolType.Push0);
+    push.dial(url);
+
+    const received: string[] = [];
+    pull.recv((err, msg) => received.push(msg.toString()));
+    await push.sendAsync("text");
+    await push.sendAsync(new Uint8Array([104, 105]));
+    await push.sendAsync(new TextEncoder().encode("ab").buffer);
+    await new Promise((resolve) => setTimeout(resolve, 50));
+
+    expect(received).toEqual(["text", "hi", "ab"]);
+    push.close();
+    pull.close();
+  });
+});
+
+describe("sending", () => {
+  it("drops posted messages without a peer but keeps sendReliable messages until one connects", async () => {
+    const url = inprocUrl("spec-post");
+    const push = new SocketWrapper();
+    push.open(ProtocolType.Push0);
+    push.dial(url, true);
+    push.post("dropped");
+    expect(push.postDropped()).toBe(1);
+    const sent = push.sendReliable("kept");
+
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    pull.listen(url);
+    expect((await pull.recvOnce(1000)).toString()).toBe("kept");
+    await sent;
+
+    push.post("posted");
+    expect((await pull.recvOnce(1000)).toString()).toBe("posted");
+    expect(push.postDropped()).toBe(1);
+    push.close();
+    pull.close();
+    expect(() => push.sendReliable("closed")).toThrow("Socket not connected");
+    expect(() => push.post("closed")).toThrow("Socket not connected");
+  });
+
+  it("rejects sendAsync once queued bytes would exceed the memory limit", async () => {
+    const url = inprocUrl("spec-memory");
+    const push = new SocketWrapper();
+    push.open(ProtocolType.Push0);
+    push.setMemoryLimit(8);
+    const overLimit: (number | undefined)[] = [];
+    push.onEvent((err, event) => {
+      if (event.name === "overLimit") overLimit.push(event.value);
+    });
+    push.dial(url, true);
+
+    const queued = push.sendAsync("12345");
+    expect(push.memoryUsage()).toMatchObject({ queuedBytes: 5, totalBytes: 5, limitBytes: 8 });
+    await expect(push.sendAsync("6789")).rejects.toThrow("Memory limit exceeded");
+    await new Promise((resolve) => setTimeout(resolve, 20));
+    expect(overLimit).toEqual([9]);
+
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    pull.listen(url);
+    expect((await pull.recvOnce(1000)).toString()).toBe("12345");
+    await queued;
+    expect(push.memoryUsage().queuedBytes).toBe(0);
+    push.close();
+    pull.close();
+  });
+
+  it("hands undeliverable messages to the dead-letter sink", async () => {
+    const url = inprocUrl("spec-dead-letter");
+    const push = new SocketWrapper();
+    push.open(ProtocolType.Push0);
+    push.setMemoryLimit(4);
+    const letters: DeadLetter[] = [];
+    push.setDeadLetter((err, letter) => letters.push(letter));
+    await expect(push.sendAsync("first")).rejects.toThrow("Memory limit exceeded");
+    await new Promise((resolve) => setTimeout(resolve, 20));
+    expect(letters).toEqual([{ message: Buffer.from("first"), reason: "memoryLimit" }]);
+
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    pull.listen(url);
+    const forward = new SocketWrapper();
+    forward.open(ProtocolType.Push0);
+    forward.dial(url);
+    push.setDeadLetter(forward);
+    await expect(push.sendAsync("second")).rejects.toThrow("Memory limit exceeded");
+    expect((await pull.recvOnce(1000)).toString()).toBe("second");
+    expect(() => push.setDeadLetter(push)).toThrow("A socket cannot be its own dead-letter sink");
+
+    push.close();
+    forward.close();
+    pull.close();
+  });
+
+  it("sends delayed messages in deadline order and rejects unsent ones on close", async () => {
+    const url = inprocUrl("spec-send-after");
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    pull.listen(url);
+    const push = new SocketWrapper();
+    push.open(ProtocolType.Push0);
+    push.dial(url);
+
+    const received: string[] = [];
+    pull.recv((err, msg) => received.push(msg.toString()));
+    const started = Date.now();
+    const late = push.sendAfter("late", 80);
+    const early = push.sendAfter("early", 20);
+    await early;
+    expect(received).not.toContain("late");
+    await late;
+    expect(Date.now() - started >= 70).toBe(true);
+    await new Promise((resolve) => setTimeout(resolve, 20));
+    expect(received).toEqual(["early", "late"]);
+
+    const pending = push.sendAfter("never", 1000);
+    push.close();
+    await expect(pending).rejects.toMatchObject({ code: "SocketClosed" });
+    pull.close();
+  });
+
+  it("holds sends while reconnecting and flushes them in order", async () => {
+    const url = inprocUrl("spec-disconnected-queue");
+    const listen = () => {
+      const pull = new SocketWrapper();
+      pull.open(ProtocolType.Pull0);
+      pull.listen(url);
+      return pull;
+    };
+    let pull = listen();
+    const push = new SocketWrapper();
+    push.open(ProtocolType.Push0);
+    push.dial(url);
+    push.setDisconnectedQueue(2);
+    await push.sendAsync("one");
+    expect((await pull.recvOnce(1000)).toString()).toBe("one");
+
+    pull.close();
+    await new Promise((resolve) => setTimeout(resolve, 20));
+    const held = [push.sendAsync("two"), push.sendAsync("three")];
+    await expect(push.sendAsync("four")).rejects.toThrow("Disconnected queue is full");
+
+    pull = listen();
+    await Promise.all(held);
+    expect((await pull.recvOnce(1000)).toString()).toBe("two");
+    expect((await pull.recvOnce(1000)).toString()).toBe("three");
+    push.close();
+    pull.close();
+  });
+
+  it("reports queue depths on both sides of an ipc connection", async () => {
+    const url = `ipc://${join(tmpdir(), `spec-depths-${process.pid}.ipc`)}`;
+    const push = new SocketWrapper();
+    push.open(ProtocolType.Push0);
+    push.dial(url, true);
+    const sent = push.sendAsync("queued");
+    expect(push.stats().outboxDepth).toBe(1);
+
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    pull.listen(url);
+    await sent;
+    await new Promise((resolve) => setTimeout(resolve, 50));
+    expect(push.stats()).toMatchObject({ outboxDepth: 0, sendQueueDepth: 0 });
+    expect(pull.stats().recvQueueDepth).toBe(1);
+
+    expect((await pull.recvOnce(1000)).toString()).toBe("queued");
+    expect(pull.stats().recvQueueDepth).toBe(0);
+    push.close();
+    pull.close();
+  });
+
+  it("drains once queued messages reach the peer", async () => {
+    const url = `ipc://${join(tmpdir(), `spec-drain-${process.pid}.ipc`)}`;
+    const push = new SocketWrapper();
+    push.open(ProtocolType.Push0);
+    push.dial(url, true);
+    const sent = ["a", "b"].map((body) => push.sendAsync(body));
+    await expect(push.drain(50)).rejects.toThrow("Drain timed out");
+
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    pull.listen(url);
+    const received: string[] = [];
+    pull.recv((err, msg) => received.push(msg.toString()));
+    await push.drain(2000);
+    await Promise.all(sent);
+    await new Promise((resolve) => setTimeout(resolve, 20));
+
+    expect(received).toEqual(["a", "b"]);
+    push.close();
+    pull.close();
+  });
+});
+
+describe("receiving", () => {
+  it("stops the recv loop on abort without consuming the next message", async () => {
+    const url = inprocUrl("spec-recv-abort");
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    pull.listen(url);
+    const push = new SocketWrapper();
+    push.open(ProtocolType.Push0);
+    push.dial(url);
+
+    const controller = new AbortController();
+    const received: string[] = [];
+    const done = pull.recv((err, msg) => received.push(msg.toString()), controller.signal);
+    await push.sendAsync("before");
+    await new Promise((resolve) => setTimeout(resolve, 20));
+    controller.abort();
+    await done;
+
+    await push.sendAsync("after");
+    expect((await pull.recvOnce(1000)).toString()).toBe("after");
+    expect(received).toEqual(["before"]);
+    push.close();
+    pull.close();
+  });
+
+  it("reuses one buffer across recvBorrowed callbacks and grows it for larger messages", async () => {
+    const url = inprocUrl("spec-recv-borrowed");
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    pull.listen(url);
+    const push = new SocketWrapper();
+    push.open(ProtocolType.Push0);
+    push.dial(url);
+
+    const received: { buffer: Buffer; length: number; text: string }[] = [];
+    const done = pull.recvBorrowed((err, buffer, length) => received.push({ buffer, length, text: buffer.subarray(0, length).toString() }), 16);
+    for (const message of ["a", "bb", "x".repeat(40), "c"]) await push.sendAsync(message);
+    await new Promise((resolve) => setTimeout(resolve, 50));
+
+    expect(received.map(({ length, text }) => [length, text])).toEqual([[1, "a"], [2, "bb"], [40, "x".repeat(40)], [1, "c"]]);
+    expect(received[0].buffer.length).toBe(16);
+    expect(received[1].buffer).toBe(received[0].buffer);
+    expect(received[2].buffer).not.toBe(received[0].buffer);
+    expect(received[2].buffer.length).toBeGreaterThanOrEqual(40);
+    expect(received[3].buffer).toBe(received[2].buffer);
+    pull.close();
+    await done;
+    push.close();
+  });
+
+  it("copies messages into the caller's buffer with recvInto", async () => {
+    const url = inprocUrl("spec-recv-into");
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    pull.listen(url);
+    const push = new SocketWrapper();
+    push.open(ProtocolType.Push0);
+    push.dial(url);
+
+    const buffer = Buffer.alloc(8);
+    const first = pull.recvInto(buffer);
+    await expect(pull.recvInto(Buffer.alloc(8))).rejects.toThrow("Another recvInto is already waiting");
+    await push.sendAsync("hello");
+    expect(await first).toBe(5);
+    expect(buffer.subarray(0, 5).toString()).toBe("hello");
+
+    await push.sendAsync("0123456789abc");
+    expect(await pull.recvInto(buffer)).toBe(13);
+    expect(buffer.toString()).toBe("01234567");
+
+    pull.setTimeouts(20, 20);
+    await expect(pull.recvInto(buffer)).rejects.toMatchObject({ message: "Receive timeout", nngCode: NngErrorCode.TimedOut });
+
+    pull.setTimeouts(0, 0);
+    const waiting = pull.recvInto(buffer);
+    pull.close();
+    await expect(waiting).rejects.toMatchObject({ code: "SocketClosed" });
+    push.close();
+  });
+
+  it("resolves the recv loop on close without reporting an error", async () => {
+    const url = inprocUrl("spec-recv-close");
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    pull.listen(url);
+    const push = new SocketWrapper();
+    push.open(ProtocolType.Push0);
+    push.dial(url);
+
+    const events: string[] = [];
+    pull.onEvent((err, event) => events.push(event.name));
+    const received: string[] = [];
+    const done = pull.recv((err, msg) => received.push(msg.toString()));
+    await push.sendAsync("only");
+    await new Promise((resolve) => setTimeout(resolve, 20));
+    pull.close();
+    await done;
+
+    expect(received).toEqual(["only"]);
+    expect(events).not.toContain("error");
+    push.close();
+  });
+
+  it("reports receive timeouts as warning events", async () => {
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    pull.listen(inprocUrl("spec-recv-warning"));
+    pull.setTimeouts(20, 20);
+
+    const warnings: SocketEvent[] = [];
+    pull.onEvent((err, event) => event.name === "warning" && warnings.push(event));
+    const done = pull.recv(() => {});
+    await new Promise((resolve) => setTimeout(resolve, 80));
+    pull.close();
+    await done;
+
+    expect(warnings.length > 0).toBe(true);
+    expect(warnings[0]).toMatchObject({ code: "recvTimedOut", message: "Receive timed out." });
+  });
+
+  it("carries socket labels into stats, events and option dumps", async () => {
+    const labels = { service: "pricing", role: "sub" };
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    pull.setLabels(labels);
+    pull.listen(inprocUrl("spec-labels"));
+    pull.setTimeouts(20, 20);
+
+    const warnings: SocketEvent[] = [];
+    pull.onEvent((err, event) => event.name === "warning" && warnings.push(event));
+    const done = pull.recv(() => {});
+    await new Promise((resolve) => setTimeout(resolve, 80));
+
+    expect(pull.labels()).toEqual(labels);
+    expect(pull.stats().labels).toEqual(labels);
+    expect(pull.dumpOptions().labels).toEqual(labels);
+    expect(warnings[0]).toMatchObject({ code: "recvTimedOut", labels });
+    pull.close();
+    await done;
+
+    const sub = new Subscriber();
+    sub.setLabels(labels);
+    sub.subscribe("a");
+    expect(sub.stats()).toEqual([{ topic: "a", received: 0, dropped: 0, lag: 0, labels }]);
+    sub.close();
+  });
+
+  it("keeps the most recent sent and received messages for capture()", async () => {
+    const url = inprocUrl("spec-capture");
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    pull.listen(url);
+    pull.setCapture(2);
+    const push = new SocketWrapper();
+    push.open(ProtocolType.Push0);
+    push.setCapture(5);
+    push.dial(url);
+
+    pull.recv(() => {});
+    for (const body of ["a", "b", "c"]) {
+      await push.sendAsync(body);
+    }
+    await new Promise((resolve) => setTimeout(resolve, 20));
+
+    expect(push.capture().map((entry) => [entry.direction, entry.data.toString()])).toEqual([
+      ["sent", "a"],
+      ["sent", "b"],
+      ["sent", "c"],
+    ]);
+    expect(pull.capture()).toEqual([
+      expect.objectContaining({ direction: "received", data: Buffer.from("b"), pipe: expect.any(Number), at: expect.any(Number) }),
+      expect.objectContaining({ direction: "received", data: Buffer.from("c") }),
+    ]);
+    pull.setCapture(0);
+    expect(pull.capture()).toEqual([]);
+    push.close();
+    pull.close();
+  });
+
+  it("writes no crash dump when sockets close normally", async () => {
+    const dir = join(tmpdir(), `spec-crash-dumps-${process.pid}`);
+    rmSync(dir, { recursive: true, force: true });
+    const url = inprocUrl("spec-crash-dump");
+    const pull = new SocketWrapper();
+    pull.setCrashDump(dir);
+    pull.open(ProtocolType.Pull0);
+    pull.setCapture(4);
+    pull.listen(url);
+    const events: string[] = [];
+    pull.onEvent((err, event) => events.push(event.name));
+    const push = new SocketWrapper();
+    push.open(ProtocolType.Push0);
+    push.dial(url);
+    const done = pull.recv(() => {});
+    await push.sendAsync("a");
+    await new Promise((resolve) => setTimeout(resolve, 20));
+    pull.close();
+    await done;
+
+    expect(events).not.toContain("crashDump");
+    expect(events).not.toContain("error");
+    expect(existsSync(dir)).toBe(false);
+    pull.setCrashDump(null);
+    push.close();
+  });
+
+  it("records received traffic and replays it through another socket", async () => {
+    const path = join(tmpdir(), `spec-recording-${process.pid}.bin`);
+    const [recordedUrl, replayUrl] = [inprocUrl("spec-record"), inprocUrl("spec-replay")];
+    const recorder = new SocketWrapper();
+    recorder.open(ProtocolType.Pull0);
+    recorder.listen(recordedUrl);
+    recorder.record(path);
+    const source = new SocketWrapper();
+    source.open(ProtocolType.Push0);
+    source.dial(recordedUrl);
+    recorder.recv(() => {});
+    await source.sendAsync("a");
+    await source.sendAsync("b");
+    await new Promise((resolve) => setTimeout(resolve, 20));
+    recorder.stopRecording();
+
+    const target = new SocketWrapper();
+    target.open(ProtocolType.Pull0);
+    target.listen(replayUrl);
+    const replayer = new SocketWrapper();
+    replayer.open(ProtocolType.Push0);
+    replayer.dial(replayUrl);
+    expect(await replayer.replay(path, { speed: Infinity, direction: "received" })).toBe(2);
+    expect((await target.recvOnce(1000)).toString()).toBe("a");
+    expect((await target.recvOnce(1000)).toString()).toBe("b");
+    expect(await replayer.replay(path, { direction: "sent" })).toBe(0);
+    [source, recorder, replayer, target].forEach((socket) => socket.close());
+  });
+
+  it("injects drops, duplicates and latency into delivery", async () => {
+    const url = inprocUrl("spec-faults");
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    pull.listen(url);
+    const push = new SocketWrapper();
+    push.open(ProtocolType.Push0);
+    push.dial(url);
+    const received: string[] = [];
+    pull.recv((err, msg) => received.push(msg.toString()));
+    const send = async (body: string, wait: number) => {
+      await push.sendAsync(body);
+      await new Promise((resolve) => setTimeout(resolve, wait));
+    };
+
+    expect(() => pull.setFaults({ dropRate: 2 })).toThrow("dropRate must be between 0 and 1");
+    expect(() => pull.setFaults({ direction: "sideways" })).toThrow('direction must be "send", "recv" or "both", got "sideways"');
+    pull.setFaults({ dropRate: 1, direction: "recv" });
+    await send("dropped", 20);
+    pull.setFaults({ duplicateRate: 1, direction: "recv" });
+    await send("twice", 20);
+    pull.setFaults({ latencyMs: 80, direction: "recv" });
+    await send("late", 20);
+    expect(received).toEqual(["twice", "twice"]);
+    await new Promise((resolve) => setTimeout(resolve, 100));
+    pull.setFaults(null);
+    await send("plain", 20);
+
+    expect(received).toEqual(["twice", "twice", "late", "plain"]);
+    push.close();
+    pull.close();
+  });
+
+  it.skipIf(process.platform !== "linux")("pins the receive thread to a cpu and niceness", async () => {
+    const url = inprocUrl("spec-pinning");
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    pull.listen(url);
+    const push = new SocketWrapper();
+    push.open(ProtocolType.Push0);
+    push.dial(url);
+
+    expect(() => pull.setReceiveThread({ niceness: 40 })).toThrow("niceness must be between -20 and 19, got 40");
+    expect(() => pull.setReceiveThread({ cpu: 4096 })).toThrow("cpu must be less than");
+    pull.setReceiveThread({ cpu: 0, niceness: 5 });
+    const warnings: SocketEvent[] = [];
+    pull.onEvent((err, event) => event.name === "warning" && warnings.push(event));
+    const received: string[] = [];
+    pull.recv((err, msg) => received.push(msg.toString()));
+    await push.sendAsync("pinned");
+    await new Promise((resolve) => setTimeout(resolve, 20));
+
+    expect(received).toEqual(["pinned"]);
+    expect(warnings).toEqual([]);
+    push.close();
+    pull.close();
+  });
+
+  it("limits how many delivered messages wait on the event loop", async () => {
+    const url = inprocUrl("spec-delivery-budget");
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    pull.listen(url);
+    const push = new SocketWrapper();
+    push.open(ProtocolType.Push0);
+    push.setNanomsgOption("SNDBUF", 16);
+    push.dial(url);
+
+    const inFlightWhileBusy = async (budget: number | null) => {
+      pull.setDeliveryBudget(budget);
+      const controller = new AbortController();
+      const received: string[] = [];
+      const done = pull.recv((err, msg) => received.push(msg.toString()) === 5 && controller.abort(), controller.signal);
+      ["1", "2", "3", "4", "5"].forEach((body) => push.post(body));
+      const until = Date.now() + 50;
+      while (Date.now() < until) {}
+      const inFlight = pull.memoryUsage().inFlightBytes;
+      await done;
+      expect(received).toEqual(["1", "2", "3", "4", "5"]);
+      return inFlight;
+    };
+
+    expect(await inFlightWhileBusy(null)).toBe(5);
+    expect(await inFlightWhileBusy(1)).toBe(1);
+    push.close();
+    pull.close();
+  });
+
+  it("reports a stalled event when the recv loop waits on a blocked event loop", async () => {
+    const url = inprocUrl("spec-watchdog");
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    pull.listen(url);
+    pull.setDeliveryBudget(1);
+    pull.setRecvWatchdog({ stallMs: 30 });
+    const stalls: SocketEvent[] = [];
+    pull.onEvent((err, event) => event.name === "stalled" && stalls.push(event));
+    const received: string[] = [];
+    pull.recv((err, msg) => received.push(msg.toString()));
+    const push = new SocketWrapper();
+    push.open(ProtocolType.Push0);
+    push.setNanomsgOption("SNDBUF", 16);
+    push.dial(url);
+
+    ["1", "2", "3"].forEach((body) => push.post(body));
+    const until = Date.now() + 100;
+    while (Date.now() < until) {}
+    await new Promise((resolve) => setTimeout(resolve, 50));
+
+    expect(received).toEqual(["1", "2", "3"]);
+    expect(stalls).toHaveLength(1);
+    expect(stalls[0]).toMatchObject({ code: "deliveryBudget", message: expect.stringContaining("has been in deliveryBudget") });
+    expect(stalls[0].value).toBeGreaterThanOrEqual(30);
+
+    pull.setRecvWatchdog(null);
+    ["4", "5"].forEach((body) => push.post(body));
+    const later = Date.now() + 100;
+    while (Date.now() < later) {}
+    await new Promise((resolve) => setTimeout(resolve, 50));
+    expect(received).toHaveLength(5);
+    expect(stalls).toHaveLength(1);
+    push.close();
+    pull.close();
+  });
+
+  it("applies the callback error policy when a recv callback throws", async () => {
+    const url = inprocUrl("spec-callback-error");
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    pull.listen(url);
+    const push = new SocketWrapper();
+    push.open(ProtocolType.Push0);
+    push.dial(url);
+    const events: SocketEvent[] = [];
+    pull.onEvent((err, event) => ["warning", "callbackError"].includes(event.name) && events.push(event));
+
+    pull.setCallbackErrorPolicy(CallbackErrorPolicy.Log);
+    const controller = new AbortController();
+    const received: string[] = [];
+    const logged = pull.recv((err, msg) => {
+      if (msg.toString() === "bad") throw new Error("handler failed");
+      received.push(msg.toString());
+    }, controller.signal);
+    await push.sendAsync("bad");
+    await push.sendAsync("good");
+    await new Promise((resolve) => setTimeout(resolve, 20));
+    controller.abort();
+    await logged;
+    expect(received).toEqual(["good"]);
+    expect(events).toEqual([expect.objectContaining({ name: "warning", code: "callbackThrew", message: expect.stringContaining("handler failed") })]);
+
+    pull.setCallbackErrorPolicy(CallbackErrorPolicy.Stop);
+    const stopped = pull.recv(async () => {
+      throw new Error("async failure");
+    });
+    await push.sendAsync("first");
+    await stopped;
+    await new Promise((resolve) => setTimeout(resolve, 20));
+    expect(events[1]).toMatchObject({ name: "callbackError", code: "stopped", message: expect.stringContaining("async failure") });
+    await push.sendAsync("next");
+    expect((await pull.recvOnce(1000)).toString()).toBe("next");
+    push.close();
+    pull.close();
+  });
+
+  it("redelivers envelopes until they are acknowledged", async () => {
+    const url = inprocUrl("spec-envelopes");
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    pull.listen(url);
+    const push = new SocketWrapper();
+    push.open(ProtocolType.Push0);
+    push.dial(url);
+    pull.setAcknowledgements({ mode: AckMode.AtLeastOnce, timeoutMs: 50, maxRedeliveries: 1 });
+    const letters: string[] = [];
+    pull.setDeadLetter((err, letter) => letters.push(`${letter.reason}:${letter.message.toString()}`));
+
+    const deliveries: string[] = [];
+    const acks: boolean[] = [];
+    pull.recvEnvelopes((err, envelope) => {
+      const body = envelope.body.toString();
+      deliveries.push(`${body}#${envelope.redelivered}`);
+      if (body === "ok") acks.push(envelope.ack(), envelope.ack());
+      if (body === "retry") envelope.redelivered === 0 ? envelope.nack(true) : envelope.ack();
+      if (body === "drop") envelope.nack(false);
+    });
+    for (const body of ["ok", "retry", "drop", "ignore"]) {
+      await push.sendAsync(body);
+    }
+    await new Promise((resolve) => setTimeout(resolve, 250));
+
+    expect(acks).toEqual([true, false]);
+    expect(deliveries.sort()).toEqual(["drop#0", "ignore#0", "ignore#1", "ok#0", "retry#0", "retry#1"]);
+    expect(letters.sort()).toEqual(["nacked:drop", "redeliveryLimit:ignore"]);
+    push.close();
+    pull.close();
+  });
+
+  it("pushes failed envelopes back to the distributor with an attempt count", async () => {
+    const [jobsUrl, retryUrl] = [inprocUrl("spec-requeue-jobs"), inprocUrl("spec-requeue-retry")];
+    const distributor = new SocketWrapper();
+    distributor.open(ProtocolType.Push0);
+    distributor.listen(jobsUrl);
+    const returns = new SocketWrapper();
+    returns.open(ProtocolType.Pull0);
+    returns.listen(retryUrl);
+    returns.recv((err, msg) => distributor.sendAsync(msg));
+
+    const worker = new SocketWrapper();
+    worker.open(ProtocolType.Pull0);
+    worker.dial(jobsUrl);
+    const retry = new SocketWrapper();
+    retry.open(ProtocolType.Push0);
+    retry.dial(retryUrl);
+    expect(() => worker.setRequeue(returns)).toThrow("The requeue socket must be able to send");
+    worker.setRequeue(retry, { maxAttempts: 3 });
+    const letters: DeadLetter[] = [];
+    worker.setDeadLetter((err, letter) => letters.push(letter));
+    const attempts: string[] = [];
+    worker.recvEnvelopes((err, envelope) => {
+      attempts.push(`${envelope.body.toString()}#${envelope.attempts}`);
+      envelope.nack();
+    });
+
+    await distributor.sendAsync("job");
+    await new Promise((resolve) => setTimeout(resolve, 100));
+    expect(attempts).toEqual(["job#0", "job#1", "job#2"]);
+    expect(letters).toEqual([expect.objectContaining({ message: Buffer.from("job"), reason: "retryLimit" })]);
+    [worker, retry, returns, distributor].forEach((socket) => socket.close());
+  });
+
+  it("lets an unref'd recv loop stop keeping the process alive", async () => {
+    const run = (keepAlive: boolean) => {
+      const script = `
+        const { SocketWrapper, ProtocolType } = require(${JSON.stringify(join(__dirname, "..", "index.js"))});
+        const pull = new SocketWrapper();
+        pull.open(ProtocolType.Pull0);
+        pull.listen(${JSON.stringify(inprocUrl("spec-unref"))});
+        pull.unref();
+        pull.recv(() => {});
+        ${keepAlive ? "pull.ref();" : ""}
+        console.log(pull.hasRef());
+      `;
+      const child = spawn(process.execPath, ["-e", script]);
+      let output = "";
+      child.stdout.on("data", (chunk) => (output += chunk));
+      const exited = new Promise<number | null>((resolve) => child.on("exit", (code) => resolve(code)));
+      return { child, exited, output: () => output.trim() };
+    };
+
+    const script = run(false);
+    expect(await script.exited).toBe(0);
+    expect(script.output()).toBe("false");
+
+    const daemon = run(true);
+    const outcome = await Promise.race([daemon.exited, new Promise((resolve) => setTimeout(() => resolve("running"), 500))]);
+    expect(outcome).toBe("running");
+    expect(daemon.output()).toBe("true");
+    daemon.child.kill();
+    await daemon.exited;
+  });
+
+  it("delivers ArrayBuffers that can be transferred to another thread", async () => {
+    const url = inprocUrl("spec-transferable");
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    pull.listen(url);
+    const push = new SocketWrapper();
+    push.open(ProtocolType.Push0);
+    push.dial(url);
+
+    const received = new Promise<ArrayBuffer>((resolve) => pull.recvTransferable((err, data) => resolve(data)));
+    await push.sendAsync("moved");
+    const data = await received;
+    const { port1, port2 } = new MessageChannel();
+    const transferred = new Promise<ArrayBuffer>((resolve) => port2.once("message", resolve));
+    port1.postMessage(data, [data]);
+
+    expect(Buffer.from(await transferred).toString()).toBe("moved");
+    expect(data.byteLength).toBe(0);
+    port1.close();
+    push.close();
+    pull.close();
+  });
+
+  it("splits messages above the threshold into begin, chunk and end parts", async () => {
+    const url = inprocUrl("spec-chunked");
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    pull.listen(url);
+    const push = new SocketWrapper();
+    push.open(ProtocolType.Push0);
+    push.dial(url);
+
+    const parts: (string | number | undefined)[][] = [];
+    pull.recvChunked((err, part) => parts.push([part.kind, part.data ? part.data.toString() : part.size]), 4, 4);
+    await push.sendAsync("hi");
+    await push.sendAsync("abcdefghij");
+    await new Promise((resolve) => setTimeout(resolve, 50));
+
+    expect(parts).toEqual([
+      ["message", "hi"],
+      ["begin", 10],
+      ["chunk", "abcd"],
+      ["chunk", "efgh"],
+      ["chunk", "ij"],
+      ["end", 10],
+    ]);
+    push.close();
+    pull.close();
+  });
+});
+
+describe("routing", () => {
+  it("keeps each key on one worker and only moves keys of a removed worker", async () => {
+    const urls = [inprocUrl("spec-sticky"), inprocUrl("spec-sticky"), inprocUrl("spec-sticky")];
+    const received = urls.map(() => [] as string[]);
+    const workers = urls.map((url, i) => {
+      const pull = new SocketWrapper();
+      pull.open(ProtocolType.Pull0);
+      pull.listen(url);
+      pull.recv((err, msg) => received[i].push(msg.toString()));
+      return pull;
+    });
+    const router = new StickyRouter(ProtocolType.Push0);
+    const ids = urls.map((url) => router.addWorker(url));
+    expect(router.workerCount()).toBe(3);
+
+    const keys = ["a", "b", "c", "d", "e", "f", "g", "h"];
+    const before = keys.map((key) => router.send(key, key));
+    expect(keys.map((key) => router.send(key, key))).toEqual(before);
+    await new Promise((resolve) => setTimeout(resolve, 50));
+    ids.forEach((id, i) => {
+      const routed = keys.filter((key, j) => before[j] === id);
+      expect(received[i]).toEqual([...routed, ...routed]);
+    });
+
+    expect(router.removeWorker(ids[0])).toBe(true);
+    const after = keys.map((key) => router.send(key, key));
+    keys.forEach((key, i) => {
+      if (before[i] === ids[0]) expect(after[i]).not.toBe(ids[0]);
+      else expect(after[i]).toBe(before[i]);
+    });
+
+    router.close();
+    workers.forEach((worker) => worker.close());
+  });
+
+  it("bridges local ipc producers to a tcp consumer and relays replies back", async () => {
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    pull.listen("tcp://127.0.0.1:0");
+    const port = pull.dumpOptions().endpoints[0].options.find((entry) => entry.name === "tcp-bound-port")!.value;
+    const ipcUrl = `ipc://${join(tmpdir(), `spec-bridge-${process.pid}.ipc`)}`;
+    const relay = bridge({ from: ipcUrl, to: `tcp://127.0.0.1:${port}`, protocolPair: BridgeProtocol.PushPull });
+    const push = new SocketWrapper();
+    push.open(ProtocolType.Push0);
+    push.dial(ipcUrl);
+    await push.sendAsync("hello");
+    await push.sendAsync("world");
+    expect((await pull.recvOnce(1000)).toString()).toBe("hello");
+    expect((await pull.recvOnce(1000)).toString()).toBe("world");
+    expect(relay.stats()).toMatchObject({ forwarded: 2, returned: 0, bytes: 10, dropped: 0 });
+
+    const echoUrl = inprocUrl("spec-bridge-echo");
+    const echo = startEchoServer(ProtocolType.Rep0, echoUrl);
+    const rpcUrl = inprocUrl("spec-bridge-rpc");
+    const rpc = bridge({ from: rpcUrl, to: echoUrl, protocolPair: BridgeProtocol.ReqRep });
+    const req = new SocketWrapper();
+    req.open(ProtocolType.Req0);
+    req.dial(rpcUrl);
+    expect((await req.send(Buffer.from("ping"))).toString()).toBe("ping");
+    expect(rpc.stats()).toMatchObject({ forwarded: 1, returned: 1 });
+
+    [push, pull, req].forEach((socket) => socket.close());
+    [relay, rpc, echo].forEach((device) => device.close());
+  });
+
+  it("fans messages out to each downstream without a stalled one holding back the rest", async () => {
+    const [url, liveUrl, ipcUrl] = [inprocUrl("spec-fan-out"), inprocUrl("spec-fan-out-live"), `ipc://${join(tmpdir(), `spec-fan-out-${process.pid}.ipc`)}`];
+    const live = [ProtocolType.Pull0, ProtocolType.Sub0].map((protocol) => {
+      const socket = new SocketWrapper();
+      socket.open(protocol);
+      return socket;
+    });
+    live[0].listen(liveUrl);
+    live[1].subscribe("");
+    const fanOut = new FanOut(url);
+    const events: string[] = [];
+    fanOut.onEvent((err, event) => events.push(`${event.name}:${event.message?.split(":")[0]}`));
+    fanOut.addDownstream("live", { url: liveUrl, protocol: ProtocolType.Push0 });
+    fanOut.addDownstream("ipc", { url: ipcUrl, protocol: ProtocolType.Pub0, listen: true });
+    fanOut.addDownstream("stalled", { url: inprocUrl("spec-fan-out-nobody"), protocol: ProtocolType.Push0, queueSize: 1, sendTimeoutMs: 20 });
+    live[1].dial(ipcUrl);
+    await new Promise((resolve) => setTimeout(resolve, 50));
+
+    const push = new SocketWrapper();
+    push.open(ProtocolType.Push0);
+    push.dial(url);
+    for (const v of ["a", "b", "c"]) await push.sendAsync(v);
+    for (const socket of live) {
+      expect((await socket.recvOnce(1000)).toString()).toBe("a");
+      expect((await socket.recvOnce(1000)).toString()).toBe("b");
+      expect((await socket.recvOnce(1000)).toString()).toBe("c");
+    }
+    await new Promise((resolve) => setTimeout(resolve, 100));
+
+    const stats = fanOut.stats();
+    expect(stats.received).toBe(3);
+    expect(stats.downstreams.map((d) => d.name)).toEqual(["live", "ipc", "stalled"]);
+    expect(stats.downstreams[0]).toMatchObject({ sent: 3, dropped: 0, failed: 0 });
+    expect(stats.downstreams[2].sent).toBe(0);
+    expect(stats.downstreams[2].dropped + stats.downstreams[2].failed).toBeGreaterThan(0);
+    expect(events).toContain("downstreamFailed:stalled");
+    expect(fanOut.removeDownstream("stalled")).toBe(true);
+    expect(fanOut.removeDownstream("stalled")).toBe(false);
+
+    [push, ...live].forEach((socket) => socket.close());
+    fanOut.close();
+  });
+
+  it("merges several upstreams into one sequenced stream tagged by source", async () => {
+    const [edgeUrl, sensorUrl] = [inprocUrl("spec-fan-in-edge"), inprocUrl("spec-fan-in-sensor")];
+    const fanIn = new FanIn();
+    fanIn.addUpstream("edge", { url: edgeUrl });
+    fanIn.addUpstream("sensor", { url: sensorUrl, protocol: ProtocolType.Pair1 });
+    const merged: SourcedMessage[] = [];
+    fanIn.recv((err, message) => merged.push(message));
+    const [edge, sensor] = [ProtocolType.Push0, ProtocolType.Pair1].map((protocol, i) => {
+      const socket = new SocketWrapper();
+      socket.open(protocol);
+      socket.dial([edgeUrl, sensorUrl][i]);
+      return socket;
+    });
+
+    await edge.sendAsync("e1");
+    await new Promise((resolve) => setTimeout(resolve, 20));
+    await sensor.sendAsync("s1");
+    await new Promise((resolve) => setTimeout(resolve, 20));
+    await edge.sendAsync("e2");
+    await new Promise((resolve) => setTimeout(resolve, 50));
+
+    expect(merged.map((m) => `${m.sequence}:${m.source}:${m.data}`)).toEqual(["1:edge:e1", "2:sensor:s1", "3:edge:e2"]);
+    expect(merged[1].receivedAt).toBeGreaterThanOrEqual(merged[0].receivedAt);
+    expect(fanIn.stats()).toEqual({ delivered: 3, queued: 0, upstreams: [{ name: "edge", received: 2 }, { name: "sensor", received: 1 }] });
+    expect(() => fanIn.recv(() => {})).toThrow();
+    expect(fanIn.removeUpstream("sensor")).toBe(true);
+    expect(fanIn.removeUpstream("sensor")).toBe(false);
+
+    [edge, sensor].forEach((socket) => socket.close());
+    fanIn.close();
+  });
+
+  it("routes each message to the downstreams whose rules match it", async () => {
+    const pub = new Publisher();
+    pub.listen("inproc://spec-rules");
+    const fanOut = new FanOut("inproc://spec-rules", { protocol: ProtocolType.Sub0, dial: true });
+    const rules: Record<string, RoutingRule[]> = {
+      sensors: [{ topic: "sensors.*" }],
+      large: [{ minSize: 100 }],
+      alerts: [{ prefix: Buffer.from("alerts") }, { topic: "audit", maxSize: 20 }],
+    };
+    const received: Record<string, number> = {};
+    const sinks = Object.entries(rules).map(([name, rule]) => {
+      const url = inprocUrl(`spec-rules-${name}`);
+      const pull = new SocketWrapper();
+      pull.open(ProtocolType.Pull0);
+      pull.listen(url);
+      received[name] = 0;
+      pull.recv(() => received[name]++);
+      fanOut.addDownstream(name, { url, protocol: ProtocolType.Push0, rules: rule });
+      return pull;
+    });
+    expect(() => fanOut.addDownstream("bad", { url: inprocUrl("spec-rules-bad"), protocol: ProtocolType.Push0, rules: [{ minSize: 2, maxSize: 1 }] })).toThrow("minSize must not exceed maxSize");
+    await new Promise((resolve) => setTimeout(resolve, 50));
+
+    pub.publish("sensors.temp", Buffer.from("21"));
+    pub.publish("sensors.temp", Buffer.alloc(200));
+    pub.publish("alerts.disk", Buffer.from("full"));
+    pub.publish("audit", Buffer.from("x"));
+    pub.publish("audit", Buffer.from("too long for the rule"));
+    pub.publish("other", Buffer.from("x"));
+    await new Promise((resolve) => setTimeout(resolve, 50));
+
+    expect(received).toEqual({ sensors: 2, large: 1, alerts: 2 });
+    expect(fanOut.stats()).toMatchObject({ received: 6, unrouted: 2 });
+
+    sinks.forEach((sink) => sink.close());
+    fanOut.close();
+    pub.close();
+  });
+});
+
+describe("pubsub", () => {
+  it("replays retained messages to late subscribers", async () => {
+    const pub = new Publisher();
+    pub.listen("inproc://spec-retained");
+    pub.setRetain(2);
+    ["1", "2", "3"].forEach((v) => pub.publish("a", Buffer.from(v)));
+
+    const sub = new Subscriber();
+    sub.subscribe("a");
+    sub.connect("inproc://spec-retained");
+    const received: TopicMessage[] = [];
+    sub.recv((err, msg) => received.push(msg));
+
+    await new Promise((resolve) => setTimeout(resolve, 100));
+    sub.close();
+    pub.close();
+
+    expect(received.map((m) => m.data.toString())).toEqual(["2", "3"]);
+    expect(received.every((m) => m.replayed)).toBe(true);
+  });
+
+  it("sends each new subscriber one snapshot before live deltas", async () => {
+    const pub = new Publisher();
+    pub.listen("inproc://spec-snapshot");
+    let state = 0;
+    pub.setSnapshotProvider("s", () => Buffer.from(`snapshot ${state}`));
+
+    const subscribe = () => {
+      const sub = new Subscriber();
+      sub.connect("inproc://spec-snapshot");
+      sub.subscribe("s");
+      const received: TopicMessage[] = [];
+      sub.recv((err, msg) => received.push(msg));
+      return { sub, received };
+    };
+    const first = subscribe();
+    await new Promise((resolve) => setTimeout(resolve, 100));
+    pub.publish("s", Buffer.from(`delta ${++state}`));
+    const second = subscribe();
+    await new Promise((resolve) => setTimeout(resolve, 100));
+    pub.publish("s", Buffer.from(`delta ${++state}`));
+    await new Promise((resolve) => setTimeout(resolve, 100));
+    first.sub.close();
+    second.sub.close();
+    pub.close();
+
+    expect(first.received.map((m) => m.data.toString())).toEqual(["snapshot 0", "delta 1", "delta 2"]);
+    expect(second.received.map((m) => m.data.toString())).toEqual(["snapshot 1", "delta 2"]);
+    expect(second.received[0].snapshot).toBe(true);
+  });
+
+  it("publishes on a native interval until cleared", async () => {
+    const url = inprocUrl("spec-publish-interval");
+    const pub = new Publisher();
+    pub.listen(url);
+    const sub = new Subscriber();
+    sub.subscribe("tick");
+    sub.subscribe("beat");
+    sub.connect(url);
+    const received: string[] = [];
+    sub.recv((err, msg) => received.push(`${msg.topic}:${msg.data.toString()}`));
+    await new Promise((resolve) => setTimeout(resolve, 50));
+
+    let count = 0;
+    const produced = pub.publishInterval("tick", () => (++count % 2 === 0 ? null : `n${count}`), 20);
+    const fixed = pub.publishInterval("beat", "b", 20);
+    await new Promise((resolve) => setTimeout(resolve, 130));
+    expect(pub.clearPublishInterval(produced)).toBe(true);
+    expect(pub.clearPublishInterval(fixed)).toBe(true);
+    expect(pub.clearPublishInterval(fixed)).toBe(false);
+    await new Promise((resolve) => setTimeout(resolve, 20));
+    const settled = received.length;
+    await new Promise((resolve) => setTimeout(resolve, 60));
+
+    expect(received).toHaveLength(settled);
+    expect(received).toContain("tick:n1");
+    expect(received).not.toContain("tick:n2");
+    expect(received.filter((entry) => entry === "beat:b").length >= 3).toBe(true);
+    sub.close();
+    pub.close();
+  });
+
+  it("resubscribes after the publisher restarts", async () => {
+    const url = inprocUrl("spec-resubscribe");
+    let pub = new Publisher();
+    pub.listen(url);
+    const sub = new Subscriber();
+    const resubscribed: SocketEvent[] = [];
+    sub.onEvent((err, event) => event.name === "resubscribed" && resubscribed.push(event));
+    sub.subscribe("a");
+    sub.subscribe("b");
+    sub.connect(url);
+    const received: string[] = [];
+    sub.recv((err, msg) => received.push(`${msg.topic}:${msg.data.toString()}`));
+    await new Promise((resolve) => setTimeout(resolve, 50));
+
+    pub.close();
+    pub = new Publisher();
+    pub.listen(url);
+    for (let i = 0; i < 100 && resubscribed.length === 0; i++) {
+      await new Promise((resolve) => setTimeout(resolve, 20));
+    }
+    expect(resubscribed).toEqual([expect.objectContaining({ value: 2 })]);
+    ["a", "c", "b"].forEach((topic) => pub.publish(topic, "after"));
+    await new Promise((resolve) => setTimeout(resolve, 50));
+
+    expect(received).toEqual(["a:after", "b:after"]);
+    sub.close();
+    pub.close();
+  });
+
+  it("routes each topic through its hash partition", async () => {
+    const urls = [inprocUrl("spec-partition"), inprocUrl("spec-partition"), inprocUrl("spec-partition")];
+    const pub = new PartitionedPublisher(urls);
+    expect(pub.partitions()).toBe(3);
+
+    const sub = new PartitionedSubscriber(urls);
+    expect(sub.subscribe("orders")).toBe(partitionFor("orders", 3));
+    const received: TopicMessage[] = [];
+    sub.recv((err, msg) => received.push(msg));
+    await new Promise((resolve) => setTimeout(resolve, 20));
+
+    expect(pub.publish("orders", "o1")).toBe(partitionFor("orders", 3));
+    pub.publish("prices", "p1");
+    pub.publish("orders", "o2");
+    await new Promise((resolve) => setTimeout(resolve, 50));
+
+    expect(received.map((m) => `${m.topic}:${m.data}`)).toEqual(["orders:o1", "orders:o2"]);
+    sub.close();
+    pub.close();
+  });
+
+  it("disconnects or reports tcp subscribers that stop reading", async () => {
+    const port = await new Promise<number>((resolve) => {
+      const server = createServer();
+      server.listen(0, "127.0.0.1", () => {
+        const { port } = server.address() as AddressInfo;
+        server.close(() => resolve(port));
+      });
+    });
+    const stalled = () => {
+      const socket: Socket = connect(port, "127.0.0.1");
+      socket.write(Buffer.from([0, 0x53, 0x50, 0, 0, 0x21, 0, 0]));
+      socket.pause();
+      return socket;
+    };
+    const until = async (check: () => boolean) => {
+      for (let i = 0; i < 100 && !check(); i++) await new Promise((resolve) => setTimeout(resolve, 50));
+    };
+
+    const events: SocketEvent[] = [];
+    const pub = new Publisher();
+    pub.onEvent((err, event) => events.push(event));
+    pub.listen(`tcp://127.0.0.1:${port}`);
+    pub.setSlowConsumerPolicy({ policy: SlowConsumerPolicy.Disconnect, graceMs: 200, checkIntervalMs: 50 });
+    const healthy = new Subscriber();
+    healthy.subscribe("s");
+    healthy.connect(`tcp://127.0.0.1:${port}`);
+    healthy.recv(() => {});
+    const first = stalled();
+    let closed = false;
+    first.on("close", () => (closed = true));
+    first.on("error", () => {});
+    await new Promise((resolve) => setTimeout(resolve, 50));
+
+    const id = pub.publishInterval("s", Buffer.alloc(256 * 1024), 5);
+    await until(() => events.some((event) => event.name === "slowConsumerDisconnected"));
+    first.resume();
+    await until(() => closed);
+    expect(closed).toBe(true);
+    expect(events.filter((event) => event.name.startsWith("slowConsumer")).map((event) => event.name)).toEqual(["slowConsumerDisconnected"]);
+
+    events.length = 0;
+    pub.setSlowConsumerPolicy({ policy: SlowConsumerPolicy.Skip, graceMs: 200, checkIntervalMs: 50 });
+    const second = stalled();
+    await until(() => events.some((event) => event.name === "slowConsumer"));
+    pub.clearPublishInterval(id);
+    second.resume();
+    await until(() => events.some((event) => event.name === "slowConsumerRecovered"));
+
+    const skipped = events.filter((event) => event.name.startsWith("slowConsumer"));
+    expect(skipped.map((event) => [event.name, event.message])).toEqual([
+      ["slowConsumer", "send queue full, messages are being skipped"],
+      ["slowConsumerRecovered", undefined],
+    ]);
+    expect(skipped[0].value).toBeGreaterThan(0);
+    expect(second.destroyed).toBe(false);
+    second.destroy();
+    healthy.close();
+    pub.close();
+  });
+
+  it("buffers messages while paused and flushes them in order on resume", async () => {
+    const pub = new Publisher();
+    pub.listen("inproc://spec-pause");
+    const sub = new Subscriber();
+    sub.subscribe("p");
+    sub.connect("inproc://spec-pause");
+    const received: string[] = [];
+    sub.recv((err, msg) => received.push(msg.data.toString()));
+    await new Promise((resolve) => setTimeout(resolve, 50));
+
+    pub.publish("p", Buffer.from("0"));
+    pub.pause();
+    ["1", "2", "3"].forEach((v) => pub.publish("p", Buffer.from(v)));
+    expect(pub.pauseState()).toEqual({ paused: true, buffered: 3, overflowed: 0 });
+    await new Promise((resolve) => setTimeout(resolve, 50));
+    expect(received).toEqual(["0"]);
+
+    expect(pub.resume()).toBe(3);
+    expect(pub.pauseState()).toEqual({ paused: false, buffered: 0, overflowed: 0 });
+    pub.publish("p", Buffer.from("4"));
+    await new Promise((resolve) => setTimeout(resolve, 50));
+    expect(received).toEqual(["0", "1", "2", "3", "4"]);
+    expect(sub.stats()).toMatchObject([{ topic: "p", received: 5, dropped: 0 }]);
+    sub.close();
+    pub.close();
+  });
+
+  it("counts messages past the pause limit and reports the first overflow", async () => {
+    const events: SocketEvent[] = [];
+    const pub = new Publisher();
+    pub.onEvent((err, event) => events.push(event));
+    pub.listen("inproc://spec-pause-overflow");
+    pub.pause(2);
+    ["1", "2", "3", "4"].forEach((v) => pub.publish("p", Buffer.from(v)));
+
+    expect(pub.pauseState()).toEqual({ paused: true, buffered: 2, overflowed: 2 });
+    expect(pub.resume()).toBe(2);
+    await new Promise((resolve) => setTimeout(resolve, 20));
+    expect(events.filter((event) => event.name === "pauseOverflow").map((event) => event.value)).toEqual([2]);
+    pub.close();
+  });
+
+  it("keeps unsent messages buffered when resume hits a send error", () => {
+    const pub = new Publisher();
+    pub.listen("inproc://spec-pause-error");
+    pub.pause();
+    ["1", "2", "3"].forEach((v) => pub.publish("p", Buffer.from(v)));
+    pub.close();
+
+    expect(() => pub.resume()).toThrow("after flushing 0 messages, 3 still buffered");
+    expect(pub.pauseState()).toEqual({ paused: true, buffered: 3, overflowed: 0 });
+    expect(() => pub.resume()).toThrow("after flushing 0 messages, 3 still buffered");
+  });
+
+  it("counts sequence gaps from the publisher as dropped messages", async () => {
+    const url = inprocUrl("spec-sub-stats");
+    const pub = new SocketWrapper();
+    pub.open(ProtocolType.Pub0);
+    pub.listen(url);
+    const sub = new Subscriber();
+    sub.subscribe("a");
+    sub.connect(url);
+    const received: string[] = [];
+    sub.recv((err, msg) => received.push(msg.data.toString()));
+    await new Promise((resolve) => setTimeout(resolve, 20));
+
+    const frame = (sequence: number, body: string) => {
+      const header = Buffer.from([0x61, 0, 0x02, 0, 0, 0, 0, 0, 0, 0, 0]);
+      header.writeBigUInt64BE(BigInt(sequence), 3);
+      return Buffer.concat([header, Buffer.from(body)]);
+    };
+    [1, 2, 5].forEach((sequence) => pub.post(frame(sequence, String(sequence))));
+    await new Promise((resolve) => setTimeout(resolve, 50));
+
+    expect(received).toEqual(["1", "2", "5"]);
+    expect(sub.stats()).toMatchObject([{ topic: "a", received: 3, dropped: 2, lag: 0 }]);
+    sub.close();
+    pub.close();
+  });
+
+  it("retries failed jobs until a worker completes them", async () => {
+    const queue = new JobQueue({ maxAttempts: 3 });
+    queue.listen("inproc://spec-jobs", "inproc://spec-jobs-acks");
+    const worker = new JobQueue();
+    worker.connect("inproc://spec-jobs", "inproc://spec-jobs-acks");
+    const attempts: number[] = [];
+    worker.process(async (err, job) => {
+      attempts.push(job.attempts);
+      if (job.attempts === 0) throw new Error("flaky");
+    });
+
+    await queue.enqueue("job");
+    expect(attempts).toEqual([0, 1]);
+    expect(queue.stats()).toMatchObject({ completed: 1, retried: 1, outstanding: 0 });
+
+    worker.close();
+    queue.close();
+  });
+
+  it("hands queued jobs to workers by priority", async () => {
+    const queue = new JobQueue();
+    queue.listen("inproc://spec-lanes", "inproc://spec-lanes-acks");
+    const first = queue.enqueue("first");
+    await new Promise((resolve) => setTimeout(resolve, 20));
+    const rest = [queue.enqueue("low"), queue.enqueue("high", { priority: 9 }), queue.enqueue("mid", { priority: 5 })];
+    expect(() => queue.enqueue("bad", { priority: 256 })).toThrow("priority must be between 0 and 255");
+
+    const worker = new JobQueue();
+    const order: string[] = [];
+    worker.connect("inproc://spec-lanes", "inproc://spec-lanes-acks");
+    worker.process(async (err, job) => order.push(job.data.toString()));
+    await Promise.all([first, ...rest]);
+    expect(order).toEqual(["first", "high", "mid", "low"]);
+
+    worker.close();
+    queue.close();
+  });
+
+  it("delays enqueued jobs and backs off between retries", async () => {
+    const queue = new JobQueue({ retryDelayMs: 40, maxRetryDelayMs: 60 });
+    queue.listen("inproc://spec-backoff", "inproc://spec-backoff-acks");
+    const worker = new JobQueue();
+    worker.connect("inproc://spec-backoff", "inproc://spec-backoff-acks");
+    const started = Date.now();
+    const seen: number[] = [];
+    worker.process(async (err, job) => {
+      seen.push(Date.now() - started);
+      if (job.attempts < 3) throw new Error("flaky");
+    });
+
+    const done = queue.enqueue("job", { delayMs: 50 });
+    expect(queue.stats()).toMatchObject({ delayed: 1, waiting: 0 });
+    await done;
+    expect(seen).toHaveLength(4);
+    expect(seen[0]).toBeGreaterThanOrEqual(45);
+    expect(seen[1] - seen[0]).toBeGreaterThanOrEqual(35);
+    expect(seen[2] - seen[1]).toBeGreaterThanOrEqual(55);
+    expect(seen[3] - seen[2]).toBeGreaterThanOrEqual(55);
+    expect(queue.stats()).toMatchObject({ completed: 1, retried: 3, delayed: 0 });
+
+    worker.close();
+    queue.close();
+  });
+
+  it("enqueues recurring jobs on a cron schedule", async () => {
+    const queue = new JobQueue();
+    queue.listen("inproc://spec-cron", "inproc://spec-cron-acks");
+    const worker = new JobQueue();
+    worker.connect("inproc://spec-cron", "inproc://spec-cron-acks");
+    const received: string[] = [];
+    worker.process(async (err, job) => received.push(job.data.toString()));
+
+    expect(() => queue.schedule("bad", "* * *", "tick")).toThrow("Invalid cron expression '* * *'");
+    queue.schedule("tick", "* * * * * *", "tick");
+    queue.schedule("nightly", "@daily", "report");
+    expect(queue.schedules()).toEqual(["nightly", "tick"]);
+    for (let i = 0; i < 25 && received.length === 0; i++) {
+      await new Promise((resolve) => setTimeout(resolve, 100));
+    }
+    expect(received[0]).toBe("tick");
+
+    expect(queue.unschedule("tick")).toBe(true);
+    expect(queue.unschedule("tick")).toBe(false);
+    expect(queue.schedules()).toEqual(["nightly"]);
+
+    worker.close();
+    queue.close();
+  });
+
+  it("runs each jobId once and remembers completed ids across restarts", async () => {
+    const dedupPath = join(tmpdir(), `spec-dedup-${process.pid}.bin`);
+    rmSync(dedupPath, { force: true });
+    const processed: string[] = [];
+    const start = (name: string) => {
+      const queue = new JobQueue({ dedupPath });
+      queue.listen(`inproc://${name}`, `inproc://${name}-acks`);
+      const worker = new JobQueue();
+      worker.connect(`inproc://${name}`, `inproc://${name}-acks`);
+      worker.process(async (err, job) => processed.push(job.data.toString()));
+      return [queue, worker];
+    };
+
+    let [queue, worker] = start("spec-dedup");
+    await Promise.all([queue.enqueue("a", { jobId: "a" }), queue.enqueue("a again", { jobId: "a" })]);
+    await queue.enqueue("a later", { jobId: "a" });
+    expect(processed).toEqual(["a"]);
+    expect(queue.stats()).toMatchObject({ completed: 1, deduplicated: 2 });
+    worker.close();
+    queue.close();
+
+    [queue, worker] = start("spec-dedup-restarted");
+    await queue.enqueue("a restarted", { jobId: "a" });
+    await queue.enqueue("b", { jobId: "b" });
+    expect(processed).toEqual(["a", "b"]);
+    expect(queue.stats()).toMatchObject({ completed: 1, deduplicated: 1 });
+    worker.close();
+    queue.close();
+  });
+
+  it("shares topic messages among the members of each consumer group", async () => {
+    const pub = new Publisher();
+    pub.listen("inproc://spec-groups");
+    const groups = ["workers", "auditors"].map((name) => {
+      const group = new ConsumerGroup(`inproc://spec-group-${name}`);
+      group.subscribe("orders");
+      group.connect("inproc://spec-groups");
+      return group;
+    });
+    const received = new Map<GroupConsumer, string[]>();
+    const members = [0, 0, 1].map((index) => {
+      const member = new GroupConsumer();
+      member.connect(`inproc://spec-group-${["workers", "auditors"][index]}`);
+      received.set(member, []);
+      member.recv((err, msg) => received.get(member)!.push(`${msg.topic}:${msg.data}`));
+      return member;
+    });
+    for (let i = 0; i < 50 && (groups[0].stats().members < 2 || groups[1].stats().members < 1); i++) {
+      await new Promise((resolve) => setTimeout(resolve, 20));
+    }
+    await new Promise((resolve) => setTimeout(resolve, 50));
+
+    for (let i = 0; i < 10; i++) {
+      pub.publish("orders", Buffer.from(String(i)));
+      pub.publish("other", Buffer.from(String(i)));
+      await new Promise((resolve) => setTimeout(resolve, 2));
+    }
+    await new Promise((resolve) => setTimeout(resolve, 100));
+
+    const [first, second, auditor] = members.map((member) => received.get(member)!);
+    expect(first.length + second.length).toBe(10);
+    expect(first.length).toBeGreaterThan(0);
+    expect(second.length).toBeGreaterThan(0);
+    expect([...first, ...second].sort()).toEqual(auditor.slice().sort());
+    expect(auditor).toHaveLength(10);
+    expect(members.map((member) => member.received())).toEqual([first.length, second.length, 10]);
+    expect(groups[0].stats()).toEqual({ members: 2, received: 10, forwarded: 10 });
+
+    members.forEach((member) => member.close());
+    groups.forEach((group) => group.close());
+    pub.close();
+  });
+
+  it("drops malformed topic frames and counts them by reason", async () => {
+    const url = inprocUrl("spec-malformed");
+    const pub = new SocketWrapper();
+    pub.open(ProtocolType.Pub0);
+    pub.listen(url);
+    const sub = new Subscriber();
+    const codes: (string | undefined)[] = [];
+    sub.onEvent((err, event) => event.name === "malformedFrame" && codes.push(event.code));
+    sub.subscribe("t");
+    sub.connect(url);
+    const received: string[] = [];
+    sub.recv((err, msg) => received.push(msg.data.toString()));
+    await new Promise((resolve) => setTimeout(resolve, 50));
+
+    [[0x74, 0], [0x74, 0, 0x80, 0x78], [0x74, 0, 0x02, 0], [0x74, 0, 0, 0x6f, 0x6b]].forEach((bytes) => pub.post(Buffer.from(bytes)));
+    await new Promise((resolve) => setTimeout(resolve, 50));
+
+    expect(received).toEqual(["ok"]);
+    expect(sub.malformedFrames()).toEqual({ truncated: 2, missingDelimiter: 0, invalidUtf8: 0, unknownKind: 0, unknownFlags: 1 });
+    expect(codes).toEqual(["truncated", "unknownFlags"]);
+    sub.close();
+    pub.close();
+  });
+
+  it("subscribes raw sockets to binary topic prefixes", async () => {
+    const url = inprocUrl("spec-binary-topic");
+    const pub = new SocketWrapper();
+    pub.open(ProtocolType.Pub0);
+    pub.listen(url);
+    const sub = new SocketWrapper();
+    sub.open(ProtocolType.Sub0);
+    sub.dial(url);
+    sub.subscribe(Buffer.from([0xff, 0x01]));
+
+    const received: Buffer[] = [];
+    sub.recv((err, msg) => received.push(msg));
+    await new Promise((resolve) => setTimeout(resolve, 50));
+    pub.post(Buffer.from([0xfe, 0x01, 1]));
+    pub.post(Buffer.from([0xff, 0x01, 2]));
+    await new Promise((resolve) => setTimeout(resolve, 50));
+
+    expect(received).toEqual([Buffer.from([0xff, 0x01, 2])]);
+    sub.close();
+    pub.close();
+  });
+
+  it("subscribes to several prefixes in one call before dialing", async () => {
+    const url = inprocUrl("spec-subscribe-many");
+    const pub = new SocketWrapper();
+    pub.open(ProtocolType.Pub0);
+    pub.listen(url);
+    const sub = new SocketWrapper();
+    sub.open(ProtocolType.Sub0);
+    sub.subscribeMany(["a", Buffer.from([0xff]), new Uint8Array([0x62])]);
+    sub.dial(url);
+    expect(() => pub.subscribeMany(["a"])).toThrow("subscribeMany is only supported on Sub0 sockets, not Pub0");
+
+    const received: string[] = [];
+    sub.recv((err, msg) => received.push(msg.toString("hex")));
+    await new Promise((resolve) => setTimeout(resolve, 50));
+    ["a1", "c1", "b1"].forEach((body) => pub.post(body));
+    pub.post(Buffer.from([0xff, 0x02]));
+    await new Promise((resolve) => setTimeout(resolve, 50));
+
+    expect(received).toEqual([Buffer.from("a1").toString("hex"), Buffer.from("b1").toString("hex"), "ff02"]);
+    sub.close();
+    pub.close();
+  });
+
+  it("estimates topic traffic from sampled messages", () => {
+    const pub = new Publisher();
+    pub.listen(inprocUrl("spec-topic-sampling"));
+    pub.setTopicStatsSampling({ everyNth: 4 });
+    for (let i = 0; i < 9; i++) {
+      pub.publish("a", "abc");
+    }
+    expect(pub.topicStats()).toEqual([{ topic: "a", messages: 9, bytes: 27 }]);
+
+    expect(() => pub.setTopicStatsSampling({ everyNth: 0 })).toThrow("everyNth must be at least 1");
+    expect(() => pub.setTopicStatsSampling({ probability: 2 })).toThrow("probability must be in (0, 1], got 2");
+    pub.setTopicStatsSampling(null);
+    pub.publish("b", "abcd");
+    expect(pub.topicStats()).toContainEqual({ topic: "b", messages: 1, bytes: 4 });
+    pub.close();
+  });
+
+  it("forwards unfiltered traffic through a raw Sub0/Pub0 broker", async () => {
+    const [upstream, downstream] = [inprocUrl("spec-broker-in"), inprocUrl("spec-broker-out")];
+    const pub = new SocketWrapper();
+    pub.open(ProtocolType.Pub0);
+    pub.listen(upstream);
+    const brokerIn = new SocketWrapper();
+    brokerIn.open(ProtocolType.Sub0, true);
+    brokerIn.dial(upstream);
+    const brokerOut = new SocketWrapper();
+    brokerOut.open(ProtocolType.Pub0, true);
+    brokerOut.listen(downstream);
+    const sub = new SocketWrapper();
+    sub.open(ProtocolType.Sub0);
+    sub.dial(downstream);
+    sub.subscribe(Buffer.from("a"));
+
+    const forwarded: string[] = [];
+    brokerIn.recv((err, msg) => {
+      forwarded.push(msg.toString());
+      brokerOut.post(msg);
+    });
+    const received: string[] = [];
+    sub.recv((err, msg) => received.push(msg.toString()));
+    await new Promise((resolve) => setTimeout(resolve, 50));
+    pub.post("a1");
+    pub.post("b1");
+    await new Promise((resolve) => setTimeout(resolve, 50));
+
+    expect(forwarded).toEqual(["a1", "b1"]);
+    expect(received).toEqual(["a1"]);
+    expect(() => new SocketWrapper().open(ProtocolType.Push0, true)).toThrow("Raw mode is only supported for Req0, Rep0, Pub0 and Sub0");
+    [sub, brokerOut, brokerIn, pub].forEach((socket) => socket.close());
+  });
+
+  it("appends published messages to the event log with sequential offsets", async () => {
+    const path = join(tmpdir(), `spec-offsets-${process.pid}.log`);
+    rmSync(path, { force: true });
+    let log = new EventLog(path);
+    log.listen("inproc://spec-log-offsets");
+    const sub = new Subscriber();
+    sub.subscribe("a");
+    sub.connect("inproc://spec-log-offsets");
+    const live: string[] = [];
+    sub.recv((err, msg) => live.push(msg.data.toString()));
+    await new Promise((resolve) => setTimeout(resolve, 50));
+
+    expect(["0", "1", "2"].map((v) => log.publish("a", v))).toEqual([0, 1, 2]);
+    const followed: number[] = [];
+    const id = log.follow(1, (err, record) => followed.push(record.offset));
+    await new Promise((resolve) => setTimeout(resolve, 50));
+    expect(log.unfollow(id)).toBe(true);
+    expect(log.unfollow(id)).toBe(false);
+    log.publish("b", "3");
+    await new Promise((resolve) => setTimeout(resolve, 50));
+    expect(live).toEqual(["0", "1", "2"]);
+    expect(followed).toEqual([1, 2]);
+    sub.close();
+    log.close();
+
+    log = new EventLog(path);
+    expect(log.nextOffset()).toBe(4);
+    expect(log.read(2, 1)).toEqual([expect.objectContaining({ offset: 2, topic: "a", data: Buffer.from("2") })]);
+    expect(log.read(3).map((record) => record.topic)).toEqual(["b"]);
+    expect(log.read(9)).toEqual([]);
+    expect(log.publish("a", "4")).toBe(4);
+    log.close();
+  });
+
+  it("resumes log subscribers from an offset before switching to live messages", async () => {
+    const log = new EventLog(join(tmpdir(), `spec-event-log-${process.pid}.log`));
+    log.listen("inproc://spec-log-live");
+    log.listenCatchUp("inproc://spec-log-catch-up");
+    const base = log.nextOffset();
+    for (let i = 0; i < 5; i++) log.publish("a", String(i));
+
+    const sub = new LogSubscriber();
+    sub.connect("inproc://spec-log-live", "inproc://spec-log-catch-up");
+    const received: number[] = [];
+    sub.recv(base + 2, (err, record) => received.push(record.offset - base));
+    await new Promise((resolve) => setTimeout(resolve, 50));
+    log.publish("a", "5");
+    await new Promise((resolve) => setTimeout(resolve, 50));
+
+    expect(received).toEqual([2, 3, 4, 5]);
+    expect(sub.offset()).toBe(base + 6);
+    sub.close();
+    log.close();
+  });
+});
+
+describe("rpc", () => {
+  it("lets the server call methods registered on the client", async () => {
+    const server = new RpcServer();
+    server.listen("inproc://spec-rpc-duplex");
+    let peer = 0;
+    server.handle("hello", (err, call) => {
+      peer = call.peerId;
+      call.end(Buffer.from("hi"));
+    });
+
+    const client = new RpcClient();
+    client.connect("inproc://spec-rpc-duplex");
+    client.handle("whoami", (err, call) => call.end(Buffer.from("client")));
+
+    expect((await client.call("hello", Buffer.alloc(0))).toString()).toBe("hi");
+    expect(server.peers()).toEqual([peer]);
+    expect((await server.call(peer, "whoami", Buffer.alloc(0))).toString()).toBe("client");
+
+    client.close();
+    server.close();
+  });
+
+  it("rejects outstanding calls with SocketClosed when the client closes", async () => {
+    const server = new RpcServer();
+    server.listen("inproc://spec-rpc-closed");
+    server.handle("never", () => {});
+
+    const client = new RpcClient();
+    client.connect("inproc://spec-rpc-closed");
+    await new Promise((resolve) => setTimeout(resolve, 20));
+
+    const pending = client.call("never", Buffer.alloc(0));
+    client.close();
+    await expect(pending).rejects.toMatchObject({ code: "SocketClosed" });
+    server.close();
+  });
+
+  it("negotiates the highest common protocol version and rejects incompatible peers", async () => {
+    const server = new RpcServer();
+    server.setHandshake({ version: 3, minVersion: 2, metadata: { name: "server" } });
+    server.listen("inproc://spec-rpc-handshake");
+    server.handle("version", (err, call) => call.end(Buffer.from(String(server.peerInfo(call.peerId)?.version))));
+
+    const client = new RpcClient();
+    client.setHandshake({ version: 2 });
+    client.connect("inproc://spec-rpc-handshake");
+    expect((await client.call("version", Buffer.alloc(0))).toString()).toBe("2");
+    expect(client.serverInfo()).toMatchObject({ version: 2, metadata: { name: "server" } });
+
+    const events: SocketEvent[] = [];
+    const old = new RpcClient();
+    old.setHandshake({ version: 1 });
+    old.onEvent((err, event) => events.push(event));
+    old.connect("inproc://spec-rpc-handshake");
+    await new Promise((resolve) => setTimeout(resolve, 50));
+
+    expect(events).toContainEqual(expect.objectContaining({ name: "handshakeRejected" }));
+    expect(old.serverInfo()).toBeNull();
+    old.close();
+    client.close();
+    server.close();
+  });
+
+  it("only serves clients whose token the verifier accepts", async () => {
+    const events: SocketEvent[] = [];
+    const server = new RpcServer();
+    server.onEvent((err, event) => events.push(event));
+    server.setAuthVerifier((err, request) => {
+      if (request.token === "secret") request.accept();
+      else request.reject("bad token");
+    });
+    server.listen("inproc://spec-rpc-auth");
+    server.handle("hello", (err, call) => call.end(Buffer.from("hi")));
+
+    const client = new RpcClient();
+    client.setAuthToken("secret");
+    client.connect("inproc://spec-rpc-auth");
+    expect((await client.call("hello", Buffer.alloc(0))).toString()).toBe("hi");
+
+    const intruder = new RpcClient();
+    intruder.setAuthToken("guess");
+    intruder.connect("inproc://spec-rpc-auth");
+    await expect(intruder.call("hello", Buffer.alloc(0))).rejects.toMatchObject({ code: "SocketClosed" });
+    await new Promise((resolve) => setTimeout(resolve, 20));
+
+    expect(events.filter((event) => event.name.startsWith("auth")).map((event) => [event.name, event.message])).toEqual([
+      ["authenticated", undefined],
+      ["authRejected", "bad token"],
+    ]);
+    expect(server.peers()).toHaveLength(1);
+    intruder.close();
+    client.close();
+    server.close();
+  });
+
+  it("drops connections that fail the shared-secret challenge", async () => {
+    const events: SocketEvent[] = [];
+    const server = new RpcServer();
+    server.onEvent((err, event) => events.push(event));
+    server.setSharedSecret(Buffer.from("k1"), 100);
+    server.listen("inproc://spec-rpc-psk");
+    server.handle("hello", (err, call) => call.end(Buffer.from("hi")));
+
+    const client = new RpcClient();
+    client.setSharedSecret(Buffer.from("k1"));
+    client.connect("inproc://spec-rpc-psk");
+    expect((await client.call("hello", Buffer.alloc(0))).toString()).toBe("hi");
+
+    const wrongEvents: SocketEvent[] = [];
+    const wrong = new RpcClient();
+    wrong.onEvent((err, event) => wrongEvents.push(event));
+    wrong.setSharedSecret(Buffer.from("k2"));
+    wrong.connect("inproc://spec-rpc-psk");
+    await expect(wrong.call("hello", Buffer.alloc(0))).rejects.toMatchObject({ code: "SocketClosed" });
+    const silent = new RpcClient();
+    silent.connect("inproc://spec-rpc-psk");
+    await expect(silent.call("hello", Buffer.alloc(0))).rejects.toMatchObject({ code: "SocketClosed" });
+    await new Promise((resolve) => setTimeout(resolve, 20));
+
+    expect([...events, ...wrongEvents].map((event) => event.message)).toContain("Shared secret mismatch");
+    expect(events.filter((event) => event.message !== "Shared secret mismatch").map((event) => [event.name, event.message])).toEqual([
+      ["pskVerified", undefined],
+      ["pskRejected", "Shared secret check timed out"],
+    ]);
+    expect(server.peers()).toHaveLength(1);
+    wrong.close();
+    silent.close();
+    client.close();
+    server.close();
+  });
+
+  it("keeps the shared secret and handlers when a client reconnects after close", async () => {
+    const events: SocketEvent[] = [];
+    const server = new RpcServer();
+    server.onEvent((err, event) => events.push(event));
+    server.setSharedSecret(Buffer.from("k1"), 100);
+    server.listen("inproc://spec-rpc-reconnect");
+    let peer = 0;
+    server.handle("hello", (err, call) => {
+      peer = call.peerId;
+      call.end(Buffer.from("hi"));
+    });
+
+    const client = new RpcClient();
+    client.setSharedSecret(Buffer.from("k1"));
+    client.handle("ping", (err, call) => call.end(Buffer.from("pong")));
+    client.connect("inproc://spec-rpc-reconnect");
+    expect((await client.call("hello", Buffer.alloc(0))).toString()).toBe("hi");
+
+    client.close();
+    client.connect("inproc://spec-rpc-reconnect");
+    expect((await client.call("hello", Buffer.alloc(0))).toString()).toBe("hi");
+    expect((await server.call(peer, "ping", Buffer.alloc(0))).toString()).toBe("pong");
+    await new Promise((resolve) => setTimeout(resolve, 150));
+
+    expect(events.map((event) => event.name)).toEqual(["pskVerified", "pskVerified"]);
+    client.close();
+    server.close();
+  });
+
+  it("settles outstanding calls according to the close mode", async () => {
+    const server = new RpcServer();
+    server.listen("inproc://spec-rpc-close-modes");
+    server.handle("never", () => {});
+    server.handle("slow", (err, call) => setTimeout(() => call.end(Buffer.from("done")), 30));
+
+    const rejecting = new RpcClient();
+    rejecting.connect("inproc://spec-rpc-close-modes");
+    const rejected = rejecting.call("never", Buffer.alloc(0));
+    await new Promise((resolve) => setTimeout(resolve, 20));
+    rejecting.close({ mode: CloseMode.Reject });
+    await expect(rejected).rejects.toMatchObject({ code: "SocketClosed" });
+
+    const waiting = new RpcClient();
+    waiting.connect("inproc://spec-rpc-close-modes");
+    const answered = waiting.call("slow", Buffer.alloc(0));
+    await new Promise((resolve) => setTimeout(resolve, 10));
+    waiting.close({ mode: CloseMode.Wait, waitMs: 1000 });
+    await expect(waiting.call("slow", Buffer.alloc(0))).rejects.toMatchObject({ message: "Socket not connected" });
+    expect((await answered).toString()).toBe("done");
+
+    const detaching = new RpcClient();
+    detaching.connect("inproc://spec-rpc-close-modes");
+    const timed = detaching.call("never", Buffer.alloc(0), { timeoutMs: 30 });
+    const detached = detaching.call("never", Buffer.alloc(0));
+    await new Promise((resolve) => setTimeout(resolve, 10));
+    const closedAt = Date.now();
+    detaching.close({ mode: CloseMode.Detach, waitMs: 100 });
+    await expect(timed).rejects.toMatchObject({ code: "CallTimedOut" });
+    await expect(detached).rejects.toMatchObject({ code: "SocketClosed" });
+    expect(Date.now() - closedAt).toBeGreaterThanOrEqual(90);
+    server.close();
+  });
+
+  it("keeps a reconnected client working while close(Wait) is still draining", async () => {
+    const server = new RpcServer();
+    server.listen("inproc://spec-rpc-close-wait");
+    let peer = 0;
+    server.handle("hello", (err, call) => {
+      peer = call.peerId;
+      call.end(Buffer.from("hi"));
+    });
+    server.handle("never", () => {});
+
+    const client = new RpcClient();
+    client.connect("inproc://spec-rpc-close-wait");
+    const stuck = client.call("never", Buffer.alloc(0));
+    await new Promise((resolve) => setTimeout(resolve, 20));
+    client.close({ mode: CloseMode.Wait, waitMs: 100 });
+    client.connect("inproc://spec-rpc-close-wait");
+    client.handle("ping", (err, call) => call.end(Buffer.from("pong")));
+
+    expect((await client.call("hello", Buffer.alloc(0))).toString()).toBe("hi");
+    await expect(stuck).rejects.toMatchObject({ code: "SocketClosed" });
+    await new Promise((resolve) => setTimeout(resolve, 50));
+    expect((await client.call("hello", Buffer.alloc(0))).toString()).toBe("hi");
+    expect((await server.call(peer, "ping", Buffer.alloc(0))).toString()).toBe("pong");
+
+    client.close();
+    server.close();
+  });
+
+  it("collects replies from every server subscribed to a topic", async () => {
+    const client = new TopicRpcClient();
+    client.listen("inproc://spec-topic-rpc", "inproc://spec-topic-rpc-replies");
+    const servers = ["a", "b"].map((name) => {
+      const server = new TopicRpcServer(name);
+      server.handle("orders", "status", (err, call) => call.end(Buffer.from(`${name}:${call.topic}`)));
+      server.connect("inproc://spec-topic-rpc");
+      return server;
+    });
+    await new Promise((resolve) => setTimeout(resolve, 20));
+
+    const replies = await client.call("orders", "status", Buffer.alloc(0), { expected: 2 });
+    expect(replies.map((reply) => reply.data?.toString()).sort()).toEqual(["a:orders", "b:orders"]);
+
+    client.close();
+    servers.forEach((server) => server.close());
+  });
+});
+
+const certKeyFile = join(__dirname, "fixtures", "tls.pem");
+const tlsEnabled = (() => {
+  const probe = new Publisher();
+  try {
+    probe.listenTls("tls+tcp://127.0.0.1:0", { certKeyFile });
+    return true;
+  } catch (err) {
+    return !String(err).includes("NotSupported");
+  } finally {
+    probe.close();
+  }
+})();
+
+describe.skipIf(!tlsEnabled)("tls", () => {
+  it("reloads the certificate when the watched file changes", async () => {
+    const file = join(tmpdir(), `spec-tls-reload-${process.pid}.pem`);
+    copyFileSync(certKeyFile, file);
+    const pub = new Publisher();
+    const events: SocketEvent[] = [];
+    pub.onEvent((err, event) => events.push(event));
+    pub.listenTls("tls+tcp://127.0.0.1:0", { certKeyFile: file, watchIntervalMs: 20 });
+    await new Promise((resolve) => setTimeout(resolve, 50));
+
+    writeFileSync(file, readFileSync(certKeyFile));
+    await new Promise((resolve) => setTimeout(resolve, 100));
+    expect(events.map((event) => event.name)).toEqual(["tlsReloaded"]);
+
+    writeFileSync(file, "not a certificate");
+    await new Promise((resolve) => setTimeout(resolve, 100));
+    expect(events.map((event) => event.name)).toEqual(["tlsReloaded", "tlsReloadFailed"]);
+    expect(() => pub.reloadTls()).toThrow("Failed to load certificate");
+
+    pub.close();
+  });
+
+  it("applies TLS settings per endpoint next to a plain ipc listener", async () => {
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    const ipcUrl = `ipc://${join(tmpdir(), `spec-tls-mixed-${process.pid}.ipc`)}`;
+    pull.listen(ipcUrl);
+    const listener = pull.createListener("tls+tcp://127.0.0.1:0", { tlsCertKeyFile: certKeyFile, tlsAuthMode: TlsAuthMode.None });
+    pull.startListener(listener);
+    const port = pull.dumpOptions().endpoints.find((endpoint) => endpoint.id === listener)!.options.find((entry) => entry.name === "tcp-bound-port")!.value;
+    const tlsUrl = `tls+tcp://127.0.0.1:${port}`;
+    const received: string[] = [];
+    pull.recv((err, msg) => received.push(msg.toString()));
+    expect(() => pull.createListener(tlsUrl, { tlsServerName: "localhost" })).toThrow("tlsServerName only applies to dialers");
+
+    const connect = (url: string, options?: EndpointOptions) => {
+      const push = new SocketWrapper();
+      push.open(ProtocolType.Push0);
+      push.startDialer(push.createDialer(url, options), true);
+      return push;
+    };
+    const pushes = [
+      connect(ipcUrl),
+      connect(tlsUrl, { tlsCaFile: certKeyFile, tlsServerName: "localhost" }),
+      connect(tlsUrl, { tlsCaFile: certKeyFile, tlsServerName: "wrong.example" }),
+    ];
+    await new Promise((resolve) => setTimeout(resolve, 200));
+    await Promise.all(pushes.slice(0, 2).map((push, i) => push.sendAsync(`via ${i}`)));
+    await new Promise((resolve) => setTimeout(resolve, 50));
+
+    expect(received.sort()).toEqual(["via 0", "via 1"]);
+    expect(pushes[2].stats().endpoints).toEqual([]);
+    [...pushes, pull].forEach((socket) => socket.close());
+  });
+
+  it("reports whether the TLS peer was verified on each pipe", async () => {
+    const pull = new SocketWrapper();
+    pull.open(ProtocolType.Pull0);
+    const listened: PipeInfo[] = [];
+    pull.onPipeAdded((err, info) => listened.push(info));
+    const listener = pull.createListener("tls+tcp://127.0.0.1:0", { tlsCertKeyFile: certKeyFile, tlsAuthMode: TlsAuthMode.None });
+    pull.startListener(listener);
+    const port = pull.dumpOptions().endpoints[0].options.find((entry) => entry.name === "tcp-bound-port")!.value;
+    const ipcUrl = `ipc://${join(tmpdir(), `spec-tls-verified-${process.pid}.ipc`)}`;
+    pull.listen(ipcUrl);
+
+    const push = new SocketWrapper();
+    push.open(ProtocolType.Push0);
+    const dialed: PipeInfo[] = [];
+    push.onPipeAdded((err, info) => dialed.push(info));
+    push.startDialer(push.createDialer(`tls+tcp://127.0.0.1:${port}`, { tlsCaFile: certKeyFile, tlsServerName: "localhost" }));
+    push.dial(ipcUrl);
+    await new Promise((resolve) => setTimeout(resolve, 100));
+
+    expect(dialed.map((info) => info.tlsVerified)).toEqual([true, undefined]);
+    expect(listened.map((info) => info.tlsVerified)).toEqual([false, undefined]);
+    push.close();
+    pull.close();
+  });
+});